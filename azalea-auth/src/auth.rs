@@ -1,6 +1,9 @@
 //! Handle Minecraft (Xbox) authentication.
 
-use crate::cache::{self, CachedAccount, ExpiringValue};
+use crate::{
+    cache::{self, CachedAccount, ExpiringValue},
+    AuthEndpoints,
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -24,6 +27,9 @@ pub struct AuthOpts {
     /// The directory to store the cache in. If this is not set, caching is not
     /// done.
     pub cache_file: Option<PathBuf>,
+    /// The servers to authenticate with. This defaults to the official
+    /// Microsoft and Mojang servers.
+    pub endpoints: AuthEndpoints,
 }
 
 #[derive(Debug, Error)]
@@ -74,22 +80,24 @@ pub async fn auth(email: &str, opts: AuthOpts) -> Result<AuthResult, AuthError>
         minecraft_access_token = account.mca.data.access_token.clone();
     } else {
         let client = reqwest::Client::new();
+        let endpoints = &opts.endpoints;
         let mut msa = if let Some(account) = cached_account {
             account.msa
         } else {
-            interactive_get_ms_auth_token(&client, email).await?
+            interactive_get_ms_auth_token(&client, endpoints, email).await?
         };
         if msa.is_expired() {
             log::trace!("refreshing Microsoft auth token");
-            msa = refresh_ms_auth_token(&client, &msa.data.refresh_token).await?;
+            msa = refresh_ms_auth_token(&client, endpoints, &msa.data.refresh_token).await?;
         }
         let ms_access_token = &msa.data.access_token;
         log::trace!("Got access token: {}", ms_access_token);
 
-        let xbl_auth = auth_with_xbox_live(&client, ms_access_token).await?;
+        let xbl_auth = auth_with_xbox_live(&client, endpoints, ms_access_token).await?;
 
         let xsts_token = obtain_xsts_for_minecraft(
             &client,
+            endpoints,
             &xbl_auth
                 .get()
                 .expect("Xbox Live auth token shouldn't have expired yet")
@@ -98,7 +106,8 @@ pub async fn auth(email: &str, opts: AuthOpts) -> Result<AuthResult, AuthError>
         .await?;
 
         // Minecraft auth
-        let mca = auth_with_minecraft(&client, &xbl_auth.data.user_hash, &xsts_token).await?;
+        let mca =
+            auth_with_minecraft(&client, endpoints, &xbl_auth.data.user_hash, &xsts_token).await?;
 
         minecraft_access_token = mca
             .get()
@@ -107,13 +116,13 @@ pub async fn auth(email: &str, opts: AuthOpts) -> Result<AuthResult, AuthError>
             .to_string();
 
         if opts.check_ownership {
-            let has_game = check_ownership(&client, &minecraft_access_token).await?;
+            let has_game = check_ownership(&client, endpoints, &minecraft_access_token).await?;
             if !has_game {
                 return Err(AuthError::DoesNotOwnGame);
             }
         }
 
-        profile = get_profile(&client, &minecraft_access_token).await?;
+        profile = get_profile_with_endpoints(&client, endpoints, &minecraft_access_token).await?;
 
         if let Some(cache_file) = opts.cache_file {
             if let Err(e) = cache::set_account_in_cache(
@@ -230,10 +239,11 @@ pub enum GetMicrosoftAuthTokenError {
 /// Asks the user to go to a webpage and log in with Microsoft.
 async fn interactive_get_ms_auth_token(
     client: &reqwest::Client,
+    endpoints: &AuthEndpoints,
     email: &str,
 ) -> Result<ExpiringValue<AccessTokenResponse>, GetMicrosoftAuthTokenError> {
     let res = client
-        .post("https://login.live.com/oauth20_connect.srf")
+        .post(format!("{}/oauth20_connect.srf", endpoints.microsoft_login))
         .form(&vec![
             ("scope", "service::user.auth.xboxlive.com::MBI_SSL"),
             ("client_id", CLIENT_ID),
@@ -257,7 +267,8 @@ async fn interactive_get_ms_auth_token(
         log::trace!("Polling to check if user has logged in...");
         if let Ok(access_token_response) = client
             .post(format!(
-                "https://login.live.com/oauth20_token.srf?client_id={CLIENT_ID}"
+                "{}/oauth20_token.srf?client_id={CLIENT_ID}",
+                endpoints.microsoft_login
             ))
             .form(&vec![
                 ("client_id", CLIENT_ID),
@@ -293,10 +304,11 @@ pub enum RefreshMicrosoftAuthTokenError {
 
 async fn refresh_ms_auth_token(
     client: &reqwest::Client,
+    endpoints: &AuthEndpoints,
     refresh_token: &str,
 ) -> Result<ExpiringValue<AccessTokenResponse>, RefreshMicrosoftAuthTokenError> {
     let access_token_response = client
-        .post("https://login.live.com/oauth20_token.srf")
+        .post(format!("{}/oauth20_token.srf", endpoints.microsoft_login))
         .form(&vec![
             ("scope", "service::user.auth.xboxlive.com::MBI_SSL"),
             ("client_id", CLIENT_ID),
@@ -329,6 +341,7 @@ pub enum XboxLiveAuthError {
 
 async fn auth_with_xbox_live(
     client: &reqwest::Client,
+    endpoints: &AuthEndpoints,
     access_token: &str,
 ) -> Result<ExpiringValue<XboxLiveAuth>, XboxLiveAuthError> {
     let auth_json = json!({
@@ -345,7 +358,7 @@ async fn auth_with_xbox_live(
     let payload = auth_json.to_string();
     log::trace!("auth_json: {:#?}", auth_json);
     let res = client
-        .post("https://user.auth.xboxlive.com/user/authenticate")
+        .post(format!("{}/user/authenticate", endpoints.xbox_user_auth))
         .header("Content-Type", "application/json")
         .header("Accept", "application/json")
         .header("x-xbl-contract-version", "1")
@@ -380,10 +393,11 @@ pub enum MinecraftXstsAuthError {
 
 async fn obtain_xsts_for_minecraft(
     client: &reqwest::Client,
+    endpoints: &AuthEndpoints,
     xbl_auth_token: &str,
 ) -> Result<String, MinecraftXstsAuthError> {
     let res = client
-        .post("https://xsts.auth.xboxlive.com/xsts/authorize")
+        .post(format!("{}/xsts/authorize", endpoints.xbox_xsts))
         .header("Accept", "application/json")
         .json(&json!({
             "Properties": {
@@ -410,11 +424,15 @@ pub enum MinecraftAuthError {
 
 async fn auth_with_minecraft(
     client: &reqwest::Client,
+    endpoints: &AuthEndpoints,
     user_hash: &str,
     xsts_token: &str,
 ) -> Result<ExpiringValue<MinecraftAuthResponse>, MinecraftAuthError> {
     let res = client
-        .post("https://api.minecraftservices.com/authentication/login_with_xbox")
+        .post(format!(
+            "{}/authentication/login_with_xbox",
+            endpoints.minecraft_services
+        ))
         .header("Accept", "application/json")
         .json(&json!({
             "identityToken": format!("XBL3.0 x={user_hash};{xsts_token}")
@@ -441,10 +459,14 @@ pub enum CheckOwnershipError {
 
 async fn check_ownership(
     client: &reqwest::Client,
+    endpoints: &AuthEndpoints,
     minecraft_access_token: &str,
 ) -> Result<bool, CheckOwnershipError> {
     let res = client
-        .get("https://api.minecraftservices.com/entitlements/mcstore")
+        .get(format!(
+            "{}/entitlements/mcstore",
+            endpoints.minecraft_services
+        ))
         .header("Authorization", format!("Bearer {minecraft_access_token}"))
         .send()
         .await?
@@ -467,9 +489,22 @@ pub enum GetProfileError {
 pub async fn get_profile(
     client: &reqwest::Client,
     minecraft_access_token: &str,
+) -> Result<ProfileResponse, GetProfileError> {
+    get_profile_with_endpoints(client, &AuthEndpoints::default(), minecraft_access_token).await
+}
+
+/// Like [`get_profile`], but ask the Minecraft services server from the given
+/// [`AuthEndpoints`] instead of Mojang's.
+pub async fn get_profile_with_endpoints(
+    client: &reqwest::Client,
+    endpoints: &AuthEndpoints,
+    minecraft_access_token: &str,
 ) -> Result<ProfileResponse, GetProfileError> {
     let res = client
-        .get("https://api.minecraftservices.com/minecraft/profile")
+        .get(format!(
+            "{}/minecraft/profile",
+            endpoints.minecraft_services
+        ))
         .header("Authorization", format!("Bearer {minecraft_access_token}"))
         .send()
         .await?
@@ -0,0 +1,77 @@
+//! The URLs of the servers that we authenticate with.
+
+/// The base URLs of the services used for authentication. The default is the
+/// official Microsoft and Mojang servers.
+///
+/// Every URL is a base without a trailing slash, and the same paths as the
+/// official servers are appended to it. You can point them at an
+/// authlib-injector compatible server (like Ely.by, Drasl or a self-hosted
+/// Yggdrasil server) with [`AuthEndpoints::authlib_injector`], or at a local
+/// mock server in tests.
+///
+/// ```
+/// # use azalea_auth::AuthEndpoints;
+/// let endpoints = AuthEndpoints::authlib_injector("https://drasl.example.com/authlib-injector");
+/// assert_eq!(
+///     endpoints.session_server,
+///     "https://drasl.example.com/authlib-injector/sessionserver"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthEndpoints {
+    /// Used for getting and refreshing Microsoft access tokens.
+    pub microsoft_login: String,
+    /// Used for authenticating with Xbox Live.
+    pub xbox_user_auth: String,
+    /// Used for getting an XSTS token for Minecraft.
+    pub xbox_xsts: String,
+    /// Used for getting the Minecraft access token, profile, ownership and
+    /// chat signing certificates.
+    pub minecraft_services: String,
+    /// Used for joining servers and checking if players joined.
+    pub session_server: String,
+}
+
+impl Default for AuthEndpoints {
+    fn default() -> Self {
+        Self {
+            microsoft_login: "https://login.live.com".to_string(),
+            xbox_user_auth: "https://user.auth.xboxlive.com".to_string(),
+            xbox_xsts: "https://xsts.auth.xboxlive.com".to_string(),
+            minecraft_services: "https://api.minecraftservices.com".to_string(),
+            session_server: "https://sessionserver.mojang.com".to_string(),
+        }
+    }
+}
+
+impl AuthEndpoints {
+    /// The official Microsoft and Mojang servers.
+    pub fn mojang() -> Self {
+        Self::default()
+    }
+
+    /// Use the session server and Minecraft services of an authlib-injector
+    /// compatible server, given the URL of its API root. Microsoft and Xbox
+    /// Live are still used for logging in with [`auth`](crate::auth), since
+    /// these servers give out access tokens some other way.
+    pub fn authlib_injector(api_root: &str) -> Self {
+        let api_root = api_root.trim_end_matches('/');
+        Self {
+            minecraft_services: format!("{api_root}/minecraftservices"),
+            session_server: format!("{api_root}/sessionserver"),
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn session_server(mut self, url: &str) -> Self {
+        self.session_server = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    #[must_use]
+    pub fn minecraft_services(mut self, url: &str) -> Self {
+        self.minecraft_services = url.trim_end_matches('/').to_string();
+        self
+    }
+}
@@ -2,7 +2,12 @@
 
 mod auth;
 mod cache;
+pub mod certs;
+mod endpoints;
 pub mod game_profile;
 pub mod sessionserver;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 
 pub use auth::*;
+pub use endpoints::*;
@@ -6,7 +6,10 @@ use serde_json::json;
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::game_profile::{GameProfile, SerializableGameProfile};
+use crate::{
+    game_profile::{GameProfile, SerializableGameProfile},
+    AuthEndpoints,
+};
 
 #[derive(Debug, Error)]
 pub enum ClientSessionServerError {
@@ -57,6 +60,27 @@ pub async fn join(
     private_key: &[u8],
     uuid: &Uuid,
     server_id: &str,
+) -> Result<(), ClientSessionServerError> {
+    join_with_endpoints(
+        access_token,
+        public_key,
+        private_key,
+        uuid,
+        server_id,
+        &AuthEndpoints::default(),
+    )
+    .await
+}
+
+/// Like [`join`], but use the session server from the given
+/// [`AuthEndpoints`] instead of Mojang's.
+pub async fn join_with_endpoints(
+    access_token: &str,
+    public_key: &[u8],
+    private_key: &[u8],
+    uuid: &Uuid,
+    server_id: &str,
+    endpoints: &AuthEndpoints,
 ) -> Result<(), ClientSessionServerError> {
     let client = reqwest::Client::new();
 
@@ -75,7 +99,10 @@ pub async fn join(
         "serverId": server_hash
     });
     let res = client
-        .post("https://sessionserver.mojang.com/session/minecraft/join")
+        .post(format!(
+            "{}/session/minecraft/join",
+            endpoints.session_server
+        ))
         .json(&data)
         .send()
         .await?;
@@ -119,6 +146,25 @@ pub async fn serverside_auth(
     public_key: &[u8],
     private_key: &[u8; 16],
     ip: Option<&str>,
+) -> Result<GameProfile, ServerSessionServerError> {
+    serverside_auth_with_endpoints(
+        username,
+        public_key,
+        private_key,
+        ip,
+        &AuthEndpoints::default(),
+    )
+    .await
+}
+
+/// Like [`serverside_auth`], but use the session server from the given
+/// [`AuthEndpoints`] instead of Mojang's.
+pub async fn serverside_auth_with_endpoints(
+    username: &str,
+    public_key: &[u8],
+    private_key: &[u8; 16],
+    ip: Option<&str>,
+    endpoints: &AuthEndpoints,
 ) -> Result<GameProfile, ServerSessionServerError> {
     let hash = azalea_crypto::hex_digest(&azalea_crypto::digest_data(
         "".as_bytes(),
@@ -127,7 +173,7 @@ pub async fn serverside_auth(
     ));
 
     let url = reqwest::Url::parse_with_params(
-        "https://sessionserver.mojang.com/session/minecraft/hasJoined",
+        &format!("{}/session/minecraft/hasJoined", endpoints.session_server),
         if let Some(ip) = ip {
             vec![("username", username), ("serverId", &hash), ("ip", ip)]
         } else {
@@ -161,3 +207,46 @@ pub async fn serverside_auth(
 
     Ok(res.json::<SerializableGameProfile>().await?.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{json_response, mock_session_server};
+
+    #[tokio::test]
+    async fn test_serverside_auth_with_endpoints() {
+        let (endpoints, request) = mock_session_server(json_response(
+            r#"{"id":"069a79f444e94726a5befca90e38aaf5","name":"Notch","properties":[]}"#,
+        ))
+        .await;
+
+        let profile =
+            serverside_auth_with_endpoints("Notch", &[1, 2, 3], &[0; 16], None, &endpoints)
+                .await
+                .unwrap();
+        assert_eq!(profile.name, "Notch");
+        assert_eq!(
+            profile.uuid,
+            Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap()
+        );
+        assert!(request
+            .await
+            .unwrap()
+            .starts_with("GET /session/minecraft/hasJoined?username=Notch&serverId="));
+    }
+
+    #[tokio::test]
+    async fn test_join_with_endpoints() {
+        let (endpoints, request) =
+            mock_session_server("HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n".to_string())
+                .await;
+
+        join_with_endpoints("token", &[1, 2, 3], &[0; 16], &Uuid::nil(), "", &endpoints)
+            .await
+            .unwrap();
+        assert_eq!(
+            request.await.unwrap(),
+            "POST /session/minecraft/join HTTP/1.1"
+        );
+    }
+}
@@ -0,0 +1,44 @@
+//! Helpers for testing code that talks to the authentication servers.
+
+use crate::AuthEndpoints;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    task::JoinHandle,
+};
+
+/// Start a session server that answers one request with the response and
+/// returns the request line it got.
+///
+/// # Panics
+///
+/// Panics if the listener couldn't be bound, or if the request couldn't be
+/// read or answered.
+pub async fn mock_session_server(response: String) -> (AuthEndpoints, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoints = AuthEndpoints::default()
+        .session_server(&format!("http://{}", listener.local_addr().unwrap()));
+    let handle = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+        stream.write_all(response.as_bytes()).await.unwrap();
+        stream.shutdown().await.unwrap();
+        let request = String::from_utf8(request).unwrap();
+        request.lines().next().unwrap().to_string()
+    });
+    (endpoints, handle)
+}
+
+/// A `200 OK` HTTP response with the given JSON body.
+#[must_use]
+pub fn json_response(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
@@ -1,7 +1,17 @@
 use std::{any::Any, rc::Rc};
 
-use crate::{exceptions::CommandSyntaxException, string_reader::StringReader};
+use crate::{
+    exceptions::CommandSyntaxException,
+    string_reader::StringReader,
+    suggestion::{Suggestions, SuggestionsBuilder},
+};
 
 pub trait ArgumentType {
     fn parse(&self, reader: &mut StringReader) -> Result<Rc<dyn Any>, CommandSyntaxException>;
+
+    /// Suggest what could be typed for this argument. By default nothing is
+    /// suggested.
+    fn list_suggestions(&self, _builder: SuggestionsBuilder) -> Suggestions {
+        Suggestions::default()
+    }
 }
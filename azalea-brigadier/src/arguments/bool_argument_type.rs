@@ -1,7 +1,10 @@
 use std::{any::Any, rc::Rc};
 
 use crate::{
-    context::CommandContext, exceptions::CommandSyntaxException, string_reader::StringReader,
+    context::CommandContext,
+    exceptions::CommandSyntaxException,
+    string_reader::StringReader,
+    suggestion::{Suggestions, SuggestionsBuilder},
 };
 
 use super::ArgumentType;
@@ -10,6 +13,16 @@ impl ArgumentType for bool {
     fn parse(&self, reader: &mut StringReader) -> Result<Rc<dyn Any>, CommandSyntaxException> {
         Ok(Rc::new(reader.read_boolean()))
     }
+
+    fn list_suggestions(&self, mut builder: SuggestionsBuilder) -> Suggestions {
+        if "true".starts_with(builder.remaining_lowercase()) {
+            builder.suggest("true");
+        }
+        if "false".starts_with(builder.remaining_lowercase()) {
+            builder.suggest("false");
+        }
+        builder.build()
+    }
 }
 
 pub fn get_bool<'a, S>(context: &'a CommandContext<S>, name: &str) -> Option<bool> {
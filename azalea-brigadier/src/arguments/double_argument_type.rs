@@ -9,7 +9,7 @@ use crate::{
 use super::ArgumentType;
 
 #[derive(Default)]
-struct Double {
+pub struct Double {
     pub minimum: Option<f64>,
     pub maximum: Option<f64>,
 }
@@ -9,7 +9,7 @@ use crate::{
 use super::ArgumentType;
 
 #[derive(Default)]
-struct Float {
+pub struct Float {
     pub minimum: Option<f32>,
     pub maximum: Option<f32>,
 }
@@ -9,7 +9,7 @@ use crate::{
 use super::ArgumentType;
 
 #[derive(Default)]
-struct Integer {
+pub struct Integer {
     pub minimum: Option<i32>,
     pub maximum: Option<i32>,
 }
@@ -9,7 +9,7 @@ use crate::{
 use super::ArgumentType;
 
 #[derive(Default)]
-struct Long {
+pub struct Long {
     pub minimum: Option<i64>,
     pub maximum: Option<i64>,
 }
@@ -1,6 +1,9 @@
 use super::argument_builder::{ArgumentBuilder, ArgumentBuilderType};
 use crate::{
-    arguments::ArgumentType, exceptions::CommandSyntaxException, string_reader::StringReader,
+    arguments::ArgumentType,
+    exceptions::CommandSyntaxException,
+    string_reader::StringReader,
+    suggestion::{Suggestions, SuggestionsBuilder},
 };
 use std::{any::Any, fmt::Debug, rc::Rc, sync::Arc};
 
@@ -22,6 +25,16 @@ impl Argument {
     pub fn parse(&self, reader: &mut StringReader) -> Result<Rc<dyn Any>, CommandSyntaxException> {
         self.parser.parse(reader)
     }
+
+    pub fn list_suggestions(&self, builder: SuggestionsBuilder) -> Suggestions {
+        self.parser.list_suggestions(builder)
+    }
+
+    /// The type of this argument. Clones of the node share the same parser,
+    /// so this can be used to tell which node a parsed node came from.
+    pub fn parser(&self) -> &Arc<dyn ArgumentType + Send + Sync> {
+        &self.parser
+    }
 }
 
 impl From<Argument> for ArgumentBuilderType {
@@ -6,6 +6,7 @@ use crate::{
     exceptions::{BuiltInExceptions, CommandSyntaxException},
     parse_results::ParseResults,
     string_reader::StringReader,
+    suggestion::{Suggestions, SuggestionsBuilder},
     tree::CommandNode,
 };
 use std::{cmp::Ordering, collections::HashMap, mem, rc::Rc, sync::Arc};
@@ -297,6 +298,55 @@ impl<S> CommandDispatcher<S> {
         })
         // Ok(if forked { successful_forks } else { result })
     }
+
+    /// Get suggestions for what could be typed at the end of the parsed
+    /// input.
+    ///
+    /// ```
+    /// # use azalea_brigadier::prelude::*;
+    /// # let mut subject = CommandDispatcher::<()>::new();
+    /// subject.register(literal("foo"));
+    /// subject.register(literal("bar"));
+    ///
+    /// let parse = subject.parse("f".into(), ());
+    /// let suggestions = CommandDispatcher::get_completion_suggestions(parse);
+    /// assert_eq!(suggestions.suggestions[0].text, "foo");
+    /// ```
+    pub fn get_completion_suggestions(parse: ParseResults<S>) -> Suggestions {
+        let cursor = parse.reader.total_length();
+        Self::get_completion_suggestions_with_cursor(parse, cursor)
+    }
+
+    /// Get suggestions for what could be typed at `cursor` in the parsed
+    /// input.
+    pub fn get_completion_suggestions_with_cursor(
+        parse: ParseResults<S>,
+        cursor: usize,
+    ) -> Suggestions {
+        let context = parse.context;
+
+        let node_before_cursor = context.find_suggestion_context(cursor);
+        let parent = node_before_cursor.parent;
+        let start = usize::min(node_before_cursor.start_pos, cursor);
+
+        let full_input = parse.reader.string();
+        let truncated_input = full_input[..cursor].to_string();
+        let truncated_input_lowercase = truncated_input.to_lowercase();
+
+        let mut all_suggestions = Vec::new();
+        for node in parent.read().children.values() {
+            let suggestions = node
+                .read()
+                .list_suggestions(SuggestionsBuilder::new_with_lowercase(
+                    &truncated_input,
+                    &truncated_input_lowercase,
+                    start,
+                ));
+            all_suggestions.push(suggestions);
+        }
+
+        Suggestions::merge(full_input, &all_suggestions)
+    }
 }
 
 impl<S> Default for CommandDispatcher<S> {
@@ -2,7 +2,7 @@ use parking_lot::RwLock;
 
 use super::{
     command_context::CommandContext, parsed_command_node::ParsedCommandNode,
-    string_range::StringRange, ParsedArgument,
+    string_range::StringRange, suggestion_context::SuggestionContext, ParsedArgument,
 };
 use crate::{
     command_dispatcher::CommandDispatcher,
@@ -99,6 +99,50 @@ impl<'a, S> CommandContextBuilder<'a, S> {
             input: input.to_string(),
         }
     }
+
+    /// Find the node that the text at `cursor` would be a child of, and where
+    /// that child starts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cursor is before the start of this context.
+    pub fn find_suggestion_context(&self, cursor: usize) -> SuggestionContext<S> {
+        assert!(
+            self.range.start() <= cursor,
+            "Can't find node before cursor"
+        );
+
+        if self.range.end() < cursor {
+            if let Some(child) = &self.child {
+                child.find_suggestion_context(cursor)
+            } else if let Some(last) = self.nodes.last() {
+                SuggestionContext {
+                    parent: last.node.clone(),
+                    start_pos: last.range.end() + 1,
+                }
+            } else {
+                SuggestionContext {
+                    parent: self.root.clone(),
+                    start_pos: self.range.start(),
+                }
+            }
+        } else {
+            let mut prev = self.root.clone();
+            for node in &self.nodes {
+                if node.range.start() <= cursor && cursor <= node.range.end() {
+                    return SuggestionContext {
+                        parent: prev,
+                        start_pos: node.range.start(),
+                    };
+                }
+                prev = node.node.clone();
+            }
+            SuggestionContext {
+                parent: prev,
+                start_pos: self.range.start(),
+            }
+        }
+    }
 }
 
 impl<S> Debug for CommandContextBuilder<'_, S> {
@@ -3,9 +3,11 @@ mod command_context_builder;
 mod parsed_argument;
 mod parsed_command_node;
 mod string_range;
+mod suggestion_context;
 
 pub use command_context::CommandContext;
 pub use command_context_builder::CommandContextBuilder;
 pub use parsed_argument::ParsedArgument;
 pub use parsed_command_node::ParsedCommandNode;
 pub use string_range::StringRange;
+pub use suggestion_context::SuggestionContext;
@@ -0,0 +1,11 @@
+use parking_lot::RwLock;
+
+use crate::tree::CommandNode;
+use std::sync::Arc;
+
+/// The node that suggestions should be looked up from, and the position in the
+/// input where the suggested text starts.
+pub struct SuggestionContext<S> {
+    pub parent: Arc<RwLock<CommandNode<S>>>,
+    pub start_pos: usize,
+}
@@ -1,4 +1,5 @@
 mod suggestions;
+mod suggestions_builder;
 
 use crate::context::StringRange;
 #[cfg(feature = "azalea-buf")]
@@ -8,6 +9,7 @@ use azalea_chat::FormattedText;
 #[cfg(feature = "azalea-buf")]
 use std::io::Write;
 pub use suggestions::*;
+pub use suggestions_builder::SuggestionsBuilder;
 
 /// A suggestion given to the user for what they might want to type next.
 ///
@@ -0,0 +1,93 @@
+use super::{Suggestion, Suggestions};
+use crate::context::StringRange;
+
+/// Collects [`Suggestion`]s for the text after `start` in `input`.
+#[derive(Debug, Clone)]
+pub struct SuggestionsBuilder {
+    input: String,
+    input_lowercase: String,
+    start: usize,
+    remaining: String,
+    remaining_lowercase: String,
+    result: Vec<Suggestion>,
+}
+
+impl SuggestionsBuilder {
+    pub fn new(input: &str, start: usize) -> Self {
+        Self::new_with_lowercase(input, input.to_lowercase().as_str(), start)
+    }
+
+    pub fn new_with_lowercase(input: &str, input_lowercase: &str, start: usize) -> Self {
+        Self {
+            input: input.to_string(),
+            input_lowercase: input_lowercase.to_string(),
+            start,
+            remaining: input[start..].to_string(),
+            // lowercasing can change the length of the string, so `start` isn't
+            // necessarily a valid index in `input_lowercase`
+            remaining_lowercase: input[start..].to_lowercase(),
+            result: Vec::new(),
+        }
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn remaining(&self) -> &str {
+        &self.remaining
+    }
+
+    pub fn remaining_lowercase(&self) -> &str {
+        &self.remaining_lowercase
+    }
+
+    pub fn build(&self) -> Suggestions {
+        Suggestions::create(&self.input, &self.result.iter().cloned().collect())
+    }
+
+    /// Suggest replacing the remaining text with `text`. Nothing happens if
+    /// the text is already what's remaining.
+    pub fn suggest(&mut self, text: &str) -> &mut Self {
+        if text == self.remaining {
+            return self;
+        }
+        self.result.push(Suggestion {
+            range: StringRange::between(self.start, self.input.len()),
+            text: text.to_string(),
+            tooltip: None,
+        });
+        self
+    }
+
+    pub fn suggest_with_tooltip(&mut self, text: &str, tooltip: String) -> &mut Self {
+        if text == self.remaining {
+            return self;
+        }
+        self.result.push(Suggestion {
+            range: StringRange::between(self.start, self.input.len()),
+            text: text.to_string(),
+            tooltip: Some(tooltip),
+        });
+        self
+    }
+
+    pub fn add(&mut self, other: &SuggestionsBuilder) -> &mut Self {
+        self.result.extend(other.result.iter().cloned());
+        self
+    }
+
+    /// Make a new empty builder for the same input, starting at `start`.
+    pub fn create_offset(&self, start: usize) -> SuggestionsBuilder {
+        SuggestionsBuilder::new_with_lowercase(&self.input, &self.input_lowercase, start)
+    }
+
+    /// Make a new empty builder with the same input and start.
+    pub fn restart(&self) -> SuggestionsBuilder {
+        self.create_offset(self.start)
+    }
+}
@@ -9,6 +9,7 @@ use crate::{
     exceptions::{BuiltInExceptions, CommandSyntaxException},
     modifier::RedirectModifier,
     string_reader::StringReader,
+    suggestion::{Suggestions, SuggestionsBuilder},
 };
 use std::{collections::HashMap, fmt::Debug, hash::Hash, ptr, sync::Arc};
 
@@ -168,6 +169,24 @@ impl<S> CommandNode<S> {
         }
     }
 
+    /// Suggest what could be typed for this node. Literals suggest their own
+    /// name if it starts with the remaining input.
+    pub fn list_suggestions(&self, mut builder: SuggestionsBuilder) -> Suggestions {
+        match &self.value {
+            ArgumentBuilderType::Argument(argument) => argument.list_suggestions(builder),
+            ArgumentBuilderType::Literal(literal) => {
+                if literal
+                    .value
+                    .to_lowercase()
+                    .starts_with(builder.remaining_lowercase())
+                {
+                    builder.suggest(&literal.value);
+                }
+                builder.build()
+            }
+        }
+    }
+
     fn parse(&self, reader: &mut StringReader) -> Option<usize> {
         match self.value {
             ArgumentBuilderType::Argument(_) => {
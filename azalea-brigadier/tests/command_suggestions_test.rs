@@ -1 +1,202 @@
+use azalea_brigadier::{
+    arguments::bool_argument_type::get_bool,
+    context::StringRange,
+    prelude::*,
+    suggestion::{Suggestion, SuggestionsBuilder},
+};
 
+fn suggestion(range: StringRange, text: &str) -> Suggestion {
+    Suggestion {
+        range,
+        text: text.to_string(),
+        tooltip: None,
+    }
+}
+
+fn test_suggestions(
+    subject: &CommandDispatcher<()>,
+    contents: &str,
+    cursor: usize,
+    range: StringRange,
+    suggestions: &[&str],
+) {
+    let result = CommandDispatcher::get_completion_suggestions_with_cursor(
+        subject.parse(contents.into(), ()),
+        cursor,
+    );
+    assert_eq!(result.range, range);
+
+    let expected = suggestions
+        .iter()
+        .map(|text| suggestion(range.clone(), text))
+        .collect::<Vec<_>>();
+    assert_eq!(result.suggestions, expected);
+}
+
+#[test]
+fn get_completion_suggestions_root_commands() {
+    let mut subject = CommandDispatcher::<()>::new();
+    subject.register(literal("foo"));
+    subject.register(literal("bar"));
+    subject.register(literal("baz"));
+
+    let result = CommandDispatcher::get_completion_suggestions(subject.parse("".into(), ()));
+
+    assert_eq!(result.range, StringRange::at(0));
+    assert_eq!(
+        result.suggestions,
+        vec![
+            suggestion(StringRange::at(0), "bar"),
+            suggestion(StringRange::at(0), "baz"),
+            suggestion(StringRange::at(0), "foo"),
+        ]
+    );
+}
+
+#[test]
+fn get_completion_suggestions_root_commands_with_input_offset() {
+    let mut subject = CommandDispatcher::<()>::new();
+    subject.register(literal("foo"));
+    subject.register(literal("bar"));
+    subject.register(literal("baz"));
+
+    let mut reader: azalea_brigadier::string_reader::StringReader = "OOO".into();
+    reader.cursor = 3;
+    let result = CommandDispatcher::get_completion_suggestions(subject.parse(reader, ()));
+
+    assert_eq!(result.range, StringRange::at(3));
+    assert_eq!(
+        result.suggestions,
+        vec![
+            suggestion(StringRange::at(3), "bar"),
+            suggestion(StringRange::at(3), "baz"),
+            suggestion(StringRange::at(3), "foo"),
+        ]
+    );
+}
+
+#[test]
+fn get_completion_suggestions_root_commands_partial() {
+    let mut subject = CommandDispatcher::<()>::new();
+    subject.register(literal("foo"));
+    subject.register(literal("bar"));
+    subject.register(literal("baz"));
+
+    test_suggestions(
+        &subject,
+        "b",
+        1,
+        StringRange::between(0, 1),
+        &["bar", "baz"],
+    );
+}
+
+#[test]
+fn get_completion_suggestions_sub_commands() {
+    let mut subject = CommandDispatcher::<()>::new();
+    subject.register(
+        literal("parent")
+            .then(literal("foo"))
+            .then(literal("bar"))
+            .then(literal("baz")),
+    );
+
+    test_suggestions(
+        &subject,
+        "parent ",
+        7,
+        StringRange::at(7),
+        &["bar", "baz", "foo"],
+    );
+}
+
+#[test]
+fn get_completion_suggestions_sub_commands_partial() {
+    let mut subject = CommandDispatcher::<()>::new();
+    subject.register(
+        literal("parent")
+            .then(literal("foo"))
+            .then(literal("bar"))
+            .then(literal("baz")),
+    );
+
+    test_suggestions(
+        &subject,
+        "parent b",
+        8,
+        StringRange::between(7, 8),
+        &["bar", "baz"],
+    );
+}
+
+#[test]
+fn get_completion_suggestions_moving_cursor_sub_commands() {
+    let mut subject = CommandDispatcher::<()>::new();
+    subject.register(
+        literal("parent_one")
+            .then(literal("faz"))
+            .then(literal("fbz"))
+            .then(literal("gaz")),
+    );
+    subject.register(literal("parent_two"));
+
+    test_suggestions(
+        &subject,
+        "parent_one faz ",
+        0,
+        StringRange::at(0),
+        &["parent_one", "parent_two"],
+    );
+    test_suggestions(
+        &subject,
+        "parent_one faz ",
+        1,
+        StringRange::between(0, 1),
+        &["parent_one", "parent_two"],
+    );
+    test_suggestions(
+        &subject,
+        "parent_one faz ",
+        11,
+        StringRange::at(11),
+        &["faz", "fbz", "gaz"],
+    );
+    test_suggestions(
+        &subject,
+        "parent_one faz ",
+        12,
+        StringRange::between(11, 12),
+        &["faz", "fbz"],
+    );
+}
+
+#[test]
+fn get_completion_suggestions_argument() {
+    let mut subject = CommandDispatcher::<()>::new();
+    subject.register(literal("toggle").then(
+        argument("value", true).executes(|c| i32::from(get_bool(c, "value").unwrap_or_default())),
+    ));
+
+    test_suggestions(
+        &subject,
+        "toggle ",
+        7,
+        StringRange::at(7),
+        &["false", "true"],
+    );
+    test_suggestions(
+        &subject,
+        "toggle t",
+        8,
+        StringRange::between(7, 8),
+        &["true"],
+    );
+}
+
+#[test]
+fn suggestions_builder_lowercase_changes_length() {
+    // 'İ' is 2 bytes but its lowercase form is 3 bytes
+    let builder = SuggestionsBuilder::new("İİ b", 5);
+    assert_eq!(builder.remaining(), "b");
+    assert_eq!(builder.remaining_lowercase(), "b");
+}
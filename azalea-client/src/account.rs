@@ -3,7 +3,12 @@
 use std::sync::Arc;
 
 use crate::get_mc_dir;
+use azalea_auth::{
+    certs::{Certificates, FetchCertificatesError},
+    AuthEndpoints,
+};
 use parking_lot::Mutex;
+use thiserror::Error;
 use uuid::Uuid;
 
 /// Something that can join Minecraft servers.
@@ -37,11 +42,25 @@ pub struct Account {
     /// Only required for online-mode accounts.
     pub uuid: Option<Uuid>,
 
+    /// The key pair used for signing chat messages, which is fetched with
+    /// [`Self::request_certs`]. If this is `None`, our chat messages won't be
+    /// signed, so servers with `enforce-secure-profile` enabled won't let us
+    /// chat.
+    ///
+    /// This is an `Arc<Mutex>` so it can be shared between clones of the
+    /// account and only has to be fetched once.
+    pub certs: Arc<Mutex<Option<Certificates>>>,
+
     /// The parameters (i.e. email) that were passed for creating this
     /// [`Account`]. This is used to for automatic reauthentication when we get
     /// "Invalid Session" errors. If you don't need that feature (like in
     /// offline mode), then you can set this to `AuthOpts::default()`.
     pub account_opts: AccountOpts,
+
+    /// The servers that this account authenticates with. This has to be set
+    /// to the servers the access token came from to join online-mode
+    /// servers that use them, like Ely.by or Drasl.
+    pub endpoints: AuthEndpoints,
 }
 
 /// The parameters that were passed for creating the associated [`Account`].
@@ -61,9 +80,11 @@ impl Account {
             username: username.to_string(),
             access_token: None,
             uuid: None,
+            certs: Arc::new(Mutex::new(None)),
             account_opts: AccountOpts::Offline {
                 username: username.to_string(),
             },
+            endpoints: AuthEndpoints::default(),
         }
     }
 
@@ -72,6 +93,16 @@ impl Account {
     /// a key for the cache, but it's recommended to use the real email to
     /// avoid confusion.
     pub async fn microsoft(email: &str) -> Result<Self, azalea_auth::AuthError> {
+        Self::microsoft_with_endpoints(email, AuthEndpoints::default()).await
+    }
+
+    /// Like [`Self::microsoft`], but authenticate with the given servers
+    /// instead of Microsoft's and Mojang's. This is mostly useful for testing
+    /// against a mock server.
+    pub async fn microsoft_with_endpoints(
+        email: &str,
+        endpoints: AuthEndpoints,
+    ) -> Result<Self, azalea_auth::AuthError> {
         let minecraft_dir = get_mc_dir::minecraft_dir().unwrap_or_else(|| {
             panic!(
                 "No {} environment variable found",
@@ -82,6 +113,7 @@ impl Account {
             email,
             azalea_auth::AuthOpts {
                 cache_file: Some(minecraft_dir.join("azalea-auth.json")),
+                endpoints: endpoints.clone(),
                 ..Default::default()
             },
         )
@@ -90,9 +122,11 @@ impl Account {
             username: auth_result.profile.name,
             access_token: Some(Arc::new(Mutex::new(auth_result.access_token))),
             uuid: Some(auth_result.profile.id),
+            certs: Arc::new(Mutex::new(None)),
             account_opts: AccountOpts::Microsoft {
                 email: email.to_string(),
             },
+            endpoints,
         })
     }
 
@@ -106,7 +140,8 @@ impl Account {
             // offline mode doesn't need to refresh so just don't do anything lol
             AccountOpts::Offline { .. } => Ok(()),
             AccountOpts::Microsoft { email } => {
-                let new_account = Account::microsoft(email).await?;
+                let new_account =
+                    Account::microsoft_with_endpoints(email, self.endpoints.clone()).await?;
                 let access_token = self
                     .access_token
                     .as_ref()
@@ -122,3 +157,35 @@ impl Account {
         }
     }
 }
+
+#[derive(Error, Debug)]
+pub enum RequestCertError {
+    #[error("Failed to fetch certificates: {0}")]
+    FetchCertificates(#[from] FetchCertificatesError),
+    #[error("You can't request certificates for an offline account")]
+    NoAccessToken,
+}
+
+impl Account {
+    /// Request the key pair used for signing chat messages from Mojang and put
+    /// it in [`Self::certs`]. This only works for Microsoft accounts.
+    ///
+    /// [`Client::join`] calls this for you if the account doesn't have
+    /// certificates yet or they should be refreshed.
+    ///
+    /// [`Client::join`]: crate::Client::join
+    pub async fn request_certs(&self) -> Result<(), RequestCertError> {
+        let access_token = self
+            .access_token
+            .as_ref()
+            .ok_or(RequestCertError::NoAccessToken)?
+            .lock()
+            .clone();
+        let certs =
+            azalea_auth::certs::fetch_certificates_with_endpoints(&access_token, &self.endpoints)
+                .await?;
+        *self.certs.lock() = Some(certs);
+
+        Ok(())
+    }
+}
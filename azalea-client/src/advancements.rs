@@ -0,0 +1,350 @@
+//! Keep track of the player's advancements.
+
+use std::collections::HashMap;
+
+use azalea_core::ResourceLocation;
+pub use azalea_protocol::packets::game::clientbound_update_advancements_packet::{
+    Advancement, AdvancementProgress, CriterionProgress, DisplayInfo, FrameType,
+};
+use azalea_protocol::packets::game::{
+    clientbound_update_advancements_packet::ClientboundUpdateAdvancementsPacket,
+    serverbound_seen_advancements_packet::{Action, ServerboundSeenAdvancementsPacket},
+};
+use bevy_app::{App, Plugin};
+use bevy_ecs::{component::Component, entity::Entity};
+
+use crate::Client;
+
+pub struct AdvancementsPlugin;
+impl Plugin for AdvancementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AdvancementMadeEvent>();
+    }
+}
+
+/// A component that contains the advancement tree that the server sent us and
+/// our progress in it.
+///
+/// ```
+/// # use azalea_client::advancements::Advancements;
+/// # use azalea_core::ResourceLocation;
+/// # fn example(client: &azalea_client::Client) {
+/// let advancements = client.component::<Advancements>();
+/// if advancements.is_done(&ResourceLocation::new("story/mine_stone")) {
+///     println!("we've mined stone");
+/// }
+/// # }
+/// ```
+#[derive(Component, Clone, Debug, Default)]
+pub struct Advancements {
+    pub advancements: HashMap<ResourceLocation, Advancement>,
+    pub progress: HashMap<ResourceLocation, AdvancementProgress>,
+}
+
+impl Advancements {
+    /// Apply an `UpdateAdvancements` packet, and return the IDs of the
+    /// advancements that were completed because of it.
+    ///
+    /// Advancements are never considered newly completed when the packet
+    /// resets the tree, since that's what the server sends when we join.
+    pub fn update(
+        &mut self,
+        packet: &ClientboundUpdateAdvancementsPacket,
+    ) -> Vec<ResourceLocation> {
+        if packet.reset {
+            self.advancements.clear();
+            self.progress.clear();
+        }
+        for id in &packet.removed {
+            self.advancements.remove(id);
+            self.progress.remove(id);
+        }
+        for (id, advancement) in &packet.added {
+            self.advancements.insert(id.clone(), advancement.clone());
+        }
+
+        let mut completed = Vec::new();
+        for (id, progress) in &packet.progress {
+            let was_done = self.is_done(id);
+            self.progress.insert(id.clone(), progress.clone());
+            if !packet.reset && !was_done && self.is_done(id) {
+                completed.push(id.clone());
+            }
+        }
+        completed
+    }
+
+    /// Returns whether we have every criterion that's required to get the
+    /// advancement.
+    pub fn is_done(&self, id: &ResourceLocation) -> bool {
+        let Some(advancement) = self.advancements.get(id) else {
+            return false;
+        };
+        let Some(progress) = self.progress.get(id) else {
+            return false;
+        };
+        // this matches vanilla, where advancements with no requirements are
+        // never done
+        !advancement.requirements.is_empty()
+            && advancement.requirements.iter().all(|requirement| {
+                requirement
+                    .iter()
+                    .any(|criterion| is_criterion_done(progress, criterion))
+            })
+    }
+
+    /// Returns the time that the advancement was completed at, in milliseconds
+    /// since the Unix epoch. This is the time that the last required criterion
+    /// was completed.
+    pub fn completed_at(&self, id: &ResourceLocation) -> Option<u64> {
+        if !self.is_done(id) {
+            return None;
+        }
+        self.progress
+            .get(id)?
+            .values()
+            .filter_map(|criterion| criterion.date)
+            .max()
+    }
+
+    /// Returns the number of requirements that have been completed and the
+    /// total number of requirements for the advancement.
+    pub fn requirements_done(&self, id: &ResourceLocation) -> Option<(usize, usize)> {
+        let advancement = self.advancements.get(id)?;
+        let done = match self.progress.get(id) {
+            Some(progress) => advancement
+                .requirements
+                .iter()
+                .filter(|requirement| {
+                    requirement
+                        .iter()
+                        .any(|criterion| is_criterion_done(progress, criterion))
+                })
+                .count(),
+            None => 0,
+        };
+        Some((done, advancement.requirements.len()))
+    }
+
+    /// Get the IDs of the advancements that have no parent. These are the tabs
+    /// in the vanilla advancements screen.
+    pub fn roots(&self) -> Vec<&ResourceLocation> {
+        self.advancements
+            .iter()
+            .filter(|(_, advancement)| advancement.parent_id.is_none())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Get the IDs of the advancements whose parent is the given advancement.
+    pub fn children(&self, id: &ResourceLocation) -> Vec<&ResourceLocation> {
+        self.advancements
+            .iter()
+            .filter(|(_, advancement)| advancement.parent_id.as_ref() == Some(id))
+            .map(|(id, _)| id)
+            .collect()
+    }
+}
+
+fn is_criterion_done(progress: &AdvancementProgress, criterion: &str) -> bool {
+    progress
+        .get(&ResourceLocation::new(criterion))
+        .and_then(|criterion| criterion.date)
+        .is_some()
+}
+
+/// A local player completed an advancement.
+#[derive(Debug, Clone)]
+pub struct AdvancementMadeEvent {
+    pub entity: Entity,
+    pub id: ResourceLocation,
+    pub advancement: Advancement,
+}
+
+impl Client {
+    /// Tell the server that we opened the advancements screen on the given tab.
+    /// Tabs are identified by their root advancement, see
+    /// [`Advancements::roots`].
+    ///
+    /// The vanilla client sends this whenever the tab is switched.
+    pub fn open_advancement_tab(&self, tab: ResourceLocation) {
+        self.write_packet(
+            ServerboundSeenAdvancementsPacket {
+                action: Action::OpenedTab,
+                tab: Some(tab),
+            }
+            .get(),
+        );
+    }
+
+    /// Tell the server that we closed the advancements screen.
+    pub fn close_advancements_screen(&self) {
+        self.write_packet(
+            ServerboundSeenAdvancementsPacket {
+                action: Action::ClosedScreen,
+                tab: None,
+            }
+            .get(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn advancement(parent: Option<&str>, requirements: &[&[&str]]) -> Advancement {
+        Advancement {
+            parent_id: parent.map(ResourceLocation::new),
+            display: None,
+            criteria: HashMap::new(),
+            requirements: requirements
+                .iter()
+                .map(|r| r.iter().map(|c| c.to_string()).collect())
+                .collect(),
+        }
+    }
+
+    fn progress(criteria: &[(&str, Option<u64>)]) -> AdvancementProgress {
+        criteria
+            .iter()
+            .map(|&(name, date)| (ResourceLocation::new(name), CriterionProgress { date }))
+            .collect()
+    }
+
+    fn id(name: &str) -> ResourceLocation {
+        ResourceLocation::new(name)
+    }
+
+    /// The packet the server sends when we join.
+    fn reset_packet() -> ClientboundUpdateAdvancementsPacket {
+        ClientboundUpdateAdvancementsPacket {
+            reset: true,
+            added: HashMap::from([
+                (id("story/root"), advancement(None, &[&["crafting_table"]])),
+                (
+                    id("story/mine_stone"),
+                    advancement(Some("story/root"), &[&["get_stone"]]),
+                ),
+                (
+                    id("story/upgrade_tools"),
+                    advancement(
+                        Some("story/mine_stone"),
+                        &[&["stone_pickaxe"], &["stone_axe", "stone_shovel"]],
+                    ),
+                ),
+            ]),
+            removed: vec![],
+            progress: HashMap::from([
+                (
+                    id("story/root"),
+                    progress(&[("crafting_table", Some(1000))]),
+                ),
+                (id("story/mine_stone"), progress(&[("get_stone", None)])),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut advancements = Advancements::default();
+        // nothing counts as newly completed when we join
+        assert!(advancements.update(&reset_packet()).is_empty());
+
+        assert!(advancements.is_done(&id("story/root")));
+        assert_eq!(advancements.completed_at(&id("story/root")), Some(1000));
+        assert!(!advancements.is_done(&id("story/mine_stone")));
+        assert_eq!(advancements.completed_at(&id("story/mine_stone")), None);
+        // we don't have any progress for it
+        assert!(!advancements.is_done(&id("story/upgrade_tools")));
+        assert_eq!(
+            advancements.requirements_done(&id("story/upgrade_tools")),
+            Some((0, 2))
+        );
+
+        assert_eq!(advancements.roots(), vec![&id("story/root")]);
+        assert_eq!(
+            advancements.children(&id("story/root")),
+            vec![&id("story/mine_stone")]
+        );
+        assert!(advancements.children(&id("story/upgrade_tools")).is_empty());
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let mut advancements = Advancements::default();
+        advancements.update(&reset_packet());
+
+        let completed = advancements.update(&ClientboundUpdateAdvancementsPacket {
+            reset: false,
+            added: HashMap::new(),
+            removed: vec![],
+            progress: HashMap::from([
+                (
+                    id("story/mine_stone"),
+                    progress(&[("get_stone", Some(2000))]),
+                ),
+                (
+                    id("story/upgrade_tools"),
+                    progress(&[("stone_pickaxe", Some(3000)), ("stone_axe", None)]),
+                ),
+            ]),
+        });
+        assert_eq!(completed, vec![id("story/mine_stone")]);
+        assert_eq!(
+            advancements.completed_at(&id("story/mine_stone")),
+            Some(2000)
+        );
+        assert!(!advancements.is_done(&id("story/upgrade_tools")));
+        assert_eq!(
+            advancements.requirements_done(&id("story/upgrade_tools")),
+            Some((1, 2))
+        );
+
+        // any criterion in a requirement is enough
+        let completed = advancements.update(&ClientboundUpdateAdvancementsPacket {
+            reset: false,
+            added: HashMap::new(),
+            removed: vec![],
+            progress: HashMap::from([(
+                id("story/upgrade_tools"),
+                progress(&[
+                    ("stone_pickaxe", Some(3000)),
+                    ("stone_axe", None),
+                    ("stone_shovel", Some(4000)),
+                ]),
+            )]),
+        });
+        assert_eq!(completed, vec![id("story/upgrade_tools")]);
+        assert_eq!(
+            advancements.completed_at(&id("story/upgrade_tools")),
+            Some(4000)
+        );
+
+        // sending the same progress again doesn't complete it again
+        let completed = advancements.update(&ClientboundUpdateAdvancementsPacket {
+            reset: false,
+            added: HashMap::new(),
+            removed: vec![id("story/root")],
+            progress: HashMap::from([(
+                id("story/mine_stone"),
+                progress(&[("get_stone", Some(2000))]),
+            )]),
+        });
+        assert!(completed.is_empty());
+        assert!(!advancements.is_done(&id("story/root")));
+        assert!(advancements.roots().is_empty());
+    }
+
+    #[test]
+    fn test_no_requirements_is_never_done() {
+        let mut advancements = Advancements::default();
+        advancements.update(&ClientboundUpdateAdvancementsPacket {
+            reset: true,
+            added: HashMap::from([(id("recipes/root"), advancement(None, &[]))]),
+            removed: vec![],
+            progress: HashMap::from([(id("recipes/root"), progress(&[]))]),
+        });
+        assert!(!advancements.is_done(&id("recipes/root")));
+    }
+}
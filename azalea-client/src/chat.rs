@@ -1,34 +1,48 @@
 //! Implementations of chat-related features.
 
 use azalea_chat::FormattedText;
+use azalea_crypto::MessageSignature;
 use azalea_protocol::packets::game::{
+    clientbound_disguised_chat_packet::ClientboundDisguisedChatPacket,
     clientbound_player_chat_packet::ClientboundPlayerChatPacket,
     clientbound_system_chat_packet::ClientboundSystemChatPacket,
-    serverbound_chat_command_packet::ServerboundChatCommandPacket,
-    serverbound_chat_packet::{LastSeenMessagesUpdate, ServerboundChatPacket},
+    serverbound_chat_command_packet::{ArgumentSignature, ServerboundChatCommandPacket},
+    serverbound_chat_packet::ServerboundChatPacket,
 };
 use bevy_app::{App, Plugin};
 use bevy_ecs::{
+    component::Component,
     entity::Entity,
     event::{EventReader, EventWriter},
     schedule::{IntoSystemConfig, IntoSystemConfigs},
+    system::Query,
 };
 use std::{
+    collections::VecDeque,
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 use uuid::Uuid;
 
 use crate::{
+    chat_signing::{ChatSigningSession, ChatTrust, LastSeenMessagesTracker},
     client::Client,
-    local_player::{handle_send_packet_event, SendPacketEvent},
+    command_tree::CommandTree,
+    local_player::{handle_send_packet_event, GameProfileComponent, SendPacketEvent},
 };
 
+/// The number of messages kept in a [`ChatHistory`].
+pub const MAX_CHAT_HISTORY: usize = 100;
+
 /// A chat packet, either a system message or a chat message.
 #[derive(Debug, Clone, PartialEq)]
 pub enum ChatPacket {
     System(Arc<ClientboundSystemChatPacket>),
-    Player(Arc<ClientboundPlayerChatPacket>),
+    /// A chat message from a player, and whether its signature was valid.
+    Player(Arc<ClientboundPlayerChatPacket>, ChatTrust),
+    /// A chat message from a player that was sent without a signature, which
+    /// servers use for things like the `/say` command from the console.
+    Disguised(Arc<ClientboundDisguisedChatPacket>),
 }
 
 macro_rules! regex {
@@ -43,7 +57,26 @@ impl ChatPacket {
     pub fn message(&self) -> FormattedText {
         match self {
             ChatPacket::System(p) => p.content.clone(),
-            ChatPacket::Player(p) => p.message(),
+            ChatPacket::Player(p, _) => p.message(),
+            ChatPacket::Disguised(p) => p.full_message(),
+        }
+    }
+
+    /// How much we can trust that this message was really sent by the player
+    /// it claims to be from. System and disguised messages are always
+    /// [`ChatTrust::Unsigned`].
+    pub fn trust(&self) -> ChatTrust {
+        match self {
+            ChatPacket::Player(_, trust) => *trust,
+            ChatPacket::System(_) | ChatPacket::Disguised(_) => ChatTrust::Unsigned,
+        }
+    }
+
+    /// The signature of the message, if it's a signed player chat message.
+    pub fn signature(&self) -> Option<&MessageSignature> {
+        match self {
+            ChatPacket::Player(p, _) => p.signature.as_ref(),
+            ChatPacket::System(_) | ChatPacket::Disguised(_) => None,
         }
     }
 
@@ -53,12 +86,13 @@ impl ChatPacket {
     /// None.
     pub fn split_sender_and_content(&self) -> (Option<String>, String) {
         match self {
-            ChatPacket::Player(p) => (
+            ChatPacket::Player(p, _) => (
                 // If it's a player chat packet, then the sender and content
                 // are already split for us.
                 Some(p.chat_type.name.to_string()),
                 p.body.content.clone(),
             ),
+            ChatPacket::Disguised(p) => (Some(p.chat_type.name.to_string()), p.message.to_string()),
             ChatPacket::System(p) => {
                 let message = p.content.to_string();
                 // Overlay messages aren't in chat
@@ -88,8 +122,8 @@ impl ChatPacket {
     /// when a server uses a plugin to modify chat messages).
     pub fn uuid(&self) -> Option<Uuid> {
         match self {
-            ChatPacket::System(_) => None,
-            ChatPacket::Player(m) => Some(m.sender),
+            ChatPacket::System(_) | ChatPacket::Disguised(_) => None,
+            ChatPacket::Player(m, _) => Some(m.sender),
         }
     }
 
@@ -176,6 +210,52 @@ pub struct ChatReceivedEvent {
     pub packet: ChatPacket,
 }
 
+/// A component for local players with the most recent messages shown in chat,
+/// so messages the server deletes can be marked as deleted.
+#[derive(Component, Clone, Debug, Default)]
+pub struct ChatHistory {
+    /// The messages in chat, oldest first. There are at most
+    /// [`MAX_CHAT_HISTORY`] of them.
+    pub messages: VecDeque<ChatHistoryEntry>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChatHistoryEntry {
+    pub packet: ChatPacket,
+    /// Whether the server told us to delete this message.
+    pub deleted: bool,
+}
+
+impl ChatHistory {
+    /// Add a message to the end of the history, removing the oldest message if
+    /// it's full.
+    pub fn push(&mut self, packet: ChatPacket) {
+        if self.messages.len() >= MAX_CHAT_HISTORY {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(ChatHistoryEntry {
+            packet,
+            deleted: false,
+        });
+    }
+
+    /// Mark the message with the given signature as deleted. Returns whether
+    /// the message was found.
+    pub fn delete(&mut self, signature: &MessageSignature) -> bool {
+        match self
+            .messages
+            .iter_mut()
+            .find(|entry| entry.packet.signature() == Some(signature))
+        {
+            Some(entry) => {
+                entry.deleted = true;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
 /// Send a chat message (or command, if it starts with a slash) to the server.
 pub struct SendChatEvent {
     pub entity: Entity,
@@ -226,42 +306,66 @@ pub enum ChatPacketKind {
 
 fn handle_send_chat_kind_event(
     mut events: EventReader<SendChatKindEvent>,
+    mut query: Query<(
+        &GameProfileComponent,
+        &mut LastSeenMessagesTracker,
+        Option<&mut ChatSigningSession>,
+        Option<&CommandTree>,
+    )>,
     mut send_packet_events: EventWriter<SendPacketEvent>,
 ) {
     for event in events.iter() {
+        let Ok((game_profile, mut last_seen_messages, mut signing_session, command_tree)) =
+            query.get_mut(event.entity)
+        else {
+            continue;
+        };
+
         let content = event
             .content
             .chars()
             .filter(|c| !matches!(c, '\x00'..='\x1F' | '\x7F' | '§'))
             .take(256)
             .collect::<String>();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time shouldn't be before epoch")
+            .as_millis()
+            .try_into()
+            .expect("Instant should fit into a u64");
+        let salt = azalea_crypto::make_salt();
+        let (last_seen, last_seen_messages) = last_seen_messages.generate_update();
+        let sender = game_profile.uuid;
+
         let packet = match event.kind {
             ChatPacketKind::Message => ServerboundChatPacket {
+                signature: signing_session
+                    .as_mut()
+                    .map(|session| session.sign(sender, salt, timestamp, &content, &last_seen)),
                 message: content,
-                timestamp: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("Time shouldn't be before epoch")
-                    .as_millis()
-                    .try_into()
-                    .expect("Instant should fit into a u64"),
-                salt: azalea_crypto::make_salt(),
-                signature: None,
-                last_seen_messages: LastSeenMessagesUpdate::default(),
+                timestamp,
+                salt,
+                last_seen_messages,
             }
             .get(),
             ChatPacketKind::Command => {
-                // TODO: chat signing
+                let argument_signatures = match (signing_session.as_mut(), command_tree) {
+                    (Some(session), Some(command_tree)) => command_tree
+                        .signed_arguments(&content)
+                        .into_iter()
+                        .map(|(name, value)| ArgumentSignature {
+                            signature: session.sign(sender, salt, timestamp, &value, &last_seen),
+                            name,
+                        })
+                        .collect(),
+                    _ => vec![],
+                };
                 ServerboundChatCommandPacket {
                     command: content,
-                    timestamp: SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .expect("Time shouldn't be before epoch")
-                        .as_millis()
-                        .try_into()
-                        .expect("Instant should fit into a u64"),
-                    salt: azalea_crypto::make_salt(),
-                    argument_signatures: vec![],
-                    last_seen_messages: LastSeenMessagesUpdate::default(),
+                    timestamp,
+                    salt,
+                    argument_signatures,
+                    last_seen_messages,
                 }
                 .get()
             }
@@ -273,9 +377,3 @@ fn handle_send_chat_kind_event(
         });
     }
 }
-
-// TODO
-// MessageSigner, ChatMessageContent, LastSeenMessages
-// fn sign_message() -> MessageSignature {
-//     MessageSignature::default()
-// }
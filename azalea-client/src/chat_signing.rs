@@ -0,0 +1,641 @@
+//! Sign our chat messages and keep track of the messages we've seen, which
+//! servers with `enforce-secure-profile` enabled require, and verify the
+//! signatures of messages from other players.
+
+use azalea_auth::certs::Certificates;
+use azalea_core::FixedBitSet;
+use azalea_crypto::{DecodePublicKey, MessageSignature, RsaPublicKey, SignedMessageData};
+use azalea_protocol::packets::game::{
+    clientbound_player_chat_packet::{
+        ClientboundPlayerChatPacket, FilterMask, PackedLastSeenMessages, PackedMessageSignature,
+    },
+    serverbound_chat_packet::LastSeenMessagesUpdate,
+    serverbound_chat_session_update_packet::{
+        ProfilePublicKeyData, RemoteChatSessionData, ServerboundChatSessionUpdatePacket,
+    },
+    ServerboundGamePacket,
+};
+use bevy_ecs::component::Component;
+use log::warn;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+/// The number of recent messages that are included in the signatures of our
+/// messages.
+pub const LAST_SEEN_MESSAGES: usize = 20;
+
+/// How many messages we can see before we have to acknowledge them with a
+/// `ChatAck` packet if we don't send any chat messages.
+pub const MAX_UNACKNOWLEDGED_MESSAGES: u32 = 64;
+
+/// The number of signatures the server can refer to by their index instead of
+/// sending the whole signature.
+pub const MESSAGE_SIGNATURE_CACHE_SIZE: usize = 128;
+
+/// How long after being sent a message stops being shown as verified, in
+/// milliseconds.
+pub const MESSAGE_EXPIRES_AFTER: u64 = 7 * 60 * 1000;
+
+/// A component for local players that have a key pair to sign their chat
+/// messages with. This is only present if the account has
+/// [`Account::certs`](crate::Account::certs).
+#[derive(Component, Clone, Debug)]
+pub struct ChatSigningSession {
+    /// A random ID for this session, which is included in every signature.
+    pub session_id: Uuid,
+    /// The number of messages and command arguments we've signed in this
+    /// session.
+    pub messages_signed: u32,
+    pub certs: Certificates,
+}
+
+impl ChatSigningSession {
+    /// Start a new session with a random ID.
+    pub fn new(certs: Certificates) -> Self {
+        Self {
+            session_id: Uuid::new_v4(),
+            messages_signed: 0,
+            certs,
+        }
+    }
+
+    /// The packet that tells the server our session ID and public key, which
+    /// has to be sent before our signed messages can be verified.
+    pub fn update_packet(&self) -> ServerboundGamePacket {
+        ServerboundChatSessionUpdatePacket {
+            chat_session: RemoteChatSessionData {
+                session_id: self.session_id,
+                profile_public_key: ProfilePublicKeyData {
+                    expires_at: self.certs.expires_at,
+                    key: self.certs.public_key_der.clone(),
+                    key_signature: self.certs.signature_v2.clone(),
+                },
+            },
+        }
+        .get()
+    }
+
+    /// Sign a chat message or command argument, and increase the number of
+    /// messages signed in this session.
+    pub fn sign(
+        &mut self,
+        sender: Uuid,
+        salt: u64,
+        timestamp: u64,
+        content: &str,
+        last_seen: &[MessageSignature],
+    ) -> MessageSignature {
+        let signature = SignedMessageData {
+            sender,
+            session_id: self.session_id,
+            index: self.messages_signed,
+            salt,
+            timestamp,
+            content,
+            last_seen,
+        }
+        .sign(&self.certs.private_key);
+        self.messages_signed += 1;
+        signature
+    }
+}
+
+/// A component that keeps track of the signed messages we've seen recently,
+/// which we have to tell the server about when we send a message.
+///
+/// This works the same as vanilla's `LastSeenMessagesTracker`.
+#[derive(Component, Clone, Debug, Default)]
+pub struct LastSeenMessagesTracker {
+    /// A ring buffer of the last messages we've seen.
+    tracked: [Option<MessageSignature>; LAST_SEEN_MESSAGES],
+    tail: usize,
+    /// The number of messages we've seen since we last told the server.
+    offset: u32,
+    last_tracked: Option<MessageSignature>,
+}
+
+impl LastSeenMessagesTracker {
+    /// Track a signed message that we received. Returns false if it's the same
+    /// as the last one we tracked, which means it was ignored.
+    ///
+    /// Messages with invalid signatures should be tracked with `acknowledged`
+    /// set to false, so they take up a slot (which the server expects) but
+    /// aren't included in the signatures of our messages.
+    pub fn add_pending(&mut self, signature: MessageSignature, acknowledged: bool) -> bool {
+        if self.last_tracked.as_ref() == Some(&signature) {
+            return false;
+        }
+        self.last_tracked = Some(signature.clone());
+        self.tracked[self.tail] = acknowledged.then_some(signature);
+        self.tail = (self.tail + 1) % LAST_SEEN_MESSAGES;
+        self.offset += 1;
+        true
+    }
+
+    /// The number of messages we've seen since we last told the server.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Reset the number of messages we've seen since we last told the server,
+    /// and return what it was. This is what's sent in a `ChatAck` packet.
+    pub fn take_offset(&mut self) -> u32 {
+        std::mem::take(&mut self.offset)
+    }
+
+    /// Get the signatures of the messages we've seen (oldest first), which
+    /// are included in the signatures of our messages, and the update that
+    /// has to be sent to the server with the message.
+    pub fn generate_update(&mut self) -> (Vec<MessageSignature>, LastSeenMessagesUpdate) {
+        let messages = self.take_offset();
+        let mut acknowledged = FixedBitSet::<LAST_SEEN_MESSAGES>::new();
+        let mut last_seen = Vec::with_capacity(LAST_SEEN_MESSAGES);
+        for i in 0..LAST_SEEN_MESSAGES {
+            if let Some(signature) = &self.tracked[(self.tail + i) % LAST_SEEN_MESSAGES] {
+                acknowledged.set(i);
+                last_seen.push(signature.clone());
+            }
+        }
+        (
+            last_seen,
+            LastSeenMessagesUpdate {
+                messages,
+                acknowledged,
+            },
+        )
+    }
+}
+
+/// How much we can trust that a chat message was really sent by the player
+/// it claims to be from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChatTrust {
+    /// The message was signed by the sender, and the server didn't change it.
+    Verified,
+    /// The message was signed by the sender, but the server changed how it's
+    /// shown, either by replacing the content or by filtering it.
+    Modified,
+    /// The message can't be verified, because it's a system message, the
+    /// sender doesn't have a chat session, or the message is too old.
+    Unsigned,
+    /// The signature is missing or wrong, or the sender's chain of messages is
+    /// broken. This message might not have been sent by the player at all.
+    Invalid,
+}
+
+/// The chat session of another player, which is used to verify the messages
+/// they send.
+#[derive(Clone, Debug)]
+pub struct RemoteChatSession {
+    pub session_id: Uuid,
+    pub public_key: RsaPublicKey,
+    /// When the public key expires, in milliseconds since the UNIX epoch.
+    pub expires_at: u64,
+    /// The index of the last valid message from this session.
+    last_index: Option<u32>,
+    /// Whether we got an invalid message from this session, which means we
+    /// can't trust any more messages from it.
+    chain_broken: bool,
+}
+
+impl RemoteChatSession {
+    /// Parse the session data from a `PlayerInfoUpdate` packet. Returns `None`
+    /// if the public key is invalid or expired, in which case the player's
+    /// messages are treated as unsigned.
+    ///
+    /// Note that this doesn't check Mojang's signature of the public key.
+    pub fn new(data: &RemoteChatSessionData) -> Option<Self> {
+        let key = &data.profile_public_key;
+        if key.expires_at <= now_millis() {
+            return None;
+        }
+        let public_key = match RsaPublicKey::from_public_key_der(&key.key) {
+            Ok(public_key) => public_key,
+            Err(e) => {
+                warn!("Invalid chat session public key: {e}");
+                return None;
+            }
+        };
+        Some(Self {
+            session_id: data.session_id,
+            public_key,
+            expires_at: key.expires_at,
+            last_index: None,
+            chain_broken: false,
+        })
+    }
+
+    /// Check that the message was signed by this session and comes after the
+    /// previous message, and update the chain. If it's not valid, the chain
+    /// is broken and every later message will be invalid too.
+    fn update_and_validate(
+        &mut self,
+        packet: &ClientboundPlayerChatPacket,
+        last_seen: &[MessageSignature],
+    ) -> bool {
+        let valid = self.validate(packet, last_seen);
+        if valid {
+            self.last_index = Some(packet.index);
+        } else {
+            self.chain_broken = true;
+        }
+        valid
+    }
+
+    fn validate(
+        &self,
+        packet: &ClientboundPlayerChatPacket,
+        last_seen: &[MessageSignature],
+    ) -> bool {
+        if self.chain_broken || self.expires_at <= now_millis() {
+            return false;
+        }
+        let Some(signature) = &packet.signature else {
+            return false;
+        };
+        if matches!(self.last_index, Some(last_index) if packet.index <= last_index) {
+            return false;
+        }
+        SignedMessageData {
+            sender: packet.sender,
+            session_id: self.session_id,
+            index: packet.index,
+            salt: packet.body.salt,
+            timestamp: packet.body.timestamp,
+            content: &packet.body.content,
+            last_seen,
+        }
+        .verify(&self.public_key, signature)
+    }
+}
+
+/// A component for local players that keeps track of the chat sessions of the
+/// other players, so we can verify their messages.
+#[derive(Component, Clone, Debug, Default)]
+pub struct PlayerChatSessions {
+    pub sessions: HashMap<Uuid, RemoteChatSession>,
+}
+
+impl PlayerChatSessions {
+    /// Start or end a player's chat session. This resets their chain of
+    /// messages.
+    pub fn update(&mut self, uuid: Uuid, data: Option<&RemoteChatSessionData>) {
+        match data.and_then(RemoteChatSession::new) {
+            Some(session) => {
+                self.sessions.insert(uuid, session);
+            }
+            None => {
+                self.sessions.remove(&uuid);
+            }
+        }
+    }
+
+    /// Verify a chat message and update the sender's chain of messages.
+    /// `last_seen` should be the unpacked signatures from the message body.
+    pub fn verify(
+        &mut self,
+        packet: &ClientboundPlayerChatPacket,
+        last_seen: &[MessageSignature],
+    ) -> ChatTrust {
+        let Some(session) = self.sessions.get_mut(&packet.sender) else {
+            return ChatTrust::Unsigned;
+        };
+        if !session.update_and_validate(packet, last_seen) {
+            return ChatTrust::Invalid;
+        }
+        if packet.body.timestamp + MESSAGE_EXPIRES_AFTER <= now_millis() {
+            return ChatTrust::Unsigned;
+        }
+        // vanilla counts the message as modified whenever the server sends
+        // unsigned content, even if it looks the same as the signed content
+        if packet.unsigned_content.is_some() || packet.filter_mask != FilterMask::PassThrough {
+            ChatTrust::Modified
+        } else {
+            ChatTrust::Verified
+        }
+    }
+}
+
+/// A component that remembers the signatures of recent messages, so the
+/// server can refer to them by their index.
+///
+/// This works the same as vanilla's `MessageSignatureCache`.
+#[derive(Component, Clone, Debug)]
+pub struct MessageSignatureCache {
+    entries: Vec<Option<MessageSignature>>,
+}
+
+impl Default for MessageSignatureCache {
+    fn default() -> Self {
+        Self {
+            entries: vec![None; MESSAGE_SIGNATURE_CACHE_SIZE],
+        }
+    }
+}
+
+impl MessageSignatureCache {
+    /// Get the full signature, looking it up in the cache if the server only
+    /// sent its index. Returns `None` if the index isn't in the cache.
+    pub fn unpack(&self, packed: &PackedMessageSignature) -> Option<MessageSignature> {
+        match packed {
+            PackedMessageSignature::Signature(signature) => Some((**signature).clone()),
+            PackedMessageSignature::Id(id) => self.entries.get(*id as usize).cloned().flatten(),
+        }
+    }
+
+    /// Unpack every signature in the list of last seen messages, or return
+    /// `None` if any of them aren't in the cache.
+    pub fn unpack_last_seen(
+        &self,
+        last_seen: &PackedLastSeenMessages,
+    ) -> Option<Vec<MessageSignature>> {
+        last_seen
+            .entries
+            .iter()
+            .map(|packed| self.unpack(packed))
+            .collect()
+    }
+
+    /// Add the signatures of a message and the messages it saw to the front
+    /// of the cache.
+    pub fn push(&mut self, last_seen: &[MessageSignature], signature: Option<&MessageSignature>) {
+        let mut queue = last_seen.iter().cloned().collect::<VecDeque<_>>();
+        queue.extend(signature.cloned());
+        let pushed = queue.clone();
+
+        for entry in &mut self.entries {
+            let Some(next) = queue.pop_back() else {
+                break;
+            };
+            if let Some(old) = entry.replace(next) {
+                if !pushed.contains(&old) {
+                    queue.push_front(old);
+                }
+            }
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azalea_chat::FormattedText;
+    use azalea_crypto::RsaPrivateKey;
+    use azalea_protocol::packets::game::clientbound_player_chat_packet::{
+        ChatType, ChatTypeBound, PackedSignedMessageBody,
+    };
+
+    fn signature(n: u8) -> MessageSignature {
+        MessageSignature { bytes: [n; 256] }
+    }
+
+    #[test]
+    fn test_last_seen_window() {
+        let mut tracker = LastSeenMessagesTracker::default();
+        for i in 0..3 {
+            assert!(tracker.add_pending(signature(i), true));
+        }
+        let (last_seen, update) = tracker.generate_update();
+        assert_eq!(last_seen, vec![signature(0), signature(1), signature(2)]);
+        assert_eq!(update.messages, 3);
+        // the empty slots come first since the window is read from the tail
+        for i in 0..LAST_SEEN_MESSAGES {
+            assert_eq!(update.acknowledged.index(i), i >= LAST_SEEN_MESSAGES - 3);
+        }
+    }
+
+    #[test]
+    fn test_last_seen_window_wraps() {
+        let mut tracker = LastSeenMessagesTracker::default();
+        for i in 0..LAST_SEEN_MESSAGES as u8 + 5 {
+            tracker.add_pending(signature(i), true);
+        }
+        let (last_seen, update) = tracker.generate_update();
+        // only the most recent messages are kept, oldest first
+        assert_eq!(
+            last_seen,
+            (5..LAST_SEEN_MESSAGES as u8 + 5)
+                .map(signature)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(update.messages, LAST_SEEN_MESSAGES as u32 + 5);
+        for i in 0..LAST_SEEN_MESSAGES {
+            assert!(update.acknowledged.index(i));
+        }
+    }
+
+    #[test]
+    fn test_duplicate_is_ignored() {
+        let mut tracker = LastSeenMessagesTracker::default();
+        assert!(tracker.add_pending(signature(1), true));
+        assert!(!tracker.add_pending(signature(1), true));
+        assert_eq!(tracker.offset(), 1);
+        // it's only a duplicate if it's the same as the last one
+        assert!(tracker.add_pending(signature(2), true));
+        assert!(tracker.add_pending(signature(1), true));
+        assert_eq!(tracker.offset(), 3);
+    }
+
+    #[test]
+    fn test_offset_is_reset() {
+        let mut tracker = LastSeenMessagesTracker::default();
+        tracker.add_pending(signature(1), true);
+        tracker.add_pending(signature(2), true);
+        assert_eq!(tracker.take_offset(), 2);
+        assert_eq!(tracker.offset(), 0);
+
+        tracker.add_pending(signature(3), true);
+        let (last_seen, update) = tracker.generate_update();
+        // the messages are still tracked after being acknowledged
+        assert_eq!(last_seen.len(), 3);
+        assert_eq!(update.messages, 1);
+        assert_eq!(tracker.offset(), 0);
+    }
+
+    #[test]
+    fn test_ack_after_max_unacknowledged() {
+        let mut tracker = LastSeenMessagesTracker::default();
+        for i in 0..MAX_UNACKNOWLEDGED_MESSAGES {
+            tracker.add_pending(signature(i as u8), true);
+        }
+        // we only have to send an ack once we go over the limit
+        assert_eq!(tracker.offset(), MAX_UNACKNOWLEDGED_MESSAGES);
+
+        tracker.add_pending(signature(255), true);
+        assert!(tracker.offset() > MAX_UNACKNOWLEDGED_MESSAGES);
+        assert_eq!(tracker.take_offset(), MAX_UNACKNOWLEDGED_MESSAGES + 1);
+        assert_eq!(tracker.offset(), 0);
+    }
+
+    #[test]
+    fn test_unacknowledged_message_takes_a_slot() {
+        let mut tracker = LastSeenMessagesTracker::default();
+        tracker.add_pending(signature(1), true);
+        tracker.add_pending(signature(2), false);
+        tracker.add_pending(signature(3), true);
+        let (last_seen, update) = tracker.generate_update();
+        assert_eq!(last_seen, vec![signature(1), signature(3)]);
+        assert_eq!(update.messages, 3);
+        assert!(update.acknowledged.index(LAST_SEEN_MESSAGES - 3));
+        assert!(!update.acknowledged.index(LAST_SEEN_MESSAGES - 2));
+        assert!(update.acknowledged.index(LAST_SEEN_MESSAGES - 1));
+    }
+
+    #[test]
+    fn test_signature_cache_push() {
+        let mut cache = MessageSignatureCache::default();
+        cache.push(&[signature(1), signature(2)], Some(&signature(3)));
+        // the message's own signature goes first, then the ones it saw from
+        // newest to oldest
+        assert_eq!(
+            cache.unpack(&PackedMessageSignature::Id(0)),
+            Some(signature(3))
+        );
+        assert_eq!(
+            cache.unpack(&PackedMessageSignature::Id(1)),
+            Some(signature(2))
+        );
+        assert_eq!(
+            cache.unpack(&PackedMessageSignature::Id(2)),
+            Some(signature(1))
+        );
+        assert_eq!(cache.unpack(&PackedMessageSignature::Id(3)), None);
+
+        // signatures that are pushed again move to the front instead of being
+        // duplicated
+        cache.push(&[signature(2)], Some(&signature(4)));
+        let entries = (0..5)
+            .map(|i| cache.unpack(&PackedMessageSignature::Id(i)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                Some(signature(4)),
+                Some(signature(2)),
+                Some(signature(3)),
+                Some(signature(1)),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_signature_cache_is_bounded() {
+        let mut cache = MessageSignatureCache::default();
+        for i in 0..=MESSAGE_SIGNATURE_CACHE_SIZE {
+            cache.push(&[], Some(&signature(i as u8)));
+        }
+        assert_eq!(
+            cache.unpack(&PackedMessageSignature::Id(0)),
+            Some(signature(MESSAGE_SIGNATURE_CACHE_SIZE as u8))
+        );
+        assert_eq!(
+            cache.unpack(&PackedMessageSignature::Id(
+                MESSAGE_SIGNATURE_CACHE_SIZE as u32 - 1
+            )),
+            Some(signature(1))
+        );
+        assert_eq!(
+            cache.unpack(&PackedMessageSignature::Id(
+                MESSAGE_SIGNATURE_CACHE_SIZE as u32
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn test_unpack_last_seen() {
+        let mut cache = MessageSignatureCache::default();
+        cache.push(&[], Some(&signature(1)));
+
+        let last_seen = PackedLastSeenMessages {
+            entries: vec![
+                PackedMessageSignature::Id(0),
+                PackedMessageSignature::Signature(Box::new(signature(2))),
+            ],
+        };
+        assert_eq!(
+            cache.unpack_last_seen(&last_seen),
+            Some(vec![signature(1), signature(2)])
+        );
+
+        let last_seen = PackedLastSeenMessages {
+            entries: vec![PackedMessageSignature::Id(1)],
+        };
+        assert_eq!(cache.unpack_last_seen(&last_seen), None);
+    }
+
+    fn chat_packet(
+        session: &RemoteChatSession,
+        private_key: &RsaPrivateKey,
+        unsigned_content: Option<FormattedText>,
+    ) -> ClientboundPlayerChatPacket {
+        let sender = Uuid::from_u128(1);
+        let timestamp = now_millis();
+        let signature = SignedMessageData {
+            sender,
+            session_id: session.session_id,
+            index: 0,
+            salt: 2,
+            timestamp,
+            content: "hello",
+            last_seen: &[],
+        }
+        .sign(private_key);
+        ClientboundPlayerChatPacket {
+            sender,
+            index: 0,
+            signature: Some(signature),
+            body: PackedSignedMessageBody {
+                content: "hello".to_string(),
+                timestamp,
+                salt: 2,
+                last_seen: PackedLastSeenMessages { entries: vec![] },
+            },
+            unsigned_content,
+            filter_mask: FilterMask::PassThrough,
+            chat_type: ChatTypeBound {
+                chat_type: ChatType::Chat,
+                name: "bot".into(),
+                target_name: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        let session = RemoteChatSession {
+            session_id: Uuid::from_u128(2),
+            public_key: RsaPublicKey::from(&private_key),
+            expires_at: u64::MAX,
+            last_index: None,
+            chain_broken: false,
+        };
+        let mut sessions = PlayerChatSessions::default();
+
+        let packet = chat_packet(&session, &private_key, None);
+        sessions.sessions.insert(packet.sender, session.clone());
+        assert_eq!(sessions.verify(&packet, &[]), ChatTrust::Verified);
+
+        // unsigned content makes it modified even if it's the same text
+        let packet = chat_packet(&session, &private_key, Some("hello".into()));
+        sessions.sessions.insert(packet.sender, session.clone());
+        assert_eq!(sessions.verify(&packet, &[]), ChatTrust::Modified);
+
+        let mut packet = chat_packet(&session, &private_key, None);
+        packet.body.content = "goodbye".to_string();
+        sessions.sessions.insert(packet.sender, session);
+        assert_eq!(sessions.verify(&packet, &[]), ChatTrust::Invalid);
+    }
+}
@@ -1,16 +1,29 @@
 use crate::{
-    chat::ChatPlugin,
-    disconnect::{DisconnectEvent, DisconnectPlugin},
+    advancements::{Advancements, AdvancementsPlugin},
+    chat::{ChatHistory, ChatPlugin},
+    chat_signing::{
+        ChatSigningSession, LastSeenMessagesTracker, MessageSignatureCache, PlayerChatSessions,
+    },
+    command_tree::CommandSuggestionRequests,
+    crafting::CraftingPlugin,
+    disconnect::{DisconnectEvent, DisconnectPlugin, DisconnectReason},
     events::{Event, EventPlugin, LocalPlayerEvents},
     interact::{CurrentSequenceNumber, InteractPlugin},
     inventory::{InventoryComponent, InventoryPlugin},
+    latency::{Latency, LatencyPlugin},
     local_player::{
         death_event, handle_send_packet_event, update_in_loaded_chunk, GameProfileComponent,
         LocalPlayer, PhysicsState, SendPacketEvent,
     },
+    login_query::{LoginQueryContext, LoginQueryHandlers},
     movement::{LastSentLookDirection, PlayerMovePlugin},
-    packet_handling::{self, PacketHandlerPlugin, PacketReceiver},
+    packet_handling::{self, PacketErrorPolicy, PacketHandlerPlugin, PacketReceiver},
+    ping::{self, PingError},
     player::retroactively_add_game_profile_component,
+    plugin_channels::{PluginChannelsPlugin, ServerPluginChannels},
+    replay::PacketCapture,
+    resource_pack::ResourcePackPlugin,
+    statistics::{Statistics, StatsRequests},
     task_pool::TaskPoolPlugin,
     Account, PlayerInfo,
 };
@@ -20,6 +33,7 @@ use azalea_chat::FormattedText;
 use azalea_core::Vec3;
 use azalea_physics::{PhysicsPlugin, PhysicsSet};
 use azalea_protocol::{
+    capture::CaptureError,
     connect::{Connection, ConnectionError},
     packets::{
         game::{
@@ -36,13 +50,17 @@ use azalea_protocol::{
             serverbound_hello_packet::ServerboundHelloPacket,
             serverbound_key_packet::ServerboundKeyPacket, ClientboundLoginPacket,
         },
+        version::ProtocolVersion,
         ConnectionProtocol, PROTOCOL_VERSION,
     },
-    resolver, ServerAddress,
+    proxy::Proxy,
+    resolver::{self, Resolver},
+    stats::ConnectionStats,
+    ServerAddress,
 };
 use azalea_world::{
     entity::{EntityPlugin, EntityUpdateSet, Local, Position, WorldName},
-    Instance, InstanceContainer, PartialInstance,
+    tick_instances, Instance, InstanceContainer, PartialInstance,
 };
 use bevy_app::{App, CoreSchedule, IntoSystemAppConfig, Plugin, PluginGroup, PluginGroupBuilder};
 use bevy_ecs::{
@@ -57,9 +75,12 @@ use bevy_ecs::{
 use bevy_log::LogPlugin;
 use bevy_time::{prelude::FixedTime, TimePlugin};
 use derive_more::{Deref, DerefMut};
-use log::{debug, error};
+use log::{debug, error, warn};
 use parking_lot::{Mutex, RwLock};
-use std::{collections::HashMap, fmt::Debug, io, net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap, fmt::Debug, future::Future, io, net::SocketAddr, path::PathBuf,
+    sync::Arc, time::Duration,
+};
 use thiserror::Error;
 use tokio::{
     sync::{broadcast, mpsc},
@@ -167,6 +188,115 @@ pub enum JoinError {
     Auth(#[from] azalea_auth::AuthError),
     #[error("Disconnected: {reason}")]
     Disconnect { reason: FormattedText },
+    #[error("Timed out while connecting to the server")]
+    ConnectTimeout,
+    #[error("Timed out while logging in")]
+    HandshakeTimeout,
+    #[error("Couldn't start the packet capture: {0}")]
+    Capture(#[from] CaptureError),
+    #[error("Couldn't ping the server to find its version: {0}")]
+    Ping(#[from] PingError),
+}
+
+/// Which Minecraft version we use to join a server.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VersionSelection {
+    /// Always use [`PROTOCOL_VERSION`].
+    #[default]
+    Current,
+    /// Ping the server first and use its version if we support it. If we
+    /// don't, [`PROTOCOL_VERSION`] is used.
+    Detect,
+    /// Use this version.
+    Exact(ProtocolVersion),
+}
+
+/// Options for how a client connects to the server.
+///
+/// ```
+/// # use azalea_client::JoinOpts;
+/// # use azalea_protocol::proxy::Proxy;
+/// # use std::time::Duration;
+/// let opts = JoinOpts::new()
+///     .proxy(Proxy::socks5("127.0.0.1:1080".parse().unwrap()).with_remote_dns(true))
+///     .connect_timeout(Duration::from_secs(10));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct JoinOpts {
+    /// The proxy to connect to the server through.
+    pub proxy: Option<Proxy>,
+    /// How long we wait to connect to each of the server's addresses,
+    /// including the proxy's handshake. There's no timeout if this is `None`.
+    pub connect_timeout: Option<Duration>,
+    /// How long we wait for the server to let us in after connecting. There's
+    /// no timeout if this is `None`.
+    pub handshake_timeout: Option<Duration>,
+    /// The resolver used to look up the server's address. Uses
+    /// [`resolver::default_resolver`] if this is `None`.
+    pub resolver: Option<Arc<dyn Resolver>>,
+    /// A file to record every game packet to, which can be replayed later
+    /// with [`crate::replay::Replay`].
+    pub capture: Option<PathBuf>,
+    /// What to do with packets from the server that can't be read.
+    pub packet_error_policy: PacketErrorPolicy,
+    /// The Minecraft version to join with. See [`ProtocolVersion`] for the
+    /// limitations of joining with another version than
+    /// [`PROTOCOL_VERSION`].
+    pub version: VersionSelection,
+}
+
+impl JoinOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    #[must_use]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    #[must_use]
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = Some(timeout);
+        self
+    }
+
+    #[must_use]
+    pub fn resolver(mut self, resolver: impl Resolver + 'static) -> Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    #[must_use]
+    pub fn capture(mut self, path: impl Into<PathBuf>) -> Self {
+        self.capture = Some(path.into());
+        self
+    }
+
+    #[must_use]
+    pub fn packet_error_policy(mut self, policy: PacketErrorPolicy) -> Self {
+        self.packet_error_policy = policy;
+        self
+    }
+
+    #[must_use]
+    pub fn version(mut self, version: VersionSelection) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Whether the proxy looks up the server's hostname, so we shouldn't
+    /// resolve it ourselves.
+    pub fn resolves_through_proxy(&self) -> bool {
+        matches!(&self.proxy, Some(proxy) if proxy.remote_dns)
+    }
 }
 
 impl Client {
@@ -214,37 +344,80 @@ impl Client {
     pub async fn join(
         account: &Account,
         address: impl TryInto<ServerAddress>,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<Event>), JoinError> {
+        Self::join_with_opts(account, address, &JoinOpts::default()).await
+    }
+
+    /// Connect to a Minecraft server with the given [`JoinOpts`], which let you
+    /// use a proxy or set timeouts.
+    pub async fn join_with_opts(
+        account: &Account,
+        address: impl TryInto<ServerAddress>,
+        opts: &JoinOpts,
     ) -> Result<(Self, mpsc::UnboundedReceiver<Event>), JoinError> {
         let address: ServerAddress = address.try_into().map_err(|_| JoinError::InvalidAddress)?;
-        let resolved_address = resolver::resolve_address(&address).await?;
 
         // An event that causes the schedule to run. This is only used internally.
         let (run_schedule_sender, run_schedule_receiver) = mpsc::unbounded_channel();
         let app = init_ecs_app();
         let ecs_lock = start_ecs(app, run_schedule_receiver, run_schedule_sender.clone());
 
-        Self::start_client(
-            ecs_lock,
-            account,
-            &address,
-            &resolved_address,
-            run_schedule_sender,
-        )
-        .await
+        Self::start_client(ecs_lock, account, &address, None, opts, run_schedule_sender).await
     }
 
     /// Create a [`Client`] when you already have the ECS made with
     /// [`start_ecs`]. You'd usually want to use [`Self::join`] instead.
+    ///
+    /// If `resolved_address` is `None`, the address is resolved when
+    /// connecting (by the proxy if it has remote DNS enabled), and every
+    /// address the server has is tried until one works.
     pub async fn start_client(
         ecs_lock: Arc<Mutex<World>>,
         account: &Account,
         address: &ServerAddress,
-        resolved_address: &SocketAddr,
+        resolved_address: Option<&SocketAddr>,
+        opts: &JoinOpts,
         run_schedule_sender: mpsc::UnboundedSender<()>,
     ) -> Result<(Self, mpsc::UnboundedReceiver<Event>), JoinError> {
-        let conn = Connection::new(resolved_address).await?;
-        let (conn, game_profile) = Self::handshake(conn, account, address).await?;
+        // get the key pair for signing chat messages if we don't have it yet,
+        // since servers can require our messages to be signed
+        let should_request_certs = account.access_token.is_some()
+            && !matches!(account.certs.lock().as_ref(), Some(certs) if !certs.should_refresh());
+        if should_request_certs {
+            if let Err(e) = account.request_certs().await {
+                warn!("Couldn't get chat signing certificates, chat won't be signed: {e}");
+            }
+        }
+        let certs = account
+            .certs
+            .lock()
+            .clone()
+            .filter(|certs| !certs.is_expired());
+
+        let login_query_handlers = ecs_lock
+            .lock()
+            .get_resource::<LoginQueryHandlers>()
+            .cloned()
+            .unwrap_or_default();
+
+        let version = match opts.version {
+            VersionSelection::Current => ProtocolVersion::current(),
+            VersionSelection::Exact(version) => version,
+            VersionSelection::Detect => {
+                Self::detect_version(address, resolved_address, opts).await?
+            }
+        };
+
+        let mut conn = Self::connect(address, resolved_address, opts).await?;
+        conn.set_version(version);
+        let (conn, game_profile) = with_timeout(
+            opts.handshake_timeout,
+            Self::handshake(conn, account, address, &login_query_handlers),
+            JoinError::HandshakeTimeout,
+        )
+        .await?;
         let (read_conn, write_conn) = conn.into_split();
+        let connection_stats = read_conn.stats.clone();
 
         let (tx, rx) = mpsc::unbounded_channel();
 
@@ -265,10 +438,19 @@ impl Client {
         let (packet_writer_sender, packet_writer_receiver) = mpsc::unbounded_channel();
 
         // start receiving packets
-        let packet_receiver = packet_handling::PacketReceiver {
-            packets: Arc::new(Mutex::new(Vec::new())),
-            run_schedule_sender: run_schedule_sender.clone(),
+        let capture = match &opts.capture {
+            Some(path) => Some(PacketCapture::create(
+                path,
+                game_profile.clone(),
+                read_conn.version,
+            )?),
+            None => None,
         };
+        let packet_receiver = packet_handling::PacketReceiver::new(
+            run_schedule_sender.clone(),
+            capture,
+            opts.packet_error_policy,
+        );
 
         let read_packets_task = tokio::spawn(packet_receiver.clone().read_task(read_conn));
         let write_packets_task = tokio::spawn(
@@ -280,6 +462,7 @@ impl Client {
         let local_player = crate::local_player::LocalPlayer::new(
             entity,
             packet_writer_sender,
+            connection_stats,
             // default to an empty world, it'll be set correctly later when we
             // get the login packet
             Arc::new(RwLock::new(Instance::default())),
@@ -287,33 +470,143 @@ impl Client {
             write_packets_task,
         );
 
-        ecs.entity_mut(entity).insert(JoinedClientBundle {
+        ecs.entity_mut(entity).insert(JoinedClientBundle::new(
             local_player,
             packet_receiver,
-            game_profile: GameProfileComponent(game_profile),
-            physics_state: PhysicsState::default(),
-            local_player_events: LocalPlayerEvents(tx),
-            inventory: InventoryComponent::default(),
-            client_information: ClientInformation::default(),
-            tab_list: TabList::default(),
-            current_sequence_number: CurrentSequenceNumber::default(),
-            last_sent_direction: LastSentLookDirection::default(),
-            abilities: PlayerAbilities::default(),
-            _local: Local,
-        });
+            game_profile,
+            LocalPlayerEvents(tx),
+        ));
+        if let Some(certs) = certs {
+            ecs.entity_mut(entity)
+                .insert(ChatSigningSession::new(certs));
+        }
 
         Ok((client, rx))
     }
 
+    /// Open a connection to the server, either directly or through a proxy.
+    ///
+    /// If the address resolves to more than one socket address, they're tried
+    /// in order, and the connect timeout applies to each of them.
+    async fn connect(
+        address: &ServerAddress,
+        resolved_address: Option<&SocketAddr>,
+        opts: &JoinOpts,
+    ) -> Result<Connection<ClientboundHandshakePacket, ServerboundHandshakePacket>, JoinError> {
+        if let Some(proxy) = opts.proxy.as_ref().filter(|proxy| proxy.remote_dns) {
+            // the proxy looks up the IPs, but it doesn't know about SRV records
+            let resolver = opts
+                .resolver
+                .clone()
+                .unwrap_or_else(resolver::default_resolver);
+            let mut last_error = None;
+            for target in resolver::resolve_srv_with(&*resolver, address).await {
+                let conn = with_timeout(
+                    opts.connect_timeout,
+                    async {
+                        Ok(Connection::new_with_proxy(proxy, &target.host, target.port).await?)
+                    },
+                    JoinError::ConnectTimeout,
+                )
+                .await;
+                match conn {
+                    Ok(conn) => return Ok(conn),
+                    Err(e) => {
+                        warn!("Couldn't connect to {target:?} through the proxy: {e}");
+                        last_error = Some(e);
+                    }
+                }
+            }
+            // resolve_srv_with never returns an empty list
+            return Err(last_error.expect("There should be at least one address"));
+        }
+
+        let resolved_addresses = match (resolved_address, &opts.resolver) {
+            (Some(resolved_address), _) => vec![*resolved_address],
+            (None, Some(resolver)) => resolver::resolve_address_with(resolver, address).await?,
+            (None, None) => {
+                resolver::resolve_address_with(&resolver::default_resolver(), address).await?
+            }
+        };
+
+        let mut last_error = None;
+        for resolved_address in resolved_addresses {
+            let conn = with_timeout(
+                opts.connect_timeout,
+                async {
+                    Ok(match &opts.proxy {
+                        Some(proxy) => {
+                            Connection::new_with_proxy(
+                                proxy,
+                                &resolved_address.ip().to_string(),
+                                resolved_address.port(),
+                            )
+                            .await?
+                        }
+                        None => Connection::new(&resolved_address).await?,
+                    })
+                },
+                JoinError::ConnectTimeout,
+            )
+            .await;
+            match conn {
+                Ok(conn) => return Ok(conn),
+                Err(e) => {
+                    warn!("Couldn't connect to {resolved_address}: {e}");
+                    last_error = Some(e);
+                }
+            }
+        }
+        // resolve_address_with never returns an empty list
+        Err(last_error.expect("There should be at least one address"))
+    }
+
+    /// Ping the server to find the version it's on, or [`PROTOCOL_VERSION`]
+    /// if we don't support its version.
+    async fn detect_version(
+        address: &ServerAddress,
+        resolved_address: Option<&SocketAddr>,
+        opts: &JoinOpts,
+    ) -> Result<ProtocolVersion, JoinError> {
+        let conn = Self::connect(address, resolved_address, opts).await?;
+        let ping = with_timeout(
+            opts.handshake_timeout,
+            async { Ok(ping::ping_connection(conn, address).await?) },
+            JoinError::HandshakeTimeout,
+        )
+        .await?;
+
+        let protocol_version = ping.status.version.protocol;
+        match u32::try_from(protocol_version)
+            .ok()
+            .and_then(ProtocolVersion::from_protocol_version)
+        {
+            Some(version) => {
+                debug!("Joining {address:?} with version {version:?}");
+                Ok(version)
+            }
+            None => {
+                warn!(
+                    "{address:?} is on protocol version {protocol_version} ({}), which we don't \
+                    support, so we'll join with {PROTOCOL_VERSION}",
+                    ping.status.version.name
+                );
+                Ok(ProtocolVersion::current())
+            }
+        }
+    }
+
     /// Do a handshake with the server and get to the game state from the
     /// initial handshake state.
     ///
     /// This will also automatically refresh the account's access token if
-    /// it's expired.
+    /// it's expired. Custom queries from the server are answered by the
+    /// `login_query_handlers`.
     pub async fn handshake(
         mut conn: Connection<ClientboundHandshakePacket, ServerboundHandshakePacket>,
         account: &Account,
         address: &ServerAddress,
+        login_query_handlers: &LoginQueryHandlers,
     ) -> Result<
         (
             Connection<ClientboundGamePacket, ServerboundGamePacket>,
@@ -324,7 +617,7 @@ impl Client {
         // handshake
         conn.write(
             ClientIntentionPacket {
-                protocol_version: PROTOCOL_VERSION,
+                protocol_version: conn.writer.version.protocol_version(),
                 hostname: address.host.clone(),
                 port: address.port,
                 intention: ConnectionProtocol::Login,
@@ -358,13 +651,14 @@ impl Client {
 
                         while let Err(e) = {
                             let access_token = access_token.lock().clone();
-                            conn.authenticate(
+                            conn.authenticate_with_endpoints(
                                 &access_token,
                                 &account
                                     .uuid
                                     .expect("Uuid must be present if access token is present."),
                                 e.secret_key,
                                 &p,
+                                &account.endpoints,
                             )
                             .await
                         } {
@@ -413,10 +707,15 @@ impl Client {
                 }
                 ClientboundLoginPacket::CustomQuery(p) => {
                     debug!("Got custom query {:?}", p);
+                    let data = login_query_handlers.respond(
+                        &p.identifier,
+                        &p.data,
+                        &LoginQueryContext { account, address },
+                    );
                     conn.write(
                         ServerboundCustomQueryPacket {
                             transaction_id: p.transaction_id,
-                            data: None,
+                            data: data.map(Into::into),
                         }
                         .get(),
                     )
@@ -434,14 +733,17 @@ impl Client {
             .write_packet(packet);
     }
 
-    /// Disconnect this client from the server by ending all tasks.
+    /// Disconnect this client from the server.
     ///
-    /// The OwnedReadHalf for the TCP connection is in one of the tasks, so it
-    /// automatically closes the connection when that's dropped.
+    /// The packets that we already queued are still sent, and then the
+    /// connection is shut down. This sends [`Event::Disconnect`] with
+    /// [`DisconnectReason::Requested`].
     pub fn disconnect(&self) {
         self.ecs.lock().send_event(DisconnectEvent {
             entity: self.entity,
+            reason: DisconnectReason::Requested,
         });
+        self.run_schedule_sender.send(()).ok();
     }
 
     pub fn local_player<'a>(&'a self, ecs: &'a mut World) -> &'a LocalPlayer {
@@ -497,6 +799,20 @@ impl Client {
             .is_some()
     }
 
+    /// The number of packets from the server that were skipped because they
+    /// couldn't be read. See [`PacketErrorPolicy`].
+    pub fn dropped_packets(&self) -> usize {
+        self.query::<&PacketReceiver>(&mut self.ecs.lock())
+            .dropped_packets
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// How much we've sent and received on our connection to the server, by
+    /// packet type. See [`azalea_protocol::stats`] for exporting it.
+    pub fn connection_stats(&self) -> ConnectionStats {
+        self.query::<&ConnectionStats>(&mut self.ecs.lock()).clone()
+    }
+
     /// Tell the server we changed our game options (i.e. render distance, main
     /// hand). If this is not set before the login packet, the default will
     /// be sent.
@@ -558,9 +874,57 @@ pub struct JoinedClientBundle {
     pub current_sequence_number: CurrentSequenceNumber,
     pub last_sent_direction: LastSentLookDirection,
     pub abilities: PlayerAbilities,
+    pub command_suggestion_requests: CommandSuggestionRequests,
+    pub advancements: Advancements,
+    pub statistics: Statistics,
+    pub stats_requests: StatsRequests,
+    pub latency: Latency,
+    pub last_seen_messages: LastSeenMessagesTracker,
+    pub chat_sessions: PlayerChatSessions,
+    pub message_signature_cache: MessageSignatureCache,
+    pub chat_history: ChatHistory,
+    pub server_plugin_channels: ServerPluginChannels,
+    pub connection_stats: ConnectionStats,
     pub _local: Local,
 }
 
+impl JoinedClientBundle {
+    /// Make the components for a client that just logged in, with everything
+    /// else set to its default.
+    pub fn new(
+        local_player: LocalPlayer,
+        packet_receiver: PacketReceiver,
+        game_profile: GameProfile,
+        local_player_events: LocalPlayerEvents,
+    ) -> Self {
+        Self {
+            connection_stats: local_player.connection_stats.clone(),
+            local_player,
+            packet_receiver,
+            game_profile: GameProfileComponent(game_profile),
+            physics_state: PhysicsState::default(),
+            local_player_events,
+            inventory: InventoryComponent::default(),
+            client_information: ClientInformation::default(),
+            tab_list: TabList::default(),
+            current_sequence_number: CurrentSequenceNumber::default(),
+            last_sent_direction: LastSentLookDirection::default(),
+            abilities: PlayerAbilities::default(),
+            command_suggestion_requests: CommandSuggestionRequests::default(),
+            advancements: Advancements::default(),
+            statistics: Statistics::default(),
+            stats_requests: StatsRequests::default(),
+            latency: Latency::default(),
+            last_seen_messages: LastSeenMessagesTracker::default(),
+            chat_sessions: PlayerChatSessions::default(),
+            message_signature_cache: MessageSignatureCache::default(),
+            chat_history: ChatHistory::default(),
+            server_plugin_channels: ServerPluginChannels::default(),
+            _local: Local,
+        }
+    }
+}
+
 pub struct AzaleaPlugin;
 impl Plugin for AzaleaPlugin {
     fn build(&self, app: &mut App) {
@@ -578,7 +942,8 @@ impl Plugin for AzaleaPlugin {
         app.add_event::<SendPacketEvent>()
             .add_system(handle_send_packet_event);
 
-        app.init_resource::<InstanceContainer>();
+        app.init_resource::<InstanceContainer>()
+            .add_system(tick_instances.in_schedule(CoreSchedule::FixedUpdate));
     }
 }
 
@@ -609,6 +974,19 @@ pub fn init_ecs_app() -> App {
     app
 }
 
+/// Run the future, and return the given error if it takes longer than the
+/// timeout.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    future: impl Future<Output = Result<T, JoinError>>,
+    error: JoinError,
+) -> Result<T, JoinError> {
+    match timeout {
+        Some(timeout) => time::timeout(timeout, future).await.map_err(|_| error)?,
+        None => future.await,
+    }
+}
+
 /// Start running the ECS loop! You must create your `App` from [`init_ecs_app`]
 /// first.
 #[doc(hidden)]
@@ -717,6 +1095,107 @@ impl PluginGroup for DefaultPlugins {
             .add(DisconnectPlugin)
             .add(PlayerMovePlugin)
             .add(InteractPlugin)
+            .add(ResourcePackPlugin)
+            .add(PluginChannelsPlugin)
+            .add(CraftingPlugin)
+            .add(AdvancementsPlugin)
+            .add(LatencyPlugin)
             .add(TickBroadcastPlugin)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azalea_protocol::{
+        proxy::Proxy,
+        resolver::{SrvRecord, StaticResolver},
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::oneshot,
+    };
+
+    /// Start a server that accepts connections but never sends anything.
+    async fn silent_listener() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+        address
+    }
+
+    /// Start a SOCKS5 proxy that sends the host and port it was asked to
+    /// connect to, and then refuses to connect.
+    async fn refusing_socks5_proxy() -> (SocketAddr, oneshot::Receiver<(String, u16)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (target_sender, target_receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut greeting = [0; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting, [5, 1, 0]);
+            stream.write_all(&[5, 0]).await.unwrap();
+
+            let mut request = [0; 5];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request[..4], [5, 1, 0, 3]);
+            let mut host = vec![0; request[4] as usize];
+            stream.read_exact(&mut host).await.unwrap();
+            let port = stream.read_u16().await.unwrap();
+            target_sender
+                .send((String::from_utf8(host).unwrap(), port))
+                .unwrap();
+            // connection refused
+            stream
+                .write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+        });
+        (address, target_receiver)
+    }
+
+    #[tokio::test]
+    async fn test_remote_dns_proxy_gets_srv_target() {
+        let (proxy_address, target) = refusing_socks5_proxy().await;
+        let opts = JoinOpts::new()
+            .proxy(Proxy::socks5(proxy_address).with_remote_dns(true))
+            .resolver(StaticResolver::new().with_srv(
+                "example.com",
+                SrvRecord {
+                    priority: 0,
+                    weight: 0,
+                    port: 25570,
+                    target: "mc.example.com".to_string(),
+                },
+            ));
+
+        let result = Client::join_with_opts(&Account::offline("bot"), "example.com", &opts).await;
+        assert!(result.is_err());
+        assert_eq!(target.await.unwrap(), ("mc.example.com".to_string(), 25570));
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout() {
+        // the proxy never finishes its handshake
+        let opts = JoinOpts::new()
+            .proxy(Proxy::socks5(silent_listener().await))
+            .connect_timeout(Duration::from_millis(100));
+        let result = Client::join_with_opts(&Account::offline("bot"), "127.0.0.1", &opts).await;
+        assert!(matches!(result, Err(JoinError::ConnectTimeout)));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_timeout() {
+        let opts = JoinOpts::new().handshake_timeout(Duration::from_millis(100));
+        let result =
+            Client::join_with_opts(&Account::offline("bot"), silent_listener().await, &opts).await;
+        assert!(matches!(result, Err(JoinError::HandshakeTimeout)));
+    }
+}
@@ -0,0 +1,587 @@
+//! Keep track of the commands that the server says we can run, and complete
+//! partially typed commands.
+
+use std::{
+    any::Any,
+    collections::{BTreeSet, HashMap},
+    rc::Rc,
+    sync::Arc,
+};
+
+use azalea_brigadier::{
+    arguments::{
+        double_argument_type::Double, float_argument_type::Float, integer_argument_type::Integer,
+        long_argument_type::Long, string_argument_type::StringArgument, ArgumentType,
+    },
+    builder::{
+        argument_builder::ArgumentBuilderType, literal_argument_builder::Literal,
+        required_argument_builder::Argument,
+    },
+    command_dispatcher::CommandDispatcher,
+    context::{CommandContextBuilder, StringRange},
+    exceptions::{BuiltInExceptions, CommandSyntaxException},
+    string_reader::StringReader,
+    suggestion::{Suggestion, Suggestions},
+    tree::CommandNode,
+};
+use azalea_chat::FormattedText;
+use azalea_protocol::packets::game::{
+    clientbound_commands_packet::{BrigadierNodeStub, BrigadierParser, BrigadierString, NodeType},
+    serverbound_command_suggestion_packet::ServerboundCommandSuggestionPacket,
+};
+use bevy_ecs::component::Component;
+use log::warn;
+use parking_lot::RwLock;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{local_player::SendPacketEvent, Client, TickBroadcast};
+
+/// How many ticks [`Client::complete`] waits for the server to send
+/// suggestions before giving up.
+const SERVER_SUGGESTIONS_TIMEOUT_TICKS: u32 = 100;
+
+/// The command tree the server sent us, rebuilt into a [`CommandDispatcher`].
+///
+/// This is only present on local players after the server sent a `Commands`
+/// packet. None of the commands actually do anything when they're executed
+/// locally, but it can be used to check whether a command would be accepted
+/// by the server before sending it.
+///
+/// ```
+/// # use azalea_client::command_tree::CommandTree;
+/// # fn example(client: &azalea_client::Client) {
+/// let command_tree = client.component::<CommandTree>();
+/// if command_tree.validate("gamemode creative").is_ok() {
+///     client.send_command_packet("gamemode creative");
+/// }
+/// # }
+/// ```
+#[derive(Component, Clone)]
+pub struct CommandTree(Arc<CommandTreeInner>);
+
+struct CommandTreeInner {
+    dispatcher: CommandDispatcher<()>,
+    /// Every node in the tree, so we can break the reference cycles that
+    /// redirects make when the tree is dropped.
+    nodes: Vec<Arc<RwLock<CommandNode<()>>>>,
+    /// The parsers of the argument nodes that take a chat message, which have
+    /// to be signed when chat signing is enabled. Brigadier puts clones of the
+    /// nodes in the parse results, but the clones share their parser.
+    signed_arguments: Vec<Arc<dyn ArgumentType + Send + Sync>>,
+}
+
+impl CommandTree {
+    /// Rebuild the command tree from the nodes in a `Commands` packet.
+    pub fn from_stubs(entries: &[BrigadierNodeStub], root_index: u32) -> Self {
+        // create every node first so children and redirects can refer to each
+        // other by index
+        let nodes = entries
+            .iter()
+            .map(|stub| Arc::new(RwLock::new(node_from_stub(stub))))
+            .collect::<Vec<_>>();
+
+        for (stub, node) in entries.iter().zip(&nodes) {
+            let mut node = node.write();
+            for &child_index in &stub.children {
+                match nodes.get(child_index as usize) {
+                    Some(child) => node.add_child(child),
+                    None => warn!("Command node has a child that doesn't exist ({child_index})"),
+                }
+            }
+            if let Some(redirect_index) = stub.redirect_node {
+                node.redirect = nodes.get(redirect_index as usize).cloned();
+            }
+        }
+
+        let signed_arguments = entries
+            .iter()
+            .zip(&nodes)
+            .filter(|(stub, _)| {
+                matches!(
+                    stub.node_type,
+                    NodeType::Argument {
+                        parser: BrigadierParser::Message,
+                        ..
+                    }
+                )
+            })
+            .filter_map(|(_, node)| match &node.read().value {
+                ArgumentBuilderType::Argument(argument) => Some(argument.parser().clone()),
+                ArgumentBuilderType::Literal(_) => None,
+            })
+            .collect();
+
+        let mut dispatcher = CommandDispatcher::new();
+        if let Some(root) = nodes.get(root_index as usize) {
+            dispatcher.root = root.clone();
+        } else {
+            warn!("Command tree root index ({root_index}) doesn't exist");
+        }
+
+        Self(Arc::new(CommandTreeInner {
+            dispatcher,
+            nodes,
+            signed_arguments,
+        }))
+    }
+
+    /// The dispatcher containing the server's commands.
+    pub fn dispatcher(&self) -> &CommandDispatcher<()> {
+        &self.0.dispatcher
+    }
+
+    /// Check whether the server would accept the given command (without the
+    /// leading slash).
+    ///
+    /// This can't catch every error, since things like entity selectors and
+    /// NBT aren't parsed in detail.
+    pub fn validate(&self, command: &str) -> Result<(), CommandSyntaxException> {
+        self.0.dispatcher.execute(command, ()).map(|_| ())
+    }
+
+    /// Get the suggestions for what could be typed at the end of the given
+    /// partial command (without the leading slash) using only the command
+    /// tree.
+    ///
+    /// Suggestions for arguments like player names are only known by the
+    /// server, use [`Client::complete`] to ask the server for those.
+    pub fn suggestions(&self, partial: &str) -> Suggestions {
+        let parse = self.0.dispatcher.parse(partial.into(), ());
+        CommandDispatcher::get_completion_suggestions(parse)
+    }
+
+    /// Get the names and values of the arguments in the command (without the
+    /// leading slash) that should be signed, which are the ones that take a
+    /// chat message like in `/msg` or `/say`.
+    ///
+    /// Like in vanilla, arguments after a redirect back to the root (like in
+    /// `/execute run`) aren't included.
+    pub fn signed_arguments(&self, command: &str) -> Vec<(String, String)> {
+        let parse = self.0.dispatcher.parse(command.into(), ());
+
+        let mut arguments = self.collect_signed_arguments(command, &parse.context);
+        let mut context = &parse.context;
+        while let Some(child) = &context.child {
+            if Arc::ptr_eq(&child.root, &parse.context.root) {
+                break;
+            }
+            arguments.extend(self.collect_signed_arguments(command, child));
+            context = child;
+        }
+        arguments
+    }
+
+    fn collect_signed_arguments(
+        &self,
+        command: &str,
+        context: &CommandContextBuilder<()>,
+    ) -> Vec<(String, String)> {
+        let mut arguments = Vec::new();
+        for parsed in &context.nodes {
+            let node = parsed.node.read();
+            let ArgumentBuilderType::Argument(argument) = &node.value else {
+                continue;
+            };
+            if !self
+                .0
+                .signed_arguments
+                .iter()
+                .any(|parser| Arc::ptr_eq(parser, argument.parser()))
+            {
+                continue;
+            }
+            let name = argument.name.clone();
+            if let Some(argument) = context.arguments.get(&name) {
+                arguments.push((name, argument.range.get(command).to_string()));
+            }
+        }
+        arguments
+    }
+
+    /// The names of all the commands at the root of the tree.
+    pub fn root_commands(&self) -> Vec<String> {
+        let mut names = self
+            .0
+            .dispatcher
+            .root
+            .read()
+            .children
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// The namespaces of the namespaced commands at the root of the tree.
+    ///
+    /// Most server software also registers each command as
+    /// `namespace:command`, where the namespace is the name of the plugin that
+    /// added the command, so this is a good way to find out which plugins a
+    /// server has.
+    pub fn namespaces(&self) -> BTreeSet<String> {
+        self.root_commands()
+            .into_iter()
+            .filter_map(|name| {
+                name.split_once(':')
+                    .map(|(namespace, _)| namespace.to_string())
+            })
+            .collect()
+    }
+}
+
+impl Drop for CommandTreeInner {
+    fn drop(&mut self) {
+        // redirects (like `/execute run`) point back up the tree, so the nodes
+        // would never be freed if we didn't break the cycles
+        for node in &self.nodes {
+            let mut node = node.write();
+            node.redirect = None;
+            node.children.clear();
+            node.literals.clear();
+            node.arguments.clear();
+        }
+    }
+}
+
+fn node_from_stub(stub: &BrigadierNodeStub) -> CommandNode<()> {
+    let mut node = CommandNode::default();
+    node.value = match &stub.node_type {
+        NodeType::Root => ArgumentBuilderType::Literal(Literal::default()),
+        NodeType::Literal { name } => ArgumentBuilderType::Literal(Literal::new(name)),
+        NodeType::Argument { name, parser, .. } => {
+            ArgumentBuilderType::Argument(Argument::new(name, argument_type_for_parser(parser)))
+        }
+    };
+    if stub.is_executable {
+        node.command = Some(Arc::new(|_| 0));
+    }
+    node
+}
+
+/// Get the Brigadier argument type that should be used for parsing a
+/// [`BrigadierParser`] from the server.
+///
+/// Parsers that Brigadier doesn't have a type for are parsed loosely by
+/// [`ServerArgument`].
+pub fn argument_type_for_parser(parser: &BrigadierParser) -> Arc<dyn ArgumentType + Send + Sync> {
+    match parser {
+        BrigadierParser::Bool => Arc::new(false),
+        BrigadierParser::Float(n) => Arc::new(Float {
+            minimum: n.min,
+            maximum: n.max,
+        }),
+        BrigadierParser::Double(n) => Arc::new(Double {
+            minimum: n.min,
+            maximum: n.max,
+        }),
+        BrigadierParser::Integer(n) => Arc::new(Integer {
+            minimum: n.min,
+            maximum: n.max,
+        }),
+        BrigadierParser::Long(n) => Arc::new(Long {
+            minimum: n.min,
+            maximum: n.max,
+        }),
+        BrigadierParser::String(kind) => Arc::new(match kind {
+            BrigadierString::SingleWord => StringArgument::SingleWord,
+            BrigadierString::QuotablePhrase => StringArgument::QuotablePhrase,
+            BrigadierString::GreedyPhrase => StringArgument::GreedyPhrase,
+        }),
+        _ => Arc::new(ServerArgument {
+            parser: parser.clone(),
+        }),
+    }
+}
+
+/// An argument type for parsers that only the server knows how to parse, like
+/// entity selectors or block positions.
+///
+/// This only makes sure the right number of words are present (while keeping
+/// quoted strings and brackets together), so it's more lenient than the
+/// server.
+pub struct ServerArgument {
+    pub parser: BrigadierParser,
+}
+
+impl ServerArgument {
+    /// The number of space-separated words this argument takes up, or `None`
+    /// if it takes up the rest of the input.
+    fn word_count(&self) -> Option<usize> {
+        match self.parser {
+            BrigadierParser::Message => None,
+            BrigadierParser::BlockPos | BrigadierParser::Vec3 => Some(3),
+            BrigadierParser::ColumnPos | BrigadierParser::Vec2 | BrigadierParser::Rotation => {
+                Some(2)
+            }
+            _ => Some(1),
+        }
+    }
+}
+
+impl ArgumentType for ServerArgument {
+    fn parse(&self, reader: &mut StringReader) -> Result<Rc<dyn Any>, CommandSyntaxException> {
+        let start = reader.cursor;
+        let Some(word_count) = self.word_count() else {
+            let text = reader.remaining().to_string();
+            reader.cursor = reader.total_length();
+            return Ok(Rc::new(text));
+        };
+
+        for i in 0..word_count {
+            if i > 0 {
+                if !reader.can_read() || reader.peek() != ' ' {
+                    reader.cursor = start;
+                    return Err(BuiltInExceptions::DispatcherParseException {
+                        message: format!("Expected {word_count} values for {:?}", self.parser),
+                    }
+                    .create_with_context(reader));
+                }
+                reader.skip();
+            }
+            if !read_word(reader) {
+                reader.cursor = start;
+                return Err(BuiltInExceptions::DispatcherParseException {
+                    message: format!("Expected {:?}", self.parser),
+                }
+                .create_with_context(reader));
+            }
+        }
+
+        Ok(Rc::new(reader.string()[start..reader.cursor].to_string()))
+    }
+}
+
+/// Read until the next space that isn't in quotes or brackets. Returns false
+/// if nothing was read.
+fn read_word(reader: &mut StringReader) -> bool {
+    let start = reader.cursor;
+    let mut depth = 0usize;
+    let mut quote = None;
+    while reader.can_read() {
+        let c = reader.peek();
+        if let Some(q) = quote {
+            if c == '\\' {
+                reader.skip();
+            } else if c == q {
+                quote = None;
+            }
+        } else {
+            match c {
+                ' ' if depth == 0 => break,
+                '"' | '\'' => quote = Some(c),
+                '{' | '[' | '(' => depth += 1,
+                '}' | ']' | ')' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        if reader.can_read() {
+            reader.skip();
+        }
+    }
+    reader.cursor > start
+}
+
+/// A component that keeps track of the command suggestions we've requested
+/// from the server with [`Client::complete`].
+#[derive(Component, Default)]
+pub struct CommandSuggestionRequests {
+    next_id: u32,
+    /// The responses, keyed by transaction ID. A value of `None` means we
+    /// haven't received a response yet.
+    pub responses: HashMap<u32, Option<Suggestions<FormattedText>>>,
+}
+
+impl Client {
+    /// Get suggestions for what could be typed at the end of a partial command
+    /// (without the leading slash), like pressing tab in the vanilla client.
+    ///
+    /// Literals are completed locally from the [`CommandTree`] when possible,
+    /// otherwise we ask the server and wait up to 5 seconds for a response.
+    ///
+    /// ```
+    /// # async fn example(bot: azalea_client::Client) {
+    /// let suggestions = bot.complete("gamemode ").await;
+    /// for suggestion in suggestions.suggestions {
+    ///     println!("{}", suggestion.text);
+    /// }
+    /// # }
+    /// ```
+    pub async fn complete(&self, partial: &str) -> Suggestions<FormattedText> {
+        if let Some(command_tree) = self.get_component::<CommandTree>() {
+            let local = command_tree.suggestions(partial);
+            if !local.suggestions.is_empty() {
+                return Suggestions {
+                    range: local.range,
+                    suggestions: local
+                        .suggestions
+                        .into_iter()
+                        .map(|s| Suggestion {
+                            text: s.text,
+                            range: s.range,
+                            tooltip: s.tooltip.map(FormattedText::from),
+                        })
+                        .collect(),
+                };
+            }
+        }
+
+        self.request_server_suggestions(partial).await
+    }
+
+    /// Ask the server for suggestions for the given partial command (without
+    /// the leading slash) and wait for them. You usually want
+    /// [`Self::complete`] instead, since it avoids asking the server when it
+    /// doesn't have to.
+    ///
+    /// If the server doesn't respond within 5 seconds, empty suggestions are
+    /// returned.
+    pub async fn request_server_suggestions(&self, partial: &str) -> Suggestions<FormattedText> {
+        let (id, mut receiver) = {
+            let mut ecs = self.ecs.lock();
+            let receiver = ecs.resource::<TickBroadcast>().subscribe();
+            let mut requests = self.query::<&mut CommandSuggestionRequests>(&mut ecs);
+            let id = requests.next_id;
+            requests.next_id = requests.next_id.wrapping_add(1);
+            requests.responses.insert(id, None);
+            // sent as an event so the latency can be measured from when it's
+            // actually written
+            ecs.send_event(SendPacketEvent {
+                entity: self.entity,
+                packet: ServerboundCommandSuggestionPacket {
+                    id,
+                    // the vanilla client includes the slash
+                    command: format!("/{partial}"),
+                }
+                .get(),
+            });
+            (id, receiver)
+        };
+
+        let mut ticks = 0;
+        loop {
+            match receiver.recv().await {
+                // if we missed some ticks we still want to check for the response
+                Ok(()) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => {
+                    let mut ecs = self.ecs.lock();
+                    let mut requests = self.query::<&mut CommandSuggestionRequests>(&mut ecs);
+                    requests.responses.remove(&id);
+                    break;
+                }
+            }
+            let mut ecs = self.ecs.lock();
+            let mut requests = self.query::<&mut CommandSuggestionRequests>(&mut ecs);
+            if let Some(Some(_)) = requests.responses.get(&id) {
+                let mut suggestions = requests.responses.remove(&id).flatten().unwrap();
+                // the server's range includes the slash, ours doesn't
+                suggestions.range = shift_range(&suggestions.range);
+                for suggestion in &mut suggestions.suggestions {
+                    suggestion.range = shift_range(&suggestion.range);
+                }
+                return suggestions;
+            }
+            ticks += 1;
+            if ticks >= SERVER_SUGGESTIONS_TIMEOUT_TICKS {
+                requests.responses.remove(&id);
+                break;
+            }
+        }
+
+        Suggestions::default()
+    }
+}
+
+fn shift_range(range: &StringRange) -> StringRange {
+    StringRange::between(
+        range.start().saturating_sub(1),
+        range.end().saturating_sub(1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(name: &str, children: Vec<u32>, redirect_node: Option<u32>) -> BrigadierNodeStub {
+        BrigadierNodeStub {
+            is_executable: false,
+            children,
+            redirect_node,
+            node_type: NodeType::Literal {
+                name: name.to_string(),
+            },
+        }
+    }
+
+    fn argument(name: &str, parser: BrigadierParser, children: Vec<u32>) -> BrigadierNodeStub {
+        BrigadierNodeStub {
+            is_executable: children.is_empty(),
+            children,
+            redirect_node: None,
+            node_type: NodeType::Argument {
+                name: name.to_string(),
+                parser,
+                suggestions_type: None,
+            },
+        }
+    }
+
+    /// `/msg <targets> <message>`, `/tell` (which redirects to `/msg`),
+    /// `/say <message>` and `/execute run ...`
+    fn command_tree() -> CommandTree {
+        let entries = vec![
+            BrigadierNodeStub {
+                is_executable: false,
+                children: vec![1, 4, 5, 7],
+                redirect_node: None,
+                node_type: NodeType::Root,
+            },
+            literal("msg", vec![2], None),
+            argument("targets", BrigadierParser::GameProfile, vec![3]),
+            argument("message", BrigadierParser::Message, vec![]),
+            literal("tell", vec![], Some(1)),
+            literal("say", vec![6], None),
+            argument("message", BrigadierParser::Message, vec![]),
+            literal("execute", vec![8], None),
+            literal("run", vec![], Some(0)),
+        ];
+        CommandTree::from_stubs(&entries, 0)
+    }
+
+    #[test]
+    fn test_signed_arguments() {
+        let tree = command_tree();
+        assert_eq!(
+            tree.signed_arguments("msg bot hello there"),
+            vec![("message".to_string(), "hello there".to_string())]
+        );
+        assert_eq!(
+            tree.signed_arguments("say hi"),
+            vec![("message".to_string(), "hi".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_signed_arguments_through_redirect() {
+        let tree = command_tree();
+        assert_eq!(
+            tree.signed_arguments("tell bot hi"),
+            vec![("message".to_string(), "hi".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_signed_arguments_after_redirect_to_root() {
+        // vanilla doesn't sign arguments after `/execute run` either
+        let tree = command_tree();
+        assert_eq!(tree.signed_arguments("execute run say hi"), vec![]);
+    }
+
+    #[test]
+    fn test_no_signed_arguments() {
+        let tree = command_tree();
+        assert_eq!(tree.signed_arguments("msg bot"), vec![]);
+        assert_eq!(tree.signed_arguments("unknown hello"), vec![]);
+    }
+}
@@ -0,0 +1,529 @@
+//! Keep track of the server's recipes and craft items.
+
+use std::collections::{HashMap, HashSet};
+
+use azalea_core::ResourceLocation;
+use azalea_inventory::{
+    operations::{ClickOperation, PickupClick, QuickMoveClick},
+    ItemSlot, Menu, Player,
+};
+use azalea_protocol::packets::game::{
+    clientbound_recipe_packet::RecipeBookSettings,
+    clientbound_update_recipes_packet::{Ingredient, Recipe, RecipeData},
+    serverbound_place_recipe_packet::ServerboundPlaceRecipePacket,
+};
+use azalea_registry::Item;
+use bevy_app::{App, Plugin};
+use bevy_ecs::{
+    event::EventReader,
+    system::{ResMut, Resource},
+};
+use thiserror::Error;
+
+use crate::{
+    disconnect::DisconnectEvent,
+    inventory::{ContainerClickEvent, InventoryComponent},
+    Client, TickBroadcast,
+};
+
+/// How many ticks we wait for the server to update the crafting result before
+/// giving up.
+const CRAFT_TIMEOUT_TICKS: u32 = 20;
+
+pub struct CraftingPlugin;
+impl Plugin for CraftingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RecipeBook>()
+            .add_system(remove_unlocked_recipes_on_disconnect);
+    }
+}
+
+/// A resource that contains every recipe the server told us about, and the
+/// recipes that each local player has unlocked.
+#[derive(Resource, Default, Debug)]
+pub struct RecipeBook {
+    /// All the recipes on the server, from the `UpdateRecipes` packet.
+    pub recipes: HashMap<ResourceLocation, Recipe>,
+    /// The recipes that each local player has in their recipe book.
+    pub unlocked: HashMap<bevy_ecs::entity::Entity, UnlockedRecipes>,
+}
+
+/// The recipes that a player has unlocked in their recipe book, from the
+/// `Recipe` packet.
+#[derive(Default, Debug, Clone)]
+pub struct UnlockedRecipes {
+    pub recipes: HashSet<ResourceLocation>,
+    /// Recipes that were unlocked but haven't been looked at yet. The vanilla
+    /// client shows these with a "new" marker.
+    pub highlighted: HashSet<ResourceLocation>,
+    pub settings: Option<RecipeBookSettings>,
+}
+
+impl RecipeBook {
+    /// Get the crafting recipes (shaped and shapeless) that result in the
+    /// given item, sorted by their identifier.
+    pub fn crafting_recipes_for(&self, item: Item) -> Vec<(&ResourceLocation, &Recipe)> {
+        let mut recipes = self
+            .recipes
+            .iter()
+            .filter(|(_, recipe)| {
+                CraftingPattern::from_recipe(recipe)
+                    .map(|pattern| pattern.result.kind() == item)
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+        recipes.sort_by_key(|(id, _)| id.to_string());
+        recipes
+    }
+
+    /// Whether the given player has the recipe unlocked in their recipe book.
+    pub fn is_unlocked(&self, entity: bevy_ecs::entity::Entity, recipe: &ResourceLocation) -> bool {
+        self.unlocked
+            .get(&entity)
+            .map(|unlocked| unlocked.recipes.contains(recipe))
+            .unwrap_or(false)
+    }
+}
+
+fn remove_unlocked_recipes_on_disconnect(
+    mut events: EventReader<DisconnectEvent>,
+    mut recipe_book: ResMut<RecipeBook>,
+) {
+    for event in events.iter() {
+        recipe_book.unlocked.remove(&event.entity);
+    }
+}
+
+/// A shaped or shapeless crafting recipe.
+struct CraftingPattern<'a> {
+    /// The width and height of the pattern, or `None` if the recipe is
+    /// shapeless.
+    size: Option<(usize, usize)>,
+    /// The ingredients in the pattern, row by row. Empty ingredients are empty
+    /// spaces in the grid.
+    ingredients: &'a [Ingredient],
+    result: &'a ItemSlot,
+}
+
+impl<'a> CraftingPattern<'a> {
+    fn from_recipe(recipe: &'a Recipe) -> Option<Self> {
+        match &recipe.data {
+            RecipeData::CraftingShaped(shaped) => Some(Self {
+                size: Some((shaped.width, shaped.height)),
+                ingredients: &shaped.ingredients,
+                result: &shaped.result,
+            }),
+            RecipeData::CraftingShapeless(shapeless) => Some(Self {
+                size: None,
+                ingredients: &shapeless.ingredients,
+                result: &shapeless.result,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Get the grid slot (relative to the start of the crafting grid) for
+    /// each ingredient, or `None` if the pattern doesn't fit in the grid.
+    fn grid_positions(&self, grid_width: usize) -> Option<Vec<(usize, &'a Ingredient)>> {
+        // shapeless recipes can go anywhere, so they just fill the grid in order
+        let (width, height) = self.size.unwrap_or((grid_width, 1));
+        if width > grid_width
+            || height > grid_width
+            || self.ingredients.len() > grid_width * grid_width
+        {
+            return None;
+        }
+        Some(
+            self.ingredients
+                .iter()
+                .enumerate()
+                .filter(|(_, ingredient)| !ingredient.allowed.is_empty())
+                .map(|(i, ingredient)| ((i / width) * grid_width + i % width, ingredient))
+                .collect(),
+        )
+    }
+}
+
+fn ingredient_matches(ingredient: &Ingredient, item: &ItemSlot) -> bool {
+    item.is_present()
+        && ingredient
+            .allowed
+            .iter()
+            .any(|allowed| allowed.kind() == item.kind())
+}
+
+/// The crafting grid in the menu that's currently open.
+struct CraftingGrid {
+    container_id: u8,
+    width: usize,
+    result_slot: usize,
+    first_grid_slot: usize,
+}
+
+impl CraftingGrid {
+    fn from_inventory(inventory: &InventoryComponent) -> Option<Self> {
+        match inventory.menu() {
+            Menu::Player(_) => Some(Self {
+                container_id: inventory.id,
+                width: 2,
+                result_slot: Player::CRAFT_RESULT_SLOT,
+                first_grid_slot: *Player::CRAFT_SLOTS.start(),
+            }),
+            Menu::Crafting { .. } => Some(Self {
+                container_id: inventory.id,
+                width: 3,
+                result_slot: Menu::CRAFTING_RESULT_SLOT,
+                first_grid_slot: *Menu::CRAFTING_GRID_SLOTS.start(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn slots(&self) -> std::ops::Range<usize> {
+        self.first_grid_slot..self.first_grid_slot + self.width * self.width
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CraftError {
+    #[error("There's no crafting recipe for {0}")]
+    NoRecipe(Item),
+    #[error("The recipe for {0} needs a crafting table")]
+    NeedsCraftingTable(Item),
+    #[error("Not enough ingredients to craft {0}")]
+    MissingIngredients(Item),
+    #[error("The open container doesn't have a crafting grid")]
+    NoCraftingGrid,
+    #[error("The server didn't update the crafting result")]
+    Timeout,
+}
+
+impl Client {
+    /// Craft at least `count` of an item using the player's 2x2 crafting grid,
+    /// or the 3x3 grid if a crafting table is open. Returns the number of
+    /// items that were crafted, which may be more than `count` since some
+    /// recipes make multiple items at once.
+    ///
+    /// If the recipe is unlocked in the player's recipe book then we let the
+    /// server fill the grid, otherwise the ingredients are moved one at a time
+    /// with clicks.
+    ///
+    /// ```
+    /// # async fn example(bot: azalea_client::Client) -> Result<(), azalea_client::crafting::CraftError> {
+    /// bot.craft(azalea_registry::Item::OakPlanks, 4).await?;
+    /// bot.craft(azalea_registry::Item::CraftingTable, 1).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn craft(&self, item: Item, count: u32) -> Result<u32, CraftError> {
+        let mut crafted = 0;
+        while crafted < count {
+            crafted += self.craft_once(item).await?;
+        }
+        Ok(crafted)
+    }
+
+    /// Craft the item once, returning how many items the recipe made.
+    async fn craft_once(&self, item: Item) -> Result<u32, CraftError> {
+        let (grid, operations, place_recipe) = {
+            let mut ecs = self.ecs.lock();
+            let inventory = self.query::<&InventoryComponent>(&mut ecs);
+            let grid = CraftingGrid::from_inventory(inventory).ok_or(CraftError::NoCraftingGrid)?;
+            // anything that was left in the grid is moved back to the inventory
+            // first, so plan the clicks as if that already happened
+            let mut menu = inventory.menu().clone();
+            let occupied_grid_slots = clear_grid(&mut menu, &grid);
+            let recipe_book = ecs.resource::<RecipeBook>();
+
+            let recipes = recipe_book.crafting_recipes_for(item);
+            if recipes.is_empty() {
+                return Err(CraftError::NoRecipe(item));
+            }
+
+            let mut fits_grid = false;
+            let mut chosen = None;
+            for (id, recipe) in recipes {
+                let pattern = CraftingPattern::from_recipe(recipe).unwrap();
+                let Some(positions) = pattern.grid_positions(grid.width) else {
+                    continue;
+                };
+                fits_grid = true;
+                if let Some(operations) = plan_clicks(&menu, &grid, &positions) {
+                    chosen = Some((id.clone(), operations));
+                    break;
+                }
+            }
+            let Some((recipe_id, mut operations)) = chosen else {
+                return Err(if fits_grid {
+                    CraftError::MissingIngredients(item)
+                } else {
+                    CraftError::NeedsCraftingTable(item)
+                });
+            };
+
+            let mut clicks = occupied_grid_slots
+                .into_iter()
+                .map(|slot| QuickMoveClick::Left { slot: slot as u16 }.into())
+                .collect::<Vec<ClickOperation>>();
+            let place_recipe = if recipe_book.is_unlocked(self.entity, &recipe_id) {
+                operations.clear();
+                Some(recipe_id)
+            } else {
+                None
+            };
+            clicks.extend(operations);
+            (grid, clicks, place_recipe)
+        };
+
+        for operation in operations {
+            self.ecs.lock().send_event(ContainerClickEvent {
+                entity: self.entity,
+                window_id: grid.container_id,
+                operation,
+            });
+        }
+        if let Some(recipe) = place_recipe {
+            self.write_packet(
+                ServerboundPlaceRecipePacket {
+                    container_id: grid.container_id,
+                    recipe,
+                    shift_down: false,
+                }
+                .get(),
+            );
+        }
+
+        // wait for the server to tell us the result, and then take it
+        let result = self
+            .wait_for_crafting_slot(grid.result_slot, |slot| slot.is_present())
+            .await?;
+        self.ecs.lock().send_event(ContainerClickEvent {
+            entity: self.entity,
+            window_id: grid.container_id,
+            operation: QuickMoveClick::Left {
+                slot: grid.result_slot as u16,
+            }
+            .into(),
+        });
+        self.wait_for_crafting_slot(grid.result_slot, |slot| slot.is_empty())
+            .await?;
+
+        Ok(result.count() as u32)
+    }
+
+    /// Wait until the given slot in the open menu matches the predicate, and
+    /// return its contents.
+    async fn wait_for_crafting_slot(
+        &self,
+        slot: usize,
+        predicate: impl Fn(&ItemSlot) -> bool,
+    ) -> Result<ItemSlot, CraftError> {
+        let mut receiver = {
+            let ecs = self.ecs.lock();
+            let tick_broadcast = ecs.resource::<TickBroadcast>();
+            tick_broadcast.subscribe()
+        };
+        let mut ticks = 0;
+        while receiver.recv().await.is_ok() {
+            let current = self.menu().slot(slot).cloned().unwrap_or(ItemSlot::Empty);
+            if predicate(&current) {
+                return Ok(current);
+            }
+            ticks += 1;
+            if ticks >= CRAFT_TIMEOUT_TICKS {
+                break;
+            }
+        }
+        Err(CraftError::Timeout)
+    }
+}
+
+/// Shift-click everything in the crafting grid back into the inventory, and
+/// return the slots that had items in them.
+fn clear_grid(menu: &mut Menu, grid: &CraftingGrid) -> Vec<usize> {
+    let occupied = grid
+        .slots()
+        .filter(|&slot| menu.slot(slot).map(|s| s.is_present()).unwrap_or(false))
+        .collect::<Vec<_>>();
+    for &slot in &occupied {
+        menu.quick_move_stack(slot);
+    }
+    occupied
+}
+
+/// Get the clicks that put one of each ingredient in its place in the grid, or
+/// `None` if we don't have enough ingredients in our inventory.
+fn plan_clicks(
+    menu: &Menu,
+    grid: &CraftingGrid,
+    positions: &[(usize, &Ingredient)],
+) -> Option<Vec<ClickOperation>> {
+    // how many items we've taken from each slot so far
+    let mut taken: HashMap<usize, i8> = HashMap::new();
+    let mut operations = Vec::new();
+    for &(grid_index, ingredient) in positions {
+        let source = menu.player_slots_range().find(|&i| {
+            let item = menu.slot(i).unwrap();
+            ingredient_matches(ingredient, item) && item.count() > *taken.get(&i).unwrap_or(&0)
+        })?;
+        *taken.entry(source).or_default() += 1;
+
+        // pick up the stack, drop one item in the grid, and put the rest back
+        let target = (grid.first_grid_slot + grid_index) as u16;
+        operations.push(
+            PickupClick::Left {
+                slot: Some(source as u16),
+            }
+            .into(),
+        );
+        operations.push(PickupClick::Right { slot: Some(target) }.into());
+        operations.push(
+            PickupClick::Left {
+                slot: Some(source as u16),
+            }
+            .into(),
+        );
+    }
+    Some(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azalea_inventory::ItemSlotData;
+
+    fn item(kind: Item, count: i8) -> ItemSlot {
+        ItemSlot::Present(ItemSlotData {
+            kind,
+            count,
+            nbt: Default::default(),
+        })
+    }
+
+    fn ingredient(kind: Item) -> Ingredient {
+        Ingredient {
+            allowed: vec![item(kind, 1)],
+        }
+    }
+
+    fn player_grid() -> CraftingGrid {
+        CraftingGrid {
+            container_id: 0,
+            width: 2,
+            result_slot: Player::CRAFT_RESULT_SLOT,
+            first_grid_slot: *Player::CRAFT_SLOTS.start(),
+        }
+    }
+
+    /// Turn the clicks into (left click, slot) pairs so they can be compared.
+    fn pickups(operations: &[ClickOperation]) -> Vec<(bool, u16)> {
+        operations
+            .iter()
+            .map(|operation| match operation {
+                ClickOperation::Pickup(PickupClick::Left { slot: Some(slot) }) => (true, *slot),
+                ClickOperation::Pickup(PickupClick::Right { slot: Some(slot) }) => (false, *slot),
+                operation => panic!("Unexpected click: {operation:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_grid_positions_shaped() {
+        let ingredients = [
+            ingredient(Item::OakPlanks),
+            Ingredient { allowed: vec![] },
+            ingredient(Item::OakPlanks),
+            ingredient(Item::OakPlanks),
+        ];
+        let result = item(Item::Stick, 4);
+        let pattern = CraftingPattern {
+            size: Some((2, 2)),
+            ingredients: &ingredients,
+            result: &result,
+        };
+        // the empty ingredient is skipped
+        let positions = pattern
+            .grid_positions(3)
+            .unwrap()
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![0, 3, 4]);
+        let positions = pattern
+            .grid_positions(2)
+            .unwrap()
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn test_grid_positions_too_big() {
+        let ingredients = vec![ingredient(Item::OakPlanks); 9];
+        let result = item(Item::Chest, 1);
+        let shaped = CraftingPattern {
+            size: Some((3, 3)),
+            ingredients: &ingredients,
+            result: &result,
+        };
+        assert!(shaped.grid_positions(2).is_none());
+        assert!(shaped.grid_positions(3).is_some());
+
+        let shapeless = CraftingPattern {
+            size: None,
+            ingredients: &ingredients,
+            result: &result,
+        };
+        assert!(shapeless.grid_positions(2).is_none());
+        assert_eq!(shapeless.grid_positions(3).unwrap().len(), 9);
+    }
+
+    #[test]
+    fn test_plan_clicks() {
+        let mut menu = Menu::Player(Player::default());
+        *menu.slot_mut(9).unwrap() = item(Item::OakPlanks, 1);
+        *menu.slot_mut(10).unwrap() = item(Item::OakPlanks, 1);
+        let planks = ingredient(Item::OakPlanks);
+        let grid = player_grid();
+
+        let operations = plan_clicks(&menu, &grid, &[(0, &planks), (2, &planks)]).unwrap();
+        assert_eq!(
+            pickups(&operations),
+            vec![
+                (true, 9),
+                (false, 1),
+                (true, 9),
+                (true, 10),
+                (false, 3),
+                (true, 10),
+            ]
+        );
+
+        // there aren't enough planks for a third one
+        assert!(plan_clicks(&menu, &grid, &[(0, &planks), (1, &planks), (2, &planks)]).is_none());
+    }
+
+    #[test]
+    fn test_plan_clicks_after_clearing_grid() {
+        // the only planks are in the grid from the last time we crafted
+        let mut menu = Menu::Player(Player::default());
+        *menu.slot_mut(2).unwrap() = item(Item::OakPlanks, 2);
+        let planks = ingredient(Item::OakPlanks);
+        let grid = player_grid();
+        let positions = [(0, &planks), (2, &planks)];
+        assert!(plan_clicks(&menu, &grid, &positions).is_none());
+
+        assert_eq!(clear_grid(&mut menu, &grid), vec![2]);
+        assert!(menu.slot(2).unwrap().is_empty());
+        let operations = plan_clicks(&menu, &grid, &positions).unwrap();
+        let sources = pickups(&operations)
+            .into_iter()
+            .filter(|&(left, _)| left)
+            .map(|(_, slot)| slot)
+            .collect::<Vec<_>>();
+        assert!(sources
+            .iter()
+            .all(|&slot| menu.player_slots_range().contains(&(slot as usize))));
+    }
+}
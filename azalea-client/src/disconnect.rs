@@ -1,5 +1,9 @@
 //! Disconnect a client from the server.
 
+use std::{fmt, sync::Arc, time::Duration};
+
+use azalea_chat::FormattedText;
+use azalea_protocol::read::ReadPacketError;
 use bevy_app::{App, CoreSet, Plugin};
 use bevy_ecs::{
     component::Component,
@@ -11,7 +15,20 @@ use bevy_ecs::{
 };
 use derive_more::Deref;
 
-use crate::{client::JoinedClientBundle, LocalPlayer};
+use crate::{
+    client::JoinedClientBundle,
+    packet_handling::{PacketError, PacketReceiver},
+    LocalPlayer,
+};
+
+/// We disconnect if the server doesn't send us anything for this long. The
+/// server sends a keep-alive packet every 15 seconds, so this only happens if
+/// the connection stopped working.
+pub const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long we wait for the packets that are still queued to be sent when we
+/// disconnect, before the connection is closed anyways.
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct DisconnectPlugin;
 impl Plugin for DisconnectPlugin {
@@ -31,6 +48,44 @@ impl Plugin for DisconnectPlugin {
 /// An event sent when a client is getting disconnected.
 pub struct DisconnectEvent {
     pub entity: Entity,
+    pub reason: DisconnectReason,
+}
+
+/// Why a client was disconnected from the server.
+#[derive(Clone, Debug)]
+pub enum DisconnectReason {
+    /// The server kicked us. This is the message it sent.
+    Kicked(FormattedText),
+    /// The server didn't send us anything for [`KEEP_ALIVE_TIMEOUT`].
+    TimedOut,
+    /// The connection was closed or reset without the server telling us why.
+    ConnectionLost(Arc<ReadPacketError>),
+    /// The server sent a packet that we couldn't read, and the
+    /// [`PacketErrorPolicy`] said to disconnect.
+    ///
+    /// [`PacketErrorPolicy`]: crate::packet_handling::PacketErrorPolicy
+    PacketError(Arc<PacketError>),
+    /// The server sent a bundle with too many packets in it.
+    BundleTooBig,
+    /// We disconnected with [`Client::disconnect`](crate::Client::disconnect).
+    Requested,
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisconnectReason::Kicked(reason) => write!(f, "Kicked: {reason}"),
+            DisconnectReason::TimedOut => write!(f, "Timed out"),
+            DisconnectReason::ConnectionLost(error) => write!(f, "Connection lost: {error}"),
+            DisconnectReason::PacketError(error) => {
+                write!(f, "Couldn't read a packet: {}", error.error)
+            }
+            DisconnectReason::BundleTooBig => {
+                write!(f, "The server sent a bundle that was too big")
+            }
+            DisconnectReason::Requested => write!(f, "Disconnected"),
+        }
+    }
 }
 
 /// System that removes the [`JoinedClientBundle`] from the entity when it
@@ -39,7 +94,7 @@ pub fn remove_components_from_disconnected_players(
     mut commands: Commands,
     mut events: EventReader<DisconnectEvent>,
 ) {
-    for DisconnectEvent { entity } in events.iter() {
+    for DisconnectEvent { entity, .. } in events.iter() {
         commands.entity(*entity).remove::<JoinedClientBundle>();
     }
 }
@@ -58,13 +113,189 @@ fn update_read_packets_task_running_component(
             .insert(ReadPacketsTaskRunning(running));
     }
 }
-fn disconnect_on_read_packets_ended(
-    local_player: Query<(Entity, &ReadPacketsTaskRunning), Changed<ReadPacketsTaskRunning>>,
+/// Send a [`DisconnectEvent`] when the task that reads packets ends, with the
+/// reason that it ended.
+pub fn disconnect_on_read_packets_ended(
+    local_player: Query<
+        (Entity, &ReadPacketsTaskRunning, &PacketReceiver),
+        Changed<ReadPacketsTaskRunning>,
+    >,
     mut disconnect_events: EventWriter<DisconnectEvent>,
 ) {
-    for (entity, &read_packets_task_running) in &local_player {
+    for (entity, &read_packets_task_running, packet_receiver) in &local_player {
         if !*read_packets_task_running {
-            disconnect_events.send(DisconnectEvent { entity });
+            let reason = packet_receiver
+                .disconnect_reason
+                .lock()
+                .take()
+                .unwrap_or_else(|| {
+                    // the task panicked or was aborted
+                    DisconnectReason::ConnectionLost(Arc::new(ReadPacketError::ConnectionClosed))
+                });
+            disconnect_events.send(DisconnectEvent { entity, reason });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{packet_handling::PacketErrorPolicy, test_utils::join_test_server, Event};
+    use azalea_protocol::{
+        connect::Connection,
+        packets::game::{
+            clientbound_disconnect_packet::ClientboundDisconnectPacket,
+            serverbound_keep_alive_packet::ServerboundKeepAlivePacket, ClientboundGamePacket,
+            ServerboundGamePacket,
+        },
+        stats::ConnectionStats,
+    };
+    use azalea_world::Instance;
+    use parking_lot::RwLock;
+    use tokio::sync::mpsc;
+
+    fn keep_alive(id: u64) -> ServerboundGamePacket {
+        ServerboundKeepAlivePacket { id }.get()
+    }
+
+    async fn next_disconnect(rx: &mut mpsc::UnboundedReceiver<Event>) -> DisconnectReason {
+        loop {
+            if let Event::Disconnect(reason) = rx.recv().await.unwrap() {
+                return reason;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queued_packets_are_flushed() {
+        let (client_stream, server_stream) = tokio::io::duplex(1024);
+        let (read_conn, write_conn) =
+            Connection::<ClientboundGamePacket, ServerboundGamePacket, _, _>::wrap_stream(
+                client_stream,
+            )
+            .into_split();
+        let mut server =
+            Connection::<ServerboundGamePacket, ClientboundGamePacket, _, _>::wrap_stream(
+                server_stream,
+            );
+
+        let (run_schedule_sender, _run_schedule_receiver) = mpsc::unbounded_channel();
+        let packet_receiver =
+            PacketReceiver::new(run_schedule_sender, None, PacketErrorPolicy::SKIP);
+        let (packet_writer_sender, packet_writer_receiver) = mpsc::unbounded_channel();
+        let local_player = LocalPlayer::new(
+            Entity::from_raw(0),
+            packet_writer_sender,
+            ConnectionStats::default(),
+            Arc::new(RwLock::new(Instance::default())),
+            tokio::spawn(packet_receiver.clone().read_task(read_conn)),
+            tokio::spawn(
+                packet_receiver
+                    .clone()
+                    .write_task(write_conn, packet_writer_receiver),
+            ),
+        );
+
+        // the duplex buffer is too small for all of these, so most of them
+        // are still queued when we disconnect
+        for id in 0..200 {
+            local_player.write_packet(keep_alive(id));
         }
+        // this is what happens when the JoinedClientBundle is removed
+        drop(local_player);
+
+        for id in 0..200 {
+            match server.read().await.unwrap() {
+                ServerboundGamePacket::KeepAlive(p) => assert_eq!(p.id, id),
+                packet => panic!("Expected a keep alive packet, got {packet:?}"),
+            }
+        }
+        assert!(matches!(
+            *server.read().await.unwrap_err(),
+            ReadPacketError::ConnectionClosed
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect() {
+        let (client, mut rx, mut player) = join_test_server().await;
+        for id in 0..10 {
+            client.write_packet(keep_alive(id));
+        }
+        client.disconnect();
+
+        let mut ids = Vec::new();
+        loop {
+            match player.connection.read().await {
+                Ok(ServerboundGamePacket::KeepAlive(p)) => ids.push(p.id),
+                Ok(_) => {}
+                Err(error) => {
+                    assert!(matches!(*error, ReadPacketError::ConnectionClosed));
+                    break;
+                }
+            }
+        }
+        assert_eq!(ids, (0..10).collect::<Vec<_>>());
+        assert!(matches!(
+            next_disconnect(&mut rx).await,
+            DisconnectReason::Requested
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_kicked() {
+        let (_client, mut rx, mut player) = join_test_server().await;
+        player
+            .connection
+            .write(
+                ClientboundDisconnectPacket {
+                    reason: "Server closed".into(),
+                }
+                .get(),
+            )
+            .await
+            .unwrap();
+
+        let reason = next_disconnect(&mut rx).await;
+        let DisconnectReason::Kicked(message) = &reason else {
+            panic!("Expected to be kicked, got {reason:?}");
+        };
+        assert_eq!(message.to_string(), "Server closed");
+        assert_eq!(reason.to_string(), "Kicked: Server closed");
+    }
+
+    #[tokio::test]
+    async fn test_connection_lost() {
+        let (_client, mut rx, player) = join_test_server().await;
+        drop(player);
+
+        let reason = next_disconnect(&mut rx).await;
+        assert!(matches!(
+            reason,
+            DisconnectReason::ConnectionLost(ref error)
+                if matches!(**error, ReadPacketError::ConnectionClosed)
+        ));
+    }
+
+    // the ECS uses real time, so this only runs the read task
+    #[tokio::test(start_paused = true)]
+    async fn test_timed_out() {
+        let (client_stream, _server_stream) = tokio::io::duplex(1024);
+        let (read_conn, _write_conn) =
+            Connection::<ClientboundGamePacket, ServerboundGamePacket, _, _>::wrap_stream(
+                client_stream,
+            )
+            .into_split();
+        let (run_schedule_sender, _run_schedule_receiver) = mpsc::unbounded_channel();
+        let packet_receiver =
+            PacketReceiver::new(run_schedule_sender, None, PacketErrorPolicy::SKIP);
+
+        let start = tokio::time::Instant::now();
+        packet_receiver.clone().read_task(read_conn).await;
+        assert_eq!(start.elapsed(), KEEP_ALIVE_TIMEOUT);
+        assert!(matches!(
+            packet_receiver.disconnect_reason.lock().take(),
+            Some(DisconnectReason::TimedOut)
+        ));
     }
 }
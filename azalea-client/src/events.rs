@@ -1,23 +1,35 @@
 //! Defines the [`Event`] enum and makes those events trigger when they're sent
 //! in the ECS.
 
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
+use azalea_chat::FormattedText;
+use azalea_core::ResourceLocation;
 use azalea_protocol::packets::game::{
     clientbound_player_combat_kill_packet::ClientboundPlayerCombatKillPacket, ClientboundGamePacket,
 };
 use azalea_world::entity::MinecraftEntityId;
-use bevy_app::{App, CoreSchedule, IntoSystemAppConfig, Plugin};
-use bevy_ecs::{component::Component, event::EventReader, query::Added, system::Query};
+use bevy_app::{App, CoreSchedule, CoreSet, IntoSystemAppConfig, Plugin};
+use bevy_ecs::{
+    component::Component, event::EventReader, query::Added, schedule::IntoSystemConfig,
+    system::Query,
+};
 use derive_more::{Deref, DerefMut};
 use tokio::sync::mpsc;
 
 use crate::{
+    advancements::{Advancement, AdvancementMadeEvent},
     chat::{ChatPacket, ChatReceivedEvent},
+    disconnect::{
+        disconnect_on_read_packets_ended, remove_components_from_disconnected_players,
+        DisconnectEvent, DisconnectReason,
+    },
     packet_handling::{
-        AddPlayerEvent, DeathEvent, KeepAliveEvent, PacketEvent, RemovePlayerEvent,
-        UpdatePlayerEvent,
+        AddPlayerEvent, DeathEvent, KeepAliveEvent, PacketError, PacketErrorEvent, PacketEvent,
+        RemovePlayerEvent, UpdatePlayerEvent,
     },
+    plugin_channels::PluginMessageEvent,
+    resource_pack::ResourcePackEvent,
     PlayerInfo,
 };
 
@@ -87,6 +99,62 @@ pub enum Event {
     Death(Option<Arc<ClientboundPlayerCombatKillPacket>>),
     /// A `KeepAlive` packet was sent by the server.
     KeepAlive(u64),
+    /// The server asked us to use a resource pack.
+    ///
+    /// The response is sent automatically based on the
+    /// [`ResourcePackPolicy`] resource, unless it's set to
+    /// [`ResourcePackPolicy::Manual`], in which case you should call
+    /// [`Client::send_resource_pack_status`] yourself.
+    ///
+    /// [`ResourcePackPolicy`]: crate::resource_pack::ResourcePackPolicy
+    /// [`ResourcePackPolicy::Manual`]: crate::resource_pack::ResourcePackPolicy::Manual
+    /// [`Client::send_resource_pack_status`]: crate::Client::send_resource_pack_status
+    ResourcePack {
+        url: String,
+        hash: String,
+        required: bool,
+        prompt: Option<FormattedText>,
+    },
+    /// We completed an advancement. This isn't sent for advancements that
+    /// were already done when we joined.
+    AdvancementMade {
+        id: ResourceLocation,
+        advancement: Arc<Advancement>,
+    },
+    /// The server sent us a plugin message. This includes messages on
+    /// channels we didn't register.
+    ///
+    /// If you want the messages on one channel as a type, use
+    /// [`PluginMessagePlugin`] instead.
+    ///
+    /// [`PluginMessagePlugin`]: crate::plugin_channels::PluginMessagePlugin
+    PluginMessage {
+        channel: ResourceLocation,
+        data: Arc<Vec<u8>>,
+    },
+    /// The server sent a packet that we couldn't read. Depending on the
+    /// [`PacketErrorPolicy`], it was either skipped or we disconnected.
+    ///
+    /// [`PacketErrorPolicy`]: crate::packet_handling::PacketErrorPolicy
+    PacketError(Arc<PacketError>),
+    /// We were disconnected from the server. This is the last event that's
+    /// sent for this client.
+    Disconnect(DisconnectReason),
+    /// We're going to try joining the server again after `delay`, because we
+    /// were disconnected. `attempt` starts at 1 and goes up every time joining
+    /// fails.
+    ///
+    /// This is only sent if the bot has a reconnect policy in `azalea`, and
+    /// the [`Client`](crate::Client) is still the one that was disconnected.
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+        reason: DisconnectReason,
+    },
+    /// We joined the server again after being disconnected. The
+    /// [`Client`](crate::Client) that this is sent with is the new one, and
+    /// the events after this are for it.
+    Reconnected,
 }
 
 /// A component that contains an event sender for events that are only
@@ -109,6 +177,18 @@ impl Plugin for EventPlugin {
             .add_system(remove_player_listener)
             .add_system(death_listener)
             .add_system(keepalive_listener)
+            .add_system(resource_pack_listener)
+            .add_system(advancement_made_listener)
+            .add_system(plugin_message_listener)
+            .add_system(packet_error_listener)
+            .add_system(
+                // this has to run before the components (including
+                // LocalPlayerEvents) are removed
+                disconnect_listener
+                    .in_base_set(CoreSet::PostUpdate)
+                    .after(disconnect_on_read_packets_ended)
+                    .before(remove_components_from_disconnected_players),
+            )
             .add_system(tick_listener.in_schedule(CoreSchedule::FixedUpdate));
     }
 }
@@ -214,3 +294,83 @@ fn keepalive_listener(query: Query<&LocalPlayerEvents>, mut events: EventReader<
             .unwrap();
     }
 }
+
+fn resource_pack_listener(
+    query: Query<&LocalPlayerEvents>,
+    mut events: EventReader<ResourcePackEvent>,
+) {
+    for event in events.iter() {
+        let local_player_events = query
+            .get(event.entity)
+            .expect("Non-localplayer entities shouldn't be able to receive resource pack events");
+        local_player_events
+            .send(Event::ResourcePack {
+                url: event.url.clone(),
+                hash: event.hash.clone(),
+                required: event.required,
+                prompt: event.prompt.clone(),
+            })
+            .unwrap();
+    }
+}
+
+fn advancement_made_listener(
+    query: Query<&LocalPlayerEvents>,
+    mut events: EventReader<AdvancementMadeEvent>,
+) {
+    for event in events.iter() {
+        let local_player_events = query
+            .get(event.entity)
+            .expect("Non-localplayer entities shouldn't be able to receive advancement events");
+        local_player_events
+            .send(Event::AdvancementMade {
+                id: event.id.clone(),
+                advancement: Arc::new(event.advancement.clone()),
+            })
+            .unwrap();
+    }
+}
+
+fn plugin_message_listener(
+    query: Query<&LocalPlayerEvents>,
+    mut events: EventReader<PluginMessageEvent>,
+) {
+    for event in events.iter() {
+        let local_player_events = query
+            .get(event.entity)
+            .expect("Non-localplayer entities shouldn't be able to receive plugin messages");
+        local_player_events
+            .send(Event::PluginMessage {
+                channel: event.channel.clone(),
+                data: Arc::new(event.data.clone()),
+            })
+            .unwrap();
+    }
+}
+
+fn packet_error_listener(
+    query: Query<&LocalPlayerEvents>,
+    mut events: EventReader<PacketErrorEvent>,
+) {
+    for event in events.iter() {
+        if let Ok(local_player_events) = query.get(event.entity) {
+            local_player_events
+                .send(Event::PacketError(event.error.clone()))
+                .unwrap();
+        }
+    }
+}
+
+fn disconnect_listener(query: Query<&LocalPlayerEvents>, mut events: EventReader<DisconnectEvent>) {
+    // we might get kicked and lose the connection in the same update, but only
+    // the first reason is sent
+    let mut disconnected = HashSet::new();
+    for event in events.iter() {
+        if !disconnected.insert(event.entity) {
+            continue;
+        }
+        if let Ok(local_player_events) = query.get(event.entity) {
+            let _ = local_player_events.send(Event::Disconnect(event.reason.clone()));
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! Measure the latency of our connection to the server.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use azalea_protocol::packets::game::{ClientboundGamePacket, ServerboundGamePacket};
+use bevy_app::{App, Plugin};
+use bevy_ecs::{component::Component, event::EventReader, system::Query};
+
+use crate::{
+    local_player::{GameProfileComponent, SendPacketEvent},
+    packet_handling::{AddPlayerEvent, PacketEvent, UpdatePlayerEvent},
+    PlayerInfo,
+};
+
+/// How long we remember a command suggestions request that the server didn't
+/// answer.
+const PENDING_REQUEST_LIFETIME: Duration = Duration::from_secs(60);
+
+pub struct LatencyPlugin;
+impl Plugin for LatencyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_keep_alive_latency)
+            .add_system(start_command_suggestions_timing)
+            .add_system(finish_command_suggestions_timing);
+    }
+}
+
+/// A component with the round-trip time between us and the server.
+///
+/// ```
+/// # use azalea_client::latency::Latency;
+/// # fn example(client: &azalea_client::Client) {
+/// if let Some(round_trip) = client.component::<Latency>().round_trip() {
+///     println!("our ping is {}ms", round_trip.as_millis());
+/// }
+/// # }
+/// ```
+#[derive(Component, Clone, Debug, Default)]
+pub struct Latency {
+    /// The latency that the server measured from how long it took us to
+    /// respond to its keep alive packets. Vanilla servers send this to every
+    /// player in the tab list, and average it over multiple keep alives.
+    pub keep_alive: Option<LatencySample>,
+    /// The latency of the last command suggestions request that the server
+    /// answered, from [`Client::complete`].
+    ///
+    /// [`Client::complete`]: crate::Client::complete
+    pub command_suggestions: Option<LatencySample>,
+    /// When we sent each command suggestions request that hasn't been
+    /// answered yet, by its ID.
+    pending_command_suggestions: HashMap<u32, Instant>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LatencySample {
+    pub round_trip: Duration,
+    /// When we got this measurement.
+    pub measured_at: Instant,
+}
+
+impl Latency {
+    /// Get the most recently measured round-trip time, or `None` if we haven't
+    /// measured it yet.
+    pub fn round_trip(&self) -> Option<Duration> {
+        [self.keep_alive, self.command_suggestions]
+            .into_iter()
+            .flatten()
+            .max_by_key(|sample| sample.measured_at)
+            .map(|sample| sample.round_trip)
+    }
+}
+
+/// Update the keep alive latency when the server tells us our own latency in
+/// the tab list.
+fn update_keep_alive_latency(
+    mut add_player_events: EventReader<AddPlayerEvent>,
+    mut update_player_events: EventReader<UpdatePlayerEvent>,
+    mut query: Query<(&GameProfileComponent, &mut Latency)>,
+) {
+    let infos = add_player_events
+        .iter()
+        .map(|e| (e.entity, &e.info))
+        .chain(update_player_events.iter().map(|e| (e.entity, &e.info)));
+    for (entity, info) in infos {
+        let Ok((game_profile, mut latency)) = query.get_mut(entity) else {
+            continue;
+        };
+        if game_profile.uuid != info.uuid {
+            continue;
+        }
+        update_from_player_info(&mut latency, info);
+    }
+}
+
+fn update_from_player_info(latency: &mut Latency, info: &PlayerInfo) {
+    // the server sends 0 before it's measured anything
+    if info.latency <= 0 {
+        return;
+    }
+    latency.keep_alive = Some(LatencySample {
+        round_trip: Duration::from_millis(info.latency as u64),
+        measured_at: Instant::now(),
+    });
+}
+
+/// Remember when we sent command suggestions requests.
+fn start_command_suggestions_timing(
+    mut events: EventReader<SendPacketEvent>,
+    mut query: Query<&mut Latency>,
+) {
+    for event in events.iter() {
+        let ServerboundGamePacket::CommandSuggestion(p) = &event.packet else {
+            continue;
+        };
+        let Ok(mut latency) = query.get_mut(event.entity) else {
+            continue;
+        };
+        let now = Instant::now();
+        // forget about requests that the server never answered
+        latency
+            .pending_command_suggestions
+            .retain(|_, sent_at| now.duration_since(*sent_at) < PENDING_REQUEST_LIFETIME);
+        latency.pending_command_suggestions.insert(p.id, now);
+    }
+}
+
+/// Measure how long the server took to answer a command suggestions request.
+fn finish_command_suggestions_timing(
+    mut events: EventReader<PacketEvent>,
+    mut query: Query<&mut Latency>,
+) {
+    for event in events.iter() {
+        let ClientboundGamePacket::CommandSuggestions(p) = &event.packet else {
+            continue;
+        };
+        let Ok(mut latency) = query.get_mut(event.entity) else {
+            continue;
+        };
+        if let Some(sent_at) = latency.pending_command_suggestions.remove(&p.id) {
+            latency.command_suggestions = Some(LatencySample {
+                round_trip: sent_at.elapsed(),
+                measured_at: Instant::now(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azalea_auth::game_profile::GameProfile;
+    use azalea_core::GameMode;
+    use azalea_protocol::packets::game::{
+        clientbound_command_suggestions_packet::ClientboundCommandSuggestionsPacket,
+        serverbound_command_suggestion_packet::ServerboundCommandSuggestionPacket,
+    };
+    use bevy_ecs::entity::Entity;
+    use uuid::Uuid;
+
+    fn app() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugin(LatencyPlugin)
+            .add_event::<SendPacketEvent>()
+            .add_event::<PacketEvent>()
+            .add_event::<AddPlayerEvent>()
+            .add_event::<UpdatePlayerEvent>();
+        let entity = app
+            .world
+            .spawn((
+                Latency::default(),
+                GameProfileComponent(GameProfile::new(Uuid::from_u128(1), "bot".to_string())),
+            ))
+            .id();
+        (app, entity)
+    }
+
+    fn player_info(uuid: Uuid, latency: i32) -> PlayerInfo {
+        PlayerInfo {
+            profile: GameProfile::new(uuid, "bot".to_string()),
+            uuid,
+            gamemode: GameMode::Survival,
+            latency,
+            display_name: None,
+        }
+    }
+
+    fn send_request(app: &mut App, entity: Entity, id: u32) {
+        app.world.send_event(SendPacketEvent {
+            entity,
+            packet: ServerboundCommandSuggestionPacket {
+                id,
+                command: "/give ".to_string(),
+            }
+            .get(),
+        });
+        app.update();
+    }
+
+    fn receive_response(app: &mut App, entity: Entity, id: u32) {
+        app.world.send_event(PacketEvent {
+            entity,
+            packet: ClientboundCommandSuggestionsPacket {
+                id,
+                suggestions: Default::default(),
+            }
+            .get(),
+        });
+        app.update();
+    }
+
+    #[test]
+    fn test_command_suggestions_latency() {
+        let (mut app, entity) = app();
+        send_request(&mut app, entity, 3);
+        std::thread::sleep(Duration::from_millis(20));
+
+        // a response to a request we didn't make doesn't count
+        receive_response(&mut app, entity, 4);
+        assert!(app
+            .world
+            .get::<Latency>(entity)
+            .unwrap()
+            .round_trip()
+            .is_none());
+
+        receive_response(&mut app, entity, 3);
+        let latency = app.world.get::<Latency>(entity).unwrap();
+        assert!(latency.command_suggestions.unwrap().round_trip >= Duration::from_millis(20));
+        assert!(latency.pending_command_suggestions.is_empty());
+    }
+
+    #[test]
+    fn test_keep_alive_latency() {
+        let (mut app, entity) = app();
+        // other players' latency is ignored
+        app.world.send_event(UpdatePlayerEvent {
+            entity,
+            info: player_info(Uuid::from_u128(2), 50),
+        });
+        // and so is our latency before the server measured it
+        app.world.send_event(UpdatePlayerEvent {
+            entity,
+            info: player_info(Uuid::from_u128(1), 0),
+        });
+        app.update();
+        assert!(app
+            .world
+            .get::<Latency>(entity)
+            .unwrap()
+            .keep_alive
+            .is_none());
+
+        app.world.send_event(UpdatePlayerEvent {
+            entity,
+            info: player_info(Uuid::from_u128(1), 120),
+        });
+        app.update();
+        let latency = app.world.get::<Latency>(entity).unwrap();
+        assert_eq!(latency.round_trip(), Some(Duration::from_millis(120)));
+    }
+
+    #[test]
+    fn test_round_trip_is_most_recent() {
+        let now = Instant::now();
+        let latency = Latency {
+            keep_alive: Some(LatencySample {
+                round_trip: Duration::from_millis(100),
+                measured_at: now,
+            }),
+            command_suggestions: Some(LatencySample {
+                round_trip: Duration::from_millis(30),
+                measured_at: now + Duration::from_secs(1),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(latency.round_trip(), Some(Duration::from_millis(30)));
+    }
+}
@@ -12,25 +12,38 @@
 #![feature(type_alias_impl_trait)]
 
 mod account;
+pub mod advancements;
 pub mod chat;
+pub mod chat_signing;
 mod client;
+pub mod command_tree;
+pub mod crafting;
 pub mod disconnect;
 mod entity_query;
 mod events;
 mod get_mc_dir;
 pub mod interact;
 pub mod inventory;
+pub mod latency;
 mod local_player;
+pub mod login_query;
 mod movement;
 pub mod packet_handling;
 pub mod ping;
 mod player;
+pub mod plugin_channels;
+pub mod query;
+pub mod replay;
+pub mod resource_pack;
+pub mod statistics;
 pub mod task_pool;
+#[cfg(test)]
+mod test_utils;
 
-pub use account::{Account, AccountOpts};
+pub use account::{Account, AccountOpts, RequestCertError};
 pub use client::{
-    init_ecs_app, start_ecs, Client, ClientInformation, JoinError, JoinedClientBundle, TabList,
-    TickBroadcast,
+    init_ecs_app, start_ecs, Client, ClientInformation, JoinError, JoinOpts, JoinedClientBundle,
+    TabList, TickBroadcast, VersionSelection,
 };
 pub use events::Event;
 pub use local_player::{GameProfileComponent, LocalPlayer};
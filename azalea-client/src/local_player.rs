@@ -2,7 +2,7 @@ use std::{io, sync::Arc};
 
 use azalea_auth::game_profile::GameProfile;
 use azalea_core::{ChunkPos, GameMode};
-use azalea_protocol::packets::game::ServerboundGamePacket;
+use azalea_protocol::{packets::game::ServerboundGamePacket, stats::ConnectionStats};
 use azalea_world::{
     entity::{self, Dead, WorldName},
     Instance, InstanceContainer, PartialInstance,
@@ -20,6 +20,7 @@ use thiserror::Error;
 use tokio::{sync::mpsc, task::JoinHandle};
 
 use crate::{
+    disconnect::FLUSH_TIMEOUT,
     events::{Event, LocalPlayerEvents},
     ClientInformation, WalkDirection,
 };
@@ -36,6 +37,9 @@ use crate::{
 #[derive(Component)]
 pub struct LocalPlayer {
     packet_writer: mpsc::UnboundedSender<ServerboundGamePacket>,
+    /// The traffic on our connection, which is also a component on this
+    /// entity. We count the packets that are waiting in `packet_writer` here.
+    pub(crate) connection_stats: ConnectionStats,
 
     /// The partial instance is the world this client currently has loaded. It
     /// has a limited render distance.
@@ -95,6 +99,7 @@ impl LocalPlayer {
     pub fn new(
         entity: Entity,
         packet_writer: mpsc::UnboundedSender<ServerboundGamePacket>,
+        connection_stats: ConnectionStats,
         world: Arc<RwLock<Instance>>,
         read_packets_task: JoinHandle<()>,
         write_packets_task: JoinHandle<()>,
@@ -103,6 +108,7 @@ impl LocalPlayer {
 
         LocalPlayer {
             packet_writer,
+            connection_stats,
 
             world,
             partial_instance: Arc::new(RwLock::new(PartialInstance::new(
@@ -117,6 +123,7 @@ impl LocalPlayer {
 
     /// Write a packet directly to the server.
     pub fn write_packet(&self, packet: ServerboundGamePacket) {
+        self.connection_stats.write_queued();
         self.packet_writer
             .send(packet)
             .expect("write_packet shouldn't be able to be called if the connection is closed");
@@ -125,9 +132,22 @@ impl LocalPlayer {
 
 impl Drop for LocalPlayer {
     /// Stop every active task when the `LocalPlayer` is dropped.
+    ///
+    /// The write task stops by itself once it wrote the packets that are still
+    /// queued, since `packet_writer` is dropped with us. It's only aborted if
+    /// that takes longer than [`FLUSH_TIMEOUT`].
     fn drop(&mut self) {
         self.read_packets_task.abort();
-        self.write_packets_task.abort();
+        let write_packets_task = self.write_packets_task.abort_handle();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    tokio::time::sleep(FLUSH_TIMEOUT).await;
+                    write_packets_task.abort();
+                });
+            }
+            Err(_) => write_packets_task.abort(),
+        }
     }
 }
 
@@ -0,0 +1,237 @@
+//! Answer the custom queries that servers and proxies can send while we're
+//! logging in, like Velocity's player info forwarding.
+
+use std::{io::Cursor, sync::Arc};
+
+use azalea_buf::{McBufReadable, McBufVarWritable, McBufWritable};
+use azalea_core::ResourceLocation;
+use azalea_protocol::ServerAddress;
+use bevy_ecs::system::Resource;
+use uuid::Uuid;
+
+use crate::Account;
+
+/// The channel Velocity uses to ask for the player's info when modern
+/// forwarding is enabled.
+pub const VELOCITY_PLAYER_INFO_CHANNEL: &str = "velocity:player_info";
+
+/// Information about the client that's logging in, given to
+/// [`LoginQueryHandler`]s.
+pub struct LoginQueryContext<'a> {
+    pub account: &'a Account,
+    /// The address of the server we're joining.
+    pub address: &'a ServerAddress,
+}
+
+/// Something that can answer the custom queries that the server sends while
+/// we're logging in. Add these with `ClientBuilder::login_query_handler` or by
+/// adding them to the [`LoginQueryHandlers`] resource.
+///
+/// Closures with the same arguments as [`LoginQueryHandler::handle`] also
+/// implement this trait.
+pub trait LoginQueryHandler: Send + Sync + 'static {
+    /// Answer a query on the given channel. Return `None` if this handler
+    /// doesn't know about the channel, so the next handler can try.
+    fn handle(
+        &self,
+        channel: &ResourceLocation,
+        data: &[u8],
+        context: &LoginQueryContext,
+    ) -> Option<Vec<u8>>;
+}
+
+impl<F> LoginQueryHandler for F
+where
+    F: Fn(&ResourceLocation, &[u8], &LoginQueryContext) -> Option<Vec<u8>> + Send + Sync + 'static,
+{
+    fn handle(
+        &self,
+        channel: &ResourceLocation,
+        data: &[u8],
+        context: &LoginQueryContext,
+    ) -> Option<Vec<u8>> {
+        self(channel, data, context)
+    }
+}
+
+/// A resource with the handlers that answer custom login queries, in the
+/// order they're tried. If none of them answer, we tell the server we didn't
+/// understand the query.
+#[derive(Resource, Clone, Default)]
+pub struct LoginQueryHandlers(pub Vec<Arc<dyn LoginQueryHandler>>);
+
+impl LoginQueryHandlers {
+    pub fn add(&mut self, handler: impl LoginQueryHandler) {
+        self.0.push(Arc::new(handler));
+    }
+
+    /// Get the response from the first handler that answers the query.
+    pub fn respond(
+        &self,
+        channel: &ResourceLocation,
+        data: &[u8],
+        context: &LoginQueryContext,
+    ) -> Option<Vec<u8>> {
+        self.0
+            .iter()
+            .find_map(|handler| handler.handle(channel, data, context))
+    }
+}
+
+/// A [`LoginQueryHandler`] for Velocity's modern forwarding, which lets you
+/// join a backend server directly as if you were coming from the proxy.
+///
+/// The secret must be the same as the `forwarding-secret` in the backend's
+/// config.
+///
+/// ```no_run
+/// # use azalea_client::login_query::{LoginQueryHandlers, VelocityForwarding};
+/// # fn example(app: &mut bevy_app::App) {
+/// let mut handlers = LoginQueryHandlers::default();
+/// handlers.add(VelocityForwarding::new(b"my secret"));
+/// app.insert_resource(handlers);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct VelocityForwarding {
+    secret: Vec<u8>,
+    /// The IP address that we tell the backend we're connecting from.
+    pub client_address: String,
+    /// The UUID that we tell the backend we have. If this isn't set, the
+    /// account's UUID is used, or vanilla's offline UUID for offline
+    /// accounts.
+    pub uuid: Option<Uuid>,
+}
+
+impl VelocityForwarding {
+    /// The version of the forwarding format that we send, which doesn't
+    /// include the chat signing key.
+    pub const VERSION: u8 = 1;
+
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: secret.to_vec(),
+            client_address: "127.0.0.1".to_string(),
+            uuid: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_client_address(mut self, client_address: &str) -> Self {
+        self.client_address = client_address.to_string();
+        self
+    }
+
+    #[must_use]
+    pub fn with_uuid(mut self, uuid: Uuid) -> Self {
+        self.uuid = Some(uuid);
+        self
+    }
+
+    /// The forwarded player info, without the signature.
+    fn player_info(&self, account: &Account) -> Vec<u8> {
+        let uuid = self
+            .uuid
+            .or(account.uuid)
+            .unwrap_or_else(|| azalea_crypto::offline_uuid(&account.username));
+
+        let mut data = Vec::new();
+        (Self::VERSION as u32)
+            .var_write_into(&mut data)
+            .and_then(|_| self.client_address.write_into(&mut data))
+            .and_then(|_| uuid.write_into(&mut data))
+            .and_then(|_| account.username.write_into(&mut data))
+            // we don't know our skin or other profile properties
+            .and_then(|_| 0u32.var_write_into(&mut data))
+            .expect("Writing to a Vec shouldn't fail");
+        data
+    }
+}
+
+impl LoginQueryHandler for VelocityForwarding {
+    fn handle(
+        &self,
+        channel: &ResourceLocation,
+        data: &[u8],
+        context: &LoginQueryContext,
+    ) -> Option<Vec<u8>> {
+        if channel.to_string() != VELOCITY_PLAYER_INFO_CHANNEL {
+            return None;
+        }
+        // the proxy can tell us the newest version it supports, but every
+        // version supports the first one
+        if let Ok(max_version) = u8::read_from(&mut Cursor::new(data)) {
+            if max_version < Self::VERSION {
+                return None;
+            }
+        }
+
+        let player_info = self.player_info(context.account);
+        let mut response = azalea_crypto::hmac_sha256(&self.secret, &player_info).to_vec();
+        response.extend(player_info);
+        Some(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn respond(forwarding: &VelocityForwarding, data: &[u8]) -> Option<Vec<u8>> {
+        let account = Account::offline("bot");
+        let address = ServerAddress::try_from("localhost").unwrap();
+        forwarding.handle(
+            &ResourceLocation::new(VELOCITY_PLAYER_INFO_CHANNEL),
+            data,
+            &LoginQueryContext {
+                account: &account,
+                address: &address,
+            },
+        )
+    }
+
+    #[test]
+    fn test_velocity_forwarding() {
+        let response = respond(&VelocityForwarding::new(b"hunter2"), &[1]).unwrap();
+
+        let mut player_info = vec![1, 9];
+        player_info.extend(b"127.0.0.1");
+        // vanilla's offline UUID for "bot"
+        player_info.extend(
+            Uuid::parse_str("67128b5b-2e6b-3ad1-baa0-1b937b03e5c5")
+                .unwrap()
+                .as_bytes(),
+        );
+        player_info.push(3);
+        player_info.extend(b"bot");
+        player_info.push(0);
+
+        // HMAC-SHA256 of the player info with the secret "hunter2"
+        let mut expected = vec![
+            0x7f, 0x63, 0x30, 0x40, 0x29, 0x7a, 0xf9, 0xaf, 0x5a, 0xbe, 0xb5, 0x63, 0x76, 0x51,
+            0x79, 0x9d, 0x74, 0x83, 0x18, 0x40, 0x8f, 0x49, 0x55, 0x9f, 0x35, 0x98, 0x5a, 0x8f,
+            0xba, 0xb0, 0x44, 0xe9,
+        ];
+        expected.extend(player_info);
+        assert_eq!(response, expected);
+    }
+
+    #[test]
+    fn test_velocity_forwarding_ignores_other_queries() {
+        let forwarding = VelocityForwarding::new(b"hunter2");
+        // the proxy only supports a version older than ours
+        assert_eq!(respond(&forwarding, &[0]), None);
+
+        let account = Account::offline("bot");
+        let address = ServerAddress::try_from("localhost").unwrap();
+        let response = forwarding.handle(
+            &ResourceLocation::new("bungeecord:main"),
+            &[],
+            &LoginQueryContext {
+                account: &account,
+                address: &address,
+            },
+        );
+        assert_eq!(response, None);
+    }
+}
@@ -1,22 +1,34 @@
-use std::{collections::HashSet, io::Cursor, sync::Arc};
+use std::{
+    collections::HashSet,
+    io::Cursor,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
-use azalea_core::{ChunkPos, GameMode, ResourceLocation, Vec3};
+use azalea_core::{ChunkPos, GameMode, Vec3};
 use azalea_protocol::{
     connect::{ReadConnection, WriteConnection},
-    packets::game::{
-        clientbound_player_combat_kill_packet::ClientboundPlayerCombatKillPacket,
-        serverbound_accept_teleportation_packet::ServerboundAcceptTeleportationPacket,
-        serverbound_custom_payload_packet::ServerboundCustomPayloadPacket,
-        serverbound_keep_alive_packet::ServerboundKeepAlivePacket,
-        serverbound_move_player_pos_rot_packet::ServerboundMovePlayerPosRotPacket,
-        ClientboundGamePacket, ServerboundGamePacket,
+    packets::{
+        game::{
+            clientbound_player_combat_kill_packet::ClientboundPlayerCombatKillPacket,
+            clientbound_recipe_packet::State as RecipeState,
+            serverbound_accept_teleportation_packet::ServerboundAcceptTeleportationPacket,
+            serverbound_chat_ack_packet::ServerboundChatAckPacket,
+            serverbound_keep_alive_packet::ServerboundKeepAlivePacket,
+            serverbound_move_player_pos_rot_packet::ServerboundMovePlayerPosRotPacket,
+            serverbound_pong_packet::ServerboundPongPacket, ClientboundGamePacket,
+            ServerboundGamePacket,
+        },
+        ProtocolPacket,
     },
     read::ReadPacketError,
 };
 use azalea_world::{
     entity::{
         metadata::{apply_metadata, Health, PlayerMetadataBundle},
-        Dead, EntityBundle, EntityKind, EntityUpdateSet, LastSentPosition, LookDirection,
+        Dead, EntityBundle, EntityKind, EntityUpdateSet, LastSentPosition, Local, LookDirection,
         MinecraftEntityId, Physics, PlayerBundle, Position, WorldName,
     },
     entity::{LoadedBy, RelativeEntityUpdate},
@@ -27,23 +39,38 @@ use bevy_ecs::{
     component::Component,
     entity::Entity,
     event::{EventReader, EventWriter, Events},
+    query::Without,
     schedule::IntoSystemConfig,
     system::{Commands, Query, ResMut, SystemState},
     world::World,
 };
 use log::{debug, error, trace, warn};
 use parking_lot::Mutex;
-use tokio::sync::mpsc;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc,
+};
 
 use crate::{
-    chat::{ChatPacket, ChatReceivedEvent},
+    advancements::{AdvancementMadeEvent, Advancements},
+    chat::{ChatHistory, ChatPacket, ChatReceivedEvent},
+    chat_signing::{
+        ChatSigningSession, ChatTrust, LastSeenMessagesTracker, MessageSignatureCache,
+        PlayerChatSessions, MAX_UNACKNOWLEDGED_MESSAGES,
+    },
     client::{PlayerAbilities, TabList},
-    disconnect::DisconnectEvent,
+    command_tree::{CommandSuggestionRequests, CommandTree},
+    crafting::RecipeBook,
+    disconnect::{DisconnectEvent, DisconnectReason, KEEP_ALIVE_TIMEOUT},
     inventory::{
         ClientSideCloseContainerEvent, InventoryComponent, MenuOpenedEvent,
         SetContainerContentEvent,
     },
     local_player::{GameProfileComponent, LocalGameMode, LocalPlayer},
+    plugin_channels::PluginMessageEvent,
+    replay::PacketCapture,
+    resource_pack::ResourcePackEvent,
+    statistics::{Statistics, StatsRequests},
     ClientInformation, PlayerInfo,
 };
 
@@ -75,6 +102,20 @@ pub struct PacketEvent {
     pub packet: ClientboundGamePacket,
 }
 
+/// An event that's sent when a client receives a bundle of packets, which is
+/// a group of packets that the server wants the client to apply at the same
+/// time (like an entity being spawned and its metadata being set).
+///
+/// The packets in the bundle are also sent individually as [`PacketEvent`]s in
+/// the same update, so you only need this if you care about which packets
+/// were grouped together. The bundle delimiters aren't included in `packets`.
+#[derive(Debug, Clone)]
+pub struct PacketBundleEvent {
+    /// The client entity that received the packets.
+    pub entity: Entity,
+    pub packets: Vec<ClientboundGamePacket>,
+}
+
 pub struct PacketHandlerPlugin;
 
 impl Plugin for PacketHandlerPlugin {
@@ -87,6 +128,8 @@ impl Plugin for PacketHandlerPlugin {
                     .before(EntityUpdateSet::Deindex),
             )
             .init_resource::<Events<PacketEvent>>()
+            .add_event::<PacketBundleEvent>()
+            .add_event::<PacketErrorEvent>()
             .add_event::<AddPlayerEvent>()
             .add_event::<RemovePlayerEvent>()
             .add_event::<UpdatePlayerEvent>()
@@ -140,16 +183,105 @@ pub struct KeepAliveEvent {
     pub id: u64,
 }
 
+/// The maximum number of packets that can be in a bundle. This is the same as
+/// vanilla.
+const MAX_BUNDLE_PACKETS: usize = 4096;
+
+/// What the client does when the server sends a packet that it can't read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PacketErrorAction {
+    /// Log the packet and its bytes, count it as dropped, and keep reading
+    /// packets.
+    #[default]
+    Skip,
+    /// Disconnect from the server, like vanilla does.
+    Disconnect,
+}
+
+/// Decides what happens to packets from the server that can't be read. By
+/// default, they're skipped so a single modded or off-spec packet (usually
+/// from a proxy or plugin) doesn't disconnect the bot.
+///
+/// Either way, an [`Event::PacketError`] is sent for every packet that
+/// couldn't be read.
+///
+/// [`Event::PacketError`]: crate::Event::PacketError
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PacketErrorPolicy {
+    /// What to do with packets with an id that doesn't exist in this state.
+    pub unknown_packet: PacketErrorAction,
+    /// What to do with packets that failed to parse or had data left over
+    /// after parsing.
+    pub malformed_packet: PacketErrorAction,
+}
+
+impl PacketErrorPolicy {
+    /// Skip every packet that can't be read.
+    pub const SKIP: Self = Self {
+        unknown_packet: PacketErrorAction::Skip,
+        malformed_packet: PacketErrorAction::Skip,
+    };
+    /// Disconnect when any packet can't be read.
+    pub const DISCONNECT: Self = Self {
+        unknown_packet: PacketErrorAction::Disconnect,
+        malformed_packet: PacketErrorAction::Disconnect,
+    };
+
+    /// The action for the error that happened while decoding a packet.
+    pub fn action_for(&self, error: &ReadPacketError) -> PacketErrorAction {
+        match error {
+            ReadPacketError::UnknownPacketId { .. } => self.unknown_packet,
+            _ => self.malformed_packet,
+        }
+    }
+}
+
+/// A packet from the server that couldn't be read.
+#[derive(Debug)]
+pub struct PacketError {
+    pub error: Box<ReadPacketError>,
+    /// The packet id followed by the packet's data, after decompression.
+    pub data: Vec<u8>,
+    /// What we did with the packet, decided by the [`PacketErrorPolicy`].
+    pub action: PacketErrorAction,
+}
+
+/// An event that's sent when we get a packet from the server that we couldn't
+/// read.
+#[derive(Debug, Clone)]
+pub struct PacketErrorEvent {
+    /// The local player entity that received this event.
+    pub entity: Entity,
+    pub error: Arc<PacketError>,
+}
+
 /// Something that receives packets from the server.
+///
+/// Packets in a bundle are only added to the queue once the whole bundle was
+/// received, so they're always handled in the same update.
 #[derive(Component, Clone)]
 pub struct PacketReceiver {
     pub packets: Arc<Mutex<Vec<ClientboundGamePacket>>>,
     pub run_schedule_sender: mpsc::UnboundedSender<()>,
+    /// Where every packet we send and receive is recorded, if anywhere.
+    pub capture: Option<PacketCapture>,
+    pub error_policy: PacketErrorPolicy,
+    /// Packets that couldn't be read, which are sent as [`PacketErrorEvent`]s
+    /// in the next update.
+    pub errors: Arc<Mutex<Vec<Arc<PacketError>>>>,
+    /// The number of packets that were skipped on this connection because
+    /// they couldn't be read.
+    pub dropped_packets: Arc<AtomicUsize>,
+    /// Why [`Self::read_task`] ended, which is set when it ends and taken when
+    /// the [`DisconnectEvent`] is sent.
+    pub disconnect_reason: Arc<Mutex<Option<DisconnectReason>>>,
 }
 
 pub fn send_packet_events(
     query: Query<(Entity, &PacketReceiver)>,
     mut packet_events: ResMut<Events<PacketEvent>>,
+    mut packet_bundle_events: EventWriter<PacketBundleEvent>,
+    mut packet_error_events: EventWriter<PacketErrorEvent>,
 ) {
     // we manually clear and send the events at the beginning of each update
     // since otherwise it'd cause issues with events in process_packet_events
@@ -158,7 +290,21 @@ pub fn send_packet_events(
     for (player_entity, packet_receiver) in &query {
         let mut packets = packet_receiver.packets.lock();
         if !packets.is_empty() {
+            let mut bundle: Option<Vec<ClientboundGamePacket>> = None;
             for packet in packets.iter() {
+                if let ClientboundGamePacket::Bundle(_) = packet {
+                    // the read task only adds complete bundles, so the
+                    // delimiters always come in pairs
+                    match bundle.take() {
+                        Some(packets) => packet_bundle_events.send(PacketBundleEvent {
+                            entity: player_entity,
+                            packets,
+                        }),
+                        None => bundle = Some(Vec::new()),
+                    }
+                } else if let Some(bundle) = &mut bundle {
+                    bundle.push(packet.clone());
+                }
                 packet_events.send(PacketEvent {
                     entity: player_entity,
                     packet: packet.clone(),
@@ -167,6 +313,12 @@ pub fn send_packet_events(
             // clear the packets right after we read them
             packets.clear();
         }
+        for error in packet_receiver.errors.lock().drain(..) {
+            packet_error_events.send(PacketErrorEvent {
+                entity: player_entity,
+                error,
+            });
+        }
     }
 }
 
@@ -195,12 +347,18 @@ fn process_packet_events(ecs: &mut World) {
                         Option<&mut WorldName>,
                         &GameProfileComponent,
                         &ClientInformation,
+                        Option<&ChatSigningSession>,
                     )>,
                     ResMut<InstanceContainer>,
                 )> = SystemState::new(ecs);
                 let (mut commands, mut query, mut instance_container) = system_state.get_mut(ecs);
-                let (mut local_player, world_name, game_profile, client_information) =
-                    query.get_mut(player_entity).unwrap();
+                let (
+                    mut local_player,
+                    world_name,
+                    game_profile,
+                    client_information,
+                    signing_session,
+                ) = query.get_mut(player_entity).unwrap();
 
                 {
                     let dimension = &p
@@ -271,15 +429,10 @@ fn process_packet_events(ecs: &mut World) {
                 );
                 local_player.write_packet(client_information.clone().get());
 
-                // brand
-                local_player.write_packet(
-                    ServerboundCustomPayloadPacket {
-                        identifier: ResourceLocation::new("brand"),
-                        // they don't have to know :)
-                        data: "vanilla".into(),
-                    }
-                    .get(),
-                );
+                // tell the server our public key so it can verify our messages
+                if let Some(signing_session) = signing_session {
+                    local_player.write_packet(signing_session.update_packet());
+                }
 
                 system_state.apply(ecs);
             }
@@ -288,12 +441,30 @@ fn process_packet_events(ecs: &mut World) {
             }
             ClientboundGamePacket::CustomPayload(p) => {
                 debug!("Got custom payload packet {:?}", p);
+
+                let mut system_state: SystemState<EventWriter<PluginMessageEvent>> =
+                    SystemState::new(ecs);
+                let mut plugin_message_events = system_state.get_mut(ecs);
+
+                plugin_message_events.send(PluginMessageEvent {
+                    entity: player_entity,
+                    channel: p.identifier.clone(),
+                    data: p.data.0.clone(),
+                });
             }
             ClientboundGamePacket::ChangeDifficulty(p) => {
                 debug!("Got difficulty packet {:?}", p);
             }
-            ClientboundGamePacket::Commands(_p) => {
+            ClientboundGamePacket::Commands(p) => {
                 debug!("Got declare commands packet");
+
+                let mut system_state: SystemState<Commands> = SystemState::new(ecs);
+                let mut commands = system_state.get_mut(ecs);
+                commands
+                    .entity(player_entity)
+                    .insert(CommandTree::from_stubs(&p.entries, p.root_index));
+
+                system_state.apply(ecs);
             }
             ClientboundGamePacket::PlayerAbilities(p) => {
                 debug!("Got player abilities packet {:?}", p);
@@ -317,18 +488,48 @@ fn process_packet_events(ecs: &mut World) {
                 let mut disconnect_events = system_state.get_mut(ecs);
                 disconnect_events.send(DisconnectEvent {
                     entity: player_entity,
+                    reason: DisconnectReason::Kicked(p.reason.clone()),
                 });
                 // bye
                 return;
             }
-            ClientboundGamePacket::UpdateRecipes(_p) => {
+            ClientboundGamePacket::UpdateRecipes(p) => {
                 debug!("Got update recipes packet");
+
+                let mut system_state: SystemState<ResMut<RecipeBook>> = SystemState::new(ecs);
+                let mut recipe_book = system_state.get_mut(ecs);
+                recipe_book.recipes = p
+                    .recipes
+                    .iter()
+                    .map(|recipe| (recipe.identifier.clone(), recipe.clone()))
+                    .collect();
             }
             ClientboundGamePacket::EntityEvent(_p) => {
                 // debug!("Got entity event packet {:?}", p);
             }
-            ClientboundGamePacket::Recipe(_p) => {
+            ClientboundGamePacket::Recipe(p) => {
                 debug!("Got recipe packet");
+
+                let mut system_state: SystemState<ResMut<RecipeBook>> = SystemState::new(ecs);
+                let mut recipe_book = system_state.get_mut(ecs);
+                let unlocked = recipe_book.unlocked.entry(player_entity).or_default();
+                unlocked.settings = Some(p.settings.clone());
+                match &p.action {
+                    RecipeState::Init { to_highlight } => {
+                        unlocked.recipes = p.recipes.iter().cloned().collect();
+                        unlocked.highlighted = to_highlight.iter().cloned().collect();
+                    }
+                    RecipeState::Add => {
+                        unlocked.recipes.extend(p.recipes.iter().cloned());
+                        unlocked.highlighted.extend(p.recipes.iter().cloned());
+                    }
+                    RecipeState::Remove => {
+                        for recipe in &p.recipes {
+                            unlocked.recipes.remove(recipe);
+                            unlocked.highlighted.remove(recipe);
+                        }
+                    }
+                }
             }
             ClientboundGamePacket::PlayerPosition(p) => {
                 // TODO: reply with teleport confirm
@@ -423,16 +624,24 @@ fn process_packet_events(ecs: &mut World) {
             ClientboundGamePacket::PlayerInfoUpdate(p) => {
                 debug!("Got player info packet {:?}", p);
 
+                #[allow(clippy::type_complexity)]
                 let mut system_state: SystemState<(
-                    Query<&mut TabList>,
+                    Query<(&mut TabList, &mut PlayerChatSessions)>,
                     EventWriter<AddPlayerEvent>,
                     EventWriter<UpdatePlayerEvent>,
                 )> = SystemState::new(ecs);
                 let (mut query, mut add_player_events, mut update_player_events) =
                     system_state.get_mut(ecs);
-                let mut tab_list = query.get_mut(player_entity).unwrap();
+                let (mut tab_list, mut chat_sessions) = query.get_mut(player_entity).unwrap();
 
                 for updated_info in &p.entries {
+                    if p.actions.initialize_chat {
+                        chat_sessions.update(
+                            updated_info.profile.uuid,
+                            updated_info.chat_session.as_ref(),
+                        );
+                    }
+
                     // add the new player maybe
                     if p.actions.add_player {
                         let info = PlayerInfo {
@@ -472,14 +681,16 @@ fn process_packet_events(ecs: &mut World) {
                 }
             }
             ClientboundGamePacket::PlayerInfoRemove(p) => {
+                #[allow(clippy::type_complexity)]
                 let mut system_state: SystemState<(
-                    Query<&mut TabList>,
+                    Query<(&mut TabList, &mut PlayerChatSessions)>,
                     EventWriter<RemovePlayerEvent>,
                 )> = SystemState::new(ecs);
                 let (mut query, mut remove_player_events) = system_state.get_mut(ecs);
-                let mut tab_list = query.get_mut(player_entity).unwrap();
+                let (mut tab_list, mut chat_sessions) = query.get_mut(player_entity).unwrap();
 
                 for uuid in &p.profile_ids {
+                    chat_sessions.sessions.remove(uuid);
                     if let Some(info) = tab_list.remove(uuid) {
                         remove_player_events.send(RemovePlayerEvent {
                             entity: player_entity,
@@ -701,6 +912,21 @@ fn process_packet_events(ecs: &mut World) {
             }
             ClientboundGamePacket::UpdateAdvancements(p) => {
                 debug!("Got update advancements packet {:?}", p);
+
+                let mut system_state: SystemState<(
+                    Query<&mut Advancements>,
+                    EventWriter<AdvancementMadeEvent>,
+                )> = SystemState::new(ecs);
+                let (mut query, mut advancement_made_events) = system_state.get_mut(ecs);
+                let mut advancements = query.get_mut(player_entity).unwrap();
+
+                for id in advancements.update(&p) {
+                    advancement_made_events.send(AdvancementMadeEvent {
+                        entity: player_entity,
+                        advancement: advancements.advancements[&id].clone(),
+                        id,
+                    });
+                }
             }
             ClientboundGamePacket::RotateHead(_p) => {
                 // debug!("Got rotate head packet {:?}", p);
@@ -789,25 +1015,91 @@ fn process_packet_events(ecs: &mut World) {
             ClientboundGamePacket::PlayerChat(p) => {
                 debug!("Got player chat packet {:?}", p);
 
-                let mut system_state: SystemState<EventWriter<ChatReceivedEvent>> =
-                    SystemState::new(ecs);
-                let mut chat_events = system_state.get_mut(ecs);
+                #[allow(clippy::type_complexity)]
+                let mut system_state: SystemState<(
+                    EventWriter<ChatReceivedEvent>,
+                    Query<(
+                        &LocalPlayer,
+                        &mut LastSeenMessagesTracker,
+                        &mut PlayerChatSessions,
+                        &mut MessageSignatureCache,
+                        &mut ChatHistory,
+                    )>,
+                )> = SystemState::new(ecs);
+                let (mut chat_events, mut query) = system_state.get_mut(ecs);
+                let (
+                    local_player,
+                    mut last_seen_messages,
+                    mut chat_sessions,
+                    mut signature_cache,
+                    mut chat_history,
+                ) = query.get_mut(player_entity).unwrap();
+
+                let trust = match signature_cache.unpack_last_seen(&p.body.last_seen) {
+                    Some(last_seen) => {
+                        let trust = chat_sessions.verify(&p, &last_seen);
+                        if trust != ChatTrust::Invalid {
+                            signature_cache.push(&last_seen, p.signature.as_ref());
+                        }
+                        trust
+                    }
+                    None => {
+                        warn!(
+                            "Couldn't unpack the last seen messages of a chat message from {}",
+                            p.sender
+                        );
+                        ChatTrust::Invalid
+                    }
+                };
+                if trust == ChatTrust::Invalid {
+                    warn!(
+                        "Got a chat message from {} with an invalid signature",
+                        p.sender
+                    );
+                }
 
+                if let Some(signature) = &p.signature {
+                    // acknowledge the messages we've seen if we haven't sent a
+                    // chat message in a while, since the server keeps them
+                    // until we do
+                    if last_seen_messages
+                        .add_pending(signature.clone(), trust != ChatTrust::Invalid)
+                        && last_seen_messages.offset() > MAX_UNACKNOWLEDGED_MESSAGES
+                    {
+                        local_player.write_packet(
+                            ServerboundChatAckPacket {
+                                messages: last_seen_messages.take_offset(),
+                            }
+                            .get(),
+                        );
+                    }
+                }
+
+                let packet = ChatPacket::Player(Arc::new(p.clone()), trust);
+                chat_history.push(packet.clone());
                 chat_events.send(ChatReceivedEvent {
                     entity: player_entity,
-                    packet: ChatPacket::Player(Arc::new(p.clone())),
+                    packet,
                 });
             }
             ClientboundGamePacket::SystemChat(p) => {
                 debug!("Got system chat packet {:?}", p);
 
-                let mut system_state: SystemState<EventWriter<ChatReceivedEvent>> =
-                    SystemState::new(ecs);
-                let mut chat_events = system_state.get_mut(ecs);
+                let mut system_state: SystemState<(
+                    EventWriter<ChatReceivedEvent>,
+                    Query<&mut ChatHistory>,
+                )> = SystemState::new(ecs);
+                let (mut chat_events, mut query) = system_state.get_mut(ecs);
+                let mut chat_history = query.get_mut(player_entity).unwrap();
 
+                let packet = ChatPacket::System(Arc::new(p.clone()));
+                // overlay messages are shown above the hotbar instead of in chat
+                if !p.overlay {
+                    chat_history.push(packet.clone());
+                }
                 chat_events.send(ChatReceivedEvent {
                     entity: player_entity,
-                    packet: ChatPacket::System(Arc::new(p.clone())),
+                    packet,
                 });
             }
             ClientboundGamePacket::Sound(_p) => {
@@ -876,7 +1168,21 @@ fn process_packet_events(ecs: &mut World) {
                 debug!("Got update mob effect packet {:?}", p);
             }
             ClientboundGamePacket::AddExperienceOrb(_) => {}
-            ClientboundGamePacket::AwardStats(_) => {}
+            ClientboundGamePacket::AwardStats(p) => {
+                debug!("Got award stats packet");
+
+                let mut system_state: SystemState<Query<(&mut Statistics, &mut StatsRequests)>> =
+                    SystemState::new(ecs);
+                let mut query = system_state.get_mut(ecs);
+                let (mut statistics, mut requests) = query.get_mut(player_entity).unwrap();
+
+                for (&stat, &value) in &p.stats {
+                    statistics.set(stat, value);
+                }
+                if !requests.receive() {
+                    debug!("Got statistics that no request is waiting for");
+                }
+            }
             ClientboundGamePacket::BlockChangedAck(_) => {}
             ClientboundGamePacket::BlockDestruction(_) => {}
             ClientboundGamePacket::BlockEntityData(_) => {}
@@ -884,7 +1190,17 @@ fn process_packet_events(ecs: &mut World) {
                 debug!("Got block event packet {:?}", p);
             }
             ClientboundGamePacket::BossEvent(_) => {}
-            ClientboundGamePacket::CommandSuggestions(_) => {}
+            ClientboundGamePacket::CommandSuggestions(p) => {
+                let mut system_state: SystemState<Query<&mut CommandSuggestionRequests>> =
+                    SystemState::new(ecs);
+                let mut query = system_state.get_mut(ecs);
+                let mut requests = query.get_mut(player_entity).unwrap();
+
+                // ignore responses to requests we didn't make (or gave up on)
+                if let Some(response) = requests.responses.get_mut(&p.id) {
+                    *response = Some(p.suggestions.clone());
+                }
+            }
             ClientboundGamePacket::ContainerSetContent(p) => {
                 debug!("Got container set content packet {:?}", p);
 
@@ -974,9 +1290,58 @@ fn process_packet_events(ecs: &mut World) {
             }
             ClientboundGamePacket::Cooldown(_) => {}
             ClientboundGamePacket::CustomChatCompletions(_) => {}
-            ClientboundGamePacket::DeleteChat(_) => {}
+            ClientboundGamePacket::DeleteChat(p) => {
+                debug!("Got delete chat packet {:?}", p);
+
+                let mut system_state: SystemState<
+                    Query<(&MessageSignatureCache, &mut ChatHistory)>,
+                > = SystemState::new(ecs);
+                let mut query = system_state.get_mut(ecs);
+                let (signature_cache, mut chat_history) = query.get_mut(player_entity).unwrap();
+
+                match signature_cache.unpack(&p.signature) {
+                    Some(signature) => {
+                        if !chat_history.delete(&signature) {
+                            debug!("Couldn't find the chat message to delete");
+                        }
+                    }
+                    None => warn!("Couldn't unpack the signature of a deleted chat message"),
+                }
+            }
             ClientboundGamePacket::Explode(_) => {}
-            ClientboundGamePacket::ForgetLevelChunk(_) => {}
+            ClientboundGamePacket::ForgetLevelChunk(p) => {
+                debug!("Got forget level chunk packet {p:?}");
+                let pos = ChunkPos::new(p.x, p.z);
+
+                #[allow(clippy::type_complexity)]
+                let mut system_state: SystemState<(
+                    Query<&LocalPlayer>,
+                    Query<(&MinecraftEntityId, &mut LoadedBy), Without<Local>>,
+                )> = SystemState::new(ecs);
+                let (query, mut loaded_by_query) = system_state.get_mut(ecs);
+                let local_player = query.get(player_entity).unwrap();
+
+                let mut world = local_player.world.write();
+                let mut partial_world = local_player.partial_instance.write();
+
+                partial_world.chunks.set(&pos, None, &mut world.chunks);
+
+                // stop loading the entities in the chunk, they'll be despawned
+                // if no other clients have them loaded
+                let entities = world
+                    .entities_by_chunk
+                    .get(&pos)
+                    .cloned()
+                    .unwrap_or_default();
+                for entity in entities {
+                    let Ok((id, mut loaded_by)) = loaded_by_query.get_mut(entity) else {
+                        continue;
+                    };
+                    if loaded_by.remove(&player_entity) {
+                        partial_world.entity_infos.updates_received.remove(id);
+                    }
+                }
+            }
             ClientboundGamePacket::HorseScreenOpen(_) => {}
             ClientboundGamePacket::MapItemData(_) => {}
             ClientboundGamePacket::MerchantOffers(_) => {}
@@ -995,7 +1360,18 @@ fn process_packet_events(ecs: &mut World) {
                 })
             }
             ClientboundGamePacket::OpenSignEditor(_) => {}
-            ClientboundGamePacket::Ping(_) => {}
+            ClientboundGamePacket::Ping(p) => {
+                trace!("Got ping packet {p:?}");
+
+                // answer right away so the pong is sent in the same order
+                // relative to our other responses as the ping was received
+                // relative to the other packets, since anticheats use this to
+                // tell which packets we've processed
+                let mut system_state: SystemState<Query<&LocalPlayer>> = SystemState::new(ecs);
+                let mut query = system_state.get_mut(ecs);
+                let local_player = query.get_mut(player_entity).unwrap();
+                local_player.write_packet(ServerboundPongPacket { id: p.id }.get());
+            }
             ClientboundGamePacket::PlaceGhostRecipe(_) => {}
             ClientboundGamePacket::PlayerCombatEnd(_) => {}
             ClientboundGamePacket::PlayerCombatEnter(_) => {}
@@ -1023,7 +1399,21 @@ fn process_packet_events(ecs: &mut World) {
             }
             ClientboundGamePacket::PlayerLookAt(_) => {}
             ClientboundGamePacket::RemoveMobEffect(_) => {}
-            ClientboundGamePacket::ResourcePack(_) => {}
+            ClientboundGamePacket::ResourcePack(p) => {
+                debug!("Got resource pack packet {:?}", p);
+
+                let mut system_state: SystemState<EventWriter<ResourcePackEvent>> =
+                    SystemState::new(ecs);
+                let mut resource_pack_events = system_state.get_mut(ecs);
+
+                resource_pack_events.send(ResourcePackEvent {
+                    entity: player_entity,
+                    url: p.url,
+                    hash: p.hash,
+                    required: p.required,
+                    prompt: p.prompt,
+                });
+            }
             ClientboundGamePacket::Respawn(p) => {
                 debug!("Got respawn packet {:?}", p);
 
@@ -1059,8 +1449,26 @@ fn process_packet_events(ecs: &mut World) {
             ClientboundGamePacket::TabList(_) => {}
             ClientboundGamePacket::TagQuery(_) => {}
             ClientboundGamePacket::TakeItemEntity(_) => {}
-            ClientboundGamePacket::DisguisedChat(_) => {}
+            ClientboundGamePacket::DisguisedChat(p) => {
+                debug!("Got disguised chat packet {:?}", p);
+
+                let mut system_state: SystemState<(
+                    EventWriter<ChatReceivedEvent>,
+                    Query<&mut ChatHistory>,
+                )> = SystemState::new(ecs);
+                let (mut chat_events, mut query) = system_state.get_mut(ecs);
+                let mut chat_history = query.get_mut(player_entity).unwrap();
+
+                let packet = ChatPacket::Disguised(Arc::new(p.clone()));
+                chat_history.push(packet.clone());
+                chat_events.send(ChatReceivedEvent {
+                    entity: player_entity,
+                    packet,
+                });
+            }
             ClientboundGamePacket::UpdateEnabledFeatures(_) => {}
+            // the packets in bundles are already grouped together by the
+            // PacketReceiver, so we don't need to do anything here
             ClientboundGamePacket::Bundle(_) => {}
             ClientboundGamePacket::DamageEvent(_) => {}
             ClientboundGamePacket::HurtAnimation(_) => {}
@@ -1069,40 +1477,461 @@ fn process_packet_events(ecs: &mut World) {
 }
 
 impl PacketReceiver {
+    /// Create a receiver with an empty queue. `run_schedule_sender` is
+    /// notified whenever new packets are queued.
+    pub fn new(
+        run_schedule_sender: mpsc::UnboundedSender<()>,
+        capture: Option<PacketCapture>,
+        error_policy: PacketErrorPolicy,
+    ) -> Self {
+        Self {
+            packets: Arc::new(Mutex::new(Vec::new())),
+            run_schedule_sender,
+            capture,
+            error_policy,
+            errors: Arc::new(Mutex::new(Vec::new())),
+            dropped_packets: Arc::new(AtomicUsize::new(0)),
+            disconnect_reason: Arc::new(Mutex::new(None)),
+        }
+    }
+
     /// Loop that reads from the connection and adds the packets to the queue +
     /// runs the schedule.
-    pub async fn read_task(self, mut read_conn: ReadConnection<ClientboundGamePacket>) {
+    pub async fn read_task(
+        self,
+        mut read_conn: ReadConnection<ClientboundGamePacket, impl AsyncRead + Unpin + Send>,
+    ) {
+        let reason = self.read_packets(&mut read_conn).await;
+        *self.disconnect_reason.lock() = Some(reason);
+    }
+
+    /// Read packets until we should disconnect, and return why.
+    async fn read_packets(
+        &self,
+        read_conn: &mut ReadConnection<ClientboundGamePacket, impl AsyncRead + Unpin + Send>,
+    ) -> DisconnectReason {
+        // the packets we've received since the start of the current bundle,
+        // including the opening delimiter
+        let mut bundle: Option<Vec<ClientboundGamePacket>> = None;
         loop {
-            match read_conn.read().await {
-                Ok(packet) => {
-                    self.packets.lock().push(packet);
-                    // tell the client to run all the systems
-                    self.run_schedule_sender.send(()).unwrap();
-                }
-                Err(error) => {
+            let data = match tokio::time::timeout(KEEP_ALIVE_TIMEOUT, read_conn.read_raw()).await {
+                Ok(Ok(data)) => data,
+                Ok(Err(error)) => {
                     if !matches!(*error, ReadPacketError::ConnectionClosed) {
                         error!("Error reading packet from Client: {error:?}");
                     }
-                    break;
+                    return DisconnectReason::ConnectionLost(error.into());
                 }
+                Err(_) => {
+                    error!(
+                        "Disconnecting because the server didn't send anything for \
+                        {KEEP_ALIVE_TIMEOUT:?}."
+                    );
+                    return DisconnectReason::TimedOut;
+                }
+            };
+            if let Some(capture) = &self.capture {
+                capture.record::<ClientboundGamePacket>(&data);
+            }
+            let result = match read_conn.decode(&data) {
+                Ok(packet) => self.receive_packet(packet, &mut bundle),
+                Err(error) => self.receive_packet_error(error, data),
+            };
+            if let Err(reason) = result {
+                return reason;
             }
         }
     }
 
+    /// Handle a packet that couldn't be decoded based on the
+    /// [`PacketErrorPolicy`]. This returns an error if we should disconnect.
+    pub(crate) fn receive_packet_error(
+        &self,
+        error: Box<ReadPacketError>,
+        data: Vec<u8>,
+    ) -> Result<(), DisconnectReason> {
+        let action = self.error_policy.action_for(&error);
+        let data_string = if data.len() > 500 {
+            format!("{:?}...", &data[..500])
+        } else {
+            format!("{data:?}")
+        };
+        match action {
+            PacketErrorAction::Skip => {
+                self.dropped_packets.fetch_add(1, Ordering::Relaxed);
+                warn!("Skipping packet that couldn't be read: {error}. Bytes: {data_string}");
+            }
+            PacketErrorAction::Disconnect => {
+                error!("Disconnecting because of a packet that couldn't be read: {error}. Bytes: {data_string}");
+            }
+        }
+        let packet_error = Arc::new(PacketError {
+            error,
+            data,
+            action,
+        });
+        self.errors.lock().push(packet_error.clone());
+        self.run_schedule_sender.send(()).unwrap();
+        match action {
+            PacketErrorAction::Skip => Ok(()),
+            PacketErrorAction::Disconnect => Err(DisconnectReason::PacketError(packet_error)),
+        }
+    }
+
+    /// Add a packet to the queue and run the schedule, or hold on to it if
+    /// it's part of a bundle that hasn't ended yet. This returns an error if
+    /// the bundle was too big and we should disconnect.
+    pub(crate) fn receive_packet(
+        &self,
+        packet: ClientboundGamePacket,
+        bundle: &mut Option<Vec<ClientboundGamePacket>>,
+    ) -> Result<(), DisconnectReason> {
+        let is_delimiter = matches!(packet, ClientboundGamePacket::Bundle(_));
+        match bundle {
+            None if is_delimiter => {
+                *bundle = Some(vec![packet]);
+                return Ok(());
+            }
+            None => self.packets.lock().push(packet),
+            Some(bundle_packets) => {
+                bundle_packets.push(packet);
+                if !is_delimiter {
+                    // the opening delimiter doesn't count
+                    if bundle_packets.len() - 1 > MAX_BUNDLE_PACKETS {
+                        // this is what vanilla does too
+                        error!(
+                            "Disconnecting because a bundle had more than \
+                            {MAX_BUNDLE_PACKETS} packets."
+                        );
+                        return Err(DisconnectReason::BundleTooBig);
+                    }
+                    return Ok(());
+                }
+                self.packets.lock().append(bundle_packets);
+                *bundle = None;
+            }
+        }
+        // tell the client to run all the systems
+        self.run_schedule_sender.send(()).unwrap();
+        Ok(())
+    }
+
     /// Consume the [`ServerboundGamePacket`] queue and actually write the
     /// packets to the server. It's like this so writing packets doesn't need to
     /// be awaited.
+    ///
+    /// When the sender for the queue is dropped, the packets that are left are
+    /// written and the connection is shut down.
     pub async fn write_task(
         self,
-        mut write_conn: WriteConnection<ServerboundGamePacket>,
+        mut write_conn: WriteConnection<ServerboundGamePacket, impl AsyncWrite + Unpin + Send>,
         mut write_receiver: mpsc::UnboundedReceiver<ServerboundGamePacket>,
     ) {
         while let Some(packet) = write_receiver.recv().await {
-            if let Err(err) = write_conn.write(packet).await {
+            write_conn.stats.write_dequeued();
+            let data = match write_conn.encode(&packet) {
+                Ok(data) => data,
+                Err(err) => {
+                    error!("Disconnecting because we couldn't encode a packet: {err}.");
+                    return;
+                }
+            };
+            if let Some(capture) = &self.capture {
+                capture.record::<ServerboundGamePacket>(&data);
+            }
+            if let Err(err) = write_conn.write_encoded(packet.name(), data).await {
                 error!("Disconnecting because we couldn't write a packet: {err}.");
-                break;
+                return;
             };
         }
-        // receiver is automatically closed when it's dropped
+        // every packet was written, so the server gets everything we sent
+        // before the connection closes
+        if let Err(err) = write_conn.shutdown().await {
+            debug!("Couldn't shut down the connection: {err}.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::{join_test_server, join_test_server_with_opts, login_packet},
+        Event, JoinOpts,
+    };
+    use azalea_buf::McBufWritable;
+    use azalea_core::BitSet;
+    use azalea_nbt::Nbt;
+    use azalea_protocol::{
+        packets::game::{
+            clientbound_add_entity_packet::ClientboundAddEntityPacket,
+            clientbound_bundle_packet::ClientboundBundlePacket,
+            clientbound_forget_level_chunk_packet::ClientboundForgetLevelChunkPacket,
+            clientbound_keep_alive_packet::ClientboundKeepAlivePacket,
+            clientbound_level_chunk_with_light_packet::{
+                ClientboundLevelChunkPacketData, ClientboundLevelChunkWithLightPacket,
+            },
+            clientbound_light_update_packet::ClientboundLightUpdatePacketData,
+            clientbound_teleport_entity_packet::ClientboundTeleportEntityPacket,
+        },
+        server::JoinedPlayer,
+    };
+    use azalea_world::Chunk;
+    use uuid::Uuid;
+
+    fn packet_receiver() -> (PacketReceiver, mpsc::UnboundedReceiver<()>) {
+        let (run_schedule_sender, run_schedule_receiver) = mpsc::unbounded_channel();
+        (
+            PacketReceiver::new(run_schedule_sender, None, PacketErrorPolicy::SKIP),
+            run_schedule_receiver,
+        )
+    }
+
+    fn keep_alive(id: u64) -> ClientboundGamePacket {
+        ClientboundKeepAlivePacket { id }.get()
+    }
+
+    #[test]
+    fn test_bundle_is_queued_when_it_ends() {
+        let (receiver, mut run_schedule_receiver) = packet_receiver();
+        let mut bundle = None;
+
+        receiver
+            .receive_packet(ClientboundBundlePacket {}.get(), &mut bundle)
+            .unwrap();
+        receiver.receive_packet(keep_alive(1), &mut bundle).unwrap();
+        receiver.receive_packet(keep_alive(2), &mut bundle).unwrap();
+        assert!(receiver.packets.lock().is_empty());
+        assert!(run_schedule_receiver.try_recv().is_err());
+
+        receiver
+            .receive_packet(ClientboundBundlePacket {}.get(), &mut bundle)
+            .unwrap();
+        assert!(bundle.is_none());
+        assert!(matches!(
+            receiver.packets.lock()[..],
+            [
+                ClientboundGamePacket::Bundle(_),
+                ClientboundGamePacket::KeepAlive(ClientboundKeepAlivePacket { id: 1 }),
+                ClientboundGamePacket::KeepAlive(ClientboundKeepAlivePacket { id: 2 }),
+                ClientboundGamePacket::Bundle(_),
+            ]
+        ));
+        assert!(run_schedule_receiver.try_recv().is_ok());
+
+        // packets after the bundle are queued right away
+        receiver.receive_packet(keep_alive(3), &mut bundle).unwrap();
+        assert_eq!(receiver.packets.lock().len(), 5);
+    }
+
+    #[test]
+    fn test_bundle_too_big() {
+        let (receiver, _run_schedule_receiver) = packet_receiver();
+        let mut bundle = None;
+
+        receiver
+            .receive_packet(ClientboundBundlePacket {}.get(), &mut bundle)
+            .unwrap();
+        for i in 0..MAX_BUNDLE_PACKETS as u64 {
+            receiver.receive_packet(keep_alive(i), &mut bundle).unwrap();
+        }
+        assert!(matches!(
+            receiver.receive_packet(keep_alive(0), &mut bundle),
+            Err(DisconnectReason::BundleTooBig)
+        ));
+        assert!(receiver.packets.lock().is_empty());
+    }
+
+    /// A packet with an id that doesn't exist in the game state.
+    const UNKNOWN_PACKET: [u8; 3] = [0x7f, 0x01, 0x02];
+
+    async fn next_packet_error(rx: &mut mpsc::UnboundedReceiver<Event>) -> Arc<PacketError> {
+        loop {
+            if let Event::PacketError(error) = rx.recv().await.unwrap() {
+                return error;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_packet_is_skipped() {
+        let opts = JoinOpts::new().packet_error_policy(PacketErrorPolicy::SKIP);
+        let (client, mut rx, mut player) = join_test_server_with_opts(&opts).await;
+        player
+            .connection
+            .writer
+            .write_raw(UNKNOWN_PACKET.to_vec())
+            .await
+            .unwrap();
+
+        let error = next_packet_error(&mut rx).await;
+        assert!(matches!(
+            *error.error,
+            ReadPacketError::UnknownPacketId { .. }
+        ));
+        assert_eq!(error.action, PacketErrorAction::Skip);
+        assert_eq!(error.data, UNKNOWN_PACKET);
+        assert_eq!(client.dropped_packets(), 1);
+
+        // we're still connected and reading packets
+        player.connection.write(keep_alive(1)).await.unwrap();
+        loop {
+            if let ServerboundGamePacket::KeepAlive(p) = player.connection.read().await.unwrap() {
+                assert_eq!(p.id, 1);
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_packet_disconnects() {
+        let opts = JoinOpts::new().packet_error_policy(PacketErrorPolicy::DISCONNECT);
+        let (_client, mut rx, mut player) = join_test_server_with_opts(&opts).await;
+        player
+            .connection
+            .writer
+            .write_raw(UNKNOWN_PACKET.to_vec())
+            .await
+            .unwrap();
+
+        let error = next_packet_error(&mut rx).await;
+        assert_eq!(error.action, PacketErrorAction::Disconnect);
+        loop {
+            if let Event::Disconnect(reason) = rx.recv().await.unwrap() {
+                let DisconnectReason::PacketError(reason_error) = reason else {
+                    panic!("expected a packet error, got {reason:?}");
+                };
+                assert!(Arc::ptr_eq(&reason_error, &error));
+                break;
+            }
+        }
+    }
+
+    /// Wait until the client handled every packet we sent so far.
+    async fn wait_for_client(player: &mut JoinedPlayer) {
+        // keep-alives are answered while the packets are being handled, so
+        // the second one is only answered after the systems that ran after
+        // the first one (like the ones that update the entity indexes)
+        for id in [1, 2] {
+            player.connection.write(keep_alive(id)).await.unwrap();
+            loop {
+                if let ServerboundGamePacket::KeepAlive(p) = player.connection.read().await.unwrap()
+                {
+                    if p.id == id {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forget_level_chunk() {
+        let (client, _rx, mut player) = join_test_server().await;
+        player.connection.write(login_packet().get()).await.unwrap();
+
+        let mut data = Vec::new();
+        Chunk::default().write_into(&mut data).unwrap();
+        player
+            .connection
+            .write(
+                ClientboundLevelChunkWithLightPacket {
+                    x: 0,
+                    z: 0,
+                    chunk_data: ClientboundLevelChunkPacketData {
+                        heightmaps: Nbt::End,
+                        data,
+                        block_entities: Vec::new(),
+                    },
+                    light_data: ClientboundLightUpdatePacketData {
+                        trust_edges: true,
+                        sky_y_mask: BitSet::default(),
+                        block_y_mask: BitSet::default(),
+                        empty_sky_y_mask: BitSet::default(),
+                        empty_block_y_mask: BitSet::default(),
+                        sky_updates: Vec::new(),
+                        block_updates: Vec::new(),
+                    },
+                }
+                .get(),
+            )
+            .await
+            .unwrap();
+        player
+            .connection
+            .write(
+                ClientboundAddEntityPacket {
+                    id: 2,
+                    uuid: Uuid::new_v4(),
+                    entity_type: azalea_registry::EntityKind::Zombie,
+                    position: Vec3::new(1., 70., 1.),
+                    x_rot: 0,
+                    y_rot: 0,
+                    y_head_rot: 0,
+                    data: 0,
+                    x_vel: 0,
+                    y_vel: 0,
+                    z_vel: 0,
+                }
+                .get(),
+            )
+            .await
+            .unwrap();
+        wait_for_client(&mut player).await;
+        // so we've received an update for the entity
+        player
+            .connection
+            .write(
+                ClientboundTeleportEntityPacket {
+                    id: 2,
+                    position: Vec3::new(2., 70., 2.),
+                    y_rot: 0,
+                    x_rot: 0,
+                    on_ground: true,
+                }
+                .get(),
+            )
+            .await
+            .unwrap();
+        wait_for_client(&mut player).await;
+
+        let pos = ChunkPos::new(0, 0);
+        let partial_instance = client
+            .query::<&LocalPlayer>(&mut client.ecs.lock())
+            .partial_instance
+            .clone();
+        let entity = client
+            .world()
+            .read()
+            .entity_by_id(&MinecraftEntityId(2))
+            .unwrap();
+        assert!(partial_instance.read().chunks.limited_get(&pos).is_some());
+        assert!(client
+            .ecs
+            .lock()
+            .get::<LoadedBy>(entity)
+            .unwrap()
+            .contains(&client.entity));
+        assert!(partial_instance
+            .read()
+            .entity_infos
+            .updates_received
+            .contains_key(&MinecraftEntityId(2)));
+
+        player
+            .connection
+            .write(ClientboundForgetLevelChunkPacket { x: 0, z: 0 }.get())
+            .await
+            .unwrap();
+        wait_for_client(&mut player).await;
+
+        assert!(partial_instance.read().chunks.limited_get(&pos).is_none());
+        assert!(!partial_instance
+            .read()
+            .entity_infos
+            .updates_received
+            .contains_key(&MinecraftEntityId(2)));
+        // no other client has the entity loaded, so it's despawned
+        assert!(client.ecs.lock().get::<LoadedBy>(entity).is_none());
     }
 }
@@ -3,9 +3,13 @@
 use azalea_protocol::{
     connect::{Connection, ConnectionError},
     packets::{
-        handshake::client_intention_packet::ClientIntentionPacket,
+        handshake::{
+            client_intention_packet::ClientIntentionPacket, ClientboundHandshakePacket,
+            ServerboundHandshakePacket,
+        },
         status::{
             clientbound_status_response_packet::ClientboundStatusResponsePacket,
+            serverbound_ping_request_packet::ServerboundPingRequestPacket,
             serverbound_status_request_packet::ServerboundStatusRequestPacket,
             ClientboundStatusPacket,
         },
@@ -13,8 +17,15 @@ use azalea_protocol::{
     },
     resolver, ServerAddress,
 };
-use std::io;
+use std::{
+    io,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
 
 #[derive(Error, Debug)]
 pub enum PingError {
@@ -28,6 +39,18 @@ pub enum PingError {
     WritePacket(#[from] io::Error),
     #[error("The given address could not be parsed into a ServerAddress")]
     InvalidAddress,
+    #[error("The server sent an invalid response: {0}")]
+    InvalidResponse(String),
+}
+
+/// The result of [`ping_server`].
+#[derive(Clone, Debug)]
+pub struct PingResult {
+    /// The server's status, containing things like the MOTD and player count.
+    pub status: ClientboundStatusResponsePacket,
+    /// How long it took for the server to respond to our ping request. This
+    /// is what the vanilla client shows in the server list.
+    pub latency: Duration,
 }
 
 /// Ping a Minecraft server.
@@ -40,18 +63,28 @@ pub enum PingError {
 /// #[tokio::main]
 /// async fn main() {
 ///     let response = ping::ping_server("play.hypixel.net").await.unwrap();
-///     println!("{}", response.description.to_ansi());
+///     println!("{}", response.status.description.to_ansi());
+///     println!("{}ms", response.latency.as_millis());
 /// }
 /// ```
-pub async fn ping_server(
-    address: impl TryInto<ServerAddress>,
-) -> Result<ClientboundStatusResponsePacket, PingError> {
+pub async fn ping_server(address: impl TryInto<ServerAddress>) -> Result<PingResult, PingError> {
     let address: ServerAddress = address.try_into().map_err(|_| PingError::InvalidAddress)?;
 
     let resolved_address = resolver::resolve_address(&address).await?;
 
-    let mut conn = Connection::new(&resolved_address).await?;
+    let conn = Connection::new(&resolved_address).await?;
+    ping_connection(conn, &address).await
+}
 
+/// Ping a Minecraft server over a connection that's in the handshake state.
+/// This is useful if you connected to the server yourself, like through a
+/// proxy.
+///
+/// The address is the one that's sent to the server in the handshake.
+pub async fn ping_connection(
+    mut conn: Connection<ClientboundHandshakePacket, ServerboundHandshakePacket>,
+    address: &ServerAddress,
+) -> Result<PingResult, PingError> {
     // send the client intention packet and switch to the status state
     conn.write(
         ClientIntentionPacket {
@@ -68,14 +101,189 @@ pub async fn ping_server(
     // send the empty status request packet
     conn.write(ServerboundStatusRequestPacket {}.get()).await?;
 
-    let packet = conn.read().await?;
-
-    loop {
-        match packet {
-            ClientboundStatusPacket::StatusResponse(p) => return Ok(p),
+    let status = loop {
+        match conn.read().await? {
+            ClientboundStatusPacket::StatusResponse(p) => break p,
             ClientboundStatusPacket::PongResponse(_) => {
                 // we should never get this packet since we didn't send a ping
             }
         }
+    };
+
+    // the vanilla client sends the current time, but the server just echoes
+    // it back so it doesn't really matter
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    let sent_at = Instant::now();
+    conn.write(ServerboundPingRequestPacket { time }.get())
+        .await?;
+
+    loop {
+        match conn.read().await? {
+            ClientboundStatusPacket::PongResponse(p) if p.time == time => {
+                return Ok(PingResult {
+                    status,
+                    latency: sent_at.elapsed(),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The result of [`ping_legacy`].
+#[derive(Clone, Debug)]
+pub struct LegacyPingResult {
+    /// The protocol version of the server. This is only sent by servers newer
+    /// than 1.4.
+    pub protocol_version: Option<i32>,
+    /// The name of the server's version, like "1.19.4". This is only sent by
+    /// servers newer than 1.4.
+    pub version: Option<String>,
+    /// The message of the day, with legacy `§` formatting codes.
+    pub motd: String,
+    pub online_players: i32,
+    pub max_players: i32,
+    /// How long it took for the server to respond.
+    pub latency: Duration,
+}
+
+/// Ping a Minecraft server with the legacy server list ping that clients
+/// before 1.7 used (the one that starts with `0xFE`).
+///
+/// Most modern servers still support this, and it's the only way to ping
+/// servers older than 1.7.
+///
+/// ```rust,no_run
+/// use azalea_client::ping;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let response = ping::ping_legacy("localhost").await.unwrap();
+///     println!("{}/{}", response.online_players, response.max_players);
+/// }
+/// ```
+pub async fn ping_legacy(
+    address: impl TryInto<ServerAddress>,
+) -> Result<LegacyPingResult, PingError> {
+    let address: ServerAddress = address.try_into().map_err(|_| PingError::InvalidAddress)?;
+    let resolved_address = resolver::resolve_address(&address).await?;
+
+    let mut stream = TcpStream::connect(resolved_address).await?;
+
+    // this is the 1.6 format, older servers ignore everything after the 0x01
+    let mut host_data = Vec::new();
+    // the protocol version of 1.6.4
+    host_data.push(78);
+    write_legacy_string(&mut host_data, &address.host);
+    host_data.extend((address.port as i32).to_be_bytes());
+
+    let mut request = vec![0xfe, 0x01, 0xfa];
+    write_legacy_string(&mut request, "MC|PingHost");
+    request.extend((host_data.len() as u16).to_be_bytes());
+    request.extend(host_data);
+
+    let sent_at = Instant::now();
+    stream.write_all(&request).await?;
+
+    let packet_id = stream.read_u8().await?;
+    let latency = sent_at.elapsed();
+    if packet_id != 0xff {
+        return Err(PingError::InvalidResponse(format!(
+            "Expected a kick packet (0xff), got {packet_id:#x}"
+        )));
+    }
+    let length = stream.read_u16().await?;
+    let mut data = vec![0; length as usize * 2];
+    stream.read_exact(&mut data).await?;
+    let response = String::from_utf16_lossy(
+        &data
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>(),
+    );
+
+    parse_legacy_response(&response, latency)
+}
+
+/// Write a string the way the legacy protocol does, as UTF-16 with its length
+/// in characters before it.
+fn write_legacy_string(buf: &mut Vec<u8>, string: &str) {
+    let chars = string.encode_utf16().collect::<Vec<_>>();
+    buf.extend((chars.len() as u16).to_be_bytes());
+    for c in chars {
+        buf.extend(c.to_be_bytes());
+    }
+}
+
+fn parse_legacy_response(response: &str, latency: Duration) -> Result<LegacyPingResult, PingError> {
+    let invalid = || PingError::InvalidResponse(response.to_string());
+
+    if let Some(response) = response.strip_prefix("§1\0") {
+        // 1.4+ servers separate the fields with null characters
+        let fields = response.split('\0').collect::<Vec<_>>();
+        let [protocol_version, version, motd, online, max] = fields[..] else {
+            return Err(invalid());
+        };
+        Ok(LegacyPingResult {
+            protocol_version: Some(protocol_version.parse().map_err(|_| invalid())?),
+            version: Some(version.to_string()),
+            motd: motd.to_string(),
+            online_players: online.parse().map_err(|_| invalid())?,
+            max_players: max.parse().map_err(|_| invalid())?,
+            latency,
+        })
+    } else {
+        // older servers send `motd§online§max`, and the motd can't have § in it
+        let mut fields = response.rsplitn(3, '§');
+        let max = fields.next().ok_or_else(invalid)?;
+        let online = fields.next().ok_or_else(invalid)?;
+        let motd = fields.next().ok_or_else(invalid)?;
+        Ok(LegacyPingResult {
+            protocol_version: None,
+            version: None,
+            motd: motd.to_string(),
+            online_players: online.parse().map_err(|_| invalid())?,
+            max_players: max.parse().map_err(|_| invalid())?,
+            latency,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_legacy_response() {
+        // what a vanilla 1.19.4 server sends
+        let response = "§1\0127\x001.19.4\0A Minecraft Server\x003\x0020";
+        let result = parse_legacy_response(response, Duration::from_millis(5)).unwrap();
+        assert_eq!(result.protocol_version, Some(127));
+        assert_eq!(result.version.as_deref(), Some("1.19.4"));
+        assert_eq!(result.motd, "A Minecraft Server");
+        assert_eq!(result.online_players, 3);
+        assert_eq!(result.max_players, 20);
+        assert_eq!(result.latency, Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_parse_legacy_response_beta() {
+        // servers from beta 1.8 to 1.3 only send the motd and player counts
+        let response = "A Minecraft Server§0§20";
+        let result = parse_legacy_response(response, Duration::ZERO).unwrap();
+        assert_eq!(result.protocol_version, None);
+        assert_eq!(result.version, None);
+        assert_eq!(result.motd, "A Minecraft Server");
+        assert_eq!(result.online_players, 0);
+        assert_eq!(result.max_players, 20);
+    }
+
+    #[test]
+    fn test_parse_legacy_response_invalid() {
+        assert!(parse_legacy_response("§1\0127\x001.19.4", Duration::ZERO).is_err());
+        assert!(parse_legacy_response("A Minecraft Server", Duration::ZERO).is_err());
     }
 }
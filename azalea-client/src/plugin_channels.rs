@@ -0,0 +1,370 @@
+//! Send and receive plugin messages, which are custom payloads that servers
+//! and proxies (like BungeeCord) use to talk to clients.
+
+use std::{collections::HashSet, io::Cursor, marker::PhantomData};
+
+use azalea_buf::{McBufReadable, McBufWritable};
+use azalea_core::ResourceLocation;
+use azalea_protocol::packets::game::serverbound_custom_payload_packet::ServerboundCustomPayloadPacket;
+use azalea_world::entity::{Local, MinecraftEntityId};
+use bevy_app::{App, Plugin};
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    event::{EventReader, EventWriter},
+    query::{Added, With},
+    schedule::IntoSystemConfig,
+    system::{Query, Res, Resource},
+};
+use log::{debug, warn};
+
+use crate::{
+    local_player::{handle_send_packet_event, SendPacketEvent},
+    Client,
+};
+
+/// The channel for telling the other side which channels we want to receive
+/// messages on.
+pub const REGISTER_CHANNEL: &str = "minecraft:register";
+/// The channel for telling the other side we don't want to receive messages
+/// on some channels anymore.
+pub const UNREGISTER_CHANNEL: &str = "minecraft:unregister";
+/// The channel for telling the server which client we're using.
+pub const BRAND_CHANNEL: &str = "minecraft:brand";
+
+pub struct PluginChannelsPlugin;
+impl Plugin for PluginChannelsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PluginChannels>()
+            .add_event::<PluginMessageEvent>()
+            .add_system(send_brand_and_channels.before(handle_send_packet_event))
+            .add_system(handle_register_messages);
+    }
+}
+
+/// A resource with the brand and plugin channels that clients send to the
+/// server when they join.
+///
+/// ```
+/// # use azalea_client::plugin_channels::PluginChannels;
+/// # fn example(app: &mut bevy_app::App) {
+/// app.insert_resource(PluginChannels::new("azalea").with_channel("bungeecord:main"));
+/// # }
+/// ```
+#[derive(Resource, Clone, Debug)]
+pub struct PluginChannels {
+    /// The client brand that's shown in the server's F3 menu and given to
+    /// plugins. Defaults to "vanilla".
+    pub brand: String,
+    /// The channels we register with `minecraft:register` when we join.
+    pub channels: HashSet<ResourceLocation>,
+}
+
+impl Default for PluginChannels {
+    fn default() -> Self {
+        Self::new("vanilla")
+    }
+}
+
+impl PluginChannels {
+    pub fn new(brand: &str) -> Self {
+        Self {
+            brand: brand.to_string(),
+            channels: HashSet::new(),
+        }
+    }
+
+    /// Register a channel when clients join.
+    #[must_use]
+    pub fn with_channel(mut self, channel: &str) -> Self {
+        self.channels.insert(ResourceLocation::new(channel));
+        self
+    }
+}
+
+/// A component with the channels that the server registered with
+/// `minecraft:register`, which are the channels it wants to receive messages
+/// on.
+#[derive(Component, Clone, Debug, Default)]
+pub struct ServerPluginChannels(pub HashSet<ResourceLocation>);
+
+/// A local player received a plugin message from the server.
+#[derive(Debug, Clone)]
+pub struct PluginMessageEvent {
+    pub entity: Entity,
+    pub channel: ResourceLocation,
+    pub data: Vec<u8>,
+}
+
+/// A type of plugin message that's always sent on the same channel. Add a
+/// [`PluginMessagePlugin`] for it to register the channel and receive the
+/// messages as [`PluginPayloadEvent`]s.
+///
+/// Note that BungeeCord writes strings with Java's `writeUTF`, which is a
+/// `u16` length followed by the bytes, so you may have to implement
+/// [`McBufReadable`] and [`McBufWritable`] yourself.
+///
+/// ```
+/// # use azalea_buf::{BufReadError, McBufReadable, McBufWritable};
+/// # use azalea_client::plugin_channels::PluginMessage;
+/// # use azalea_core::ResourceLocation;
+/// # use std::io::{Cursor, Read, Write};
+/// /// BungeeCord's response to a `PlayerCount` request.
+/// struct PlayerCount {
+///     server: String,
+///     count: i32,
+/// }
+///
+/// fn read_utf(buf: &mut Cursor<&[u8]>) -> Result<String, BufReadError> {
+///     let mut bytes = vec![0; u16::read_from(buf)? as usize];
+///     buf.read_exact(&mut bytes)?;
+///     Ok(String::from_utf8_lossy(&bytes).into_owned())
+/// }
+///
+/// impl McBufReadable for PlayerCount {
+///     fn read_from(buf: &mut Cursor<&[u8]>) -> Result<Self, BufReadError> {
+///         let _subchannel = read_utf(buf)?;
+///         Ok(PlayerCount {
+///             server: read_utf(buf)?,
+///             count: i32::read_from(buf)?,
+///         })
+///     }
+/// }
+/// impl McBufWritable for PlayerCount {
+///     fn write_into(&self, buf: &mut impl Write) -> Result<(), std::io::Error> {
+///         for s in ["PlayerCount", self.server.as_str()] {
+///             (s.len() as u16).write_into(buf)?;
+///             buf.write_all(s.as_bytes())?;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// impl PluginMessage for PlayerCount {
+///     fn channel() -> ResourceLocation {
+///         ResourceLocation::new("bungeecord:main")
+///     }
+/// }
+/// ```
+pub trait PluginMessage: McBufReadable + McBufWritable + Send + Sync + 'static {
+    fn channel() -> ResourceLocation;
+}
+
+/// A plugin that registers the channel for a type of [`PluginMessage`] and
+/// sends a [`PluginPayloadEvent`] every time one is received.
+pub struct PluginMessagePlugin<T: PluginMessage>(PhantomData<T>);
+
+impl<T: PluginMessage> Default for PluginMessagePlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: PluginMessage> Plugin for PluginMessagePlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.world
+            .get_resource_or_insert_with(PluginChannels::default)
+            .channels
+            .insert(T::channel());
+        app.add_event::<PluginPayloadEvent<T>>()
+            .add_system(decode_plugin_messages::<T>);
+    }
+}
+
+/// A local player received a [`PluginMessage`] of type `T` from the server.
+#[derive(Debug, Clone)]
+pub struct PluginPayloadEvent<T: PluginMessage> {
+    pub entity: Entity,
+    pub message: T,
+}
+
+fn decode_plugin_messages<T: PluginMessage>(
+    mut events: EventReader<PluginMessageEvent>,
+    mut payload_events: EventWriter<PluginPayloadEvent<T>>,
+) {
+    let channel = T::channel();
+    for event in events.iter() {
+        if event.channel != channel {
+            continue;
+        }
+        match T::read_from(&mut Cursor::new(&event.data)) {
+            Ok(message) => payload_events.send(PluginPayloadEvent {
+                entity: event.entity,
+                message,
+            }),
+            Err(e) => warn!("Couldn't read plugin message on {channel}: {e}"),
+        }
+    }
+}
+
+/// Join channel names with null bytes, which is how they're sent in
+/// `minecraft:register` and `minecraft:unregister`.
+fn encode_channel_list<'a>(channels: impl IntoIterator<Item = &'a ResourceLocation>) -> Vec<u8> {
+    channels
+        .into_iter()
+        .map(|channel| channel.to_string())
+        .collect::<Vec<_>>()
+        .join("\0")
+        .into_bytes()
+}
+
+fn decode_channel_list(data: &[u8]) -> impl Iterator<Item = ResourceLocation> + '_ {
+    data.split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| ResourceLocation::new(&String::from_utf8_lossy(name)))
+}
+
+// when MinecraftEntityId is added to a local player, it means we just got the
+// login packet
+fn send_brand_and_channels(
+    query: Query<Entity, (Added<MinecraftEntityId>, With<Local>)>,
+    plugin_channels: Res<PluginChannels>,
+    mut send_packet_events: EventWriter<SendPacketEvent>,
+) {
+    for entity in &query {
+        let mut brand = Vec::new();
+        plugin_channels
+            .brand
+            .write_into(&mut brand)
+            .expect("Writing to a Vec shouldn't fail");
+        send_packet_events.send(SendPacketEvent {
+            entity,
+            packet: ServerboundCustomPayloadPacket {
+                identifier: ResourceLocation::new(BRAND_CHANNEL),
+                data: brand.into(),
+            }
+            .get(),
+        });
+
+        if !plugin_channels.channels.is_empty() {
+            send_packet_events.send(SendPacketEvent {
+                entity,
+                packet: ServerboundCustomPayloadPacket {
+                    identifier: ResourceLocation::new(REGISTER_CHANNEL),
+                    data: encode_channel_list(&plugin_channels.channels).into(),
+                }
+                .get(),
+            });
+        }
+    }
+}
+
+fn handle_register_messages(
+    mut events: EventReader<PluginMessageEvent>,
+    mut query: Query<&mut ServerPluginChannels>,
+) {
+    for event in events.iter() {
+        let channel = event.channel.to_string();
+        if channel != REGISTER_CHANNEL && channel != UNREGISTER_CHANNEL {
+            continue;
+        }
+        let Ok(mut server_channels) = query.get_mut(event.entity) else {
+            continue;
+        };
+        for name in decode_channel_list(&event.data) {
+            debug!("Got {channel} for plugin channel {name}");
+            if channel == REGISTER_CHANNEL {
+                server_channels.0.insert(name);
+            } else {
+                server_channels.0.remove(&name);
+            }
+        }
+    }
+}
+
+impl Client {
+    /// Send a plugin message to the server on the given channel.
+    ///
+    /// ```no_run
+    /// # use azalea_client::Client;
+    /// # fn example(bot: &Client) {
+    /// // ask BungeeCord to move us to the "lobby" server
+    /// let mut data = Vec::new();
+    /// for s in ["Connect", "lobby"] {
+    ///     data.extend((s.len() as u16).to_be_bytes());
+    ///     data.extend(s.as_bytes());
+    /// }
+    /// bot.send_plugin_message("bungeecord:main", &data);
+    /// # }
+    /// ```
+    pub fn send_plugin_message(&self, channel: &str, data: &[u8]) {
+        self.write_packet(
+            ServerboundCustomPayloadPacket {
+                identifier: ResourceLocation::new(channel),
+                data: data.to_vec().into(),
+            }
+            .get(),
+        );
+    }
+
+    /// Encode a [`PluginMessage`] and send it to the server on its channel.
+    pub fn send_plugin_payload<T: PluginMessage>(&self, message: &T) {
+        let mut data = Vec::new();
+        message
+            .write_into(&mut data)
+            .expect("Writing to a Vec shouldn't fail");
+        self.send_plugin_message(&T::channel().to_string(), &data);
+    }
+
+    /// Tell the server we want to receive messages on these channels. Channels
+    /// in [`PluginChannels`] are registered automatically when we join.
+    pub fn register_plugin_channels(&self, channels: &[&str]) {
+        let channels = channels
+            .iter()
+            .map(|channel| ResourceLocation::new(channel))
+            .collect::<Vec<_>>();
+        self.send_plugin_message(REGISTER_CHANNEL, &encode_channel_list(&channels));
+    }
+
+    /// Tell the server we don't want to receive messages on these channels
+    /// anymore.
+    pub fn unregister_plugin_channels(&self, channels: &[&str]) {
+        let channels = channels
+            .iter()
+            .map(|channel| ResourceLocation::new(channel))
+            .collect::<Vec<_>>();
+        self.send_plugin_message(UNREGISTER_CHANNEL, &encode_channel_list(&channels));
+    }
+
+    /// Get the channels the server registered, which are the ones it wants to
+    /// receive plugin messages on.
+    pub fn server_plugin_channels(&self) -> HashSet<ResourceLocation> {
+        self.component::<ServerPluginChannels>().0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_list_round_trip() {
+        let channels = vec![
+            ResourceLocation::new("bungeecord:main"),
+            ResourceLocation::new("minecraft:brand"),
+        ];
+        let data = encode_channel_list(&channels);
+        assert_eq!(data, b"bungeecord:main\0minecraft:brand");
+        assert_eq!(decode_channel_list(&data).collect::<Vec<_>>(), channels);
+    }
+
+    #[test]
+    fn test_decode_channel_list_trailing_nul() {
+        // some servers end the list with a null byte
+        assert_eq!(
+            decode_channel_list(b"bungeecord:main\0minecraft:brand\0").collect::<Vec<_>>(),
+            vec![
+                ResourceLocation::new("bungeecord:main"),
+                ResourceLocation::new("minecraft:brand"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_channel_list() {
+        let data = encode_channel_list(&[]);
+        assert!(data.is_empty());
+        assert_eq!(decode_channel_list(&data).count(), 0);
+        assert_eq!(decode_channel_list(b"\0").count(), 0);
+    }
+}
@@ -0,0 +1,316 @@
+//! Get information about a server with the UDP query protocol (also known as
+//! GameSpy4).
+//!
+//! Servers have to enable this with `enable-query=true` in their
+//! `server.properties`, but when it is enabled it gives more information than
+//! the normal server list ping, like the full player list and plugins.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use azalea_protocol::{
+    resolver::{self, Resolver, ResolverError},
+    ServerAddress,
+};
+use thiserror::Error;
+use tokio::{net::UdpSocket, time::timeout};
+
+/// How long [`query_server`] waits for each response from the server.
+pub const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const MAGIC: [u8; 2] = [0xfe, 0xfd];
+const HANDSHAKE_TYPE: u8 = 9;
+const STAT_TYPE: u8 = 0;
+
+#[derive(Error, Debug)]
+pub enum QueryError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("The given address could not be parsed into a ServerAddress")]
+    InvalidAddress,
+    #[error("{0}")]
+    Resolver(#[from] ResolverError),
+    #[error("The server didn't respond in time, query might not be enabled")]
+    Timeout,
+    #[error("The server sent an invalid response")]
+    InvalidResponse,
+}
+
+/// The full stats that a server sent in response to a query.
+#[derive(Clone, Debug)]
+pub struct QueryResponse {
+    /// The message of the day.
+    pub motd: String,
+    /// This is always "SMP" on vanilla servers.
+    pub game_type: String,
+    /// This is always "MINECRAFT" on vanilla servers.
+    pub game_id: String,
+    /// The name of the server's version, like "1.19.4".
+    pub version: String,
+    /// The name and version of the server software, like
+    /// "CraftBukkit on Bukkit 1.19.4-R0.1-SNAPSHOT". This is `None` on vanilla
+    /// servers.
+    pub server_mod: Option<String>,
+    /// The plugins on the server, including their versions. Some servers hide
+    /// these.
+    pub plugins: Vec<String>,
+    /// The name of the default world.
+    pub map: String,
+    pub online_players: i32,
+    pub max_players: i32,
+    pub host_port: u16,
+    pub host_ip: String,
+    /// The names of every player that's online.
+    pub players: Vec<String>,
+    /// All the key-value pairs that the server sent, including ones that
+    /// aren't in the other fields.
+    pub raw: HashMap<String, String>,
+}
+
+/// Get the full stats of a server with the query protocol. Note that the
+/// query port is usually the same as the server's normal port, but it can be
+/// changed in the server's `server.properties` with `query.port`.
+///
+/// ```rust,no_run
+/// use azalea_client::query;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let response = query::query_server("localhost:25565", query::DEFAULT_QUERY_TIMEOUT)
+///         .await
+///         .unwrap();
+///     println!("{:?}", response.players);
+/// }
+/// ```
+pub async fn query_server(
+    address: impl TryInto<ServerAddress>,
+    response_timeout: Duration,
+) -> Result<QueryResponse, QueryError> {
+    let address: ServerAddress = address.try_into().map_err(|_| QueryError::InvalidAddress)?;
+
+    let ip = match address.host.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        // SRV records aren't used since they point to the game port, which
+        // isn't necessarily the query port
+        Err(_) => *resolver::default_resolver()
+            .lookup_ip(&address.host)
+            .await?
+            .first()
+            .ok_or(ResolverError::NoIp)?,
+    };
+    let target = SocketAddr::new(ip, address.port);
+
+    // the socket has to be the same family as the server's address
+    let local_ip = match ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(local_ip, 0)).await?;
+    socket.connect(target).await?;
+
+    // only the lower 4 bits of each byte are used
+    let session_id = rand_session_id() & 0x0f0f0f0f;
+
+    // get a challenge token, which we have to send back to prove that the
+    // address isn't spoofed
+    let mut request = Vec::with_capacity(15);
+    request.extend(MAGIC);
+    request.push(HANDSHAKE_TYPE);
+    request.extend(session_id.to_be_bytes());
+    let response = send_and_receive(&socket, &request, response_timeout).await?;
+    let payload = strip_header(&response, HANDSHAKE_TYPE, session_id)?;
+    let challenge_token: i32 = read_string(&mut &payload[..])
+        .ok_or(QueryError::InvalidResponse)?
+        .parse()
+        .map_err(|_| QueryError::InvalidResponse)?;
+
+    // ask for the full stats, which is the basic stat request with four bytes
+    // of padding at the end
+    let mut request = Vec::with_capacity(15);
+    request.extend(MAGIC);
+    request.push(STAT_TYPE);
+    request.extend(session_id.to_be_bytes());
+    request.extend(challenge_token.to_be_bytes());
+    request.extend([0; 4]);
+    let response = send_and_receive(&socket, &request, response_timeout).await?;
+    let payload = strip_header(&response, STAT_TYPE, session_id)?;
+
+    parse_full_stat(payload).ok_or(QueryError::InvalidResponse)
+}
+
+async fn send_and_receive(
+    socket: &UdpSocket,
+    request: &[u8],
+    response_timeout: Duration,
+) -> Result<Vec<u8>, QueryError> {
+    socket.send(request).await?;
+    let mut buf = vec![0; 65535];
+    let len = timeout(response_timeout, socket.recv(&mut buf))
+        .await
+        .map_err(|_| QueryError::Timeout)??;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+/// Check the type and session ID at the start of a response and return the
+/// rest of it.
+fn strip_header(response: &[u8], kind: u8, session_id: i32) -> Result<&[u8], QueryError> {
+    if response.len() < 5 || response[0] != kind || response[1..5] != session_id.to_be_bytes() {
+        return Err(QueryError::InvalidResponse);
+    }
+    Ok(&response[5..])
+}
+
+/// Read a null-terminated string. The query protocol uses ISO-8859-1 so every
+/// byte is one character.
+fn read_string(data: &mut &[u8]) -> Option<String> {
+    let end = data.iter().position(|&b| b == 0)?;
+    let string = data[..end].iter().map(|&b| b as char).collect();
+    *data = &data[end + 1..];
+    Some(string)
+}
+
+fn parse_full_stat(payload: &[u8]) -> Option<QueryResponse> {
+    // the key-values section starts with "splitnum\0\x80\0"
+    let mut data = payload.get(11..)?;
+
+    let mut raw = HashMap::new();
+    loop {
+        let key = read_string(&mut data)?;
+        if key.is_empty() {
+            break;
+        }
+        let value = read_string(&mut data)?;
+        raw.insert(key, value);
+    }
+
+    // and the players section starts with "\x01player_\0\0"
+    let mut data = data.get(10..)?;
+    let mut players = Vec::new();
+    loop {
+        let player = read_string(&mut data)?;
+        if player.is_empty() {
+            break;
+        }
+        players.push(player);
+    }
+
+    let get = |key: &str| raw.get(key).cloned().unwrap_or_default();
+
+    // plugins look like "Paper on 1.19.4: WorldEdit 7.2; Essentials 2.19"
+    let plugins_value = get("plugins");
+    let (server_mod, plugins) = match plugins_value.split_once(": ") {
+        Some((server_mod, plugins)) => (
+            Some(server_mod.to_string()),
+            plugins.split("; ").map(str::to_string).collect(),
+        ),
+        None if plugins_value.is_empty() => (None, Vec::new()),
+        None => (Some(plugins_value), Vec::new()),
+    };
+
+    Some(QueryResponse {
+        motd: get("hostname"),
+        game_type: get("gametype"),
+        game_id: get("game_id"),
+        version: get("version"),
+        server_mod,
+        plugins,
+        map: get("map"),
+        online_players: get("numplayers").parse().ok()?,
+        max_players: get("maxplayers").parse().ok()?,
+        host_port: get("hostport").parse().ok()?,
+        host_ip: get("hostip"),
+        players,
+        raw,
+    })
+}
+
+fn rand_session_id() -> i32 {
+    // this doesn't have to be secure, it's just so responses to old requests
+    // get ignored
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as i32)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_stat() {
+        // a vanilla 1.19.4 server with two players online
+        let payload = b"splitnum\0\x80\0\
+            hostname\0A Minecraft Server\0\
+            gametype\0SMP\0\
+            game_id\0MINECRAFT\0\
+            version\x001.19.4\0\
+            plugins\0\0\
+            map\0world\0\
+            numplayers\x002\0\
+            maxplayers\x0020\0\
+            hostport\x0025565\0\
+            hostip\x00127.0.0.1\0\
+            \0\x01player_\0\0\
+            bot0\0bot1\0\0";
+        let response = parse_full_stat(payload).unwrap();
+        assert_eq!(response.motd, "A Minecraft Server");
+        assert_eq!(response.game_type, "SMP");
+        assert_eq!(response.game_id, "MINECRAFT");
+        assert_eq!(response.version, "1.19.4");
+        assert_eq!(response.server_mod, None);
+        assert!(response.plugins.is_empty());
+        assert_eq!(response.map, "world");
+        assert_eq!(response.online_players, 2);
+        assert_eq!(response.max_players, 20);
+        assert_eq!(response.host_port, 25565);
+        assert_eq!(response.host_ip, "127.0.0.1");
+        assert_eq!(response.players, vec!["bot0", "bot1"]);
+    }
+
+    #[test]
+    fn test_parse_full_stat_plugins() {
+        let payload = b"splitnum\0\x80\0\
+            hostname\0A Paper Server\0\
+            gametype\0SMP\0\
+            game_id\0MINECRAFT\0\
+            version\x001.19.4\0\
+            plugins\0Paper on 1.19.4-R0.1-SNAPSHOT: WorldEdit 7.2.14; Essentials 2.19.7\0\
+            map\0world\0\
+            numplayers\x000\0\
+            maxplayers\x0020\0\
+            hostport\x0025565\0\
+            hostip\x000.0.0.0\0\
+            \0\x01player_\0\0\
+            \0";
+        let response = parse_full_stat(payload).unwrap();
+        assert_eq!(
+            response.server_mod.as_deref(),
+            Some("Paper on 1.19.4-R0.1-SNAPSHOT")
+        );
+        assert_eq!(
+            response.plugins,
+            vec!["WorldEdit 7.2.14", "Essentials 2.19.7"]
+        );
+        assert!(response.players.is_empty());
+    }
+
+    #[test]
+    fn test_parse_full_stat_truncated() {
+        assert!(parse_full_stat(b"splitnum\0\x80\0hostname\0A Minecraft").is_none());
+    }
+
+    #[test]
+    fn test_strip_header() {
+        let session_id = 0x01020304;
+        let response = [0, 1, 2, 3, 4, 5, 6];
+        assert_eq!(strip_header(&response, 0, session_id).unwrap(), &[5, 6]);
+        assert!(strip_header(&response, 9, session_id).is_err());
+        assert!(strip_header(&response, 0, 0x0f0f0f0f).is_err());
+    }
+}
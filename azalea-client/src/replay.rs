@@ -0,0 +1,438 @@
+//! Record the packets a client sends and receives, and replay them into an
+//! ECS later without a server.
+//!
+//! Record a session by setting [`JoinOpts::capture`], then replay it with
+//! [`Replay::start`]. This is useful for reproducing bugs that only happened
+//! on a live server and for writing tests from real server traffic.
+//!
+//! ```no_run
+//! # use azalea_client::replay::{Replay, ReplaySpeed};
+//! # use azalea_protocol::capture::CaptureReader;
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let capture = CaptureReader::new(std::fs::File::open("session.azcap")?)?;
+//! let mut replay = Replay::start(capture, ReplaySpeed::Instant);
+//! replay.finished().await?;
+//! println!("the bot ended up at {:?}", replay.client.position());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`JoinOpts::capture`]: crate::JoinOpts::capture
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use azalea_auth::game_profile::GameProfile;
+use azalea_protocol::{
+    capture::{CaptureError, CaptureReader, CaptureWriter, PacketDirection},
+    packets::{
+        game::{ClientboundGamePacket, ServerboundGamePacket},
+        version::ProtocolVersion,
+        ConnectionProtocol, ProtocolPacket,
+    },
+    stats::ConnectionStats,
+};
+use azalea_world::Instance;
+use log::warn;
+use parking_lot::{Mutex, RwLock};
+use tokio::{sync::mpsc, task::JoinHandle, time::Instant};
+
+use crate::{
+    client::{init_ecs_app, start_ecs, JoinedClientBundle},
+    events::LocalPlayerEvents,
+    packet_handling::{PacketErrorPolicy, PacketReceiver},
+    Client, Event, LocalPlayer,
+};
+
+/// Records the packets of a client to a capture file. This is cheap to
+/// clone.
+#[derive(Clone)]
+pub struct PacketCapture(Arc<Mutex<CaptureWriter<Box<dyn Write + Send>>>>);
+
+impl PacketCapture {
+    /// Create a capture file at the path, replacing it if it already exists.
+    /// `version` is the version of the connection that's recorded.
+    pub fn create(
+        path: &Path,
+        game_profile: GameProfile,
+        version: ProtocolVersion,
+    ) -> Result<Self, CaptureError> {
+        let file: Box<dyn Write + Send> = Box::new(BufWriter::new(File::create(path)?));
+        Ok(Self(Arc::new(Mutex::new(CaptureWriter::new_for_version(
+            file,
+            game_profile,
+            version,
+        )?))))
+    }
+
+    /// Record the id and data of a packet the way it was sent or received,
+    /// before it's decoded so packets that can't be decoded are recorded too.
+    /// `P` is the packet type of the connection, like
+    /// `ClientboundGamePacket`. Errors are logged instead of returned so a
+    /// broken capture doesn't disconnect the client.
+    pub fn record<P: ProtocolPacket>(&self, data: &[u8]) {
+        if let Err(e) = self
+            .0
+            .lock()
+            .write_raw(P::DIRECTION, P::PROTOCOL, data.to_vec())
+        {
+            warn!("Couldn't record packet: {e}");
+        }
+    }
+}
+
+/// How fast a [`Replay`] feeds packets to the client.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplaySpeed {
+    /// Wait between packets as long as the client did when they were
+    /// recorded.
+    RealTime,
+    /// Go this many times faster than real time.
+    Accelerated(SpeedMultiplier),
+    /// Don't wait between packets at all.
+    Instant,
+}
+
+/// How many times faster than real time an accelerated [`Replay`] goes. This
+/// is always positive and finite.
+///
+/// ```
+/// # use azalea_client::replay::{ReplaySpeed, SpeedMultiplier};
+/// let speed = ReplaySpeed::Accelerated(SpeedMultiplier::new(4.).unwrap());
+/// assert!(SpeedMultiplier::new(0.).is_none());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeedMultiplier(f64);
+
+impl SpeedMultiplier {
+    /// Returns `None` if the multiplier is zero, negative, infinite or NaN.
+    pub fn new(multiplier: f64) -> Option<Self> {
+        (multiplier.is_finite() && multiplier > 0.).then_some(Self(multiplier))
+    }
+
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+/// A client in its own ECS that receives the packets from a capture instead
+/// of from a server.
+pub struct Replay {
+    pub client: Client,
+    /// The events that the client sent, like [`Event::Chat`].
+    pub events: mpsc::UnboundedReceiver<Event>,
+    /// The packets that the client tried to send to the server.
+    pub sent_packets: mpsc::UnboundedReceiver<ServerboundGamePacket>,
+    packet_receiver: PacketReceiver,
+    feed_task: JoinHandle<Result<(), CaptureError>>,
+}
+
+impl Replay {
+    /// Make a new ECS with a client for the player in the capture's header,
+    /// and start feeding it the clientbound game packets from the capture.
+    pub fn start(capture: CaptureReader, speed: ReplaySpeed) -> Self {
+        let (run_schedule_sender, run_schedule_receiver) = mpsc::unbounded_channel();
+        let ecs_lock = start_ecs(
+            init_ecs_app(),
+            run_schedule_receiver,
+            run_schedule_sender.clone(),
+        );
+
+        let game_profile = capture.header.game_profile.clone();
+        let (events_sender, events) = mpsc::unbounded_channel();
+        let (packet_writer_sender, mut packet_writer_receiver) = mpsc::unbounded_channel();
+        let (sent_packets_sender, sent_packets) = mpsc::unbounded_channel();
+        let packet_receiver =
+            PacketReceiver::new(run_schedule_sender.clone(), None, PacketErrorPolicy::SKIP);
+        let connection_stats = ConnectionStats::default();
+
+        // instead of writing to a server, the packets the client sends are
+        // passed on to `sent_packets`
+        let write_packets_task = tokio::spawn({
+            let connection_stats = connection_stats.clone();
+            async move {
+                while let Some(packet) = packet_writer_receiver.recv().await {
+                    connection_stats.write_dequeued();
+                    if sent_packets_sender.send(packet).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let client = {
+            let mut ecs = ecs_lock.lock();
+            let entity = ecs.spawn_empty().id();
+            let local_player = LocalPlayer::new(
+                entity,
+                packet_writer_sender,
+                connection_stats,
+                Arc::new(RwLock::new(Instance::default())),
+                // the packets are fed by `feed_packets` instead, but the
+                // client is disconnected when this task ends, so it has to
+                // keep running for as long as the replay is
+                tokio::spawn(std::future::pending()),
+                write_packets_task,
+            );
+            ecs.entity_mut(entity).insert(JoinedClientBundle::new(
+                local_player,
+                packet_receiver.clone(),
+                game_profile.clone(),
+                LocalPlayerEvents(events_sender),
+            ));
+            Client::new(game_profile, entity, ecs_lock.clone(), run_schedule_sender)
+        };
+
+        let feed_task = tokio::spawn(feed_packets(capture, packet_receiver.clone(), speed));
+
+        Self {
+            client,
+            events,
+            sent_packets,
+            packet_receiver,
+            feed_task,
+        }
+    }
+
+    /// Wait until every packet in the capture was handled by the ECS.
+    pub async fn finished(&mut self) -> Result<(), CaptureError> {
+        (&mut self.feed_task)
+            .await
+            .expect("The replay task shouldn't panic")?;
+        // the packets are taken out of the queue while the ECS is locked, so
+        // once the queue is empty we only have to wait for that update to end
+        while !self.packet_receiver.packets.lock().is_empty() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        drop(self.client.ecs.lock());
+        Ok(())
+    }
+}
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        self.feed_task.abort();
+    }
+}
+
+async fn feed_packets(
+    capture: CaptureReader,
+    packet_receiver: PacketReceiver,
+    speed: ReplaySpeed,
+) -> Result<(), CaptureError> {
+    let version = capture
+        .header
+        .version()
+        .ok_or(CaptureError::UnsupportedProtocolVersion(
+            capture.header.protocol_version,
+        ))?;
+    let start = Instant::now();
+    let mut bundle = None;
+    for captured in capture {
+        let captured = captured?;
+        if captured.direction != PacketDirection::Clientbound
+            || captured.protocol != ConnectionProtocol::Game
+        {
+            continue;
+        }
+
+        let time = Duration::from_millis(captured.time);
+        match speed {
+            ReplaySpeed::RealTime => tokio::time::sleep_until(start + time).await,
+            ReplaySpeed::Accelerated(multiplier) => {
+                // very small multipliers can make the time too big for a
+                // Duration, in which case we wait (practically) forever
+                let time = Duration::try_from_secs_f64(time.as_secs_f64() / multiplier.get())
+                    .unwrap_or(Duration::MAX);
+                tokio::time::sleep(time.saturating_sub(start.elapsed())).await;
+            }
+            ReplaySpeed::Instant => {}
+        }
+
+        match captured.decode_for_version::<ClientboundGamePacket>(version) {
+            Ok(packet) => {
+                if packet_receiver.receive_packet(packet, &mut bundle).is_err() {
+                    break;
+                }
+            }
+            Err(error) => {
+                if packet_receiver
+                    .receive_packet_error(error, captured.data)
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::{join_test_server_with_opts, login_packet},
+        JoinOpts,
+    };
+    use azalea_core::Vec3;
+    use azalea_protocol::packets::{
+        game::{
+            clientbound_keep_alive_packet::ClientboundKeepAlivePacket,
+            clientbound_player_position_packet::{
+                ClientboundPlayerPositionPacket, RelativeMovements,
+            },
+            serverbound_keep_alive_packet::ServerboundKeepAlivePacket,
+        },
+        PROTOCOL_VERSION,
+    };
+    use std::io::Cursor;
+    use uuid::Uuid;
+
+    fn capture(packets: &[(PacketDirection, ClientboundGamePacket)]) -> CaptureReader {
+        let mut data = Vec::new();
+        let mut writer = CaptureWriter::new(
+            &mut data,
+            GameProfile::new(Uuid::from_u128(1), "bot".to_string()),
+        )
+        .unwrap();
+        for (direction, packet) in packets {
+            writer
+                .write_packet(*direction, ConnectionProtocol::Game, packet)
+                .unwrap();
+        }
+        // serverbound packets are only in the capture for reference
+        writer
+            .write_packet(
+                PacketDirection::Serverbound,
+                ConnectionProtocol::Game,
+                &ServerboundKeepAlivePacket { id: 1 }.get(),
+            )
+            .unwrap();
+        CaptureReader::new(Cursor::new(data)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let capture = capture(&[
+            (PacketDirection::Clientbound, login_packet().get()),
+            (
+                PacketDirection::Clientbound,
+                ClientboundPlayerPositionPacket {
+                    x: 1.,
+                    y: 2.,
+                    z: 3.,
+                    y_rot: 0.,
+                    x_rot: 0.,
+                    relative_arguments: RelativeMovements {
+                        x: false,
+                        y: false,
+                        z: false,
+                        y_rot: false,
+                        x_rot: false,
+                    },
+                    id: 1,
+                }
+                .get(),
+            ),
+        ]);
+        assert_eq!(capture.header.game_profile.name, "bot");
+
+        let mut replay = Replay::start(capture, ReplaySpeed::Instant);
+        replay.finished().await.unwrap();
+        assert_eq!(replay.client.position(), Vec3::new(1., 2., 3.));
+        // we're still connected after the capture ended
+        assert_eq!(replay.client.dropped_packets(), 0);
+
+        // the client confirms the teleport, which is passed on to
+        // `sent_packets` instead of staying in the write queue
+        loop {
+            if let ServerboundGamePacket::AcceptTeleportation(p) =
+                replay.sent_packets.recv().await.unwrap()
+            {
+                assert_eq!(p.id, 1);
+                break;
+            }
+        }
+        let connection_stats = replay.client.connection_stats();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while connection_stats.snapshot().write_queue_depth > 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .expect("The sent packets should be taken out of the write queue");
+    }
+
+    #[test]
+    fn test_speed_multiplier() {
+        assert_eq!(SpeedMultiplier::new(2.).map(SpeedMultiplier::get), Some(2.));
+        assert!(SpeedMultiplier::new(0.).is_none());
+        assert!(SpeedMultiplier::new(-1.).is_none());
+        assert!(SpeedMultiplier::new(f64::NAN).is_none());
+        assert!(SpeedMultiplier::new(f64::INFINITY).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_capture_records_raw_packets() {
+        let path = std::env::temp_dir().join(format!(
+            "azalea-capture-test-{}.azcap",
+            rand::random::<u64>()
+        ));
+        let (_client, _rx, mut player) =
+            join_test_server_with_opts(&JoinOpts::new().capture(&path)).await;
+
+        // a packet that can't be decoded, which is still recorded
+        let unknown_packet = vec![0x7f, 0x01, 0x02];
+        player
+            .connection
+            .writer
+            .write_raw(unknown_packet.clone())
+            .await
+            .unwrap();
+        player
+            .connection
+            .write(ClientboundKeepAlivePacket { id: 5 }.get())
+            .await
+            .unwrap();
+        loop {
+            if let ServerboundGamePacket::KeepAlive(p) = player.connection.read().await.unwrap() {
+                assert_eq!(p.id, 5);
+                break;
+            }
+        }
+
+        let capture = CaptureReader::new(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(capture.header.protocol_version, PROTOCOL_VERSION);
+        assert_eq!(capture.header.game_profile.name, "bot");
+        let packets = capture.collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(packets
+            .iter()
+            .all(|packet| packet.protocol == ConnectionProtocol::Game));
+
+        let clientbound = packets
+            .iter()
+            .filter(|packet| packet.direction == PacketDirection::Clientbound)
+            .collect::<Vec<_>>();
+        assert_eq!(clientbound[0].data, unknown_packet);
+        assert!(matches!(
+            clientbound[1].decode::<ClientboundGamePacket>().unwrap(),
+            ClientboundGamePacket::KeepAlive(ClientboundKeepAlivePacket { id: 5 })
+        ));
+        assert!(packets.iter().any(|packet| {
+            packet.direction == PacketDirection::Serverbound
+                && matches!(
+                    packet.decode::<ServerboundGamePacket>(),
+                    Ok(ServerboundGamePacket::KeepAlive(
+                        ServerboundKeepAlivePacket { id: 5 }
+                    ))
+                )
+        }));
+    }
+}
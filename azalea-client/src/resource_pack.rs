@@ -0,0 +1,188 @@
+//! Respond to resource pack prompts from the server.
+
+use azalea_chat::FormattedText;
+pub use azalea_protocol::packets::game::serverbound_resource_pack_packet::Action as ResourcePackAction;
+use azalea_protocol::packets::game::serverbound_resource_pack_packet::ServerboundResourcePackPacket;
+use bevy_app::{App, Plugin};
+use bevy_ecs::{
+    entity::Entity,
+    event::{EventReader, EventWriter},
+    schedule::IntoSystemConfig,
+    system::{Res, Resource},
+};
+use log::debug;
+
+use crate::{
+    local_player::{handle_send_packet_event, SendPacketEvent},
+    Client,
+};
+
+pub struct ResourcePackPlugin;
+impl Plugin for ResourcePackPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ResourcePackPolicy>()
+            .add_event::<ResourcePackEvent>()
+            .add_system(handle_resource_pack_event.before(handle_send_packet_event));
+    }
+}
+
+/// A resource that decides how clients automatically respond when the server
+/// asks them to use a resource pack.
+///
+/// Azalea never actually downloads resource packs, so if the server requires
+/// one you'll probably want [`Self::AcceptAndFakeLoad`] (the default).
+///
+/// ```
+/// # use azalea_client::resource_pack::ResourcePackPolicy;
+/// # fn example(app: &mut bevy_app::App) {
+/// app.insert_resource(ResourcePackPolicy::Decline);
+/// # }
+/// ```
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResourcePackPolicy {
+    /// Tell the server we accepted the resource pack, but never say whether
+    /// it finished loading.
+    Accept,
+    /// Tell the server we declined the resource pack. Servers that require a
+    /// resource pack will kick us for this.
+    Decline,
+    /// Tell the server we accepted the resource pack and then immediately
+    /// pretend that it loaded successfully.
+    #[default]
+    AcceptAndFakeLoad,
+    /// Don't respond automatically. You should respond yourself from your
+    /// handler with [`Client::send_resource_pack_status`] when you get an
+    /// [`Event::ResourcePack`].
+    ///
+    /// [`Event::ResourcePack`]: crate::Event::ResourcePack
+    Manual,
+}
+
+impl ResourcePackPolicy {
+    /// The statuses that are sent to the server (in order) when we get a
+    /// resource pack prompt with this policy.
+    pub fn actions(&self) -> &'static [ResourcePackAction] {
+        match self {
+            ResourcePackPolicy::Accept => &[ResourcePackAction::Accepted],
+            ResourcePackPolicy::Decline => &[ResourcePackAction::Declined],
+            ResourcePackPolicy::AcceptAndFakeLoad => &[
+                ResourcePackAction::Accepted,
+                ResourcePackAction::SuccessfullyLoaded,
+            ],
+            ResourcePackPolicy::Manual => &[],
+        }
+    }
+}
+
+/// The server asked a local player to use a resource pack.
+#[derive(Debug, Clone)]
+pub struct ResourcePackEvent {
+    /// The local player entity that received the prompt.
+    pub entity: Entity,
+    pub url: String,
+    /// The SHA-1 hash of the resource pack, as a hex string. This may be
+    /// empty.
+    pub hash: String,
+    /// Whether the server will kick us if we don't accept the resource pack.
+    pub required: bool,
+    /// The custom message that's shown on the prompt.
+    pub prompt: Option<FormattedText>,
+}
+
+fn handle_resource_pack_event(
+    mut events: EventReader<ResourcePackEvent>,
+    mut send_packet_events: EventWriter<SendPacketEvent>,
+    policy: Res<ResourcePackPolicy>,
+) {
+    for event in events.iter() {
+        debug!(
+            "Responding to resource pack {} with {:?}",
+            event.url, *policy
+        );
+        for &action in policy.actions() {
+            send_packet_events.send(SendPacketEvent {
+                entity: event.entity,
+                packet: ServerboundResourcePackPacket { action }.get(),
+            });
+        }
+    }
+}
+
+impl Client {
+    /// Tell the server the status of the resource pack it asked us to use.
+    ///
+    /// You usually don't need this since [`ResourcePackPolicy`] will respond
+    /// for you, unless it's set to [`ResourcePackPolicy::Manual`].
+    pub fn send_resource_pack_status(&self, action: ResourcePackAction) {
+        self.write_packet(ServerboundResourcePackPacket { action }.get());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azalea_protocol::packets::game::ServerboundGamePacket;
+    use bevy_ecs::event::Events;
+
+    /// Send a resource pack prompt with the policy and return the statuses
+    /// that we responded with.
+    fn respond_with(policy: ResourcePackPolicy) -> Vec<ResourcePackAction> {
+        let mut app = App::new();
+        app.add_plugin(ResourcePackPlugin)
+            .add_event::<SendPacketEvent>()
+            .insert_resource(policy);
+        let entity = app.world.spawn_empty().id();
+        app.world.send_event(ResourcePackEvent {
+            entity,
+            url: "https://example.com/pack.zip".to_string(),
+            hash: String::new(),
+            required: true,
+            prompt: None,
+        });
+        app.update();
+
+        let events = app.world.resource::<Events<SendPacketEvent>>();
+        events
+            .iter_current_update_events()
+            .map(|event| {
+                assert_eq!(event.entity, entity);
+                match &event.packet {
+                    ServerboundGamePacket::ResourcePack(p) => p.action,
+                    packet => panic!("Sent an unexpected packet: {packet:?}"),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_accept() {
+        assert!(matches!(
+            respond_with(ResourcePackPolicy::Accept)[..],
+            [ResourcePackAction::Accepted]
+        ));
+    }
+
+    #[test]
+    fn test_decline() {
+        assert!(matches!(
+            respond_with(ResourcePackPolicy::Decline)[..],
+            [ResourcePackAction::Declined]
+        ));
+    }
+
+    #[test]
+    fn test_accept_and_fake_load() {
+        assert!(matches!(
+            respond_with(ResourcePackPolicy::AcceptAndFakeLoad)[..],
+            [
+                ResourcePackAction::Accepted,
+                ResourcePackAction::SuccessfullyLoaded
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_manual() {
+        assert!(respond_with(ResourcePackPolicy::Manual).is_empty());
+    }
+}
@@ -0,0 +1,290 @@
+//! Request and keep track of the player's statistics.
+
+use std::collections::{HashMap, HashSet};
+
+pub use azalea_protocol::packets::game::clientbound_award_stats_packet::Stat;
+use azalea_protocol::packets::game::serverbound_client_command_packet::{
+    self, ServerboundClientCommandPacket,
+};
+use azalea_registry::{Block, CustomStat, EntityKind, Item};
+use bevy_ecs::component::Component;
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{Client, TickBroadcast};
+
+/// How many ticks [`Client::request_stats`] waits for the server to send our
+/// statistics before giving up.
+const STATS_TIMEOUT_TICKS: u32 = 100;
+
+/// A component that contains the statistics the server has sent us, keyed by
+/// the type of statistic.
+///
+/// The server only sends statistics when we ask for them (with
+/// [`Client::request_stats`]), and it only sends the ones that changed since
+/// the last time we asked, so this keeps all of them.
+#[derive(Component, Clone, Debug, Default)]
+pub struct Statistics {
+    /// The number of each block we've mined.
+    pub mined: HashMap<Block, i32>,
+    /// The number of each item we've crafted.
+    pub crafted: HashMap<Item, i32>,
+    /// The number of times we've used each item.
+    pub used: HashMap<Item, i32>,
+    /// The number of each item we've used up the durability of.
+    pub broken: HashMap<Item, i32>,
+    pub picked_up: HashMap<Item, i32>,
+    pub dropped: HashMap<Item, i32>,
+    /// The number of each entity we've killed.
+    pub killed: HashMap<EntityKind, i32>,
+    /// The number of times we've been killed by each entity.
+    pub killed_by: HashMap<EntityKind, i32>,
+    /// Everything else, like the distance we've walked or the number of times
+    /// we've jumped.
+    pub custom: HashMap<CustomStat, i32>,
+}
+
+impl Statistics {
+    /// Get the value of a statistic, or 0 if the server never sent it.
+    pub fn get(&self, stat: &Stat) -> i32 {
+        let value = match stat {
+            Stat::Mined(block) => self.mined.get(block),
+            Stat::Crafted(item) => self.crafted.get(item),
+            Stat::Used(item) => self.used.get(item),
+            Stat::Broken(item) => self.broken.get(item),
+            Stat::PickedUp(item) => self.picked_up.get(item),
+            Stat::Dropped(item) => self.dropped.get(item),
+            Stat::Killed(entity) => self.killed.get(entity),
+            Stat::KilledBy(entity) => self.killed_by.get(entity),
+            Stat::Custom(custom) => self.custom.get(custom),
+        };
+        value.copied().unwrap_or_default()
+    }
+
+    /// Set the value of a statistic.
+    pub fn set(&mut self, stat: Stat, value: i32) {
+        match stat {
+            Stat::Mined(block) => self.mined.insert(block, value),
+            Stat::Crafted(item) => self.crafted.insert(item, value),
+            Stat::Used(item) => self.used.insert(item, value),
+            Stat::Broken(item) => self.broken.insert(item, value),
+            Stat::PickedUp(item) => self.picked_up.insert(item, value),
+            Stat::Dropped(item) => self.dropped.insert(item, value),
+            Stat::Killed(entity) => self.killed.insert(entity, value),
+            Stat::KilledBy(entity) => self.killed_by.insert(entity, value),
+            Stat::Custom(custom) => self.custom.insert(custom, value),
+        };
+    }
+}
+
+/// A component that keeps track of the statistics requests that a local player
+/// is waiting for.
+///
+/// The server doesn't say which request it's responding to, but it responds in
+/// order, so every request gets a ticket and it's answered by the response
+/// with the same number.
+#[derive(Component, Clone, Debug, Default)]
+pub struct StatsRequests {
+    /// The number of requests that we've sent.
+    sent: u64,
+    /// The number of responses that we've received.
+    received: u64,
+    /// The requests that timed out before they were answered. Their responses
+    /// might still come, and they answer these instead of the requests after
+    /// them.
+    given_up: HashSet<u64>,
+}
+
+impl StatsRequests {
+    /// Take a ticket for a new request.
+    pub fn start(&mut self) -> u64 {
+        let ticket = self.sent;
+        self.sent += 1;
+        ticket
+    }
+
+    /// Record that the server sent our statistics. Statistics that we didn't
+    /// ask for don't answer any request.
+    ///
+    /// Returns whether a request is still waiting for these statistics, which
+    /// isn't the case if we didn't ask for them or the request timed out.
+    pub fn receive(&mut self) -> bool {
+        if self.received >= self.sent {
+            return false;
+        }
+        let ticket = self.received;
+        self.received += 1;
+        !self.given_up.remove(&ticket)
+    }
+
+    /// Whether the server responded to the request with this ticket.
+    pub fn is_answered(&self, ticket: u64) -> bool {
+        self.received > ticket
+    }
+
+    /// Stop waiting for the response to the request with this ticket. If the
+    /// response comes later, it still answers this request, so the requests
+    /// after it get their own responses.
+    pub fn give_up(&mut self, ticket: u64) {
+        if !self.is_answered(ticket) {
+            self.given_up.insert(ticket);
+        }
+    }
+}
+
+/// An error from [`Client::request_stats`].
+#[derive(Error, Debug)]
+pub enum StatsError {
+    #[error("The server didn't send our statistics")]
+    Timeout,
+}
+
+impl Client {
+    /// Ask the server for our statistics and wait for it to send them.
+    ///
+    /// ```
+    /// # use azalea_client::statistics::Stat;
+    /// # async fn example(bot: azalea_client::Client) -> Result<(), azalea_client::statistics::StatsError> {
+    /// let stats = bot.request_stats().await?;
+    /// let mined = stats.get(&Stat::Mined(azalea_registry::Block::Stone));
+    /// println!("we've mined {mined} stone");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn request_stats(&self) -> Result<Statistics, StatsError> {
+        let (ticket, mut receiver) = {
+            let mut ecs = self.ecs.lock();
+            let receiver = ecs.resource::<TickBroadcast>().subscribe();
+            let ticket = self.query::<&mut StatsRequests>(&mut ecs).start();
+            (ticket, receiver)
+        };
+
+        self.write_packet(
+            ServerboundClientCommandPacket {
+                action: serverbound_client_command_packet::Action::RequestStats,
+            }
+            .get(),
+        );
+
+        let mut ticks = 0;
+        loop {
+            if let Err(RecvError::Closed) = receiver.recv().await {
+                break;
+            }
+            let mut ecs = self.ecs.lock();
+            if self.query::<&StatsRequests>(&mut ecs).is_answered(ticket) {
+                return Ok(self.query::<&Statistics>(&mut ecs).clone());
+            }
+            ticks += 1;
+            if ticks >= STATS_TIMEOUT_TICKS {
+                self.query::<&mut StatsRequests>(&mut ecs).give_up(ticket);
+                break;
+            }
+        }
+        Err(StatsError::Timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::join_test_server;
+    use azalea_protocol::packets::game::{
+        clientbound_award_stats_packet::ClientboundAwardStatsPacket, ClientboundGamePacket,
+        ServerboundGamePacket,
+    };
+    use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+    #[test]
+    fn test_requests_are_answered_in_order() {
+        let mut requests = StatsRequests::default();
+        let first = requests.start();
+        let second = requests.start();
+        assert!(!requests.is_answered(first));
+
+        assert!(requests.receive());
+        assert!(requests.is_answered(first));
+        assert!(!requests.is_answered(second));
+
+        assert!(requests.receive());
+        assert!(requests.is_answered(second));
+    }
+
+    #[test]
+    fn test_unrequested_stats() {
+        let mut requests = StatsRequests::default();
+        assert!(!requests.receive());
+        let ticket = requests.start();
+        assert!(!requests.is_answered(ticket));
+    }
+
+    /// Wait for the client to ask for its statistics.
+    async fn read_stats_request(
+        connection: &mut azalea_protocol::connect::Connection<
+            ServerboundGamePacket,
+            ClientboundGamePacket,
+            OwnedReadHalf,
+            OwnedWriteHalf,
+        >,
+    ) {
+        loop {
+            if let ServerboundGamePacket::ClientCommand(p) = connection.read().await.unwrap() {
+                if let serverbound_client_command_packet::Action::RequestStats = p.action {
+                    return;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_stats() {
+        let (client, _rx, mut player) = join_test_server().await;
+        let server = tokio::spawn(async move {
+            for stone in 1..=2 {
+                read_stats_request(&mut player.connection).await;
+                player
+                    .connection
+                    .write(
+                        ClientboundAwardStatsPacket {
+                            stats: HashMap::from([(Stat::Mined(Block::Stone), stone)]),
+                        }
+                        .get(),
+                    )
+                    .await
+                    .unwrap();
+            }
+            player
+        });
+
+        let (first, second) = tokio::join!(client.request_stats(), client.request_stats());
+        assert!(first.unwrap().get(&Stat::Mined(Block::Stone)) >= 1);
+        assert_eq!(second.unwrap().get(&Stat::Mined(Block::Stone)), 2);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_request_stats_timeout() {
+        let (client, _rx, mut player) = join_test_server().await;
+        // the server never answers
+        let (result, _) = tokio::join!(
+            client.request_stats(),
+            read_stats_request(&mut player.connection)
+        );
+        assert!(matches!(result, Err(StatsError::Timeout)));
+    }
+
+    #[test]
+    fn test_give_up() {
+        let mut requests = StatsRequests::default();
+        let first = requests.start();
+        requests.give_up(first);
+        let second = requests.start();
+        // the late response is for the request that timed out, not the next one
+        assert!(!requests.receive());
+        assert!(requests.is_answered(first));
+        assert!(!requests.is_answered(second));
+
+        assert!(requests.receive());
+        assert!(requests.is_answered(second));
+    }
+}
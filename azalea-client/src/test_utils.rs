@@ -0,0 +1,82 @@
+//! Helpers for testing clients against a fake server.
+
+use azalea_core::{GameMode, OptionalGameType, ResourceLocation};
+use azalea_nbt::{Nbt, NbtCompound};
+use azalea_protocol::{
+    packets::game::clientbound_login_packet::{
+        registry::{DimensionTypeElement, RegistryHolder, RegistryRoot, RegistryType, TypeValue},
+        ClientboundLoginPacket,
+    },
+    server::{JoinedPlayer, Listener, ServerConfig},
+};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+
+use crate::{Account, Client, Event, JoinOpts};
+
+/// Start a server on a random port and join it with an offline-mode client.
+/// Returns the client, its events, and the server's side of the connection.
+pub async fn join_test_server() -> (Client, mpsc::UnboundedReceiver<Event>, JoinedPlayer) {
+    join_test_server_with_opts(&JoinOpts::default()).await
+}
+
+/// Like [`join_test_server`], but the client joins with the given
+/// [`JoinOpts`].
+pub async fn join_test_server_with_opts(
+    opts: &JoinOpts,
+) -> (Client, mpsc::UnboundedReceiver<Event>, JoinedPlayer) {
+    let mut listener = Listener::bind("127.0.0.1:0", ServerConfig::default())
+        .await
+        .unwrap();
+    let (client, rx) =
+        Client::join_with_opts(&Account::offline("bot"), listener.local_addr(), opts)
+            .await
+            .unwrap();
+    let player = listener.next_player().await.unwrap();
+    (client, rx, player)
+}
+
+/// A login packet for the overworld with only the parts of the registry that
+/// the client needs.
+pub fn login_packet() -> ClientboundLoginPacket {
+    let empty = || Nbt::Compound(NbtCompound::default());
+    ClientboundLoginPacket {
+        player_id: 1,
+        hardcore: false,
+        game_type: GameMode::Survival,
+        previous_game_type: OptionalGameType(None),
+        levels: vec![ResourceLocation::new("minecraft:overworld")],
+        registry_holder: RegistryHolder {
+            root: RegistryRoot {
+                trim_material: None,
+                chat_type: empty(),
+                dimension_type: RegistryType {
+                    kind: ResourceLocation::new("minecraft:dimension_type"),
+                    value: vec![TypeValue {
+                        id: 0,
+                        name: ResourceLocation::new("minecraft:overworld"),
+                        element: DimensionTypeElement {
+                            height: 384,
+                            min_y: -64,
+                            _extra: HashMap::new(),
+                        },
+                    }],
+                },
+                world_type: empty(),
+                trim_pattern: None,
+                damage_type: None,
+            },
+        },
+        dimension_type: ResourceLocation::new("minecraft:overworld"),
+        dimension: ResourceLocation::new("minecraft:overworld"),
+        seed: 0,
+        max_players: 20,
+        chunk_radius: 8,
+        simulation_distance: 8,
+        reduced_debug_info: false,
+        show_death_screen: true,
+        is_debug: false,
+        is_flat: false,
+        last_death_location: None,
+    }
+}
@@ -7,9 +7,15 @@ use aes::{
     cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit},
     Aes128,
 };
+use hmac::{Hmac, Mac};
+use md5::Md5;
 use rand::{rngs::OsRng, RngCore};
+pub use rsa::{pkcs8::DecodePublicKey, RsaPrivateKey, RsaPublicKey};
+use rsa::{pkcs8::EncodePublicKey, Pkcs1v15Encrypt};
 use sha1::{Digest, Sha1};
+use sha2::Sha256;
 pub use signing::*;
+use uuid::Uuid;
 
 fn generate_secret_key() -> [u8; 16] {
     let mut key = [0u8; 16];
@@ -25,6 +31,21 @@ pub fn digest_data(server_id: &[u8], public_key: &[u8], private_key: &[u8]) -> V
     digest.finalize().to_vec()
 }
 
+/// HMAC-SHA256 of the data, which is how Velocity signs the player info it
+/// forwards to backend servers.
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take a key of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// The UUID that vanilla servers give players with this username when they're
+/// in offline mode.
+pub fn offline_uuid(username: &str) -> Uuid {
+    uuid::Builder::from_md5_bytes(Md5::digest(format!("OfflinePlayer:{username}")).into())
+        .into_uuid()
+}
+
 pub fn hex_digest(digest: &[u8]) -> String {
     // Note that the Sha1.hexdigest() method used by minecraft is non standard.
     // It doesn't match the digest method found in most programming languages
@@ -62,6 +83,46 @@ pub fn encrypt(public_key: &[u8], nonce: &[u8]) -> Result<EncryptResult, String>
     })
 }
 
+/// The RSA key pair that a server uses so clients can send it the shared
+/// secret for encrypting the connection.
+pub struct ServerKeyPair {
+    private_key: RsaPrivateKey,
+    public_key_der: Vec<u8>,
+}
+
+impl ServerKeyPair {
+    /// Generate a new 1024-bit key pair, like vanilla servers do when they
+    /// start.
+    pub fn generate() -> Result<Self, rsa::Error> {
+        let private_key = RsaPrivateKey::new(&mut OsRng, 1024)?;
+        let public_key_der = RsaPublicKey::from(&private_key)
+            .to_public_key_der()
+            .map_err(|e| rsa::Error::Pkcs8(e.into()))?
+            .into_vec();
+        Ok(Self {
+            private_key,
+            public_key_der,
+        })
+    }
+
+    /// The public key in the DER format that's sent to clients.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key_der
+    }
+
+    /// Decrypt data that the client encrypted with [`encrypt`].
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, rsa::Error> {
+        self.private_key.decrypt(Pkcs1v15Encrypt, data)
+    }
+}
+
+/// Make a random nonce for the server to send in its hello packet.
+pub fn generate_nonce() -> [u8; 4] {
+    let mut nonce = [0u8; 4];
+    OsRng.fill_bytes(&mut nonce);
+    nonce
+}
+
 pub type Aes128CfbEnc = cfb8::Encryptor<Aes128>;
 pub type Aes128CfbDec = cfb8::Decryptor<Aes128>;
 
@@ -105,6 +166,50 @@ mod tests {
         assert_eq!(digest, "88e16a1019277b15d58faf0541e11910eb756f6");
     }
 
+    #[test]
+    fn test_hmac_sha256() {
+        // test cases from RFC 4231
+        assert_eq!(
+            hmac_sha256(b"Jefe", b"what do ya want for nothing?"),
+            [
+                0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95,
+                0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9,
+                0x64, 0xec, 0x38, 0x43
+            ]
+        );
+        assert_eq!(
+            hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            ),
+            [
+                0x60, 0xe4, 0x31, 0x59, 0x1e, 0xe0, 0xb6, 0x7f, 0x0d, 0x8a, 0x26, 0xaa, 0xcb, 0xf5,
+                0xb7, 0x7f, 0x8e, 0x0b, 0xc6, 0x21, 0x37, 0x28, 0xc5, 0x14, 0x05, 0x46, 0x04, 0x0f,
+                0x0e, 0xe3, 0x7f, 0x54
+            ]
+        );
+    }
+
+    #[test]
+    fn test_offline_uuid() {
+        assert_eq!(
+            offline_uuid("Notch").to_string(),
+            "b50ad385-829d-3141-a216-7e7d7539ba7f"
+        );
+    }
+
+    #[test]
+    fn test_server_key_pair() {
+        let key_pair = ServerKeyPair::generate().unwrap();
+        let nonce = generate_nonce();
+        let encrypted = encrypt(key_pair.public_key(), &nonce).unwrap();
+        assert_eq!(
+            key_pair.decrypt(&encrypted.encrypted_public_key).unwrap(),
+            encrypted.secret_key
+        );
+        assert_eq!(key_pair.decrypt(&encrypted.encrypted_nonce).unwrap(), nonce);
+    }
+
     #[test]
     fn encode_packet_twice() {
         let mut packet = vec![0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09];
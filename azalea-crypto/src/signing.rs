@@ -1,4 +1,10 @@
 use azalea_buf::McBuf;
+use rsa::{
+    pkcs1v15::{Signature, SigningKey, VerifyingKey},
+    signature::{SignatureEncoding, Signer, Verifier},
+    RsaPrivateKey, RsaPublicKey,
+};
+use sha2::Sha256;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, McBuf)]
@@ -22,3 +28,130 @@ pub struct SignedMessageHeader {
 pub fn make_salt() -> u64 {
     rand::random()
 }
+
+/// Everything that's included in the signature of a chat message (or of a
+/// signed command argument).
+#[derive(Clone, Debug)]
+pub struct SignedMessageData<'a> {
+    /// The UUID of the player that sent the message.
+    pub sender: Uuid,
+    /// The ID of the sender's chat session, which they sent in a
+    /// `ChatSessionUpdate` packet.
+    pub session_id: Uuid,
+    /// The number of messages that the sender has signed before this one in the
+    /// session.
+    pub index: u32,
+    pub salt: u64,
+    /// The time that the message was sent, in milliseconds since the UNIX
+    /// epoch. Only the seconds are signed.
+    pub timestamp: u64,
+    pub content: &'a str,
+    /// The signatures of the messages that the sender has seen recently.
+    pub last_seen: &'a [MessageSignature],
+}
+
+impl SignedMessageData<'_> {
+    /// The bytes that are signed, which are the same as what the vanilla
+    /// client signs.
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(60 + self.content.len() + self.last_seen.len() * 256);
+        // the version of the signature format, which is always 1
+        data.extend(1i32.to_be_bytes());
+
+        data.extend(self.sender.as_bytes());
+        data.extend(self.session_id.as_bytes());
+        data.extend(self.index.to_be_bytes());
+
+        data.extend(self.salt.to_be_bytes());
+        data.extend((self.timestamp / 1000).to_be_bytes());
+        data.extend((self.content.len() as u32).to_be_bytes());
+        data.extend(self.content.as_bytes());
+        data.extend((self.last_seen.len() as u32).to_be_bytes());
+        for signature in self.last_seen {
+            data.extend(signature.bytes);
+        }
+        data
+    }
+
+    /// Sign the message with SHA256withRSA.
+    ///
+    /// # Panics
+    /// If the key isn't 2048 bits, since the signature wouldn't fit in a
+    /// [`MessageSignature`].
+    pub fn sign(&self, private_key: &RsaPrivateKey) -> MessageSignature {
+        let signing_key = SigningKey::<Sha256>::new(private_key.clone());
+        let signature = signing_key.sign(&self.bytes()).to_vec();
+        MessageSignature {
+            bytes: signature
+                .try_into()
+                .expect("Chat signing keys should be 2048 bits"),
+        }
+    }
+
+    /// Check whether the signature is valid for this message and was made by
+    /// the owner of the public key.
+    pub fn verify(&self, public_key: &RsaPublicKey, signature: &MessageSignature) -> bool {
+        let Ok(signature) = Signature::try_from(&signature.bytes[..]) else {
+            return false;
+        };
+        VerifyingKey::<Sha256>::new(public_key.clone())
+            .verify(&self.bytes(), &signature)
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(last_seen: &[MessageSignature]) -> SignedMessageData {
+        SignedMessageData {
+            sender: Uuid::from_u128(1),
+            session_id: Uuid::from_u128(2),
+            index: 3,
+            salt: 4,
+            timestamp: 5999,
+            content: "hello",
+            last_seen,
+        }
+    }
+
+    #[test]
+    fn test_signed_message_bytes() {
+        let bytes = message(&[]).bytes();
+        let mut expected = vec![0, 0, 0, 1];
+        expected.extend(Uuid::from_u128(1).as_bytes());
+        expected.extend(Uuid::from_u128(2).as_bytes());
+        expected.extend([0, 0, 0, 3]);
+        expected.extend([0, 0, 0, 0, 0, 0, 0, 4]);
+        // only the seconds of the timestamp are included
+        expected.extend([0, 0, 0, 0, 0, 0, 0, 5]);
+        expected.extend([0, 0, 0, 5]);
+        expected.extend(b"hello");
+        expected.extend([0, 0, 0, 0]);
+        assert_eq!(bytes, expected);
+
+        let last_seen = [MessageSignature { bytes: [7; 256] }];
+        let bytes = message(&last_seen).bytes();
+        assert_eq!(bytes.len(), expected.len() + 256);
+        assert_eq!(bytes[expected.len() - 4..expected.len()], [0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_sign_message() {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        let public_key = RsaPublicKey::from(&private_key);
+        let data = message(&[]);
+        let signature = data.sign(&private_key);
+        assert!(data.verify(&public_key, &signature));
+
+        let other_data = SignedMessageData {
+            content: "goodbye",
+            ..data
+        };
+        assert!(!other_data.verify(&public_key, &signature));
+
+        let other_key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        assert!(!data.verify(&RsaPublicKey::from(&other_key), &signature));
+    }
+}
@@ -652,6 +652,8 @@ impl Menu {
             }
         }
 
+        // whatever couldn't be moved stays in the original slot
+        *self.slot_mut(item_slot_index).unwrap() = item_slot.clone();
         item_slot.is_empty()
     }
 
@@ -191,6 +191,15 @@ pub fn declare_state_packets(input: TokenStream) -> TokenStream {
         Ident::new(&format!("Clientbound{}", input.name), input.name.span());
 
     let state_name_litstr = syn::LitStr::new(&input.name.to_string(), input.name.span());
+    // GamePacket -> ConnectionProtocol::Game
+    let protocol_name = Ident::new(
+        input
+            .name
+            .to_string()
+            .strip_suffix("Packet")
+            .expect("The state name should end with `Packet`"),
+        input.name.span(),
+    );
 
     let has_serverbound_packets = !input.serverbound.packets.is_empty();
     let has_clientbound_packets = !input.clientbound.packets.is_empty();
@@ -201,6 +210,8 @@ pub fn declare_state_packets(input: TokenStream) -> TokenStream {
     let mut clientbound_id_match_contents = quote!();
     let mut serverbound_write_match_contents = quote!();
     let mut clientbound_write_match_contents = quote!();
+    let mut serverbound_name_match_contents = quote!();
+    let mut clientbound_name_match_contents = quote!();
     let mut serverbound_read_match_contents = quote!();
     let mut clientbound_read_match_contents = quote!();
 
@@ -217,6 +228,9 @@ pub fn declare_state_packets(input: TokenStream) -> TokenStream {
         serverbound_write_match_contents.extend(quote! {
             #serverbound_state_name::#variant_name(packet) => packet.write(buf),
         });
+        serverbound_name_match_contents.extend(quote! {
+            #serverbound_state_name::#variant_name(_packet) => #name_litstr,
+        });
         serverbound_read_match_contents.extend(quote! {
             #id => {
                 let data = #module::#name::read(buf).map_err(|e| crate::read::ReadPacketError::Parse {
@@ -250,6 +264,9 @@ pub fn declare_state_packets(input: TokenStream) -> TokenStream {
         clientbound_write_match_contents.extend(quote! {
             #clientbound_state_name::#variant_name(packet) => packet.write(buf),
         });
+        clientbound_name_match_contents.extend(quote! {
+            #clientbound_state_name::#variant_name(_packet) => #name_litstr,
+        });
         clientbound_read_match_contents.extend(quote! {
             #id => {
                 let data = #module::#name::read(buf).map_err(|e| crate::read::ReadPacketError::Parse {
@@ -285,6 +302,9 @@ pub fn declare_state_packets(input: TokenStream) -> TokenStream {
         serverbound_write_match_contents.extend(quote! {
             _ => unreachable!("This enum is empty and can't exist.")
         });
+        serverbound_name_match_contents.extend(quote! {
+            _ => unreachable!("This enum is empty and can't exist.")
+        });
     }
     if !has_clientbound_packets {
         clientbound_id_match_contents.extend(quote! {
@@ -293,6 +313,9 @@ pub fn declare_state_packets(input: TokenStream) -> TokenStream {
         clientbound_write_match_contents.extend(quote! {
             _ => unreachable!("This enum is empty and can't exist.")
         });
+        clientbound_name_match_contents.extend(quote! {
+            _ => unreachable!("This enum is empty and can't exist.")
+        });
     }
 
     let mut contents = quote! {
@@ -315,6 +338,11 @@ pub fn declare_state_packets(input: TokenStream) -> TokenStream {
     contents.extend(quote! {
         #[allow(unreachable_code)]
         impl crate::packets::ProtocolPacket for #serverbound_state_name {
+            const PROTOCOL: crate::packets::ConnectionProtocol =
+                crate::packets::ConnectionProtocol::#protocol_name;
+            const DIRECTION: crate::packets::PacketDirection =
+                crate::packets::PacketDirection::Serverbound;
+
             fn id(&self) -> u32 {
                 match self {
                     #serverbound_id_match_contents
@@ -327,6 +355,12 @@ pub fn declare_state_packets(input: TokenStream) -> TokenStream {
                 }
             }
 
+            fn name(&self) -> &'static str {
+                match self {
+                    #serverbound_name_match_contents
+                }
+            }
+
             /// Read a packet by its id, ConnectionProtocol, and flow
             fn read(
                 id: u32,
@@ -346,6 +380,11 @@ pub fn declare_state_packets(input: TokenStream) -> TokenStream {
     contents.extend(quote! {
         #[allow(unreachable_code)]
         impl crate::packets::ProtocolPacket for #clientbound_state_name {
+            const PROTOCOL: crate::packets::ConnectionProtocol =
+                crate::packets::ConnectionProtocol::#protocol_name;
+            const DIRECTION: crate::packets::PacketDirection =
+                crate::packets::PacketDirection::Clientbound;
+
             fn id(&self) -> u32 {
                 match self {
                     #clientbound_id_match_contents
@@ -358,6 +397,12 @@ pub fn declare_state_packets(input: TokenStream) -> TokenStream {
                 }
             }
 
+            fn name(&self) -> &'static str {
+                match self {
+                    #clientbound_name_match_contents
+                }
+            }
+
             /// Read a packet by its id, ConnectionProtocol, and flow
             fn read(
                 id: u32,
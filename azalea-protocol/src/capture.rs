@@ -0,0 +1,307 @@
+//! Record packets to a capture file and read them back, so a session can be
+//! replayed later without a server.
+//!
+//! A capture starts with [`CAPTURE_MAGIC`] and a [`CaptureHeader`], followed by
+//! [`CapturedPacket`]s until the end of the file. Packets are stored the way
+//! they are after decompression and decryption: the packet id followed by the
+//! packet's data, in the protocol version from the header.
+
+pub use crate::packets::PacketDirection;
+use crate::{
+    packets::{version::ProtocolVersion, ConnectionProtocol, ProtocolPacket},
+    read::{packet_decoder, packet_decoder_for_version, ReadPacketError},
+    write::{packet_encoder, PacketEncodeError},
+};
+use azalea_auth::game_profile::GameProfile;
+use azalea_buf::{BufReadError, McBuf, McBufReadable, McBufWritable};
+use std::{
+    fmt::Debug,
+    io::{self, Cursor, Read, Write},
+    time::Instant,
+};
+use thiserror::Error;
+
+/// The bytes that every capture file starts with.
+pub const CAPTURE_MAGIC: [u8; 8] = *b"AZALEAPC";
+/// The version of the capture format that we write. We can't read captures
+/// with a different version.
+pub const CAPTURE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum CaptureError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("Not a capture file")]
+    InvalidMagic,
+    #[error("Unsupported capture format version {0}")]
+    UnsupportedVersion(u32),
+    #[error("The capture was recorded with protocol version {0}, which we don't support")]
+    UnsupportedProtocolVersion(u32),
+    #[error("Couldn't read capture: {0}")]
+    Read(#[from] BufReadError),
+    #[error("Couldn't encode packet: {0}")]
+    Encode(#[from] PacketEncodeError),
+}
+
+/// Information about the session that a capture was recorded in.
+#[derive(Clone, Debug, McBuf)]
+pub struct CaptureHeader {
+    pub format_version: u32,
+    /// The protocol version of the connection the packets were recorded on.
+    pub protocol_version: u32,
+    /// The profile of the player that the packets were recorded for.
+    pub game_profile: GameProfile,
+}
+
+impl CaptureHeader {
+    /// The version of the packets in the capture, or `None` if we don't
+    /// support it.
+    pub fn version(&self) -> Option<ProtocolVersion> {
+        ProtocolVersion::from_protocol_version(self.protocol_version)
+    }
+}
+
+/// A packet in a capture.
+#[derive(Clone, Debug, McBuf)]
+pub struct CapturedPacket {
+    /// How many milliseconds after the start of the capture the packet was
+    /// sent or received.
+    #[var]
+    pub time: u64,
+    pub direction: PacketDirection,
+    /// The state the connection was in, which decides how the packet is
+    /// decoded.
+    pub protocol: ConnectionProtocol,
+    /// The packet id followed by the packet's data.
+    pub data: Vec<u8>,
+}
+
+impl CapturedPacket {
+    /// Decode the packet. `P` has to be the packet type for the packet's
+    /// direction and protocol, like `ClientboundGamePacket`.
+    pub fn decode<P: ProtocolPacket + Debug>(&self) -> Result<P, Box<ReadPacketError>> {
+        packet_decoder(&mut Cursor::new(&self.data[..]))
+    }
+
+    /// Like [`Self::decode`], but for a packet from a capture that was
+    /// recorded with another version. See [`CaptureHeader::version`].
+    pub fn decode_for_version<P: ProtocolPacket + Debug>(
+        &self,
+        version: ProtocolVersion,
+    ) -> Result<P, Box<ReadPacketError>> {
+        packet_decoder_for_version(&mut Cursor::new(&self.data[..]), version)
+    }
+}
+
+/// Writes packets to a capture.
+///
+/// ```no_run
+/// # use azalea_protocol::capture::{CaptureWriter, PacketDirection};
+/// # use azalea_protocol::packets::{ConnectionProtocol, game::ClientboundGamePacket};
+/// # use azalea_auth::game_profile::GameProfile;
+/// # fn example(packet: ClientboundGamePacket) -> Result<(), Box<dyn std::error::Error>> {
+/// let file = std::fs::File::create("session.azcap")?;
+/// let mut writer = CaptureWriter::new(file, GameProfile::default())?;
+/// writer.write_packet(PacketDirection::Clientbound, ConnectionProtocol::Game, &packet)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+    start: Instant,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Start a capture by writing the header.
+    pub fn new(writer: W, game_profile: GameProfile) -> Result<Self, CaptureError> {
+        Self::new_for_version(writer, game_profile, ProtocolVersion::current())
+    }
+
+    /// Start a capture of packets that are in another version than
+    /// [`PROTOCOL_VERSION`](crate::packets::PROTOCOL_VERSION), like the raw
+    /// packets from a server on that version.
+    pub fn new_for_version(
+        mut writer: W,
+        game_profile: GameProfile,
+        version: ProtocolVersion,
+    ) -> Result<Self, CaptureError> {
+        writer.write_all(&CAPTURE_MAGIC)?;
+        CaptureHeader {
+            format_version: CAPTURE_FORMAT_VERSION,
+            protocol_version: version.protocol_version(),
+            game_profile,
+        }
+        .write_into(&mut writer)?;
+        writer.flush()?;
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    /// Write a packet to the capture with the current time. The capture is
+    /// flushed after every packet so nothing is lost if the program crashes.
+    pub fn write_packet<P: ProtocolPacket + Debug>(
+        &mut self,
+        direction: PacketDirection,
+        protocol: ConnectionProtocol,
+        packet: &P,
+    ) -> Result<(), CaptureError> {
+        self.write_raw(direction, protocol, packet_encoder(packet)?)
+    }
+
+    /// Write the id and data of a packet that's already encoded, like one
+    /// from `ReadConnection::read_raw`, with the current time.
+    pub fn write_raw(
+        &mut self,
+        direction: PacketDirection,
+        protocol: ConnectionProtocol,
+        data: Vec<u8>,
+    ) -> Result<(), CaptureError> {
+        self.write(&CapturedPacket {
+            time: self.start.elapsed().as_millis() as u64,
+            direction,
+            protocol,
+            data,
+        })
+    }
+
+    /// Write a packet that was already captured, keeping its time.
+    pub fn write(&mut self, packet: &CapturedPacket) -> Result<(), CaptureError> {
+        packet.write_into(&mut self.writer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads the packets in a capture. This is an iterator of
+/// [`CapturedPacket`]s, which stops after the first error.
+pub struct CaptureReader {
+    pub header: CaptureHeader,
+    data: Vec<u8>,
+    position: usize,
+    failed: bool,
+}
+
+impl CaptureReader {
+    /// Read a whole capture into memory and parse the header.
+    pub fn new(mut reader: impl Read) -> Result<Self, CaptureError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        if !data.starts_with(&CAPTURE_MAGIC) {
+            return Err(CaptureError::InvalidMagic);
+        }
+        let mut cursor = Cursor::new(&data[CAPTURE_MAGIC.len()..]);
+        let header = CaptureHeader::read_from(&mut cursor)?;
+        if header.format_version != CAPTURE_FORMAT_VERSION {
+            return Err(CaptureError::UnsupportedVersion(header.format_version));
+        }
+        let position = CAPTURE_MAGIC.len() + cursor.position() as usize;
+
+        Ok(Self {
+            header,
+            data,
+            position,
+            failed: false,
+        })
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = Result<CapturedPacket, CaptureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.position >= self.data.len() {
+            return None;
+        }
+        let mut cursor = Cursor::new(&self.data[self.position..]);
+        match CapturedPacket::read_from(&mut cursor) {
+            Ok(packet) => {
+                self.position += cursor.position() as usize;
+                Some(Ok(packet))
+            }
+            Err(e) => {
+                // the capture was probably cut off while it was being written
+                self.failed = true;
+                Some(Err(e.into()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::game::{
+        clientbound_keep_alive_packet::ClientboundKeepAlivePacket,
+        serverbound_keep_alive_packet::ServerboundKeepAlivePacket, ClientboundGamePacket,
+        ServerboundGamePacket,
+    };
+
+    #[test]
+    fn test_write_and_read_capture() {
+        let game_profile = GameProfile::new(uuid::Uuid::nil(), "bot".to_string());
+        let mut writer = CaptureWriter::new(Vec::new(), game_profile.clone()).unwrap();
+        writer
+            .write_packet(
+                PacketDirection::Clientbound,
+                ConnectionProtocol::Game,
+                &ClientboundKeepAlivePacket { id: 1 }.get(),
+            )
+            .unwrap();
+        writer
+            .write_packet(
+                PacketDirection::Serverbound,
+                ConnectionProtocol::Game,
+                &ServerboundKeepAlivePacket { id: 1 }.get(),
+            )
+            .unwrap();
+
+        let mut reader = CaptureReader::new(&writer.writer[..]).unwrap();
+        assert_eq!(reader.header.game_profile, game_profile);
+        assert_eq!(
+            reader.header.protocol_version,
+            crate::packets::PROTOCOL_VERSION
+        );
+
+        let packet = reader.next().unwrap().unwrap();
+        assert_eq!(packet.direction, PacketDirection::Clientbound);
+        assert!(matches!(
+            packet.decode::<ClientboundGamePacket>().unwrap(),
+            ClientboundGamePacket::KeepAlive(ClientboundKeepAlivePacket { id: 1 })
+        ));
+        let packet = reader.next().unwrap().unwrap();
+        assert_eq!(packet.direction, PacketDirection::Serverbound);
+        assert!(matches!(
+            packet.decode::<ServerboundGamePacket>().unwrap(),
+            ServerboundGamePacket::KeepAlive(ServerboundKeepAlivePacket { id: 1 })
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_truncated_capture() {
+        let mut writer = CaptureWriter::new(Vec::new(), GameProfile::default()).unwrap();
+        writer
+            .write_packet(
+                PacketDirection::Clientbound,
+                ConnectionProtocol::Game,
+                &ClientboundKeepAlivePacket { id: 1 }.get(),
+            )
+            .unwrap();
+        let data = &writer.writer[..writer.writer.len() - 1];
+
+        let mut reader = CaptureReader::new(data).unwrap();
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_invalid_magic() {
+        assert!(matches!(
+            CaptureReader::new(&b"not a capture"[..]),
+            Err(CaptureError::InvalidMagic)
+        ));
+    }
+}
@@ -5,42 +5,72 @@ use crate::packets::handshake::{ClientboundHandshakePacket, ServerboundHandshake
 use crate::packets::login::clientbound_hello_packet::ClientboundHelloPacket;
 use crate::packets::login::{ClientboundLoginPacket, ServerboundLoginPacket};
 use crate::packets::status::{ClientboundStatusPacket, ServerboundStatusPacket};
+use crate::packets::version::ProtocolVersion;
 use crate::packets::ProtocolPacket;
-use crate::read::{read_packet, ReadPacketError};
-use crate::write::write_packet;
+use crate::proxy::{Proxy, ProxyError};
+use crate::read::{decompress_frame, packet_decoder_for_version, read_frame, ReadPacketError};
+use crate::stats::ConnectionStats;
+use crate::write::{encode_frame, packet_encoder_for_version, PacketEncodeError};
 use azalea_auth::game_profile::GameProfile;
-use azalea_auth::sessionserver::{ClientSessionServerError, ServerSessionServerError};
+use azalea_auth::{
+    sessionserver::{ClientSessionServerError, ServerSessionServerError},
+    AuthEndpoints,
+};
 use azalea_crypto::{Aes128CfbDec, Aes128CfbEnc};
 use bytes::BytesMut;
-use log::{error, info};
+use log::{error, info, trace};
 use std::fmt::Debug;
+use std::io::Cursor;
 use std::marker::PhantomData;
 use std::net::SocketAddr;
+use std::time::Instant;
 use thiserror::Error;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf, ReuniteError};
 use tokio::net::TcpStream;
 use uuid::Uuid;
 
 /// The read half of a connection.
-pub struct ReadConnection<R: ProtocolPacket> {
-    pub read_stream: OwnedReadHalf,
+///
+/// `S` is the stream that packets are read from, which is the read half of a
+/// `TcpStream` by default.
+pub struct ReadConnection<R: ProtocolPacket, S = OwnedReadHalf> {
+    pub read_stream: S,
     pub buffer: BytesMut,
     pub compression_threshold: Option<u32>,
     pub dec_cipher: Option<Aes128CfbDec>,
+    /// The version of the packets we read, which are translated to the ones
+    /// in [`PROTOCOL_VERSION`](crate::packets::PROTOCOL_VERSION).
+    pub version: ProtocolVersion,
+    /// The traffic on this connection, which is shared with the
+    /// [`WriteConnection`].
+    pub stats: ConnectionStats,
     _reading: PhantomData<R>,
 }
 
 /// The write half of a connection.
-pub struct WriteConnection<W: ProtocolPacket> {
-    pub write_stream: OwnedWriteHalf,
+///
+/// `S` is the stream that packets are written to, which is the write half of a
+/// `TcpStream` by default.
+pub struct WriteConnection<W: ProtocolPacket, S = OwnedWriteHalf> {
+    pub write_stream: S,
     pub compression_threshold: Option<u32>,
     pub enc_cipher: Option<Aes128CfbEnc>,
+    /// The version of the packets we write, which are translated from the
+    /// ones in [`PROTOCOL_VERSION`](crate::packets::PROTOCOL_VERSION).
+    pub version: ProtocolVersion,
+    /// The traffic on this connection, which is shared with the
+    /// [`ReadConnection`].
+    pub stats: ConnectionStats,
     _writing: PhantomData<W>,
 }
 
 /// A connection that can read and write packets.
 ///
+/// Connections are usually made over TCP, but they can use any stream that
+/// implements [`AsyncRead`] and [`AsyncWrite`] with
+/// [`Connection::wrap_stream`] or [`Connection::wrap_split`].
+///
 /// # Examples
 ///
 /// Join an offline-mode server and go through the handshake.
@@ -121,40 +151,100 @@ pub struct WriteConnection<W: ProtocolPacket> {
 ///     Ok(())
 /// }
 /// ```
-pub struct Connection<R: ProtocolPacket, W: ProtocolPacket> {
-    pub reader: ReadConnection<R>,
-    pub writer: WriteConnection<W>,
+pub struct Connection<R: ProtocolPacket, W: ProtocolPacket, RS = OwnedReadHalf, WS = OwnedWriteHalf>
+{
+    pub reader: ReadConnection<R, RS>,
+    pub writer: WriteConnection<W, WS>,
 }
 
-impl<R> ReadConnection<R>
+impl<R, S> ReadConnection<R, S>
 where
     R: ProtocolPacket + Debug,
+    S: AsyncRead + Unpin + Send,
 {
     /// Read a packet from the stream.
     pub async fn read(&mut self) -> Result<R, Box<ReadPacketError>> {
-        read_packet::<R, _>(
+        let data = self.read_raw().await?;
+        self.decode(&data)
+    }
+
+    /// Read the id and data of the next packet without decoding it. This is
+    /// useful for skipping packets that can't be decoded, since you can
+    /// decode it yourself with [`Self::decode`] and still have the bytes if
+    /// that fails.
+    pub async fn read_raw(&mut self) -> Result<Vec<u8>, Box<ReadPacketError>> {
+        let frame = read_frame(
             &mut self.read_stream,
             &mut self.buffer,
-            self.compression_threshold,
             &mut self.dec_cipher,
         )
-        .await
+        .await?;
+        let frame_len = frame.len();
+        let data = decompress_frame(frame, self.compression_threshold)?;
+        self.stats.record_received_frame(frame_len, data.len());
+        Ok(data)
+    }
+
+    /// Decode a packet that was read with [`Self::read_raw`], translating it
+    /// from our [`version`](Self::version). The packet is counted in the
+    /// [`stats`](Self::stats) if it could be decoded.
+    pub fn decode(&self, data: &[u8]) -> Result<R, Box<ReadPacketError>> {
+        let start = Instant::now();
+        let packet = packet_decoder_for_version::<R>(&mut Cursor::new(data), self.version)?;
+        self.stats
+            .record_received_packet(packet.name(), data.len(), start.elapsed());
+        Ok(packet)
     }
 }
-impl<W> WriteConnection<W>
+impl<W, S> WriteConnection<W, S>
 where
     W: ProtocolPacket + Debug,
+    S: AsyncWrite + Unpin + Send,
 {
     /// Write a packet to the server.
     pub async fn write(&mut self, packet: W) -> std::io::Result<()> {
-        if let Err(e) = write_packet(
-            &packet,
-            &mut self.write_stream,
-            self.compression_threshold,
-            &mut self.enc_cipher,
-        )
-        .await
-        {
+        trace!("Sending packet: {packet:?}");
+        let buf = self
+            .encode(&packet)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        self.write_encoded(packet.name(), buf).await
+    }
+
+    /// Encode a packet the way [`Self::write`] does, translating it to our
+    /// [`version`](Self::version).
+    pub fn encode(&self, packet: &W) -> Result<Vec<u8>, PacketEncodeError> {
+        packet_encoder_for_version(packet, self.version)
+    }
+
+    /// Write a packet that was encoded with [`Self::encode`]. The name of the
+    /// packet is only used for the [`stats`](Self::stats).
+    pub async fn write_encoded(
+        &mut self,
+        packet_name: &'static str,
+        data: Vec<u8>,
+    ) -> std::io::Result<()> {
+        let uncompressed_len = data.len();
+        let frame_len = self.write_frame(data).await?;
+        self.stats
+            .record_sent_packet(packet_name, frame_len, uncompressed_len);
+        Ok(())
+    }
+
+    /// Write the id and data of a packet that's already encoded, like one
+    /// from [`ReadConnection::read_raw`]. It's still compressed and encrypted
+    /// like normal.
+    pub async fn write_raw(&mut self, data: Vec<u8>) -> std::io::Result<()> {
+        let uncompressed_len = data.len();
+        let frame_len = self.write_frame(data).await?;
+        self.stats.record_sent_frame(frame_len, uncompressed_len);
+        Ok(())
+    }
+
+    /// Compress, frame and encrypt an encoded packet and write it, returning
+    /// the size of the frame.
+    async fn write_frame(&mut self, buf: Vec<u8>) -> std::io::Result<usize> {
+        let frame = encode_frame(buf, self.compression_threshold, &mut self.enc_cipher).await;
+        if let Err(e) = self.write_stream.write_all(&frame).await {
             // detect broken pipe
             if e.kind() == std::io::ErrorKind::BrokenPipe {
                 info!("Broken pipe, shutting down connection.");
@@ -164,7 +254,7 @@ where
             }
             return Err(e);
         }
-        Ok(())
+        Ok(frame.len())
     }
 
     /// End the connection.
@@ -173,10 +263,12 @@ where
     }
 }
 
-impl<R, W> Connection<R, W>
+impl<R, W, RS, WS> Connection<R, W, RS, WS>
 where
     R: ProtocolPacket + Debug,
     W: ProtocolPacket + Debug,
+    RS: AsyncRead + Unpin + Send,
+    WS: AsyncWrite + Unpin + Send,
 {
     /// Read a packet from the other side of the connection.
     pub async fn read(&mut self) -> Result<R, Box<ReadPacketError>> {
@@ -190,15 +282,28 @@ where
 
     /// Split the reader and writer into two objects. This doesn't allocate.
     #[must_use]
-    pub fn into_split(self) -> (ReadConnection<R>, WriteConnection<W>) {
+    pub fn into_split(self) -> (ReadConnection<R, RS>, WriteConnection<W, WS>) {
         (self.reader, self.writer)
     }
+
+    /// Set the version of the other side of the connection. Packets are
+    /// translated to and from this version, so you can keep using the packets
+    /// from [`PROTOCOL_VERSION`](crate::packets::PROTOCOL_VERSION). This is
+    /// kept when the state changes.
+    ///
+    /// This should be the same version that was sent in the handshake.
+    pub fn set_version(&mut self, version: ProtocolVersion) {
+        self.reader.version = version;
+        self.writer.version = version;
+    }
 }
 
 #[derive(Error, Debug)]
 pub enum ConnectionError {
     #[error("{0}")]
     Io(#[from] std::io::Error),
+    #[error("Proxy error: {0}")]
+    Proxy(#[from] ProxyError),
 }
 
 impl Connection<ClientboundHandshakePacket, ServerboundHandshakePacket> {
@@ -209,41 +314,42 @@ impl Connection<ClientboundHandshakePacket, ServerboundHandshakePacket> {
         // enable tcp_nodelay
         stream.set_nodelay(true)?;
 
-        let (read_stream, write_stream) = stream.into_split();
+        Ok(Connection::wrap(stream))
+    }
 
-        Ok(Connection {
-            reader: ReadConnection {
-                read_stream,
-                buffer: BytesMut::new(),
-                compression_threshold: None,
-                dec_cipher: None,
-                _reading: PhantomData,
-            },
-            writer: WriteConnection {
-                write_stream,
-                compression_threshold: None,
-                enc_cipher: None,
-                _writing: PhantomData,
-            },
-        })
+    /// Create a new connection to the given host through a proxy. The host
+    /// can be a hostname if you want the proxy to look it up, or an IP
+    /// address.
+    pub async fn new_with_proxy(
+        proxy: &Proxy,
+        host: &str,
+        port: u16,
+    ) -> Result<Self, ConnectionError> {
+        let stream = proxy.connect(host, port).await?;
+
+        stream.set_nodelay(true)?;
+
+        Ok(Connection::wrap(stream))
     }
+}
 
+impl<RS, WS> Connection<ClientboundHandshakePacket, ServerboundHandshakePacket, RS, WS> {
     /// Change our state from handshake to login. This is the state that is used
     /// for logging in.
     #[must_use]
-    pub fn login(self) -> Connection<ClientboundLoginPacket, ServerboundLoginPacket> {
+    pub fn login(self) -> Connection<ClientboundLoginPacket, ServerboundLoginPacket, RS, WS> {
         Connection::from(self)
     }
 
     /// Change our state from handshake to status. This is the state that is
     /// used for pinging the server.
     #[must_use]
-    pub fn status(self) -> Connection<ClientboundStatusPacket, ServerboundStatusPacket> {
+    pub fn status(self) -> Connection<ClientboundStatusPacket, ServerboundStatusPacket, RS, WS> {
         Connection::from(self)
     }
 }
 
-impl Connection<ClientboundLoginPacket, ServerboundLoginPacket> {
+impl<RS, WS> Connection<ClientboundLoginPacket, ServerboundLoginPacket, RS, WS> {
     /// Set our compression threshold, i.e. the maximum size that a packet is
     /// allowed to be without getting compressed. If you set it to less than 0
     /// then compression gets disabled.
@@ -269,7 +375,7 @@ impl Connection<ClientboundLoginPacket, ServerboundLoginPacket> {
     /// Change our state from login to game. This is the state that's used when
     /// you're actually in the game.
     #[must_use]
-    pub fn game(self) -> Connection<ClientboundGamePacket, ServerboundGamePacket> {
+    pub fn game(self) -> Connection<ClientboundGamePacket, ServerboundGamePacket, RS, WS> {
         Connection::from(self)
     }
 
@@ -333,34 +439,55 @@ impl Connection<ClientboundLoginPacket, ServerboundLoginPacket> {
         private_key: [u8; 16],
         packet: &ClientboundHelloPacket,
     ) -> Result<(), ClientSessionServerError> {
-        azalea_auth::sessionserver::join(
+        self.authenticate_with_endpoints(
+            access_token,
+            uuid,
+            private_key,
+            packet,
+            &AuthEndpoints::default(),
+        )
+        .await
+    }
+
+    /// Like [`Self::authenticate`], but use the session server from the given
+    /// [`AuthEndpoints`] instead of Mojang's.
+    pub async fn authenticate_with_endpoints(
+        &self,
+        access_token: &str,
+        uuid: &Uuid,
+        private_key: [u8; 16],
+        packet: &ClientboundHelloPacket,
+        endpoints: &AuthEndpoints,
+    ) -> Result<(), ClientSessionServerError> {
+        azalea_auth::sessionserver::join_with_endpoints(
             access_token,
             &packet.public_key,
             &private_key,
             uuid,
             &packet.server_id,
+            endpoints,
         )
         .await
     }
 }
 
-impl Connection<ServerboundHandshakePacket, ClientboundHandshakePacket> {
+impl<RS, WS> Connection<ServerboundHandshakePacket, ClientboundHandshakePacket, RS, WS> {
     /// Change our state from handshake to login. This is the state that is used
     /// for logging in.
     #[must_use]
-    pub fn login(self) -> Connection<ServerboundLoginPacket, ClientboundLoginPacket> {
+    pub fn login(self) -> Connection<ServerboundLoginPacket, ClientboundLoginPacket, RS, WS> {
         Connection::from(self)
     }
 
     /// Change our state from handshake to status. This is the state that is
     /// used for pinging the server.
     #[must_use]
-    pub fn status(self) -> Connection<ServerboundStatusPacket, ClientboundStatusPacket> {
+    pub fn status(self) -> Connection<ServerboundStatusPacket, ClientboundStatusPacket, RS, WS> {
         Connection::from(self)
     }
 }
 
-impl Connection<ServerboundLoginPacket, ClientboundLoginPacket> {
+impl<RS, WS> Connection<ServerboundLoginPacket, ClientboundLoginPacket, RS, WS> {
     /// Set our compression threshold, i.e. the maximum size that a packet is
     /// allowed to be without getting compressed. If you set it to less than 0
     /// then compression gets disabled.
@@ -386,7 +513,7 @@ impl Connection<ServerboundLoginPacket, ClientboundLoginPacket> {
     /// Change our state from login to game. This is the state that's used when
     /// the client is actually in the game.
     #[must_use]
-    pub fn game(self) -> Connection<ServerboundGamePacket, ClientboundGamePacket> {
+    pub fn game(self) -> Connection<ServerboundGamePacket, ClientboundGamePacket, RS, WS> {
         Connection::from(self)
     }
 
@@ -400,13 +527,40 @@ impl Connection<ServerboundLoginPacket, ClientboundLoginPacket> {
         private_key: &[u8; 16],
         ip: Option<&str>,
     ) -> Result<GameProfile, ServerSessionServerError> {
-        azalea_auth::sessionserver::serverside_auth(username, public_key, private_key, ip).await
+        self.authenticate_with_endpoints(
+            username,
+            public_key,
+            private_key,
+            ip,
+            &AuthEndpoints::default(),
+        )
+        .await
+    }
+
+    /// Like [`Self::authenticate`], but use the session server from the given
+    /// [`AuthEndpoints`] instead of Mojang's.
+    pub async fn authenticate_with_endpoints(
+        &self,
+        username: &str,
+        public_key: &[u8],
+        private_key: &[u8; 16],
+        ip: Option<&str>,
+        endpoints: &AuthEndpoints,
+    ) -> Result<GameProfile, ServerSessionServerError> {
+        azalea_auth::sessionserver::serverside_auth_with_endpoints(
+            username,
+            public_key,
+            private_key,
+            ip,
+            endpoints,
+        )
+        .await
     }
 }
 
 // rust doesn't let us implement From because allegedly it conflicts with
 // `core`'s "impl<T> From<T> for T" so we do this instead
-impl<R1, W1> Connection<R1, W1>
+impl<R1, W1, RS, WS> Connection<R1, W1, RS, WS>
 where
     R1: ProtocolPacket + Debug,
     W1: ProtocolPacket + Debug,
@@ -414,7 +568,7 @@ where
     /// Creates a `Connection` of a type from a `Connection` of another type.
     /// Useful for servers or custom packets.
     #[must_use]
-    pub fn from<R2, W2>(connection: Connection<R1, W1>) -> Connection<R2, W2>
+    pub fn from<R2, W2>(connection: Connection<R1, W1, RS, WS>) -> Connection<R2, W2, RS, WS>
     where
         R2: ProtocolPacket + Debug,
         W2: ProtocolPacket + Debug,
@@ -425,40 +579,430 @@ where
                 buffer: connection.reader.buffer,
                 compression_threshold: connection.reader.compression_threshold,
                 dec_cipher: connection.reader.dec_cipher,
+                version: connection.reader.version,
+                stats: connection.reader.stats,
                 _reading: PhantomData,
             },
             writer: WriteConnection {
                 compression_threshold: connection.writer.compression_threshold,
                 write_stream: connection.writer.write_stream,
                 enc_cipher: connection.writer.enc_cipher,
+                version: connection.writer.version,
+                stats: connection.writer.stats,
                 _writing: PhantomData,
             },
         }
     }
 
-    /// Convert an existing `TcpStream` into a `Connection`. Useful for servers.
-    pub fn wrap(stream: TcpStream) -> Connection<R1, W1> {
-        let (read_stream, write_stream) = stream.into_split();
-
+    /// Make a `Connection` from the read and write halves of any stream, like
+    /// a Unix socket or a TLS tunnel.
+    pub fn wrap_split(read_stream: RS, write_stream: WS) -> Connection<R1, W1, RS, WS> {
+        let stats = ConnectionStats::default();
         Connection {
             reader: ReadConnection {
                 read_stream,
                 buffer: BytesMut::new(),
                 compression_threshold: None,
                 dec_cipher: None,
+                version: ProtocolVersion::current(),
+                stats: stats.clone(),
                 _reading: PhantomData,
             },
             writer: WriteConnection {
                 write_stream,
                 compression_threshold: None,
                 enc_cipher: None,
+                version: ProtocolVersion::current(),
+                stats,
                 _writing: PhantomData,
             },
         }
     }
 
+    /// Get the read and write halves of the stream back from the `Connection`.
+    pub fn unwrap_split(self) -> (RS, WS) {
+        (self.reader.read_stream, self.writer.write_stream)
+    }
+}
+
+impl<R1, W1> Connection<R1, W1>
+where
+    R1: ProtocolPacket + Debug,
+    W1: ProtocolPacket + Debug,
+{
+    /// Convert an existing `TcpStream` into a `Connection`. Useful for servers.
+    pub fn wrap(stream: TcpStream) -> Connection<R1, W1> {
+        let (read_stream, write_stream) = stream.into_split();
+        Connection::wrap_split(read_stream, write_stream)
+    }
+
     /// Convert from a `Connection` into a `TcpStream`. Useful for servers.
     pub fn unwrap(self) -> Result<TcpStream, ReuniteError> {
         self.reader.read_stream.reunite(self.writer.write_stream)
     }
 }
+
+impl<R1, W1, S> Connection<R1, W1, ReadHalf<S>, WriteHalf<S>>
+where
+    R1: ProtocolPacket + Debug,
+    W1: ProtocolPacket + Debug,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Make a `Connection` from any stream that can be read from and written
+    /// to, like a [`tokio::io::DuplexStream`] for testing.
+    pub fn wrap_stream(stream: S) -> Connection<R1, W1, ReadHalf<S>, WriteHalf<S>> {
+        let (read_stream, write_stream) = tokio::io::split(stream);
+        Connection::wrap_split(read_stream, write_stream)
+    }
+
+    /// Convert from a `Connection` made with [`Connection::wrap_stream`] back
+    /// into the stream.
+    pub fn unwrap_stream(self) -> S {
+        self.reader.read_stream.unsplit(self.writer.write_stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::{
+        handshake::client_intention_packet::ClientIntentionPacket,
+        login::serverbound_hello_packet::ServerboundHelloPacket, ConnectionProtocol,
+        PROTOCOL_VERSION,
+    };
+
+    #[tokio::test]
+    async fn test_connection_over_duplex() {
+        let (client_stream, server_stream) = tokio::io::duplex(1024);
+        let mut client: Connection<ClientboundHandshakePacket, ServerboundHandshakePacket, _, _> =
+            Connection::wrap_stream(client_stream);
+        let mut server: Connection<ServerboundHandshakePacket, ClientboundHandshakePacket, _, _> =
+            Connection::wrap_stream(server_stream);
+
+        client
+            .write(
+                ClientIntentionPacket {
+                    protocol_version: PROTOCOL_VERSION,
+                    hostname: "localhost".to_string(),
+                    port: 25565,
+                    intention: ConnectionProtocol::Login,
+                }
+                .get(),
+            )
+            .await
+            .unwrap();
+        let ServerboundHandshakePacket::ClientIntention(intention) = server.read().await.unwrap();
+        assert_eq!(intention.hostname, "localhost");
+
+        // make sure compression and encryption work too
+        let mut client = client.login();
+        let mut server = server.login();
+        client.set_compression_threshold(0);
+        server.set_compression_threshold(0);
+        client.set_encryption_key([1; 16]);
+        server.set_encryption_key([1; 16]);
+
+        client
+            .write(
+                ServerboundHelloPacket {
+                    name: "bot".to_string(),
+                    profile_id: None,
+                }
+                .get(),
+            )
+            .await
+            .unwrap();
+        match server.read().await.unwrap() {
+            ServerboundLoginPacket::Hello(hello) => assert_eq!(hello.name, "bot"),
+            packet => panic!("Expected a hello packet, got {packet:?}"),
+        }
+
+        // the stats are kept when the state changes
+        let sent = client.writer.stats.snapshot();
+        let received = server.reader.stats.snapshot();
+        assert_eq!(sent.sent["ClientIntentionPacket"].count, 1);
+        assert_eq!(received.received["ServerboundHelloPacket"].count, 1);
+        assert_eq!(sent.bytes_sent, received.bytes_received);
+        assert_eq!(
+            sent.bytes_sent_uncompressed,
+            received.bytes_received_uncompressed
+        );
+        // compression makes the hello packet bigger since it's so small
+        assert!(received.bytes_received > received.bytes_received_uncompressed);
+    }
+
+    #[tokio::test]
+    async fn test_skip_unknown_packet() {
+        let (client_stream, server_stream) = tokio::io::duplex(1024);
+        let mut client: Connection<ClientboundHandshakePacket, ServerboundHandshakePacket, _, _> =
+            Connection::wrap_stream(client_stream);
+        let mut server: Connection<ServerboundHandshakePacket, ClientboundHandshakePacket, _, _> =
+            Connection::wrap_stream(server_stream);
+
+        // a packet with the length 2, the id 0x7f and one byte of data
+        client
+            .writer
+            .write_stream
+            .write_all(&[2, 0x7f, 0])
+            .await
+            .unwrap();
+        client
+            .write(
+                ClientIntentionPacket {
+                    protocol_version: PROTOCOL_VERSION,
+                    hostname: "localhost".to_string(),
+                    port: 25565,
+                    intention: ConnectionProtocol::Status,
+                }
+                .get(),
+            )
+            .await
+            .unwrap();
+
+        let data = server.reader.read_raw().await.unwrap();
+        assert_eq!(data, [0x7f, 0]);
+        assert!(matches!(
+            *crate::read::packet_decoder::<ServerboundHandshakePacket>(&mut std::io::Cursor::new(
+                &data[..]
+            ))
+            .unwrap_err(),
+            ReadPacketError::UnknownPacketId { id: 0x7f, .. }
+        ));
+        let ServerboundHandshakePacket::ClientIntention(intention) = server.read().await.unwrap();
+        assert_eq!(intention.intention, ConnectionProtocol::Status);
+    }
+
+    #[tokio::test]
+    async fn test_connection_with_older_version() {
+        use crate::packets::game::clientbound_player_position_packet::{
+            ClientboundPlayerPositionPacket, RelativeMovements,
+        };
+
+        let version = ProtocolVersion::from_protocol_version(761).unwrap();
+        let (client_stream, server_stream) = tokio::io::duplex(1024);
+        let mut client: Connection<ClientboundGamePacket, ServerboundGamePacket, _, _> =
+            Connection::wrap_stream(client_stream);
+        let mut server: Connection<ServerboundGamePacket, ClientboundGamePacket, _, _> =
+            Connection::wrap_stream(server_stream);
+        server.set_version(version);
+
+        let packet = ClientboundPlayerPositionPacket {
+            x: 1.,
+            y: 2.,
+            z: 3.,
+            y_rot: 0.,
+            x_rot: 0.,
+            relative_arguments: RelativeMovements {
+                x: false,
+                y: false,
+                z: false,
+                y_rot: false,
+                x_rot: false,
+            },
+            id: 5,
+        };
+        server.write(packet.clone().get()).await.unwrap();
+        server.write(packet.get()).await.unwrap();
+
+        // the packet has the 1.19.3 id and the `dismount_vehicle` boolean
+        let data = client.reader.read_raw().await.unwrap();
+        assert_eq!(data[0], 0x38);
+        assert_eq!(data[data.len() - 2..], [5, 0]);
+
+        client.set_version(version);
+        match client.read().await.unwrap() {
+            ClientboundGamePacket::PlayerPosition(p) => {
+                assert_eq!(p.id, 5);
+                assert_eq!(p.z, 3.);
+            }
+            packet => panic!("Expected a player position packet, got {packet:?}"),
+        }
+    }
+
+    /// The registry a vanilla 1.19.3 server sends, which doesn't have the
+    /// damage types or armor trims that were added in 1.19.4. Most of the
+    /// biomes and chat types are left out.
+    fn registry_1_19_3() -> azalea_nbt::Nbt {
+        use azalea_nbt::{Nbt, NbtCompound, NbtList};
+
+        let compound = |entries: Vec<(&str, Nbt)>| -> Nbt {
+            Nbt::Compound(NbtCompound::from_iter(
+                entries.into_iter().map(|(k, v)| (k.into(), v)),
+            ))
+        };
+        let string = |s: &str| Nbt::String(s.into());
+        let registry = |kind: &str, values: Vec<(&str, Nbt)>| {
+            compound(vec![
+                ("type", string(kind)),
+                (
+                    "value",
+                    Nbt::List(NbtList::Compound(
+                        values
+                            .into_iter()
+                            .enumerate()
+                            .map(|(id, (name, element))| {
+                                let Nbt::Compound(value) = compound(vec![
+                                    ("name", string(name)),
+                                    ("id", Nbt::Int(id as i32)),
+                                    ("element", element),
+                                ]) else {
+                                    unreachable!()
+                                };
+                                value
+                            })
+                            .collect(),
+                    )),
+                ),
+            ])
+        };
+
+        let overworld = compound(vec![
+            ("piglin_safe", Nbt::Byte(0)),
+            ("natural", Nbt::Byte(1)),
+            ("ambient_light", Nbt::Float(0.)),
+            ("monster_spawn_block_light_limit", Nbt::Int(0)),
+            ("infiniburn", string("#minecraft:infiniburn_overworld")),
+            ("respawn_anchor_works", Nbt::Byte(0)),
+            ("has_skylight", Nbt::Byte(1)),
+            ("bed_works", Nbt::Byte(1)),
+            ("effects", string("minecraft:overworld")),
+            ("has_raids", Nbt::Byte(1)),
+            ("logical_height", Nbt::Int(384)),
+            ("coordinate_scale", Nbt::Double(1.)),
+            (
+                "monster_spawn_light_level",
+                compound(vec![
+                    ("type", string("minecraft:uniform")),
+                    (
+                        "value",
+                        compound(vec![
+                            ("min_inclusive", Nbt::Int(0)),
+                            ("max_inclusive", Nbt::Int(7)),
+                        ]),
+                    ),
+                ]),
+            ),
+            ("min_y", Nbt::Int(-64)),
+            ("ultrawarm", Nbt::Byte(0)),
+            ("has_ceiling", Nbt::Byte(0)),
+            ("height", Nbt::Int(384)),
+        ]);
+        let plains = compound(vec![
+            ("precipitation", string("rain")),
+            ("temperature", Nbt::Float(0.8)),
+            ("downfall", Nbt::Float(0.4)),
+            (
+                "effects",
+                compound(vec![
+                    ("sky_color", Nbt::Int(7907327)),
+                    ("water_fog_color", Nbt::Int(329011)),
+                    ("fog_color", Nbt::Int(12638463)),
+                    ("water_color", Nbt::Int(4159204)),
+                ]),
+            ),
+        ]);
+        let chat = compound(vec![
+            (
+                "chat",
+                compound(vec![
+                    ("translation_key", string("chat.type.text")),
+                    (
+                        "parameters",
+                        Nbt::List(NbtList::String(vec!["sender".into(), "content".into()])),
+                    ),
+                ]),
+            ),
+            (
+                "narration",
+                compound(vec![
+                    ("translation_key", string("chat.type.text.narrate")),
+                    (
+                        "parameters",
+                        Nbt::List(NbtList::String(vec!["sender".into(), "content".into()])),
+                    ),
+                ]),
+            ),
+        ]);
+
+        compound(vec![(
+            "",
+            compound(vec![
+                (
+                    "minecraft:dimension_type",
+                    registry(
+                        "minecraft:dimension_type",
+                        vec![("minecraft:overworld", overworld)],
+                    ),
+                ),
+                (
+                    "minecraft:worldgen/biome",
+                    registry(
+                        "minecraft:worldgen/biome",
+                        vec![("minecraft:plains", plains)],
+                    ),
+                ),
+                (
+                    "minecraft:chat_type",
+                    registry("minecraft:chat_type", vec![("minecraft:chat", chat)]),
+                ),
+            ]),
+        )])
+    }
+
+    #[tokio::test]
+    async fn test_1_19_3_login() {
+        use azalea_buf::{McBufVarWritable, McBufWritable};
+        use azalea_core::ResourceLocation;
+
+        // a login packet like the one a 1.19.3 server sends, with its 1.19.3 id
+        let mut data = Vec::new();
+        0x24u32.var_write_into(&mut data).unwrap();
+        // player id, hardcore, game type, previous game type
+        1u32.write_into(&mut data).unwrap();
+        false.write_into(&mut data).unwrap();
+        0u8.write_into(&mut data).unwrap();
+        (-1i8).write_into(&mut data).unwrap();
+        vec![ResourceLocation::new("minecraft:overworld")]
+            .write_into(&mut data)
+            .unwrap();
+        registry_1_19_3().write_into(&mut data).unwrap();
+        // dimension type, dimension, seed
+        ResourceLocation::new("minecraft:overworld")
+            .write_into(&mut data)
+            .unwrap();
+        ResourceLocation::new("minecraft:overworld")
+            .write_into(&mut data)
+            .unwrap();
+        1234i64.write_into(&mut data).unwrap();
+        // max players, chunk radius, simulation distance
+        20i32.var_write_into(&mut data).unwrap();
+        10u32.var_write_into(&mut data).unwrap();
+        10u32.var_write_into(&mut data).unwrap();
+        // reduced debug info, show death screen, is debug, is flat, no last
+        // death location
+        data.extend([0, 1, 0, 0, 0]);
+
+        let version = ProtocolVersion::from_protocol_version(761).unwrap();
+        let (client_stream, server_stream) = tokio::io::duplex(8192);
+        let mut client: Connection<ClientboundGamePacket, ServerboundGamePacket, _, _> =
+            Connection::wrap_stream(client_stream);
+        let mut server: Connection<ServerboundGamePacket, ClientboundGamePacket, _, _> =
+            Connection::wrap_stream(server_stream);
+        client.set_version(version);
+        server.writer.write_raw(data).await.unwrap();
+
+        let ClientboundGamePacket::Login(login) = client.read().await.unwrap() else {
+            panic!("Expected a login packet");
+        };
+        assert_eq!(login.seed, 1234);
+        assert_eq!(login.chunk_radius, 10);
+        let root = login.registry_holder.root;
+        assert!(root.damage_type.is_none());
+        assert!(root.trim_material.is_none());
+        assert!(root.trim_pattern.is_none());
+        let overworld = &root.dimension_type.value[0];
+        assert_eq!(overworld.name, ResourceLocation::new("minecraft:overworld"));
+        assert_eq!(overworld.element.min_y, -64);
+        assert_eq!(overworld.element.height, 384);
+    }
+}
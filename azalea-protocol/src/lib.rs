@@ -15,12 +15,22 @@
 
 use std::{fmt::Display, net::SocketAddr, str::FromStr};
 
+#[cfg(feature = "packets")]
+pub mod capture;
 #[cfg(feature = "connecting")]
 pub mod connect;
+#[cfg(feature = "connecting")]
+pub mod mitm;
 #[cfg(feature = "packets")]
 pub mod packets;
+#[cfg(feature = "connecting")]
+pub mod proxy;
 pub mod read;
 pub mod resolver;
+#[cfg(feature = "connecting")]
+pub mod server;
+#[cfg(feature = "connecting")]
+pub mod stats;
 pub mod write;
 
 /// A host and port. It's possible that the port doesn't resolve to anything.
@@ -35,7 +45,7 @@ pub mod write;
 /// assert_eq!(addr.host, "localhost");
 /// assert_eq!(addr.port, 25565);
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ServerAddress {
     pub host: String,
     pub port: u16,
@@ -0,0 +1,493 @@
+//! A proxy that sits between a client and an offline-mode server and decodes
+//! the game packets going both ways, so they can be inspected or changed.
+//!
+//! Unlike `examples/handshake_proxy.rs`, which only forwards the bytes after
+//! the handshake, this logs the client in itself and opens its own connection
+//! to the backend, so it can read the packets even if the client's connection
+//! is encrypted.
+//!
+//! ```no_run
+//! # use azalea_protocol::{
+//! #     mitm::{MitmHandler, MitmProxy, PacketInjector},
+//! #     packets::game::ClientboundGamePacket,
+//! #     server::{Listener, ServerConfig},
+//! # };
+//! struct LogChat;
+//! impl MitmHandler for LogChat {
+//!     fn clientbound(
+//!         &self,
+//!         packet: ClientboundGamePacket,
+//!         _injector: &PacketInjector,
+//!     ) -> Option<ClientboundGamePacket> {
+//!         if let ClientboundGamePacket::SystemChat(p) = &packet {
+//!             println!("{}", p.content);
+//!         }
+//!         Some(packet)
+//!     }
+//! }
+//!
+//! # async fn example() -> std::io::Result<()> {
+//! let listener = Listener::bind("127.0.0.1:25566", ServerConfig::default()).await?;
+//! MitmProxy::new("127.0.0.1:25565".parse().unwrap(), LogChat)
+//!     .listen(listener)
+//!     .await;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    connect::{Connection, ConnectionError, ReadConnection, WriteConnection},
+    packets::{
+        game::{
+            clientbound_disconnect_packet::ClientboundDisconnectPacket, ClientboundGamePacket,
+            ServerboundGamePacket,
+        },
+        handshake::client_intention_packet::ClientIntentionPacket,
+        login::{
+            serverbound_custom_query_packet::ServerboundCustomQueryPacket,
+            serverbound_hello_packet::ServerboundHelloPacket, ClientboundLoginPacket,
+        },
+        ConnectionProtocol, ProtocolPacket, PROTOCOL_VERSION,
+    },
+    read::ReadPacketError,
+    server::{JoinedPlayer, Listener},
+};
+use azalea_auth::game_profile::GameProfile;
+use azalea_chat::FormattedText;
+use log::{debug, warn};
+use std::{fmt::Debug, net::SocketAddr, sync::Arc};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc,
+};
+
+/// Decides what happens to the packets going through a [`MitmProxy`].
+///
+/// Return the packet (changed or not) to forward it, or `None` to drop it.
+/// Packets can also be sent to either side with the [`PacketInjector`].
+///
+/// Packets that we can't decode are forwarded as-is without going through
+/// the handler.
+pub trait MitmHandler: Send + Sync + 'static {
+    /// A packet that the server sent to the client.
+    fn clientbound(
+        &self,
+        packet: ClientboundGamePacket,
+        _injector: &PacketInjector,
+    ) -> Option<ClientboundGamePacket> {
+        Some(packet)
+    }
+
+    /// A packet that the client sent to the server.
+    fn serverbound(
+        &self,
+        packet: ServerboundGamePacket,
+        _injector: &PacketInjector,
+    ) -> Option<ServerboundGamePacket> {
+        Some(packet)
+    }
+}
+
+/// A handler that forwards every packet without changing it.
+impl MitmHandler for () {}
+
+/// Sends extra packets to the client or the server in a [`MitmProxy`]
+/// session. It's cheap to clone, and packets sent after the session ends are
+/// ignored.
+#[derive(Clone)]
+pub struct PacketInjector {
+    pub game_profile: GameProfile,
+    to_client: mpsc::UnboundedSender<Outgoing<ClientboundGamePacket>>,
+    to_server: mpsc::UnboundedSender<Outgoing<ServerboundGamePacket>>,
+}
+
+impl PacketInjector {
+    /// Send a packet to the client as if the server sent it.
+    pub fn send_to_client(&self, packet: ClientboundGamePacket) {
+        let _ = self.to_client.send(Outgoing::Packet(packet));
+    }
+
+    /// Send a packet to the server as if the client sent it.
+    pub fn send_to_server(&self, packet: ServerboundGamePacket) {
+        let _ = self.to_server.send(Outgoing::Packet(packet));
+    }
+}
+
+/// A packet waiting to be written to one side of the proxy.
+enum Outgoing<P> {
+    Packet(P),
+    /// The id and data of a packet we couldn't decode, which is passed
+    /// through unchanged.
+    Raw(Vec<u8>),
+}
+
+#[derive(Error, Debug)]
+pub enum MitmError {
+    #[error("Couldn't connect to the backend: {0}")]
+    Connection(#[from] ConnectionError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Error reading packet from the backend: {0}")]
+    ReadPacket(#[from] Box<ReadPacketError>),
+    #[error("The backend is in online mode")]
+    BackendOnlineMode,
+    #[error("The backend disconnected us while logging in: {0}")]
+    BackendDisconnected(FormattedText),
+}
+
+/// A proxy that logs clients into an offline-mode backend and passes every
+/// game packet through a [`MitmHandler`].
+pub struct MitmProxy {
+    backend: SocketAddr,
+    handler: Arc<dyn MitmHandler>,
+}
+
+impl MitmProxy {
+    pub fn new(backend: SocketAddr, handler: impl MitmHandler) -> Self {
+        Self {
+            backend,
+            handler: Arc::new(handler),
+        }
+    }
+
+    /// Proxy every player that joins through the listener, until it stops.
+    pub async fn listen(self, mut listener: Listener) {
+        let proxy = Arc::new(self);
+        while let Some(player) = listener.next_player().await {
+            let proxy = proxy.clone();
+            tokio::spawn(async move {
+                let name = player.game_profile.name.clone();
+                if let Err(e) = proxy.handle_player(player).await {
+                    warn!("Couldn't proxy {name}: {e}");
+                }
+            });
+        }
+    }
+
+    /// Connect a player that already logged in to us to the backend, and
+    /// proxy their packets until either side disconnects.
+    pub async fn handle_player<RS, WS>(&self, player: JoinedPlayer<RS, WS>) -> Result<(), MitmError>
+    where
+        RS: AsyncRead + Unpin + Send + 'static,
+        WS: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (client_reader, mut client_writer) = player.connection.into_split();
+        let backend = match connect_to_backend(self.backend, &player.game_profile).await {
+            Ok(backend) => backend,
+            Err(e) => {
+                let reason = match &e {
+                    MitmError::BackendDisconnected(reason) => reason.clone(),
+                    e => e.to_string().into(),
+                };
+                client_writer
+                    .write(ClientboundDisconnectPacket { reason }.get())
+                    .await?;
+                return Err(e);
+            }
+        };
+        let (server_reader, server_writer) = backend.into_split();
+
+        let (to_client, to_client_rx) = mpsc::unbounded_channel();
+        let (to_server, to_server_rx) = mpsc::unbounded_channel();
+        let injector = PacketInjector {
+            game_profile: player.game_profile,
+            to_client,
+            to_server,
+        };
+
+        let handler = self.handler.clone();
+        let clientbound = {
+            let injector = injector.clone();
+            forward(server_reader, injector.to_client.clone(), move |packet| {
+                if let Some(packet) = handler.clientbound(packet, &injector) {
+                    injector.send_to_client(packet);
+                }
+            })
+        };
+        let handler = self.handler.clone();
+        let serverbound = {
+            forward(client_reader, injector.to_server.clone(), move |packet| {
+                if let Some(packet) = handler.serverbound(packet, &injector) {
+                    injector.send_to_server(packet);
+                }
+            })
+        };
+        let tasks = [
+            tokio::spawn(clientbound),
+            tokio::spawn(serverbound),
+            tokio::spawn(write_all(client_writer, to_client_rx)),
+            tokio::spawn(write_all(server_writer, to_server_rx)),
+        ];
+        // when either side disconnects, disconnect the other one too
+        let (_, _, remaining) = futures::future::select_all(tasks).await;
+        for task in remaining {
+            task.abort();
+        }
+        Ok(())
+    }
+}
+
+/// Log in to an offline-mode server with the given profile.
+async fn connect_to_backend(
+    address: SocketAddr,
+    game_profile: &GameProfile,
+) -> Result<Connection<ClientboundGamePacket, ServerboundGamePacket>, MitmError> {
+    let mut conn = Connection::new(&address).await?;
+    conn.write(
+        ClientIntentionPacket {
+            protocol_version: PROTOCOL_VERSION,
+            hostname: address.ip().to_string(),
+            port: address.port(),
+            intention: ConnectionProtocol::Login,
+        }
+        .get(),
+    )
+    .await?;
+    let mut conn = conn.login();
+    conn.write(
+        ServerboundHelloPacket {
+            name: game_profile.name.clone(),
+            profile_id: Some(game_profile.uuid),
+        }
+        .get(),
+    )
+    .await?;
+
+    loop {
+        match conn.read().await? {
+            ClientboundLoginPacket::Hello(_) => return Err(MitmError::BackendOnlineMode),
+            ClientboundLoginPacket::LoginCompression(p) => {
+                conn.set_compression_threshold(p.compression_threshold);
+            }
+            ClientboundLoginPacket::GameProfile(_) => return Ok(conn.game()),
+            ClientboundLoginPacket::LoginDisconnect(p) => {
+                return Err(MitmError::BackendDisconnected(p.reason))
+            }
+            ClientboundLoginPacket::CustomQuery(p) => {
+                conn.write(
+                    ServerboundCustomQueryPacket {
+                        transaction_id: p.transaction_id,
+                        data: None,
+                    }
+                    .get(),
+                )
+                .await?;
+            }
+        }
+    }
+}
+
+/// Read packets and pass them to the callback until the connection closes.
+/// Packets that can't be decoded are sent to the other side unchanged.
+async fn forward<P, S>(
+    mut reader: ReadConnection<P, S>,
+    raw: mpsc::UnboundedSender<Outgoing<P>>,
+    mut on_packet: impl FnMut(P),
+) where
+    P: ProtocolPacket + Debug,
+    S: AsyncRead + Unpin + Send,
+{
+    loop {
+        let data = match reader.read_raw().await {
+            Ok(data) => data,
+            Err(e) => match *e {
+                ReadPacketError::ConnectionClosed => return,
+                ReadPacketError::IoError { source } => {
+                    debug!("Proxied connection closed: {source}");
+                    return;
+                }
+                // the frame was already taken out of the buffer, so we can
+                // skip it and keep going
+                e => {
+                    warn!("Dropping a packet we couldn't read: {e}");
+                    continue;
+                }
+            },
+        };
+        match reader.decode(&data) {
+            Ok(packet) => on_packet(packet),
+            Err(e) => {
+                debug!("Forwarding a packet we couldn't decode: {e}");
+                let _ = raw.send(Outgoing::Raw(data));
+            }
+        }
+    }
+}
+
+/// Write packets from the channel until it closes or the connection fails.
+async fn write_all<P, S>(
+    mut writer: WriteConnection<P, S>,
+    mut packets: mpsc::UnboundedReceiver<Outgoing<P>>,
+) where
+    P: ProtocolPacket + Debug,
+    S: AsyncWrite + Unpin + Send,
+{
+    while let Some(packet) = packets.recv().await {
+        let result = match packet {
+            Outgoing::Packet(packet) => writer.write(packet).await,
+            Outgoing::Raw(data) => writer.write_raw(data).await,
+        };
+        if let Err(e) = result {
+            debug!("Couldn't write proxied packet: {e}");
+            return;
+        }
+    }
+    let _ = writer.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        packets::game::{
+            clientbound_keep_alive_packet::ClientboundKeepAlivePacket,
+            serverbound_keep_alive_packet::ServerboundKeepAlivePacket,
+        },
+        server::ServerConfig,
+    };
+
+    struct TestHandler;
+    impl MitmHandler for TestHandler {
+        fn clientbound(
+            &self,
+            packet: ClientboundGamePacket,
+            _injector: &PacketInjector,
+        ) -> Option<ClientboundGamePacket> {
+            match packet {
+                ClientboundGamePacket::KeepAlive(p) => {
+                    Some(ClientboundKeepAlivePacket { id: p.id * 2 }.get())
+                }
+                packet => Some(packet),
+            }
+        }
+
+        fn serverbound(
+            &self,
+            packet: ServerboundGamePacket,
+            injector: &PacketInjector,
+        ) -> Option<ServerboundGamePacket> {
+            match packet {
+                ServerboundGamePacket::KeepAlive(p) if p.id == 7 => {
+                    injector.send_to_client(ClientboundKeepAlivePacket { id: 7 }.get());
+                    None
+                }
+                packet => Some(packet),
+            }
+        }
+    }
+
+    async fn join(address: SocketAddr) -> Connection<ClientboundGamePacket, ServerboundGamePacket> {
+        let mut conn = Connection::new(&address).await.unwrap();
+        conn.write(
+            ClientIntentionPacket {
+                protocol_version: PROTOCOL_VERSION,
+                hostname: address.ip().to_string(),
+                port: address.port(),
+                intention: ConnectionProtocol::Login,
+            }
+            .get(),
+        )
+        .await
+        .unwrap();
+        let mut conn = conn.login();
+        conn.write(
+            ServerboundHelloPacket {
+                name: "bot".to_string(),
+                profile_id: None,
+            }
+            .get(),
+        )
+        .await
+        .unwrap();
+        loop {
+            match conn.read().await.unwrap() {
+                ClientboundLoginPacket::LoginCompression(p) => {
+                    conn.set_compression_threshold(p.compression_threshold);
+                }
+                ClientboundLoginPacket::GameProfile(_) => return conn.game(),
+                packet => panic!("Unexpected packet {packet:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mitm_proxy() {
+        let mut backend = Listener::bind("127.0.0.1:0", ServerConfig::default())
+            .await
+            .unwrap();
+        let proxy_listener = Listener::bind("127.0.0.1:0", ServerConfig::default())
+            .await
+            .unwrap();
+        let proxy_address = proxy_listener.local_addr();
+        tokio::spawn(MitmProxy::new(backend.local_addr(), TestHandler).listen(proxy_listener));
+
+        let mut client = join(proxy_address).await;
+        let mut player = backend.next_player().await.unwrap();
+        assert_eq!(player.game_profile.name, "bot");
+
+        player
+            .connection
+            .write(ClientboundKeepAlivePacket { id: 21 }.get())
+            .await
+            .unwrap();
+        match client.read().await.unwrap() {
+            ClientboundGamePacket::KeepAlive(p) => assert_eq!(p.id, 42),
+            packet => panic!("Unexpected packet {packet:?}"),
+        }
+
+        // this one is dropped and answered by the handler
+        client
+            .write(ServerboundKeepAlivePacket { id: 7 }.get())
+            .await
+            .unwrap();
+        match client.read().await.unwrap() {
+            ClientboundGamePacket::KeepAlive(p) => assert_eq!(p.id, 7),
+            packet => panic!("Unexpected packet {packet:?}"),
+        }
+        client
+            .write(ServerboundKeepAlivePacket { id: 8 }.get())
+            .await
+            .unwrap();
+        match player.connection.read().await.unwrap() {
+            ServerboundGamePacket::KeepAlive(p) => assert_eq!(p.id, 8),
+            packet => panic!("Unexpected packet {packet:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mitm_proxy_forwards_unknown_packets() {
+        let mut backend = Listener::bind("127.0.0.1:0", ServerConfig::default())
+            .await
+            .unwrap();
+        let proxy_listener = Listener::bind("127.0.0.1:0", ServerConfig::default())
+            .await
+            .unwrap();
+        let proxy_address = proxy_listener.local_addr();
+        tokio::spawn(MitmProxy::new(backend.local_addr(), TestHandler).listen(proxy_listener));
+
+        let mut client = join(proxy_address).await;
+        let mut player = backend.next_player().await.unwrap();
+
+        // a packet id that doesn't exist
+        let unknown = vec![0x7f, 1, 2, 3];
+        player
+            .connection
+            .writer
+            .write_raw(unknown.clone())
+            .await
+            .unwrap();
+        player
+            .connection
+            .write(ClientboundKeepAlivePacket { id: 21 }.get())
+            .await
+            .unwrap();
+
+        assert_eq!(client.reader.read_raw().await.unwrap(), unknown);
+        // packets after it still go through the handler
+        match client.read().await.unwrap() {
+            ClientboundGamePacket::KeepAlive(p) => assert_eq!(p.id, 42),
+            packet => panic!("Unexpected packet {packet:?}"),
+        }
+    }
+}
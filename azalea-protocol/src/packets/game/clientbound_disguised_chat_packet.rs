@@ -3,8 +3,16 @@ use azalea_buf::McBuf;
 use azalea_chat::FormattedText;
 use azalea_protocol_macros::ClientboundGamePacket;
 
-#[derive(Clone, Debug, McBuf, ClientboundGamePacket)]
+#[derive(Clone, Debug, McBuf, ClientboundGamePacket, PartialEq)]
 pub struct ClientboundDisguisedChatPacket {
     pub message: FormattedText,
     pub chat_type: ChatTypeBound,
 }
+
+impl ClientboundDisguisedChatPacket {
+    /// Get the full message, including the sender part.
+    #[must_use]
+    pub fn full_message(&self) -> FormattedText {
+        self.chat_type.decorate(self.message.clone())
+    }
+}
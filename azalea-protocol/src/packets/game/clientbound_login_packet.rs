@@ -95,12 +95,18 @@ pub mod registry {
     #[derive(Debug, Clone, Serialize, Deserialize)]
     #[cfg_attr(feature = "strict_registry", serde(deny_unknown_fields))]
     pub struct RegistryRoot {
+        /// This is `None` on servers before 1.19.4.
         #[cfg(feature = "strict_registry")]
         #[serde(rename = "minecraft:trim_material")]
-        pub trim_material: RegistryType<TrimMaterialElement>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub trim_material: Option<RegistryType<TrimMaterialElement>>,
+        /// This is `None` on servers before 1.19.4.
         #[cfg(not(feature = "strict_registry"))]
         #[serde(rename = "minecraft:trim_material")]
-        pub trim_material: Nbt,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub trim_material: Option<Nbt>,
 
         #[cfg(feature = "strict_registry")]
         #[serde(rename = "minecraft:chat_type")]
@@ -119,19 +125,31 @@ pub mod registry {
         #[serde(rename = "minecraft:worldgen/biome")]
         pub world_type: Nbt,
 
+        /// This is `None` on servers before 1.19.4.
         #[cfg(feature = "strict_registry")]
         #[serde(rename = "minecraft:trim_pattern")]
-        pub trim_pattern: RegistryType<TrimPatternElement>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub trim_pattern: Option<RegistryType<TrimPatternElement>>,
+        /// This is `None` on servers before 1.19.4.
         #[cfg(not(feature = "strict_registry"))]
         #[serde(rename = "minecraft:trim_pattern")]
-        pub trim_pattern: Nbt,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub trim_pattern: Option<Nbt>,
 
+        /// This is `None` on servers before 1.19.4.
         #[cfg(feature = "strict_registry")]
         #[serde(rename = "minecraft:damage_type")]
-        pub damage_type: RegistryType<DamageTypeElement>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub damage_type: Option<RegistryType<DamageTypeElement>>,
+        /// This is `None` on servers before 1.19.4.
         #[cfg(not(feature = "strict_registry"))]
         #[serde(rename = "minecraft:damage_type")]
-        pub damage_type: Nbt,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub damage_type: Option<Nbt>,
     }
 
     /// A collection of values for a certain type of registry data.
@@ -491,15 +509,15 @@ mod tests {
         // This is just for testing.
         let registry = RegistryHolder {
             root: RegistryRoot {
-                trim_material: Nbt::End,
+                trim_material: Some(Nbt::End),
                 chat_type: Nbt::End,
                 dimension_type: RegistryType::<DimensionTypeElement> {
                     kind: ResourceLocation::new("minecraft:dimension_type"),
                     value: Vec::new(),
                 },
                 world_type: Nbt::End,
-                trim_pattern: Nbt::End,
-                damage_type: Nbt::End,
+                trim_pattern: Some(Nbt::End),
+                damage_type: Some(Nbt::End),
             },
         };
 
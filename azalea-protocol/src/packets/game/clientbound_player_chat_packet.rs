@@ -70,22 +70,6 @@ pub struct ChatTypeBound {
     pub target_name: Option<FormattedText>,
 }
 
-// must be in Client
-#[derive(Clone, Debug, PartialEq)]
-pub struct MessageSignatureCache {
-    pub entries: Vec<Option<MessageSignature>>,
-}
-
-// impl MessageSignatureCache {
-//     pub fn unpacker(&self) -> impl Fn(u32) -> Option<SignedMessageBody> {
-
-//     }
-// }
-
-// impl PackedSignedMessageBody {
-//     pub fn unpack(&self, unpacker: impl Fn(u32) -> Option<SignedMessageBody>)
-// {} }
-
 impl ClientboundPlayerChatPacket {
     /// Returns the content of the message. If you want to get the FormattedText
     /// for the whole message including the sender part, use
@@ -100,21 +84,25 @@ impl ClientboundPlayerChatPacket {
     /// Get the full message, including the sender part.
     #[must_use]
     pub fn message(&self) -> FormattedText {
-        let sender = self.chat_type.name.clone();
-        let content = self.content();
-        let target = self.chat_type.target_name.clone();
-
-        let translation_key = self.chat_type.chat_type.chat_translation_key();
+        self.chat_type.decorate(self.content())
+    }
+}
 
+impl ChatTypeBound {
+    /// Add the sender (and target) to the content of a message, the way it's
+    /// shown in chat.
+    #[must_use]
+    pub fn decorate(&self, content: FormattedText) -> FormattedText {
         let mut args = vec![
-            StringOrComponent::FormattedText(sender),
+            StringOrComponent::FormattedText(self.name.clone()),
             StringOrComponent::FormattedText(content),
         ];
-        if let Some(target) = target {
+        if let Some(target) = self.target_name.clone() {
             args.push(StringOrComponent::FormattedText(target));
         }
 
-        let component = TranslatableComponent::new(translation_key.to_string(), args);
+        let component =
+            TranslatableComponent::new(self.chat_type.chat_translation_key().to_string(), args);
 
         FormattedText::Translatable(component)
     }
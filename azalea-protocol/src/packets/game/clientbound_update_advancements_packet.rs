@@ -16,10 +16,12 @@ pub struct ClientboundUpdateAdvancementsPacket {
 
 #[derive(Clone, Debug, McBuf)]
 pub struct Advancement {
-    parent_id: Option<ResourceLocation>,
-    display: Option<DisplayInfo>,
-    criteria: HashMap<ResourceLocation, Criterion>,
-    requirements: Vec<Vec<String>>,
+    pub parent_id: Option<ResourceLocation>,
+    pub display: Option<DisplayInfo>,
+    pub criteria: HashMap<ResourceLocation, Criterion>,
+    /// The criteria that have to be completed for the advancement to be done.
+    /// Every inner list must have at least one completed criterion.
+    pub requirements: Vec<Vec<String>>,
 }
 
 #[derive(Clone, Debug)]
@@ -110,7 +112,9 @@ pub type AdvancementProgress = HashMap<ResourceLocation, CriterionProgress>;
 
 #[derive(Clone, Debug, McBuf)]
 pub struct CriterionProgress {
-    date: Option<u64>,
+    /// When the criterion was completed, in milliseconds since the Unix epoch.
+    /// `None` if it hasn't been completed.
+    pub date: Option<u64>,
 }
 
 #[cfg(test)]
@@ -2,9 +2,10 @@ pub mod game;
 pub mod handshake;
 pub mod login;
 pub mod status;
+pub mod version;
 
 use crate::read::ReadPacketError;
-use azalea_buf::{BufReadError, McBufVarReadable, McBufVarWritable, McBufWritable};
+use azalea_buf::{BufReadError, McBuf, McBufVarReadable, McBufVarWritable, McBufWritable};
 use std::io::{Cursor, Write};
 
 // TODO: rename the packet files to just like clientbound_add_entity instead of
@@ -33,17 +34,33 @@ impl ConnectionProtocol {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, McBuf)]
+pub enum PacketDirection {
+    /// Sent by the server to the client.
+    Clientbound,
+    /// Sent by the client to the server.
+    Serverbound,
+}
+
 /// An enum of packets for a certain protocol
 pub trait ProtocolPacket
 where
     Self: Sized,
 {
+    /// The state that these packets are sent in.
+    const PROTOCOL: ConnectionProtocol;
+    /// Who sends these packets.
+    const DIRECTION: PacketDirection;
+
     fn id(&self) -> u32;
 
     /// Read a packet by its id, `ConnectionProtocol`, and flow
     fn read(id: u32, buf: &mut Cursor<&[u8]>) -> Result<Self, Box<ReadPacketError>>;
 
     fn write(&self, buf: &mut impl Write) -> Result<(), std::io::Error>;
+
+    /// The name of the packet's struct, like `ClientboundKeepAlivePacket`.
+    fn name(&self) -> &'static str;
 }
 
 impl azalea_buf::McBufReadable for ConnectionProtocol {
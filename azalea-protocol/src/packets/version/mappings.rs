@@ -0,0 +1,152 @@
+// This file was generated by codegen/genversions.py, don't edit it manually!
+
+use super::{PacketIdTable, VersionPacketIds};
+
+pub(super) static VERSIONS: &[VersionPacketIds] = &[
+    VersionPacketIds {
+        name: "1.19.4",
+        protocol_version: 762,
+        clientbound_game: None,
+        serverbound_game: None,
+        clientbound_login: None,
+        serverbound_login: None,
+        clientbound_status: None,
+        serverbound_status: None,
+    },
+    // TODO: these packets have a different layout than in 1.19.4:
+    // - ClientboundPlayerPositionPacket
+    VersionPacketIds {
+        name: "1.19.3",
+        protocol_version: 761,
+        clientbound_game: Some(PacketIdTable {
+            to_current: &[
+                0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0e, 0x0f,
+                0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e,
+                0x1f, 0x20, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2d,
+                0x2e, 0x2f, 0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x3b,
+                0x3c, 0x3d, 0x3e, 0x3f, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+                0x4a, 0x4b, 0x4c, 0x4d, 0x4e, 0x4f, 0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57,
+                0x58, 0x59, 0x5a, 0x5b, 0x5c, 0x5d, 0x5e, 0x5f, 0x60, 0x61, 0x62, 0x63, 0x64, 0x65,
+                0x66, 0x67, 0x68, 0x69, 0x6a, 0x6b, 0x6c, 0x6d, 0x6e,
+            ],
+            from_current: &[
+                None,
+                Some(0x00),
+                Some(0x01),
+                Some(0x02),
+                Some(0x03),
+                Some(0x04),
+                Some(0x05),
+                Some(0x06),
+                Some(0x07),
+                Some(0x08),
+                Some(0x09),
+                Some(0x0a),
+                Some(0x0b),
+                None,
+                Some(0x0c),
+                Some(0x0d),
+                Some(0x0e),
+                Some(0x0f),
+                Some(0x10),
+                Some(0x11),
+                Some(0x12),
+                Some(0x13),
+                Some(0x14),
+                Some(0x15),
+                None,
+                Some(0x16),
+                Some(0x17),
+                Some(0x18),
+                Some(0x19),
+                Some(0x1a),
+                Some(0x1b),
+                Some(0x1c),
+                Some(0x1d),
+                None,
+                Some(0x1e),
+                Some(0x1f),
+                Some(0x20),
+                Some(0x21),
+                Some(0x22),
+                Some(0x23),
+                Some(0x24),
+                Some(0x25),
+                Some(0x26),
+                Some(0x27),
+                Some(0x28),
+                Some(0x29),
+                Some(0x2a),
+                Some(0x2b),
+                Some(0x2c),
+                Some(0x2d),
+                Some(0x2e),
+                Some(0x2f),
+                Some(0x30),
+                Some(0x31),
+                Some(0x32),
+                Some(0x33),
+                Some(0x34),
+                Some(0x35),
+                Some(0x36),
+                Some(0x37),
+                Some(0x38),
+                Some(0x39),
+                Some(0x3a),
+                Some(0x3b),
+                Some(0x3c),
+                Some(0x3d),
+                Some(0x3e),
+                Some(0x3f),
+                Some(0x40),
+                Some(0x41),
+                Some(0x42),
+                Some(0x43),
+                Some(0x44),
+                Some(0x45),
+                Some(0x46),
+                Some(0x47),
+                Some(0x48),
+                Some(0x49),
+                Some(0x4a),
+                Some(0x4b),
+                Some(0x4c),
+                Some(0x4d),
+                Some(0x4e),
+                Some(0x4f),
+                Some(0x50),
+                Some(0x51),
+                Some(0x52),
+                Some(0x53),
+                Some(0x54),
+                Some(0x55),
+                Some(0x56),
+                Some(0x57),
+                Some(0x58),
+                Some(0x59),
+                Some(0x5a),
+                Some(0x5b),
+                Some(0x5c),
+                Some(0x5d),
+                Some(0x5e),
+                Some(0x5f),
+                Some(0x60),
+                Some(0x61),
+                Some(0x62),
+                Some(0x63),
+                Some(0x64),
+                Some(0x65),
+                Some(0x66),
+                Some(0x67),
+                Some(0x68),
+                Some(0x69),
+                Some(0x6a),
+            ],
+        }),
+        serverbound_game: None,
+        clientbound_login: None,
+        serverbound_login: None,
+        clientbound_status: None,
+        serverbound_status: None,
+    },
+];
@@ -0,0 +1,265 @@
+//! Support for joining servers on other Minecraft versions than
+//! [`PROTOCOL_VERSION`].
+//!
+//! Azalea's packets are always the ones from [`PROTOCOL_VERSION`]. When we
+//! talk to a server on another version, packet ids are translated with tables
+//! generated by `codegen/genversions.py`, and packets whose layout changed are
+//! rewritten before they're decoded (or after they're encoded).
+//!
+//! Only packets are translated. Registry ids (like block states, items and
+//! entity kinds) are still the ones from [`PROTOCOL_VERSION`], so blocks and
+//! entities that were added or moved between versions may be wrong. The
+//! `strict_registry` feature also only accepts the registry layout from
+//! [`PROTOCOL_VERSION`].
+//!
+//! ```
+//! # use azalea_protocol::packets::version::ProtocolVersion;
+//! let version = ProtocolVersion::from_protocol_version(761).unwrap();
+//! assert_eq!(version.name(), "1.19.3");
+//! ```
+
+mod mappings;
+
+use std::fmt;
+
+use super::{ConnectionProtocol, PacketDirection, PROTOCOL_VERSION};
+
+/// How the ids of the packets in one state and direction map to the ids in
+/// [`PROTOCOL_VERSION`].
+#[derive(Debug)]
+pub(crate) struct PacketIdTable {
+    /// The current id of each packet, indexed by its id in this version.
+    to_current: &'static [u32],
+    /// The id in this version of each packet, indexed by its current id.
+    /// This is `None` if the packet doesn't exist in this version.
+    from_current: &'static [Option<u32>],
+}
+
+/// The packet ids of a version. A table is `None` if the ids are the same as
+/// in [`PROTOCOL_VERSION`].
+#[derive(Debug)]
+pub(crate) struct VersionPacketIds {
+    name: &'static str,
+    protocol_version: u32,
+    clientbound_game: Option<PacketIdTable>,
+    serverbound_game: Option<PacketIdTable>,
+    clientbound_login: Option<PacketIdTable>,
+    serverbound_login: Option<PacketIdTable>,
+    clientbound_status: Option<PacketIdTable>,
+    serverbound_status: Option<PacketIdTable>,
+}
+
+impl VersionPacketIds {
+    fn table(
+        &self,
+        protocol: ConnectionProtocol,
+        direction: PacketDirection,
+    ) -> Option<&PacketIdTable> {
+        match (protocol, direction) {
+            (ConnectionProtocol::Game, PacketDirection::Clientbound) => &self.clientbound_game,
+            (ConnectionProtocol::Game, PacketDirection::Serverbound) => &self.serverbound_game,
+            (ConnectionProtocol::Login, PacketDirection::Clientbound) => &self.clientbound_login,
+            (ConnectionProtocol::Login, PacketDirection::Serverbound) => &self.serverbound_login,
+            (ConnectionProtocol::Status, PacketDirection::Clientbound) => &self.clientbound_status,
+            (ConnectionProtocol::Status, PacketDirection::Serverbound) => &self.serverbound_status,
+            // the handshake can't change, since it's how the server finds out
+            // our version
+            (ConnectionProtocol::Handshake, _) => &None,
+        }
+        .as_ref()
+    }
+}
+
+/// A Minecraft version that we can talk to.
+#[derive(Clone, Copy)]
+pub struct ProtocolVersion(&'static VersionPacketIds);
+
+impl ProtocolVersion {
+    /// The version that azalea's packets are from, [`PROTOCOL_VERSION`].
+    pub fn current() -> Self {
+        Self::from_protocol_version(PROTOCOL_VERSION)
+            .expect("The current version should always be supported")
+    }
+
+    /// Get the version with the protocol version number, or `None` if it's
+    /// not supported.
+    pub fn from_protocol_version(protocol_version: u32) -> Option<Self> {
+        mappings::VERSIONS
+            .iter()
+            .find(|version| version.protocol_version == protocol_version)
+            .map(Self)
+    }
+
+    /// Every version we support, from newest to oldest.
+    pub fn supported() -> impl Iterator<Item = Self> {
+        mappings::VERSIONS.iter().map(Self)
+    }
+
+    /// The name of the version, like `1.19.4`.
+    pub fn name(&self) -> &'static str {
+        self.0.name
+    }
+
+    /// The number that's sent in the handshake, like `762`.
+    pub fn protocol_version(&self) -> u32 {
+        self.0.protocol_version
+    }
+
+    pub fn is_current(&self) -> bool {
+        self.0.protocol_version == PROTOCOL_VERSION
+    }
+
+    /// Translate the id of a packet in this version to its id in
+    /// [`PROTOCOL_VERSION`], or `None` if we don't have that packet.
+    pub fn to_current_id(
+        &self,
+        protocol: ConnectionProtocol,
+        direction: PacketDirection,
+        id: u32,
+    ) -> Option<u32> {
+        match self.0.table(protocol, direction) {
+            Some(table) => table.to_current.get(id as usize).copied(),
+            None => Some(id),
+        }
+    }
+
+    /// Translate the id of a packet in [`PROTOCOL_VERSION`] to its id in this
+    /// version, or `None` if the packet doesn't exist in this version.
+    pub fn from_current_id(
+        &self,
+        protocol: ConnectionProtocol,
+        direction: PacketDirection,
+        id: u32,
+    ) -> Option<u32> {
+        match self.0.table(protocol, direction) {
+            Some(table) => table.from_current.get(id as usize).copied().flatten(),
+            None => Some(id),
+        }
+    }
+
+    /// Rewrite the data of a packet from this version so it can be decoded
+    /// as the packet from [`PROTOCOL_VERSION`]. `id` is the current id of the
+    /// packet.
+    pub fn upgrade_packet(
+        &self,
+        protocol: ConnectionProtocol,
+        direction: PacketDirection,
+        id: u32,
+        data: &mut Vec<u8>,
+    ) {
+        // packets that are in the TODO list for a version in mappings.rs
+        // should be handled here
+        if let (761, ConnectionProtocol::Game, PacketDirection::Clientbound, 0x3c) =
+            (self.0.protocol_version, protocol, direction, id)
+        {
+            // ClientboundPlayerPositionPacket, 1.19.4 removed the
+            // `dismount_vehicle` boolean at the end
+            data.pop();
+        }
+    }
+
+    /// Rewrite the data of a packet from [`PROTOCOL_VERSION`] so it can be
+    /// sent to a server on this version. `id` is the current id of the
+    /// packet.
+    pub fn downgrade_packet(
+        &self,
+        protocol: ConnectionProtocol,
+        direction: PacketDirection,
+        id: u32,
+        data: &mut Vec<u8>,
+    ) {
+        if let (761, ConnectionProtocol::Game, PacketDirection::Clientbound, 0x3c) =
+            (self.0.protocol_version, protocol, direction, id)
+        {
+            data.push(0);
+        }
+    }
+}
+
+impl Default for ProtocolVersion {
+    fn default() -> Self {
+        Self::current()
+    }
+}
+
+impl PartialEq for ProtocolVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.protocol_version == other.0.protocol_version
+    }
+}
+impl Eq for ProtocolVersion {}
+
+impl fmt::Debug for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.0.name, self.0.protocol_version)
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_version() {
+        let version = ProtocolVersion::current();
+        assert!(version.is_current());
+        assert_eq!(
+            version.to_current_id(ConnectionProtocol::Game, PacketDirection::Clientbound, 0x3c),
+            Some(0x3c)
+        );
+        assert_eq!(ProtocolVersion::default(), version);
+    }
+
+    #[test]
+    fn test_tables_are_inverses() {
+        for version in ProtocolVersion::supported() {
+            for protocol in [
+                ConnectionProtocol::Game,
+                ConnectionProtocol::Login,
+                ConnectionProtocol::Status,
+            ] {
+                for direction in [PacketDirection::Clientbound, PacketDirection::Serverbound] {
+                    let Some(table) = version.0.table(protocol, direction) else {
+                        continue;
+                    };
+                    for (id, &current_id) in table.to_current.iter().enumerate() {
+                        if current_id as usize >= table.from_current.len() {
+                            // the packet was removed in the current version
+                            continue;
+                        }
+                        assert_eq!(
+                            version.from_current_id(protocol, direction, current_id),
+                            Some(id as u32),
+                            "{version:?} {protocol:?} {direction:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_1_19_3_ids() {
+        let version = ProtocolVersion::from_protocol_version(761).unwrap();
+        // the bundle delimiter was added in 1.19.4
+        assert_eq!(
+            version.from_current_id(ConnectionProtocol::Game, PacketDirection::Clientbound, 0x00),
+            None
+        );
+        // ClientboundPlayerPositionPacket
+        assert_eq!(
+            version.to_current_id(ConnectionProtocol::Game, PacketDirection::Clientbound, 0x38),
+            Some(0x3c)
+        );
+        assert_eq!(
+            version.to_current_id(ConnectionProtocol::Game, PacketDirection::Serverbound, 0x12),
+            Some(0x12)
+        );
+    }
+}
@@ -0,0 +1,408 @@
+//! Connect to servers through SOCKS and HTTP proxies.
+
+use base64::Engine;
+use std::net::{IpAddr, SocketAddr};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// The longest response headers we'll accept from an HTTP proxy.
+const MAX_HTTP_RESPONSE_LENGTH: usize = 8192;
+
+/// A proxy server that we connect to the Minecraft server through.
+///
+/// ```
+/// # use azalea_protocol::proxy::Proxy;
+/// let proxy = Proxy::socks5("127.0.0.1:1080".parse().unwrap())
+///     .with_auth("username", "password")
+///     .with_remote_dns(true);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proxy {
+    pub kind: ProxyKind,
+    /// The address of the proxy server.
+    pub address: SocketAddr,
+    /// The credentials for the proxy. SOCKS4a only uses the username, which
+    /// is sent as the user ID.
+    pub auth: Option<ProxyAuth>,
+    /// Whether the proxy should look up the server's hostname instead of us.
+    /// SRV records are still looked up by us, and the proxy is given their
+    /// targets.
+    pub remote_dns: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProxyKind {
+    Socks5,
+    Socks4a,
+    /// A proxy that supports the HTTP `CONNECT` method.
+    Http,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProxyAuth {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Error, Debug)]
+pub enum ProxyError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("The proxy doesn't support any of our authentication methods")]
+    NoAcceptableAuth,
+    #[error("The proxy rejected our credentials")]
+    AuthFailed,
+    #[error("The proxy couldn't connect to the server: {0}")]
+    Rejected(String),
+    #[error("The proxy sent an invalid response")]
+    InvalidResponse,
+    #[error("{0} proxies don't support IPv6 addresses")]
+    Ipv6Unsupported(&'static str),
+}
+
+impl Proxy {
+    pub fn new(kind: ProxyKind, address: SocketAddr) -> Self {
+        Self {
+            kind,
+            address,
+            auth: None,
+            remote_dns: false,
+        }
+    }
+
+    pub fn socks5(address: SocketAddr) -> Self {
+        Self::new(ProxyKind::Socks5, address)
+    }
+
+    pub fn socks4a(address: SocketAddr) -> Self {
+        Self::new(ProxyKind::Socks4a, address)
+    }
+
+    pub fn http(address: SocketAddr) -> Self {
+        Self::new(ProxyKind::Http, address)
+    }
+
+    #[must_use]
+    pub fn with_auth(mut self, username: &str, password: &str) -> Self {
+        self.auth = Some(ProxyAuth {
+            username: username.to_string(),
+            password: password.to_string(),
+        });
+        self
+    }
+
+    #[must_use]
+    pub fn with_remote_dns(mut self, remote_dns: bool) -> Self {
+        self.remote_dns = remote_dns;
+        self
+    }
+
+    /// Connect to the proxy and ask it to connect to the given host, which can
+    /// be a hostname or an IP address. The returned stream is connected to the
+    /// server once this returns.
+    pub async fn connect(&self, host: &str, port: u16) -> Result<TcpStream, ProxyError> {
+        let mut stream = TcpStream::connect(self.address).await?;
+        match self.kind {
+            ProxyKind::Socks5 => self.socks5_handshake(&mut stream, host, port).await?,
+            ProxyKind::Socks4a => self.socks4a_handshake(&mut stream, host, port).await?,
+            ProxyKind::Http => self.http_handshake(&mut stream, host, port).await?,
+        }
+        Ok(stream)
+    }
+
+    async fn socks5_handshake(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), ProxyError> {
+        // the methods we support are no authentication (0) and username/password
+        // (2)
+        let greeting: &[u8] = if self.auth.is_some() {
+            &[5, 2, 0, 2]
+        } else {
+            &[5, 1, 0]
+        };
+        stream.write_all(greeting).await?;
+
+        let mut method = [0; 2];
+        stream.read_exact(&mut method).await?;
+        match method {
+            [5, 0] => {}
+            [5, 2] => {
+                let auth = self.auth.as_ref().ok_or(ProxyError::NoAcceptableAuth)?;
+                let mut request = vec![1];
+                write_short_string(&mut request, &auth.username)?;
+                write_short_string(&mut request, &auth.password)?;
+                stream.write_all(&request).await?;
+
+                let mut status = [0; 2];
+                stream.read_exact(&mut status).await?;
+                if status[1] != 0 {
+                    return Err(ProxyError::AuthFailed);
+                }
+            }
+            [5, 0xff] => return Err(ProxyError::NoAcceptableAuth),
+            _ => return Err(ProxyError::InvalidResponse),
+        }
+
+        let mut request = vec![5, 1, 0];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(1);
+                request.extend(ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(4);
+                request.extend(ip.octets());
+            }
+            Err(_) => {
+                request.push(3);
+                write_short_string(&mut request, host)?;
+            }
+        }
+        request.extend(port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != 5 {
+            return Err(ProxyError::InvalidResponse);
+        }
+        if reply[1] != 0 {
+            return Err(ProxyError::Rejected(socks5_reply_message(reply[1])));
+        }
+        // skip the address the proxy bound to
+        let address_length = match reply[3] {
+            1 => 4,
+            4 => 16,
+            3 => stream.read_u8().await? as usize,
+            _ => return Err(ProxyError::InvalidResponse),
+        };
+        let mut bound_address = vec![0; address_length + 2];
+        stream.read_exact(&mut bound_address).await?;
+
+        Ok(())
+    }
+
+    async fn socks4a_handshake(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), ProxyError> {
+        let user_id = self
+            .auth
+            .as_ref()
+            .map(|auth| auth.username.as_str())
+            .unwrap_or_default();
+
+        let mut request = vec![4, 1];
+        request.extend(port.to_be_bytes());
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.extend(ip.octets());
+                request.extend(user_id.as_bytes());
+                request.push(0);
+            }
+            Ok(IpAddr::V6(_)) => return Err(ProxyError::Ipv6Unsupported("SOCKS4a")),
+            Err(_) => {
+                // an invalid IP of 0.0.0.x tells the proxy to look up the
+                // hostname after the user ID
+                request.extend([0, 0, 0, 1]);
+                request.extend(user_id.as_bytes());
+                request.push(0);
+                request.extend(host.as_bytes());
+                request.push(0);
+            }
+        }
+        stream.write_all(&request).await?;
+
+        let mut reply = [0; 8];
+        stream.read_exact(&mut reply).await?;
+        match reply[1] {
+            0x5a => Ok(()),
+            0x5b => Err(ProxyError::Rejected(
+                "request rejected or failed".to_string(),
+            )),
+            0x5c | 0x5d => Err(ProxyError::AuthFailed),
+            _ => Err(ProxyError::InvalidResponse),
+        }
+    }
+
+    async fn http_handshake(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), ProxyError> {
+        let target = match host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("[{ip}]:{port}"),
+            _ => format!("{host}:{port}"),
+        };
+        let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+        if let Some(auth) = &self.auth {
+            let credentials = base64::engine::general_purpose::STANDARD
+                .encode(format!("{}:{}", auth.username, auth.password));
+            request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // read one byte at a time so we don't read past the end of the headers
+        let mut response = Vec::new();
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_HTTP_RESPONSE_LENGTH {
+                return Err(ProxyError::InvalidResponse);
+            }
+            response.push(stream.read_u8().await?);
+        }
+
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or_default();
+        let mut parts = status_line.splitn(3, ' ');
+        if !parts.next().unwrap_or_default().starts_with("HTTP/") {
+            return Err(ProxyError::InvalidResponse);
+        }
+        match parts.next() {
+            Some("200") => Ok(()),
+            Some("407") => Err(ProxyError::AuthFailed),
+            Some(_) => Err(ProxyError::Rejected(status_line.to_string())),
+            None => Err(ProxyError::InvalidResponse),
+        }
+    }
+}
+
+/// Write a string with a one-byte length, which is how SOCKS5 sends hostnames
+/// and credentials.
+fn write_short_string(buf: &mut Vec<u8>, string: &str) -> Result<(), ProxyError> {
+    let length = u8::try_from(string.len()).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "SOCKS5 strings can't be longer than 255 bytes",
+        )
+    })?;
+    buf.push(length);
+    buf.extend(string.as_bytes());
+    Ok(())
+}
+
+fn socks5_reply_message(code: u8) -> String {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Start a fake proxy that expects the given request bytes and sends the
+    /// response, then echoes everything else.
+    async fn fake_proxy(exchanges: Vec<(Vec<u8>, Vec<u8>)>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            for (expected, response) in exchanges {
+                let mut request = vec![0; expected.len()];
+                stream.read_exact(&mut request).await.unwrap();
+                assert_eq!(request, expected);
+                stream.write_all(&response).await.unwrap();
+            }
+            let mut buf = [0; 64];
+            let n = stream.read(&mut buf).await.unwrap();
+            stream.write_all(&buf[..n]).await.unwrap();
+        });
+        address
+    }
+
+    async fn assert_echoes(mut stream: TcpStream) {
+        stream.write_all(b"hello").await.unwrap();
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_socks5_with_auth() {
+        let mut connect_request = vec![5, 1, 0, 3, 11];
+        connect_request.extend(b"example.com");
+        connect_request.extend(25565u16.to_be_bytes());
+        let address = fake_proxy(vec![
+            (vec![5, 2, 0, 2], vec![5, 2]),
+            ([&[1, 4][..], b"user", &[4], b"pass"].concat(), vec![1, 0]),
+            (connect_request, vec![5, 0, 0, 1, 127, 0, 0, 1, 0x63, 0xdd]),
+        ])
+        .await;
+
+        let stream = Proxy::socks5(address)
+            .with_auth("user", "pass")
+            .connect("example.com", 25565)
+            .await
+            .unwrap();
+        assert_echoes(stream).await;
+    }
+
+    #[tokio::test]
+    async fn test_socks5_rejected() {
+        let address = fake_proxy(vec![
+            (vec![5, 1, 0], vec![5, 0]),
+            (
+                vec![5, 1, 0, 1, 10, 0, 0, 1, 0x63, 0xdd],
+                vec![5, 5, 0, 1, 0, 0, 0, 0, 0, 0],
+            ),
+        ])
+        .await;
+
+        let err = Proxy::socks5(address)
+            .connect("10.0.0.1", 25565)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ProxyError::Rejected(_)));
+    }
+
+    #[tokio::test]
+    async fn test_socks4a() {
+        let mut request = vec![4, 1, 0x63, 0xdd, 0, 0, 0, 1];
+        request.extend(b"bot\0example.com\0");
+        let address = fake_proxy(vec![(request, vec![0, 0x5a, 0, 0, 0, 0, 0, 0])]).await;
+
+        let stream = Proxy::socks4a(address)
+            .with_auth("bot", "")
+            .connect("example.com", 25565)
+            .await
+            .unwrap();
+        assert_echoes(stream).await;
+    }
+
+    #[tokio::test]
+    async fn test_http_connect() {
+        let request = b"CONNECT example.com:25565 HTTP/1.1\r\nHost: example.com:25565\r\nProxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n";
+        let address = fake_proxy(vec![(
+            request.to_vec(),
+            b"HTTP/1.1 200 Connection established\r\n\r\n".to_vec(),
+        )])
+        .await;
+
+        let stream = Proxy::http(address)
+            .with_auth("user", "pass")
+            .connect("example.com", 25565)
+            .await
+            .unwrap();
+        assert_echoes(stream).await;
+    }
+}
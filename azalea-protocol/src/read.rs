@@ -1,6 +1,6 @@
 //! Read packets from a stream.
 
-use crate::packets::ProtocolPacket;
+use crate::packets::{version::ProtocolVersion, ProtocolPacket};
 use azalea_buf::BufReadError;
 use azalea_buf::McBufVarReadable;
 use azalea_crypto::Aes128CfbDec;
@@ -126,7 +126,7 @@ fn frame_splitter(buffer: &mut BytesMut) -> Result<Option<Vec<u8>>, FrameSplitte
     Ok(None)
 }
 
-fn packet_decoder<P: ProtocolPacket + Debug>(
+pub fn packet_decoder<P: ProtocolPacket + Debug>(
     stream: &mut Cursor<&[u8]>,
 ) -> Result<P, Box<ReadPacketError>> {
     // Packet ID
@@ -135,6 +135,28 @@ fn packet_decoder<P: ProtocolPacket + Debug>(
     P::read(packet_id, stream)
 }
 
+/// Like [`packet_decoder`], but for a packet from a server on another version.
+/// The id and layout of the packet are translated to the ones in
+/// [`PROTOCOL_VERSION`](crate::packets::PROTOCOL_VERSION) before decoding.
+pub fn packet_decoder_for_version<P: ProtocolPacket + Debug>(
+    stream: &mut Cursor<&[u8]>,
+    version: ProtocolVersion,
+) -> Result<P, Box<ReadPacketError>> {
+    if version.is_current() {
+        return packet_decoder(stream);
+    }
+    let id = u32::var_read_from(stream).map_err(|e| ReadPacketError::ReadPacketId { source: e })?;
+    let Some(current_id) = version.to_current_id(P::PROTOCOL, P::DIRECTION, id) else {
+        return Err(Box::new(ReadPacketError::UnknownPacketId {
+            state_name: format!("{:?} ({version})", P::PROTOCOL),
+            id,
+        }));
+    };
+    let mut data = stream.get_ref()[stream.position() as usize..].to_vec();
+    version.upgrade_packet(P::PROTOCOL, P::DIRECTION, current_id, &mut data);
+    P::read(current_id, &mut Cursor::new(&data[..]))
+}
+
 // this is always true in multiplayer, false in singleplayer
 static VALIDATE_DECOMPRESSED: bool = true;
 
@@ -211,13 +233,48 @@ pub async fn read_packet<'a, P: ProtocolPacket + Debug, R>(
     cipher: &mut Option<Aes128CfbDec>,
 ) -> Result<P, Box<ReadPacketError>>
 where
-    R: AsyncRead + std::marker::Unpin + std::marker::Send + std::marker::Sync,
+    R: AsyncRead + std::marker::Unpin + std::marker::Send,
+{
+    let buf = read_raw_packet(stream, buffer, compression_threshold, cipher).await?;
+    let packet = packet_decoder(&mut Cursor::new(&buf[..]))?;
+
+    Ok(packet)
+}
+
+/// Read the next packet's id and data without decoding it, after decrypting
+/// and decompressing it. Decode it with [`packet_decoder`].
+///
+/// If decoding fails, the packet was still taken out of the stream, so the
+/// next packet can be read normally.
+pub async fn read_raw_packet<'a, R>(
+    stream: &'a mut R,
+    buffer: &mut BytesMut,
+    compression_threshold: Option<u32>,
+    cipher: &mut Option<Aes128CfbDec>,
+) -> Result<Vec<u8>, Box<ReadPacketError>>
+where
+    R: AsyncRead + std::marker::Unpin + std::marker::Send,
+{
+    let buf = read_frame(stream, buffer, cipher).await?;
+    decompress_frame(buf, compression_threshold)
+}
+
+/// Read the next frame from the stream and decrypt it, without the length
+/// before it. This is still compressed if compression is enabled, so it's
+/// the same size as the packet was on the network.
+pub async fn read_frame<'a, R>(
+    stream: &'a mut R,
+    buffer: &mut BytesMut,
+    cipher: &mut Option<Aes128CfbDec>,
+) -> Result<Vec<u8>, Box<ReadPacketError>>
+where
+    R: AsyncRead + std::marker::Unpin + std::marker::Send,
 {
     let mut framed = FramedRead::new(stream, BytesCodec::new());
-    let mut buf = loop {
+    loop {
         if let Some(buf) = frame_splitter(buffer).map_err(ReadPacketError::from)? {
             // we got a full packet!!
-            break buf;
+            return Ok(buf);
         } else {
             // no full packet yet :( keep reading
         };
@@ -234,8 +291,15 @@ where
         } else {
             return Err(Box::new(ReadPacketError::ConnectionClosed));
         };
-    };
+    }
+}
 
+/// Decompress a frame from [`read_frame`] if there's a compression threshold,
+/// which gives us the packet's id and data.
+pub fn decompress_frame(
+    mut buf: Vec<u8>,
+    compression_threshold: Option<u32>,
+) -> Result<Vec<u8>, Box<ReadPacketError>> {
     if let Some(compression_threshold) = compression_threshold {
         buf = compression_decoder(&mut Cursor::new(&buf[..]), compression_threshold)
             .map_err(ReadPacketError::from)?;
@@ -253,7 +317,5 @@ where
         trace!("Reading packet with bytes: {buf_string}");
     }
 
-    let packet = packet_decoder(&mut Cursor::new(&buf[..]))?;
-
-    Ok(packet)
+    Ok(buf)
 }
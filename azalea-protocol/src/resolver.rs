@@ -1,75 +1,648 @@
 //! Resolve IPs from hostnames.
+//!
+//! The functions here take a [`Resolver`], which decides how DNS lookups are
+//! done. [`SystemResolver`] uses the operating system's configuration (so it
+//! respects `/etc/hosts`), [`DnsResolver`] asks specific upstream servers,
+//! [`CachedResolver`] remembers the results of another resolver, and
+//! [`StaticResolver`] answers from a fixed map, which is useful for tests.
 
 use crate::ServerAddress;
-use async_recursion::async_recursion;
-use std::net::{IpAddr, SocketAddr};
+use async_trait::async_trait;
+use log::debug;
+use rand::Rng;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
 use thiserror::Error;
 use trust_dns_resolver::{
-    config::{ResolverConfig, ResolverOpts},
+    config::NameServerConfigGroup,
+    error::{ResolveError, ResolveErrorKind},
     TokioAsyncResolver,
 };
 
+pub use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
+
 #[derive(Error, Debug)]
 pub enum ResolverError {
     #[error("No SRV record found")]
     NoSrvRecord,
     #[error("No IP found")]
     NoIp,
+    #[error("DNS lookup failed: {0}")]
+    Dns(#[from] ResolveError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
 }
 
-/// Resolve a Minecraft server address into an IP address and port.
-/// If it's already an IP address, it's returned as-is.
-#[must_use]
-#[async_recursion]
-pub async fn resolve_address(address: &ServerAddress) -> Result<SocketAddr, ResolverError> {
-    // If the address.host is already in the format of an ip address, return it.
-    if let Ok(ip) = address.host.parse::<IpAddr>() {
-        return Ok(SocketAddr::new(ip, address.port));
+/// An SRV record, which points a name to the host and port that a server is
+/// actually running on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SrvRecord {
+    /// Records with a lower priority are tried first.
+    pub priority: u16,
+    /// How likely this record is to be picked over others with the same
+    /// priority.
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// Something that can do DNS lookups.
+#[async_trait]
+pub trait Resolver: Debug + Send + Sync {
+    /// Get the SRV records for a name, like `_minecraft._tcp.example.com`.
+    /// If there aren't any, this should return an empty Vec.
+    async fn lookup_srv(&self, name: &str) -> Result<Vec<SrvRecord>, ResolverError>;
+
+    /// Get the IPv4 and IPv6 addresses for a hostname, in the order they
+    /// should be tried.
+    async fn lookup_ip(&self, host: &str) -> Result<Vec<IpAddr>, ResolverError>;
+}
+
+#[async_trait]
+impl<R: Resolver + ?Sized> Resolver for Arc<R> {
+    async fn lookup_srv(&self, name: &str) -> Result<Vec<SrvRecord>, ResolverError> {
+        (**self).lookup_srv(name).await
+    }
+
+    async fn lookup_ip(&self, host: &str) -> Result<Vec<IpAddr>, ResolverError> {
+        (**self).lookup_ip(host).await
+    }
+}
+
+/// A [`Resolver`] that sends queries to the given upstream DNS servers.
+#[derive(Clone)]
+pub struct DnsResolver {
+    resolver: TokioAsyncResolver,
+}
+
+impl Debug for DnsResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DnsResolver").finish_non_exhaustive()
+    }
+}
+
+impl DnsResolver {
+    pub fn new(config: ResolverConfig, opts: ResolverOpts) -> Result<Self, ResolverError> {
+        Ok(Self {
+            resolver: TokioAsyncResolver::tokio(config, opts)?,
+        })
     }
 
-    // we specify Cloudflare instead of the default resolver because
-    // trust_dns_resolver has an issue on Windows where it's really slow using the
-    // default resolver
-    let resolver =
-        TokioAsyncResolver::tokio(ResolverConfig::cloudflare(), ResolverOpts::default()).unwrap();
+    /// Send queries to these nameservers on port 53.
+    pub fn with_nameservers(nameservers: &[IpAddr]) -> Result<Self, ResolverError> {
+        Self::new(
+            ResolverConfig::from_parts(
+                None,
+                vec![],
+                NameServerConfigGroup::from_ips_clear(nameservers, 53, true),
+            ),
+            ResolverOpts::default(),
+        )
+    }
 
-    // first, we do a srv lookup for _minecraft._tcp.<host>
-    let srv_redirect_result = resolver
-        .srv_lookup(format!("_minecraft._tcp.{}", address.host).as_str())
-        .await;
+    /// Send queries to Cloudflare's public DNS.
+    pub fn cloudflare() -> Result<Self, ResolverError> {
+        Self::new(ResolverConfig::cloudflare(), ResolverOpts::default())
+    }
+}
 
-    // if it resolves that means it's a redirect so we call resolve_address again
-    // with the new host
-    if let Ok(redirect_result) = srv_redirect_result {
-        let redirect_srv = redirect_result
+#[async_trait]
+impl Resolver for DnsResolver {
+    async fn lookup_srv(&self, name: &str) -> Result<Vec<SrvRecord>, ResolverError> {
+        let lookup = match self.resolver.srv_lookup(name).await {
+            Ok(lookup) => lookup,
+            Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {
+                return Ok(vec![])
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(lookup
             .iter()
-            .next()
-            .ok_or(ResolverError::NoSrvRecord)?;
-        let redirect_address = ServerAddress {
-            host: redirect_srv.target().to_utf8(),
-            port: redirect_srv.port(),
+            .map(|srv| SrvRecord {
+                priority: srv.priority(),
+                weight: srv.weight(),
+                port: srv.port(),
+                target: srv.target().to_utf8().trim_end_matches('.').to_string(),
+            })
+            .collect())
+    }
+
+    async fn lookup_ip(&self, host: &str) -> Result<Vec<IpAddr>, ResolverError> {
+        Ok(self.resolver.lookup_ip(host).await?.iter().collect())
+    }
+}
+
+/// A [`Resolver`] that uses the operating system's configuration.
+///
+/// Addresses are looked up with the system's resolver, so `/etc/hosts` is
+/// respected. The system resolver can't do SRV lookups, so those are sent to
+/// the nameservers in `/etc/resolv.conf`, or Cloudflare if it can't be read
+/// (like on Windows).
+#[derive(Clone, Debug)]
+pub struct SystemResolver {
+    srv_resolver: DnsResolver,
+}
+
+impl SystemResolver {
+    pub fn new() -> Result<Self, ResolverError> {
+        let nameservers = std::fs::read_to_string("/etc/resolv.conf")
+            .map(|resolv_conf| parse_nameservers(&resolv_conf))
+            .unwrap_or_default();
+        // we don't use trust_dns_resolver's system config because it has an issue
+        // on Windows where it's really slow
+        let srv_resolver = if nameservers.is_empty() {
+            DnsResolver::cloudflare()?
+        } else {
+            DnsResolver::with_nameservers(&nameservers)?
         };
+        Ok(Self { srv_resolver })
+    }
+}
+
+/// Get the nameserver IPs from the contents of a `resolv.conf` file.
+fn parse_nameservers(resolv_conf: &str) -> Vec<IpAddr> {
+    resolv_conf
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("nameserver") => parts.next()?.parse().ok(),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Resolver for SystemResolver {
+    async fn lookup_srv(&self, name: &str) -> Result<Vec<SrvRecord>, ResolverError> {
+        self.srv_resolver.lookup_srv(name).await
+    }
+
+    async fn lookup_ip(&self, host: &str) -> Result<Vec<IpAddr>, ResolverError> {
+        let mut ips = Vec::new();
+        for address in tokio::net::lookup_host((host, 0)).await? {
+            if !ips.contains(&address.ip()) {
+                ips.push(address.ip());
+            }
+        }
+        Ok(ips)
+    }
+}
+
+/// The default time that [`CachedResolver`] keeps results for.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// A [`Resolver`] that remembers the successful results of another resolver
+/// for a while.
+#[derive(Debug)]
+pub struct CachedResolver<R: Resolver> {
+    inner: R,
+    ttl: Duration,
+    srv_cache: Mutex<HashMap<String, (Instant, Vec<SrvRecord>)>>,
+    ip_cache: Mutex<HashMap<String, (Instant, Vec<IpAddr>)>>,
+}
+
+impl<R: Resolver> CachedResolver<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            ttl: DEFAULT_CACHE_TTL,
+            srv_cache: Mutex::new(HashMap::new()),
+            ip_cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set how long results are kept for. Defaults to [`DEFAULT_CACHE_TTL`].
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Forget everything that was cached.
+    pub fn clear(&self) {
+        self.srv_cache.lock().unwrap().clear();
+        self.ip_cache.lock().unwrap().clear();
+    }
+}
+
+fn get_cached<T: Clone>(cache: &Mutex<HashMap<String, (Instant, T)>>, key: &str) -> Option<T> {
+    let mut cache = cache.lock().unwrap();
+    match cache.get(key) {
+        Some((expires_at, value)) if *expires_at > Instant::now() => Some(value.clone()),
+        Some(_) => {
+            cache.remove(key);
+            None
+        }
+        None => None,
+    }
+}
+
+#[async_trait]
+impl<R: Resolver> Resolver for CachedResolver<R> {
+    async fn lookup_srv(&self, name: &str) -> Result<Vec<SrvRecord>, ResolverError> {
+        if let Some(records) = get_cached(&self.srv_cache, name) {
+            return Ok(records);
+        }
+        let records = self.inner.lookup_srv(name).await?;
+        self.srv_cache.lock().unwrap().insert(
+            name.to_string(),
+            (Instant::now() + self.ttl, records.clone()),
+        );
+        Ok(records)
+    }
+
+    async fn lookup_ip(&self, host: &str) -> Result<Vec<IpAddr>, ResolverError> {
+        if let Some(ips) = get_cached(&self.ip_cache, host) {
+            return Ok(ips);
+        }
+        let ips = self.inner.lookup_ip(host).await?;
+        self.ip_cache
+            .lock()
+            .unwrap()
+            .insert(host.to_string(), (Instant::now() + self.ttl, ips.clone()));
+        Ok(ips)
+    }
+}
+
+/// A [`Resolver`] that answers from a fixed map of names, and optionally asks
+/// another resolver for names that aren't in it.
+///
+/// ```
+/// # use azalea_protocol::resolver::{StaticResolver, SrvRecord};
+/// let resolver = StaticResolver::new()
+///     .with_ip("localhost", "127.0.0.1".parse().unwrap())
+///     .with_srv(
+///         "example.com",
+///         SrvRecord {
+///             priority: 0,
+///             weight: 0,
+///             port: 25566,
+///             target: "localhost".to_string(),
+///         },
+///     );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct StaticResolver {
+    pub ips: HashMap<String, Vec<IpAddr>>,
+    /// The SRV records for Minecraft servers, by the server's hostname
+    /// (without `_minecraft._tcp.`).
+    pub srv: HashMap<String, Vec<SrvRecord>>,
+    pub fallback: Option<Arc<dyn Resolver>>,
+}
 
-        if redirect_address.host == address.host {
-            let lookup_ip_result = resolver.lookup_ip(redirect_address.host).await;
-            let lookup_ip = lookup_ip_result.map_err(|_| ResolverError::NoIp)?;
-            return Ok(SocketAddr::new(
-                lookup_ip.iter().next().unwrap(),
-                redirect_address.port,
-            ));
+impl StaticResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_ip(mut self, host: &str, ip: IpAddr) -> Self {
+        self.ips.entry(host.to_string()).or_default().push(ip);
+        self
+    }
+
+    /// Add an SRV record for the Minecraft server at `host`.
+    #[must_use]
+    pub fn with_srv(mut self, host: &str, record: SrvRecord) -> Self {
+        self.srv.entry(host.to_string()).or_default().push(record);
+        self
+    }
+
+    /// Ask this resolver about names that aren't in the map.
+    #[must_use]
+    pub fn with_fallback(mut self, fallback: Arc<dyn Resolver>) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+}
+
+#[async_trait]
+impl Resolver for StaticResolver {
+    async fn lookup_srv(&self, name: &str) -> Result<Vec<SrvRecord>, ResolverError> {
+        let host = name.strip_prefix(SRV_PREFIX).unwrap_or(name);
+        if let Some(records) = self.srv.get(host) {
+            return Ok(records.clone());
+        }
+        match &self.fallback {
+            Some(fallback) => fallback.lookup_srv(name).await,
+            None => Ok(vec![]),
+        }
+    }
+
+    async fn lookup_ip(&self, host: &str) -> Result<Vec<IpAddr>, ResolverError> {
+        if let Some(ips) = self.ips.get(host) {
+            return Ok(ips.clone());
+        }
+        match &self.fallback {
+            Some(fallback) => fallback.lookup_ip(host).await,
+            None => Err(ResolverError::NoIp),
+        }
+    }
+}
+
+const SRV_PREFIX: &str = "_minecraft._tcp.";
+
+/// The resolver that [`resolve_address`] uses, which is a [`SystemResolver`]
+/// wrapped in a [`CachedResolver`]. It's only created once.
+pub fn default_resolver() -> Arc<dyn Resolver> {
+    static DEFAULT_RESOLVER: OnceLock<Arc<dyn Resolver>> = OnceLock::new();
+    DEFAULT_RESOLVER
+        .get_or_init(|| {
+            Arc::new(CachedResolver::new(
+                SystemResolver::new().expect("Couldn't create the system resolver"),
+            ))
+        })
+        .clone()
+}
+
+/// Put SRV records in the order they should be tried: lowest priority first,
+/// and shuffled by weight within the same priority like RFC 2782 says.
+fn order_srv_records(mut records: Vec<SrvRecord>) -> Vec<SrvRecord> {
+    let mut rng = rand::thread_rng();
+    // records with a weight of 0 go first so they have a small chance of being
+    // picked
+    records.sort_by_key(|record| (record.priority, record.weight != 0));
+
+    let mut ordered = Vec::with_capacity(records.len());
+    while !records.is_empty() {
+        let priority = records[0].priority;
+        let same_priority = records
+            .iter()
+            .take_while(|r| r.priority == priority)
+            .count();
+        let total_weight: u32 = records[..same_priority]
+            .iter()
+            .map(|r| r.weight as u32)
+            .sum();
+        let chosen = rng.gen_range(0..=total_weight);
+        let mut running_weight = 0;
+        let index = records[..same_priority]
+            .iter()
+            .position(|r| {
+                running_weight += r.weight as u32;
+                running_weight >= chosen
+            })
+            .unwrap_or(0);
+        ordered.push(records.remove(index));
+    }
+    ordered
+}
+
+/// Resolve a Minecraft server address into every IP address and port that we
+/// could connect to, in the order they should be tried.
+///
+/// If the address is already an IP address, it's returned as-is. Otherwise,
+/// the `_minecraft._tcp` SRV records are looked up first, and if there aren't
+/// any (or none of their targets resolve) the hostname itself is looked up.
+pub async fn resolve_address_with(
+    resolver: &(impl Resolver + ?Sized),
+    address: &ServerAddress,
+) -> Result<Vec<SocketAddr>, ResolverError> {
+    if let Ok(ip) = address.host.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, address.port)]);
+    }
+
+    let mut addresses = Vec::new();
+    let srv_records = match resolver
+        .lookup_srv(&format!("{SRV_PREFIX}{}", address.host))
+        .await
+    {
+        Ok(records) => records,
+        Err(e) => {
+            debug!("SRV lookup for {} failed: {e}", address.host);
+            vec![]
+        }
+    };
+    for record in order_srv_records(srv_records) {
+        match resolver.lookup_ip(&record.target).await {
+            Ok(ips) => addresses.extend(ips.into_iter().map(|ip| SocketAddr::new(ip, record.port))),
+            Err(e) => debug!("Couldn't resolve SRV target {}: {e}", record.target),
         }
+    }
+
+    if addresses.is_empty() {
+        addresses.extend(
+            resolver
+                .lookup_ip(&address.host)
+                .await?
+                .into_iter()
+                .map(|ip| SocketAddr::new(ip, address.port)),
+        );
+    }
+
+    let mut deduplicated = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        if !deduplicated.contains(&address) {
+            deduplicated.push(address);
+        }
+    }
+    if deduplicated.is_empty() {
+        return Err(ResolverError::NoIp);
+    }
+    Ok(deduplicated)
+}
+
+/// Look up the `_minecraft._tcp` SRV records of a server address, without
+/// resolving their targets. This is for when something else looks up the
+/// IPs, like a proxy with remote DNS.
+///
+/// The targets are returned in the order they should be tried. If the address
+/// is an IP address or there are no SRV records, the address itself is
+/// returned.
+pub async fn resolve_srv_with(
+    resolver: &(impl Resolver + ?Sized),
+    address: &ServerAddress,
+) -> Vec<ServerAddress> {
+    if address.host.parse::<IpAddr>().is_ok() {
+        return vec![address.clone()];
+    }
+
+    let srv_records = match resolver
+        .lookup_srv(&format!("{SRV_PREFIX}{}", address.host))
+        .await
+    {
+        Ok(records) => records,
+        Err(e) => {
+            debug!("SRV lookup for {} failed: {e}", address.host);
+            vec![]
+        }
+    };
+    if srv_records.is_empty() {
+        return vec![address.clone()];
+    }
+    order_srv_records(srv_records)
+        .into_iter()
+        .map(|record| ServerAddress {
+            host: record.target,
+            port: record.port,
+        })
+        .collect()
+}
+
+/// Resolve a Minecraft server address into an IP address and port with the
+/// [`default_resolver`]. If it's already an IP address, it's returned as-is.
+///
+/// Use [`resolve_address_with`] to get every address the server has.
+pub async fn resolve_address(address: &ServerAddress) -> Result<SocketAddr, ResolverError> {
+    Ok(resolve_address_with(&default_resolver(), address).await?[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn srv(priority: u16, weight: u16, port: u16, target: &str) -> SrvRecord {
+        SrvRecord {
+            priority,
+            weight,
+            port,
+            target: target.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ip_address_is_returned_as_is() {
+        let addresses = resolve_address_with(
+            &StaticResolver::new(),
+            &ServerAddress {
+                host: "::1".to_string(),
+                port: 25565,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(addresses, vec!["[::1]:25565".parse().unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn test_srv_records_by_priority() {
+        let resolver = StaticResolver::new()
+            .with_srv("example.com", srv(20, 5, 25567, "backup.example.com"))
+            .with_srv("example.com", srv(10, 5, 25566, "main.example.com"))
+            .with_ip("main.example.com", "10.0.0.1".parse().unwrap())
+            .with_ip("main.example.com", "::2".parse().unwrap())
+            .with_ip("backup.example.com", "10.0.0.2".parse().unwrap())
+            .with_ip("example.com", "10.0.0.3".parse().unwrap());
+        let addresses = resolve_address_with(
+            &resolver,
+            &ServerAddress {
+                host: "example.com".to_string(),
+                port: 25565,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            addresses,
+            vec![
+                "10.0.0.1:25566".parse().unwrap(),
+                "[::2]:25566".parse().unwrap(),
+                "10.0.0.2:25567".parse().unwrap(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_host_without_srv() {
+        let resolver = StaticResolver::new()
+            // this target doesn't resolve, so it's skipped
+            .with_srv("example.com", srv(0, 0, 25566, "missing.example.com"))
+            .with_ip("example.com", "10.0.0.3".parse().unwrap());
+        let addresses = resolve_address_with(
+            &resolver,
+            &ServerAddress {
+                host: "example.com".to_string(),
+                port: 25565,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(addresses, vec!["10.0.0.3:25565".parse().unwrap()]);
+    }
 
-        // debug!("redirecting to {:?}", redirect_address);
+    #[tokio::test]
+    async fn test_resolve_srv() {
+        let resolver = StaticResolver::new()
+            .with_srv("example.com", srv(20, 5, 25567, "backup.example.com"))
+            .with_srv("example.com", srv(10, 5, 25566, "main.example.com"));
+        let address = |host: &str, port| ServerAddress {
+            host: host.to_string(),
+            port,
+        };
+        assert_eq!(
+            resolve_srv_with(&resolver, &address("example.com", 25565)).await,
+            vec![
+                address("main.example.com", 25566),
+                address("backup.example.com", 25567)
+            ]
+        );
+        assert_eq!(
+            resolve_srv_with(&resolver, &address("other.example.com", 25565)).await,
+            vec![address("other.example.com", 25565)]
+        );
+        assert_eq!(
+            resolve_srv_with(&resolver, &address("10.0.0.1", 25565)).await,
+            vec![address("10.0.0.1", 25565)]
+        );
+    }
 
-        return resolve_address(&redirect_address).await;
+    #[test]
+    fn test_order_srv_records_keeps_every_record() {
+        let records = vec![
+            srv(1, 0, 1, "a"),
+            srv(0, 10, 2, "b"),
+            srv(0, 90, 3, "c"),
+            srv(1, 50, 4, "d"),
+        ];
+        for _ in 0..20 {
+            let ordered = order_srv_records(records.clone());
+            assert_eq!(ordered.len(), 4);
+            assert_eq!(ordered[0].priority, 0);
+            assert_eq!(ordered[1].priority, 0);
+            assert_eq!(ordered[2].priority, 1);
+            assert_eq!(ordered[3].priority, 1);
+        }
     }
 
-    // there's no redirect, try to resolve this as an ip address
-    let lookup_ip_result = resolver.lookup_ip(address.host.clone()).await;
-    let lookup_ip = lookup_ip_result.map_err(|_| ResolverError::NoIp)?;
+    #[tokio::test]
+    async fn test_cached_resolver() {
+        let inner =
+            Arc::new(StaticResolver::new().with_ip("example.com", "10.0.0.1".parse().unwrap()));
+        let resolver = CachedResolver::new(inner);
+        assert_eq!(
+            resolver.lookup_ip("example.com").await.unwrap(),
+            vec!["10.0.0.1".parse::<IpAddr>().unwrap()]
+        );
+        assert!(resolver
+            .ip_cache
+            .lock()
+            .unwrap()
+            .contains_key("example.com"));
+        // errors aren't cached
+        assert!(resolver.lookup_ip("missing.example.com").await.is_err());
+        assert!(!resolver
+            .ip_cache
+            .lock()
+            .unwrap()
+            .contains_key("missing.example.com"));
+    }
 
-    Ok(SocketAddr::new(
-        lookup_ip.iter().next().unwrap(),
-        address.port,
-    ))
+    #[test]
+    fn test_parse_nameservers() {
+        let resolv_conf = "# comment\nnameserver 1.1.1.1\nsearch lan\nnameserver ::1\n";
+        assert_eq!(
+            parse_nameservers(resolv_conf),
+            vec![
+                "1.1.1.1".parse::<IpAddr>().unwrap(),
+                "::1".parse::<IpAddr>().unwrap()
+            ]
+        );
+    }
 }
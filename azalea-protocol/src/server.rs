@@ -0,0 +1,782 @@
+//! Accept connections from Minecraft clients, for making servers, proxies, or
+//! fake servers to test bots with.
+//!
+//! [`Listener`] accepts TCP connections and logs players in, and
+//! [`handle_connection`] does the same for a single connection over any
+//! stream.
+//!
+//! ```no_run
+//! # use azalea_protocol::server::{Listener, ServerConfig};
+//! # async fn example() -> std::io::Result<()> {
+//! let mut listener = Listener::bind("127.0.0.1:25565", ServerConfig::default()).await?;
+//! while let Some(mut player) = listener.next_player().await {
+//!     println!("{} joined", player.game_profile.name);
+//!     // send the login packet and start playing with player.connection
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    connect::Connection,
+    packets::{
+        game::{ClientboundGamePacket, ServerboundGamePacket},
+        handshake::{
+            client_intention_packet::ClientIntentionPacket, ClientboundHandshakePacket,
+            ServerboundHandshakePacket,
+        },
+        login::{
+            clientbound_game_profile_packet::ClientboundGameProfilePacket,
+            clientbound_hello_packet::ClientboundHelloPacket,
+            clientbound_login_compression_packet::ClientboundLoginCompressionPacket,
+            clientbound_login_disconnect_packet::ClientboundLoginDisconnectPacket,
+            ClientboundLoginPacket, ServerboundLoginPacket,
+        },
+        status::{
+            clientbound_pong_response_packet::ClientboundPongResponsePacket,
+            clientbound_status_response_packet::{
+                ClientboundStatusResponsePacket, Players, Version,
+            },
+            ClientboundStatusPacket, ServerboundStatusPacket,
+        },
+        ConnectionProtocol, ProtocolPacket, PROTOCOL_VERSION,
+    },
+    read::ReadPacketError,
+};
+use azalea_auth::{
+    game_profile::GameProfile, sessionserver::ServerSessionServerError, AuthEndpoints,
+};
+use azalea_crypto::ServerKeyPair;
+use log::{debug, warn};
+use std::{fmt::Debug, io, net::SocketAddr, sync::Arc, time::Duration};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, ToSocketAddrs,
+    },
+    sync::mpsc,
+    task::JoinHandle,
+    time,
+};
+
+/// The compression threshold that vanilla servers use by default.
+pub const DEFAULT_COMPRESSION_THRESHOLD: u32 = 256;
+
+/// How long we wait for the client to send a packet before giving up on the
+/// connection, which is the same as vanilla's read timeout.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Something that decides what's shown in the server list when a client pings
+/// us.
+///
+/// This is implemented for [`ClientboundStatusResponsePacket`], which is
+/// always sent as-is, and for closures that take the client's
+/// [`ClientIntentionPacket`].
+pub trait StatusProvider: Send + Sync + 'static {
+    fn status(&self, intention: &ClientIntentionPacket) -> ClientboundStatusResponsePacket;
+}
+
+impl StatusProvider for ClientboundStatusResponsePacket {
+    fn status(&self, _intention: &ClientIntentionPacket) -> ClientboundStatusResponsePacket {
+        self.clone()
+    }
+}
+
+impl<F> StatusProvider for F
+where
+    F: Fn(&ClientIntentionPacket) -> ClientboundStatusResponsePacket + Send + Sync + 'static,
+{
+    fn status(&self, intention: &ClientIntentionPacket) -> ClientboundStatusResponsePacket {
+        self(intention)
+    }
+}
+
+/// How a server answers pings and logs players in.
+#[derive(Clone)]
+pub struct ServerConfig {
+    pub status: Arc<dyn StatusProvider>,
+    /// The key pair used to encrypt connections. If this is set, players have
+    /// to be authenticated with Mojang to join (online mode).
+    pub key_pair: Option<Arc<ServerKeyPair>>,
+    /// Packets at least this big are compressed, or nothing is compressed if
+    /// this is `None`. Defaults to [`DEFAULT_COMPRESSION_THRESHOLD`].
+    pub compression_threshold: Option<u32>,
+    /// Send the player's IP to Mojang when authenticating, so players can't
+    /// join through a proxy. This is `prevent-proxy-connections` in vanilla.
+    pub prevent_proxy_connections: bool,
+    /// The session server that players are checked with in online mode.
+    pub auth_endpoints: AuthEndpoints,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            status: Arc::new(ClientboundStatusResponsePacket {
+                description: "A Minecraft Server".into(),
+                favicon: None,
+                players: Players {
+                    max: 20,
+                    online: 0,
+                    sample: Vec::new(),
+                },
+                version: Version {
+                    name: "Azalea".to_string(),
+                    protocol: PROTOCOL_VERSION as i32,
+                },
+                enforces_secure_chat: None,
+            }),
+            key_pair: None,
+            compression_threshold: Some(DEFAULT_COMPRESSION_THRESHOLD),
+            prevent_proxy_connections: false,
+            auth_endpoints: AuthEndpoints::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn status(mut self, status: impl StatusProvider) -> Self {
+        self.status = Arc::new(status);
+        self
+    }
+
+    /// Generate a key pair and make players authenticate with Mojang.
+    #[must_use]
+    pub fn online_mode(mut self) -> Self {
+        self.key_pair = Some(Arc::new(
+            ServerKeyPair::generate().expect("Generating a key pair shouldn't fail"),
+        ));
+        self
+    }
+
+    #[must_use]
+    pub fn compression_threshold(mut self, threshold: Option<u32>) -> Self {
+        self.compression_threshold = threshold;
+        self
+    }
+
+    #[must_use]
+    pub fn prevent_proxy_connections(mut self, prevent_proxy_connections: bool) -> Self {
+        self.prevent_proxy_connections = prevent_proxy_connections;
+        self
+    }
+
+    #[must_use]
+    pub fn auth_endpoints(mut self, auth_endpoints: AuthEndpoints) -> Self {
+        self.auth_endpoints = auth_endpoints;
+        self
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ServerError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("Error reading packet: {0}")]
+    ReadPacket(#[from] Box<ReadPacketError>),
+    #[error("The client wanted to switch to the {0:?} state")]
+    InvalidIntention(ConnectionProtocol),
+    #[error("The client is on protocol version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Unexpected packet during login: {0}")]
+    UnexpectedPacket(String),
+    #[error("Couldn't decrypt the shared secret or nonce")]
+    Decrypt,
+    #[error("The client sent the wrong nonce")]
+    InvalidNonce,
+    #[error("Couldn't authenticate the player: {0}")]
+    Authenticate(#[from] ServerSessionServerError),
+    #[error("The client didn't send anything for {} seconds", READ_TIMEOUT.as_secs())]
+    TimedOut,
+}
+
+/// A player that finished logging in and is ready to play.
+pub struct JoinedPlayer<RS = OwnedReadHalf, WS = OwnedWriteHalf> {
+    pub connection: Connection<ServerboundGamePacket, ClientboundGamePacket, RS, WS>,
+    pub game_profile: GameProfile,
+    /// The handshake the client sent, which has the address they used to
+    /// connect.
+    pub intention: ClientIntentionPacket,
+    /// The player's address, if the connection has one.
+    pub address: Option<SocketAddr>,
+}
+
+/// Answer a ping or log a player in on a new connection. This returns `None`
+/// if the client was only pinging us.
+///
+/// `address` is the client's address, which is sent to Mojang if
+/// [`ServerConfig::prevent_proxy_connections`] is enabled.
+pub async fn handle_connection<RS, WS>(
+    mut conn: Connection<ServerboundHandshakePacket, ClientboundHandshakePacket, RS, WS>,
+    config: &ServerConfig,
+    address: Option<SocketAddr>,
+) -> Result<Option<JoinedPlayer<RS, WS>>, ServerError>
+where
+    RS: AsyncRead + Unpin + Send,
+    WS: AsyncWrite + Unpin + Send,
+{
+    let ServerboundHandshakePacket::ClientIntention(intention) = read_packet(&mut conn).await?;
+    debug!(
+        "Got intention {:?} with protocol version {}",
+        intention.intention, intention.protocol_version
+    );
+
+    match intention.intention {
+        ConnectionProtocol::Status => {
+            handle_status(conn.status(), config, &intention).await?;
+            Ok(None)
+        }
+        ConnectionProtocol::Login => {
+            let mut conn = conn.login();
+            if intention.protocol_version != PROTOCOL_VERSION {
+                let reason = if intention.protocol_version < PROTOCOL_VERSION {
+                    "Outdated client!"
+                } else {
+                    "Outdated server!"
+                };
+                conn.write(
+                    ClientboundLoginDisconnectPacket {
+                        reason: reason.into(),
+                    }
+                    .get(),
+                )
+                .await?;
+                return Err(ServerError::UnsupportedVersion(intention.protocol_version));
+            }
+            let (connection, game_profile) = login(conn, config, address).await?;
+            Ok(Some(JoinedPlayer {
+                connection,
+                game_profile,
+                intention,
+                address,
+            }))
+        }
+        other => Err(ServerError::InvalidIntention(other)),
+    }
+}
+
+async fn handle_status<RS, WS>(
+    mut conn: Connection<ServerboundStatusPacket, ClientboundStatusPacket, RS, WS>,
+    config: &ServerConfig,
+    intention: &ClientIntentionPacket,
+) -> Result<(), ServerError>
+where
+    RS: AsyncRead + Unpin + Send,
+    WS: AsyncWrite + Unpin + Send,
+{
+    loop {
+        let packet = match read_packet(&mut conn).await {
+            Ok(packet) => packet,
+            // clients can close the connection without pinging
+            Err(ServerError::ReadPacket(e)) if matches!(*e, ReadPacketError::ConnectionClosed) => {
+                return Ok(())
+            }
+            Err(e) => return Err(e),
+        };
+        match packet {
+            ServerboundStatusPacket::StatusRequest(_) => {
+                conn.write(config.status.status(intention).get()).await?;
+            }
+            ServerboundStatusPacket::PingRequest(p) => {
+                conn.write(ClientboundPongResponsePacket { time: p.time }.get())
+                    .await?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Go through the login state, encrypting the connection and authenticating
+/// the player if we're in online mode.
+async fn login<RS, WS>(
+    mut conn: Connection<ServerboundLoginPacket, ClientboundLoginPacket, RS, WS>,
+    config: &ServerConfig,
+    address: Option<SocketAddr>,
+) -> Result<
+    (
+        Connection<ServerboundGamePacket, ClientboundGamePacket, RS, WS>,
+        GameProfile,
+    ),
+    ServerError,
+>
+where
+    RS: AsyncRead + Unpin + Send,
+    WS: AsyncWrite + Unpin + Send,
+{
+    let hello = match read_packet(&mut conn).await? {
+        ServerboundLoginPacket::Hello(hello) => hello,
+        packet => return Err(ServerError::UnexpectedPacket(format!("{packet:?}"))),
+    };
+
+    let game_profile = match &config.key_pair {
+        Some(key_pair) => {
+            let nonce = azalea_crypto::generate_nonce();
+            conn.write(
+                ClientboundHelloPacket {
+                    server_id: String::new(),
+                    public_key: key_pair.public_key().to_vec(),
+                    nonce: nonce.to_vec(),
+                }
+                .get(),
+            )
+            .await?;
+
+            let key = match read_packet(&mut conn).await? {
+                ServerboundLoginPacket::Key(key) => key,
+                packet => return Err(ServerError::UnexpectedPacket(format!("{packet:?}"))),
+            };
+            let secret_key: [u8; 16] = key_pair
+                .decrypt(&key.key_bytes)
+                .ok()
+                .and_then(|secret_key| secret_key.try_into().ok())
+                .ok_or(ServerError::Decrypt)?;
+            let decrypted_nonce = key_pair
+                .decrypt(&key.encrypted_challenge)
+                .map_err(|_| ServerError::Decrypt)?;
+            if decrypted_nonce != nonce {
+                return Err(ServerError::InvalidNonce);
+            }
+            conn.set_encryption_key(secret_key);
+
+            let ip = match address {
+                Some(address) if config.prevent_proxy_connections => Some(address.ip().to_string()),
+                _ => None,
+            };
+            match conn
+                .authenticate_with_endpoints(
+                    &hello.name,
+                    key_pair.public_key(),
+                    &secret_key,
+                    ip.as_deref(),
+                    &config.auth_endpoints,
+                )
+                .await
+            {
+                Ok(game_profile) => game_profile,
+                Err(e) => {
+                    warn!("Couldn't authenticate {}: {e}", hello.name);
+                    conn.write(
+                        ClientboundLoginDisconnectPacket {
+                            reason: "Failed to verify username!".into(),
+                        }
+                        .get(),
+                    )
+                    .await?;
+                    return Err(e.into());
+                }
+            }
+        }
+        None => GameProfile::new(azalea_crypto::offline_uuid(&hello.name), hello.name),
+    };
+
+    if let Some(threshold) = config.compression_threshold {
+        conn.write(
+            ClientboundLoginCompressionPacket {
+                compression_threshold: threshold as i32,
+            }
+            .get(),
+        )
+        .await?;
+        conn.set_compression_threshold(threshold as i32);
+    }
+
+    conn.write(
+        ClientboundGameProfilePacket {
+            game_profile: game_profile.clone(),
+        }
+        .get(),
+    )
+    .await?;
+
+    Ok((conn.game(), game_profile))
+}
+
+/// Read a packet from the client, or fail with [`ServerError::TimedOut`] if
+/// it doesn't send one within [`READ_TIMEOUT`].
+async fn read_packet<R, W, RS, WS>(conn: &mut Connection<R, W, RS, WS>) -> Result<R, ServerError>
+where
+    R: ProtocolPacket + Debug,
+    W: ProtocolPacket + Debug,
+    RS: AsyncRead + Unpin + Send,
+    WS: AsyncWrite + Unpin + Send,
+{
+    match time::timeout(READ_TIMEOUT, conn.read()).await {
+        Ok(packet) => Ok(packet?),
+        Err(_) => Err(ServerError::TimedOut),
+    }
+}
+
+/// A TCP listener that answers pings and logs players in, and gives you the
+/// players that joined.
+///
+/// Every connection is handled in its own task, so slow clients don't hold
+/// up other ones.
+pub struct Listener {
+    local_addr: SocketAddr,
+    players: mpsc::UnboundedReceiver<JoinedPlayer>,
+    accept_task: JoinHandle<()>,
+}
+
+impl Listener {
+    /// Start listening for connections on the given address.
+    pub async fn bind(address: impl ToSocketAddrs, config: ServerConfig) -> io::Result<Self> {
+        let listener = TcpListener::bind(address).await?;
+        let local_addr = listener.local_addr()?;
+        let (players_tx, players) = mpsc::unbounded_channel();
+        let accept_task = tokio::spawn(accept_connections(listener, Arc::new(config), players_tx));
+        Ok(Self {
+            local_addr,
+            players,
+            accept_task,
+        })
+    }
+
+    /// The address we're listening on, which is useful if you bound to port
+    /// 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Wait for the next player to finish logging in.
+    pub async fn next_player(&mut self) -> Option<JoinedPlayer> {
+        self.players.recv().await
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+    }
+}
+
+async fn accept_connections(
+    listener: TcpListener,
+    config: Arc<ServerConfig>,
+    players_tx: mpsc::UnboundedSender<JoinedPlayer>,
+) {
+    loop {
+        let (stream, address) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Couldn't accept connection: {e}");
+                continue;
+            }
+        };
+        if let Err(e) = stream.set_nodelay(true) {
+            warn!("Couldn't set nodelay for {address}: {e}");
+        }
+
+        let config = config.clone();
+        let players_tx = players_tx.clone();
+        tokio::spawn(async move {
+            match handle_connection(Connection::wrap(stream), &config, Some(address)).await {
+                Ok(Some(player)) => {
+                    let _ = players_tx.send(player);
+                }
+                Ok(None) => {}
+                Err(e) => debug!("Connection from {address} failed: {e}"),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::{
+        handshake::client_intention_packet::ClientIntentionPacket,
+        login::{
+            serverbound_hello_packet::ServerboundHelloPacket,
+            serverbound_key_packet::ServerboundKeyPacket,
+        },
+        status::{
+            serverbound_ping_request_packet::ServerboundPingRequestPacket,
+            serverbound_status_request_packet::ServerboundStatusRequestPacket,
+        },
+    };
+    use azalea_auth::test_utils::{json_response, mock_session_server};
+    use uuid::Uuid;
+
+    fn client_and_server() -> (
+        Connection<
+            ClientboundHandshakePacket,
+            ServerboundHandshakePacket,
+            tokio::io::ReadHalf<tokio::io::DuplexStream>,
+            tokio::io::WriteHalf<tokio::io::DuplexStream>,
+        >,
+        Connection<
+            ServerboundHandshakePacket,
+            ClientboundHandshakePacket,
+            tokio::io::ReadHalf<tokio::io::DuplexStream>,
+            tokio::io::WriteHalf<tokio::io::DuplexStream>,
+        >,
+    ) {
+        let (client_stream, server_stream) = tokio::io::duplex(4096);
+        (
+            Connection::wrap_stream(client_stream),
+            Connection::wrap_stream(server_stream),
+        )
+    }
+
+    fn intention(intention: ConnectionProtocol) -> ClientIntentionPacket {
+        ClientIntentionPacket {
+            protocol_version: PROTOCOL_VERSION,
+            hostname: "localhost".to_string(),
+            port: 25565,
+            intention,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let (mut client, server) = client_and_server();
+        let server = tokio::spawn(async move {
+            let config = ServerConfig::new().status(|intention: &ClientIntentionPacket| {
+                let mut status = ServerConfig::default().status.status(intention);
+                status.players.online = 5;
+                status
+            });
+            handle_connection(server, &config, None).await
+        });
+
+        client
+            .write(intention(ConnectionProtocol::Status).get())
+            .await
+            .unwrap();
+        let mut client = client.status();
+        client
+            .write(ServerboundStatusRequestPacket {}.get())
+            .await
+            .unwrap();
+        match client.read().await.unwrap() {
+            crate::packets::status::ClientboundStatusPacket::StatusResponse(status) => {
+                assert_eq!(status.players.online, 5)
+            }
+            packet => panic!("Expected a status response, got {packet:?}"),
+        }
+        client
+            .write(ServerboundPingRequestPacket { time: 123 }.get())
+            .await
+            .unwrap();
+        match client.read().await.unwrap() {
+            crate::packets::status::ClientboundStatusPacket::PongResponse(pong) => {
+                assert_eq!(pong.time, 123)
+            }
+            packet => panic!("Expected a pong response, got {packet:?}"),
+        }
+
+        assert!(server.await.unwrap().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_offline_login() {
+        let (mut client, server) = client_and_server();
+        let server =
+            tokio::spawn(
+                async move { handle_connection(server, &ServerConfig::default(), None).await },
+            );
+
+        client
+            .write(intention(ConnectionProtocol::Login).get())
+            .await
+            .unwrap();
+        let mut client = client.login();
+        client
+            .write(
+                ServerboundHelloPacket {
+                    name: "bot".to_string(),
+                    profile_id: None,
+                }
+                .get(),
+            )
+            .await
+            .unwrap();
+
+        let game_profile = loop {
+            match client.read().await.unwrap() {
+                ClientboundLoginPacket::LoginCompression(p) => {
+                    assert_eq!(
+                        p.compression_threshold,
+                        DEFAULT_COMPRESSION_THRESHOLD as i32
+                    );
+                    client.set_compression_threshold(p.compression_threshold);
+                }
+                ClientboundLoginPacket::GameProfile(p) => break p.game_profile,
+                packet => panic!("Unexpected packet {packet:?}"),
+            }
+        };
+        assert_eq!(game_profile.name, "bot");
+        assert_eq!(game_profile.uuid, azalea_crypto::offline_uuid("bot"));
+
+        let player = server.await.unwrap().unwrap().unwrap();
+        assert_eq!(player.game_profile, game_profile);
+        assert_eq!(player.intention.hostname, "localhost");
+    }
+
+    #[tokio::test]
+    async fn test_outdated_client() {
+        let (mut client, server) = client_and_server();
+        let server =
+            tokio::spawn(
+                async move { handle_connection(server, &ServerConfig::default(), None).await },
+            );
+
+        let mut outdated = intention(ConnectionProtocol::Login);
+        outdated.protocol_version -= 1;
+        client.write(outdated.get()).await.unwrap();
+        let mut client = client.login();
+        assert!(matches!(
+            client.read().await.unwrap(),
+            ClientboundLoginPacket::LoginDisconnect(_)
+        ));
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(ServerError::UnsupportedVersion(_))
+        ));
+    }
+
+    /// Send the handshake and hello packets, and return the encryption
+    /// request.
+    async fn start_online_login(
+        mut client: Connection<
+            ClientboundHandshakePacket,
+            ServerboundHandshakePacket,
+            tokio::io::ReadHalf<tokio::io::DuplexStream>,
+            tokio::io::WriteHalf<tokio::io::DuplexStream>,
+        >,
+    ) -> (
+        Connection<
+            ClientboundLoginPacket,
+            ServerboundLoginPacket,
+            tokio::io::ReadHalf<tokio::io::DuplexStream>,
+            tokio::io::WriteHalf<tokio::io::DuplexStream>,
+        >,
+        ClientboundHelloPacket,
+    ) {
+        client
+            .write(intention(ConnectionProtocol::Login).get())
+            .await
+            .unwrap();
+        let mut client = client.login();
+        client
+            .write(
+                ServerboundHelloPacket {
+                    name: "Notch".to_string(),
+                    profile_id: None,
+                }
+                .get(),
+            )
+            .await
+            .unwrap();
+        match client.read().await.unwrap() {
+            ClientboundLoginPacket::Hello(hello) => (client, hello),
+            packet => panic!("Expected an encryption request, got {packet:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_online_login() {
+        let (endpoints, request) = mock_session_server(json_response(
+            r#"{"id":"069a79f444e94726a5befca90e38aaf5","name":"Notch","properties":[]}"#,
+        ))
+        .await;
+        let config = ServerConfig::new()
+            .online_mode()
+            .compression_threshold(None)
+            .auth_endpoints(endpoints);
+        let public_key = config.key_pair.as_ref().unwrap().public_key().to_vec();
+
+        let (client, server) = client_and_server();
+        let server = tokio::spawn(async move { handle_connection(server, &config, None).await });
+
+        let (mut client, hello) = start_online_login(client).await;
+        assert_eq!(hello.public_key, public_key);
+        let encrypted = azalea_crypto::encrypt(&hello.public_key, &hello.nonce).unwrap();
+        client
+            .write(
+                ServerboundKeyPacket {
+                    key_bytes: encrypted.encrypted_public_key,
+                    encrypted_challenge: encrypted.encrypted_nonce,
+                }
+                .get(),
+            )
+            .await
+            .unwrap();
+        client.set_encryption_key(encrypted.secret_key);
+
+        let game_profile = match client.read().await.unwrap() {
+            ClientboundLoginPacket::GameProfile(p) => p.game_profile,
+            packet => panic!("Unexpected packet {packet:?}"),
+        };
+        assert_eq!(
+            game_profile.uuid,
+            Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap()
+        );
+
+        // the server has to check the same hash that the client would've
+        // sent to Mojang
+        let hash = azalea_crypto::hex_digest(&azalea_crypto::digest_data(
+            b"",
+            &public_key,
+            &encrypted.secret_key,
+        ));
+        let request = request.await.unwrap();
+        assert!(
+            request.starts_with(&format!(
+                "GET /session/minecraft/hasJoined?username=Notch&serverId={hash} "
+            )),
+            "{request}"
+        );
+
+        let player = server.await.unwrap().unwrap().unwrap();
+        assert_eq!(player.game_profile, game_profile);
+    }
+
+    #[tokio::test]
+    async fn test_online_login_wrong_nonce() {
+        let config = ServerConfig::new().online_mode();
+        let (client, server) = client_and_server();
+        let server = tokio::spawn(async move { handle_connection(server, &config, None).await });
+
+        let (mut client, hello) = start_online_login(client).await;
+        let encrypted = azalea_crypto::encrypt(&hello.public_key, &[0; 4]).unwrap();
+        client
+            .write(
+                ServerboundKeyPacket {
+                    key_bytes: encrypted.encrypted_public_key,
+                    encrypted_challenge: encrypted.encrypted_nonce,
+                }
+                .get(),
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(ServerError::InvalidNonce)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_read_timeout() {
+        let (mut client, server) = client_and_server();
+        let server =
+            tokio::spawn(
+                async move { handle_connection(server, &ServerConfig::default(), None).await },
+            );
+
+        // connect but never send the hello packet
+        client
+            .write(intention(ConnectionProtocol::Login).get())
+            .await
+            .unwrap();
+
+        assert!(matches!(server.await.unwrap(), Err(ServerError::TimedOut)));
+    }
+}
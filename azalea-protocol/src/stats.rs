@@ -0,0 +1,388 @@
+//! Count how much is sent and received on a connection.
+//!
+//! Every [`ReadConnection`] and [`WriteConnection`] has a [`ConnectionStats`]
+//! that's shared between the two halves of the connection and kept when the
+//! state changes. Take a [`snapshot`](ConnectionStats::snapshot) of it to look
+//! at the numbers, or export them with [`ConnectionStats::to_json`] or
+//! [`to_prometheus`].
+//!
+//! [`ReadConnection`]: crate::connect::ReadConnection
+//! [`WriteConnection`]: crate::connect::WriteConnection
+
+use azalea_buf::McBufVarWritable;
+use bevy_ecs::component::Component;
+use serde::{Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// The traffic on a connection. Cloning it gives a handle to the same
+/// counters, so it can be kept around after the connection is split.
+#[derive(Component, Clone, Default)]
+pub struct ConnectionStats(Arc<Counters>);
+
+#[derive(Default)]
+struct Counters {
+    bytes_received: AtomicU64,
+    bytes_received_uncompressed: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_sent_uncompressed: AtomicU64,
+    write_queue_depth: AtomicUsize,
+    received: Mutex<HashMap<&'static str, PacketTypeStats>>,
+    sent: Mutex<HashMap<&'static str, PacketTypeStats>>,
+}
+
+/// The totals for every packet of one type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PacketTypeStats {
+    pub count: u64,
+    /// The size of the packets after decompression, including the packet id.
+    pub bytes: u64,
+    /// How long it took to decode the packets. This is always zero for
+    /// packets we sent.
+    #[serde(serialize_with = "serialize_seconds")]
+    pub decode_time: Duration,
+}
+
+/// The numbers in a [`ConnectionStats`] at one point in time.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ConnectionStatsSnapshot {
+    /// The number of bytes we received over the network, after compression.
+    pub bytes_received: u64,
+    /// The number of bytes we received, after decompression.
+    pub bytes_received_uncompressed: u64,
+    /// The number of bytes we sent over the network, after compression.
+    pub bytes_sent: u64,
+    /// The number of bytes we sent, before compression.
+    pub bytes_sent_uncompressed: u64,
+    /// The number of packets that are waiting to be written.
+    pub write_queue_depth: usize,
+    /// The packets that we received and could decode, by their name.
+    pub received: BTreeMap<&'static str, PacketTypeStats>,
+    /// The packets that we sent, by their name.
+    pub sent: BTreeMap<&'static str, PacketTypeStats>,
+}
+
+impl ConnectionStats {
+    pub fn snapshot(&self) -> ConnectionStatsSnapshot {
+        ConnectionStatsSnapshot {
+            bytes_received: self.0.bytes_received.load(Ordering::Relaxed),
+            bytes_received_uncompressed: self.0.bytes_received_uncompressed.load(Ordering::Relaxed),
+            bytes_sent: self.0.bytes_sent.load(Ordering::Relaxed),
+            bytes_sent_uncompressed: self.0.bytes_sent_uncompressed.load(Ordering::Relaxed),
+            write_queue_depth: self.0.write_queue_depth.load(Ordering::Relaxed),
+            received: self
+                .0
+                .received
+                .lock()
+                .unwrap()
+                .clone()
+                .into_iter()
+                .collect(),
+            sent: self.0.sent.lock().unwrap().clone().into_iter().collect(),
+        }
+    }
+
+    /// Serialize a [`snapshot`](Self::snapshot) of the stats as JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.snapshot()).expect("Stats should always be serializable")
+    }
+
+    /// Record a frame that we read. The sizes don't include the length before
+    /// the frame.
+    pub(crate) fn record_received_frame(&self, compressed: usize, uncompressed: usize) {
+        self.0
+            .bytes_received
+            .fetch_add(framed_size(compressed) as u64, Ordering::Relaxed);
+        self.0
+            .bytes_received_uncompressed
+            .fetch_add(uncompressed as u64, Ordering::Relaxed);
+    }
+
+    /// Record a packet that we decoded. `bytes` is the size of the packet
+    /// after decompression.
+    pub(crate) fn record_received_packet(
+        &self,
+        name: &'static str,
+        bytes: usize,
+        decode_time: Duration,
+    ) {
+        let mut received = self.0.received.lock().unwrap();
+        let stats = received.entry(name).or_default();
+        stats.count += 1;
+        stats.bytes += bytes as u64;
+        stats.decode_time += decode_time;
+    }
+
+    /// Record a packet that we wrote. `frame` is the size of the packet on
+    /// the network and `uncompressed` is the size before compression.
+    pub(crate) fn record_sent_packet(&self, name: &'static str, frame: usize, uncompressed: usize) {
+        self.record_sent_frame(frame, uncompressed);
+        let mut sent = self.0.sent.lock().unwrap();
+        let stats = sent.entry(name).or_default();
+        stats.count += 1;
+        stats.bytes += uncompressed as u64;
+    }
+
+    /// Record a packet that we wrote without encoding it ourselves, so we
+    /// don't know its name.
+    pub(crate) fn record_sent_frame(&self, frame: usize, uncompressed: usize) {
+        self.0.bytes_sent.fetch_add(frame as u64, Ordering::Relaxed);
+        self.0
+            .bytes_sent_uncompressed
+            .fetch_add(uncompressed as u64, Ordering::Relaxed);
+    }
+
+    /// Call this when a packet is added to the queue of packets that are
+    /// waiting to be written.
+    pub fn write_queued(&self) {
+        self.0.write_queue_depth.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Call this when a packet is taken out of the queue to be written.
+    pub fn write_dequeued(&self) {
+        // saturating so a mismatched call can't make it wrap around
+        let _ =
+            self.0
+                .write_queue_depth
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |depth| {
+                    Some(depth.saturating_sub(1))
+                });
+    }
+}
+
+/// The size of a frame with the VarInt length before it.
+fn framed_size(len: usize) -> usize {
+    let mut buf = Vec::new();
+    (len as u32)
+        .var_write_into(&mut buf)
+        .expect("Writing to a Vec can't fail");
+    buf.len() + len
+}
+
+/// The name of a metric, its help text and how to get its value.
+type Metric<T, V> = (&'static str, &'static str, fn(&T) -> V);
+
+fn serialize_seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Export the stats of many connections in the Prometheus text format, with a
+/// `connection` label for each one (like the bot's username).
+///
+/// ```
+/// # use azalea_protocol::stats::{to_prometheus, ConnectionStats};
+/// let stats = ConnectionStats::default();
+/// let text = to_prometheus([("bot0", &stats)]);
+/// assert!(text.contains("azalea_connection_received_bytes_total{connection=\"bot0\"} 0"));
+/// ```
+pub fn to_prometheus<'a>(
+    connections: impl IntoIterator<Item = (&'a str, &'a ConnectionStats)>,
+) -> String {
+    let snapshots = connections
+        .into_iter()
+        .map(|(name, stats)| (escape_label(name), stats.snapshot()))
+        .collect::<Vec<_>>();
+
+    let mut out = String::new();
+    let mut metric =
+        |name: &str, kind: &str, help: &str, values: &mut dyn Iterator<Item = String>| {
+            writeln!(out, "# HELP {name} {help}").unwrap();
+            writeln!(out, "# TYPE {name} {kind}").unwrap();
+            for value in values {
+                writeln!(out, "{name}{value}").unwrap();
+            }
+        };
+
+    let totals: [Metric<ConnectionStatsSnapshot, u64>; 4] = [
+        (
+            "azalea_connection_received_bytes_total",
+            "Bytes received over the network, after compression.",
+            |s| s.bytes_received,
+        ),
+        (
+            "azalea_connection_received_uncompressed_bytes_total",
+            "Bytes received, after decompression.",
+            |s| s.bytes_received_uncompressed,
+        ),
+        (
+            "azalea_connection_sent_bytes_total",
+            "Bytes sent over the network, after compression.",
+            |s| s.bytes_sent,
+        ),
+        (
+            "azalea_connection_sent_uncompressed_bytes_total",
+            "Bytes sent, before compression.",
+            |s| s.bytes_sent_uncompressed,
+        ),
+    ];
+    for (name, help, get) in totals {
+        metric(
+            name,
+            "counter",
+            help,
+            &mut snapshots
+                .iter()
+                .map(|(connection, s)| format!("{{connection=\"{connection}\"}} {}", get(s))),
+        );
+    }
+    metric(
+        "azalea_connection_write_queue_depth",
+        "gauge",
+        "Packets that are waiting to be written.",
+        &mut snapshots.iter().map(|(connection, s)| {
+            format!("{{connection=\"{connection}\"}} {}", s.write_queue_depth)
+        }),
+    );
+
+    let per_packet: [(bool, Metric<PacketTypeStats, String>); 5] = [
+        (
+            true,
+            (
+                "azalea_connection_received_packets_total",
+                "Packets received, by type.",
+                |p| p.count.to_string(),
+            ),
+        ),
+        (
+            true,
+            (
+                "azalea_connection_received_packet_bytes_total",
+                "Bytes received after decompression, by packet type.",
+                |p| p.bytes.to_string(),
+            ),
+        ),
+        (
+            true,
+            (
+                "azalea_connection_decode_seconds_total",
+                "Time spent decoding packets, by type.",
+                |p| p.decode_time.as_secs_f64().to_string(),
+            ),
+        ),
+        (
+            false,
+            (
+                "azalea_connection_sent_packets_total",
+                "Packets sent, by type.",
+                |p| p.count.to_string(),
+            ),
+        ),
+        (
+            false,
+            (
+                "azalea_connection_sent_packet_bytes_total",
+                "Bytes sent before compression, by packet type.",
+                |p| p.bytes.to_string(),
+            ),
+        ),
+    ];
+    for (received, (name, help, get)) in per_packet {
+        metric(
+            name,
+            "counter",
+            help,
+            &mut snapshots.iter().flat_map(|(connection, s)| {
+                let packets = if received { &s.received } else { &s.sent };
+                packets.iter().map(move |(packet, p)| {
+                    format!(
+                        "{{connection=\"{connection}\",packet=\"{packet}\"}} {}",
+                        get(p)
+                    )
+                })
+            }),
+        );
+    }
+
+    out
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let stats = ConnectionStats::default();
+        let handle = stats.clone();
+        stats.record_received_frame(10, 20);
+        stats.record_received_packet("ClientboundKeepAlivePacket", 20, Duration::from_millis(1));
+        stats.record_sent_packet("ServerboundKeepAlivePacket", 11, 9);
+        stats.write_queued();
+        stats.write_queued();
+        stats.write_dequeued();
+
+        let snapshot = handle.snapshot();
+        // 10 bytes and one byte for the length
+        assert_eq!(snapshot.bytes_received, 11);
+        assert_eq!(snapshot.bytes_received_uncompressed, 20);
+        assert_eq!(snapshot.bytes_sent, 11);
+        assert_eq!(snapshot.bytes_sent_uncompressed, 9);
+        assert_eq!(snapshot.write_queue_depth, 1);
+        assert_eq!(
+            snapshot.received["ClientboundKeepAlivePacket"],
+            PacketTypeStats {
+                count: 1,
+                bytes: 20,
+                decode_time: Duration::from_millis(1)
+            }
+        );
+        assert_eq!(snapshot.sent["ServerboundKeepAlivePacket"].count, 1);
+    }
+
+    #[test]
+    fn test_write_dequeued_saturates() {
+        let stats = ConnectionStats::default();
+        stats.write_dequeued();
+        assert_eq!(stats.snapshot().write_queue_depth, 0);
+    }
+
+    #[test]
+    fn test_json() {
+        let stats = ConnectionStats::default();
+        stats.record_received_packet("ClientboundKeepAlivePacket", 9, Duration::from_millis(500));
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(json["received"]["ClientboundKeepAlivePacket"]["count"], 1);
+        assert_eq!(
+            json["received"]["ClientboundKeepAlivePacket"]["decode_time"],
+            0.5
+        );
+    }
+
+    #[test]
+    fn test_prometheus() {
+        let a = ConnectionStats::default();
+        let b = ConnectionStats::default();
+        a.record_sent_packet("ServerboundChatPacket", 30, 40);
+        b.record_sent_packet("ServerboundChatPacket", 30, 40);
+        b.record_sent_packet("ServerboundChatPacket", 30, 40);
+        let text = to_prometheus([("a", &a), ("b\"", &b)]);
+
+        // the type is only written once for each metric
+        assert_eq!(
+            text.matches("# TYPE azalea_connection_sent_packets_total counter")
+                .count(),
+            1
+        );
+        assert!(text.contains(
+            "azalea_connection_sent_packets_total{connection=\"a\",packet=\"ServerboundChatPacket\"} 1\n"
+        ));
+        assert!(text.contains(
+            "azalea_connection_sent_packets_total{connection=\"b\\\"\",packet=\"ServerboundChatPacket\"} 2\n"
+        ));
+        assert!(text.contains("azalea_connection_sent_bytes_total{connection=\"b\\\"\"} 60\n"));
+    }
+}
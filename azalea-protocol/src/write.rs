@@ -1,6 +1,9 @@
 //! Write packets to a stream.
 
-use crate::{packets::ProtocolPacket, read::MAXIMUM_UNCOMPRESSED_LENGTH};
+use crate::{
+    packets::{version::ProtocolVersion, ProtocolPacket},
+    read::MAXIMUM_UNCOMPRESSED_LENGTH,
+};
 use async_compression::tokio::bufread::ZlibEncoder;
 use azalea_buf::McBufVarWritable;
 use azalea_crypto::Aes128CfbEnc;
@@ -27,6 +30,11 @@ pub enum PacketEncodeError {
         maximum: usize,
         packet_string: String,
     },
+    #[error("{packet_string} doesn't exist in {version}")]
+    NotInVersion {
+        version: ProtocolVersion,
+        packet_string: String,
+    },
 }
 
 pub fn packet_encoder<P: ProtocolPacket + std::fmt::Debug>(
@@ -35,6 +43,39 @@ pub fn packet_encoder<P: ProtocolPacket + std::fmt::Debug>(
     let mut buf = Vec::new();
     packet.id().var_write_into(&mut buf)?;
     packet.write(&mut buf)?;
+    check_packet_size(buf, packet)
+}
+
+/// Like [`packet_encoder`], but for sending a packet to a server on another
+/// version. The id and layout of the packet are translated from the ones in
+/// [`PROTOCOL_VERSION`](crate::packets::PROTOCOL_VERSION).
+pub fn packet_encoder_for_version<P: ProtocolPacket + std::fmt::Debug>(
+    packet: &P,
+    version: ProtocolVersion,
+) -> Result<Vec<u8>, PacketEncodeError> {
+    if version.is_current() {
+        return packet_encoder(packet);
+    }
+    let id = version
+        .from_current_id(P::PROTOCOL, P::DIRECTION, packet.id())
+        .ok_or_else(|| PacketEncodeError::NotInVersion {
+            version,
+            packet_string: format!("{packet:?}"),
+        })?;
+    let mut data = Vec::new();
+    packet.write(&mut data)?;
+    version.downgrade_packet(P::PROTOCOL, P::DIRECTION, packet.id(), &mut data);
+
+    let mut buf = Vec::new();
+    id.var_write_into(&mut buf)?;
+    buf.append(&mut data);
+    check_packet_size(buf, packet)
+}
+
+fn check_packet_size<P: std::fmt::Debug>(
+    buf: Vec<u8>,
+    packet: &P,
+) -> Result<Vec<u8>, PacketEncodeError> {
     if buf.len() > MAXIMUM_UNCOMPRESSED_LENGTH as usize {
         return Err(PacketEncodeError::TooBig {
             actual: buf.len(),
@@ -89,7 +130,32 @@ where
     W: AsyncWrite + Unpin + Send,
 {
     trace!("Sending packet: {:?}", packet,);
-    let mut buf = packet_encoder(packet).unwrap();
+    let buf = packet_encoder(packet).unwrap();
+    write_raw_packet(buf, stream, compression_threshold, cipher).await
+}
+
+/// Compress, frame and encrypt a packet that was encoded with
+/// [`packet_encoder`], and write it to the stream.
+pub async fn write_raw_packet<W>(
+    buf: Vec<u8>,
+    stream: &mut W,
+    compression_threshold: Option<u32>,
+    cipher: &mut Option<Aes128CfbEnc>,
+) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    let buf = encode_frame(buf, compression_threshold, cipher).await;
+    stream.write_all(&buf).await
+}
+
+/// Compress, frame and encrypt a packet that was encoded with
+/// [`packet_encoder`]. This gives the bytes that are sent over the network.
+pub async fn encode_frame(
+    mut buf: Vec<u8>,
+    compression_threshold: Option<u32>,
+    cipher: &mut Option<Aes128CfbEnc>,
+) -> Vec<u8> {
     if let Some(threshold) = compression_threshold {
         buf = compression_encoder(&buf, threshold).await.unwrap();
     }
@@ -98,5 +164,5 @@ where
     if let Some(cipher) = cipher {
         azalea_crypto::encrypt_packet(cipher, &mut buf);
     }
-    stream.write_all(&buf).await
+    buf
 }
@@ -29,11 +29,45 @@ pub struct PartialChunkStorage {
 /// A storage for chunks where they're only stored weakly, so if they're not
 /// actively being used somewhere else they'll be forgotten. This is used for
 /// shared worlds.
+///
+/// The [`ChunkCachePolicy`] decides whether chunks are kept around for a while
+/// after nothing is using them anymore.
 #[derive(Debug)]
 pub struct ChunkStorage {
     pub height: u32,
     pub min_y: i32,
     pub chunks: HashMap<ChunkPos, Weak<RwLock<Chunk>>>,
+    /// What to do with chunks that aren't in the render distance of any client
+    /// anymore. Changes take effect the next time [`Self::tick`] is called.
+    pub cache_policy: ChunkCachePolicy,
+    /// Strong references to chunks, so they don't get dropped as soon as every
+    /// client forgets them. This is always empty with
+    /// [`ChunkCachePolicy::Unload`].
+    cached: HashMap<ChunkPos, CachedChunk>,
+    ticks: u64,
+}
+
+/// How long a [`ChunkStorage`] keeps chunks that no client is using anymore.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkCachePolicy {
+    /// Forget chunks as soon as every client forgets them. This uses the least
+    /// memory.
+    #[default]
+    Unload,
+    /// Keep up to this many unused chunks, forgetting the ones that have been
+    /// unused for the longest first.
+    MaxChunks(usize),
+    /// Keep unused chunks for this many ticks after the last client stopped
+    /// using them.
+    KeepAlive { ticks: u32 },
+}
+
+#[derive(Debug)]
+struct CachedChunk {
+    chunk: Arc<RwLock<Chunk>>,
+    /// The tick that the cache became the only thing referencing this chunk,
+    /// or `None` if it's still used somewhere else.
+    unused_since: Option<u64>,
 }
 
 /// A single chunk in a world (16*?*16 blocks). This only contains the blocks
@@ -182,7 +216,7 @@ impl PartialChunkStorage {
         chunk_storage: &mut ChunkStorage,
     ) {
         if let Some(chunk) = &chunk {
-            chunk_storage.chunks.insert(*pos, Arc::downgrade(chunk));
+            chunk_storage.insert(*pos, chunk);
         } else {
             // don't remove it from the shared storage, since it'll be removed
             // automatically if this was the last reference
@@ -198,9 +232,77 @@ impl ChunkStorage {
             height,
             min_y,
             chunks: HashMap::new(),
+            cache_policy: ChunkCachePolicy::default(),
+            cached: HashMap::new(),
+            ticks: 0,
         }
     }
 
+    /// Add a chunk to the storage. It'll be kept for as long as something else
+    /// holds a strong reference to it, and after that for as long as the
+    /// [`ChunkCachePolicy`] says.
+    pub fn insert(&mut self, pos: ChunkPos, chunk: &Arc<RwLock<Chunk>>) {
+        self.chunks.insert(pos, Arc::downgrade(chunk));
+        if self.cache_policy != ChunkCachePolicy::Unload {
+            self.cached.insert(
+                pos,
+                CachedChunk {
+                    chunk: chunk.clone(),
+                    unused_since: None,
+                },
+            );
+        }
+    }
+
+    /// Forget cached chunks according to the [`ChunkCachePolicy`], and remove
+    /// entries for chunks that were dropped. This should be called every tick.
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+        let now = self.ticks;
+
+        for cached in self.cached.values_mut() {
+            if Arc::strong_count(&cached.chunk) > 1 {
+                cached.unused_since = None;
+            } else if cached.unused_since.is_none() {
+                cached.unused_since = Some(now);
+            }
+        }
+
+        match self.cache_policy {
+            ChunkCachePolicy::Unload => self.cached.clear(),
+            ChunkCachePolicy::MaxChunks(max) => {
+                let mut unused = self
+                    .cached
+                    .iter()
+                    .filter_map(|(pos, cached)| cached.unused_since.map(|since| (since, *pos)))
+                    .collect::<Vec<_>>();
+                if unused.len() > max {
+                    unused.sort_unstable_by_key(|(since, _)| *since);
+                    for (_, pos) in &unused[..unused.len() - max] {
+                        self.cached.remove(pos);
+                    }
+                }
+            }
+            ChunkCachePolicy::KeepAlive { ticks } => {
+                self.cached.retain(|_, cached| match cached.unused_since {
+                    Some(since) => now - since < ticks as u64,
+                    None => true,
+                });
+            }
+        }
+
+        self.chunks.retain(|_, chunk| chunk.strong_count() > 0);
+    }
+
+    /// The number of chunks that are only being kept because of the
+    /// [`ChunkCachePolicy`].
+    pub fn unused_cached_count(&self) -> usize {
+        self.cached
+            .values()
+            .filter(|cached| cached.unused_since.is_some())
+            .count()
+    }
+
     pub fn get(&self, pos: &ChunkPos) -> Option<Arc<RwLock<Chunk>>> {
         self.chunks.get(pos).and_then(|chunk| chunk.upgrade())
     }
@@ -420,4 +522,55 @@ mod tests {
             .get_block_state(&BlockPos { x: 0, y: -65, z: 0 })
             .is_none());
     }
+
+    #[test]
+    fn test_unload_policy_forgets_chunks() {
+        let mut chunk_storage = ChunkStorage::default();
+        let mut partial_chunk_storage = PartialChunkStorage::default();
+        let pos = ChunkPos { x: 0, z: 0 };
+        partial_chunk_storage.set(&pos, Some(Chunk::default()), &mut chunk_storage);
+        chunk_storage.tick();
+        assert!(chunk_storage.get(&pos).is_some());
+
+        partial_chunk_storage.set(&pos, None, &mut chunk_storage);
+        assert!(chunk_storage.get(&pos).is_none());
+        chunk_storage.tick();
+        assert!(chunk_storage.chunks.is_empty());
+    }
+
+    #[test]
+    fn test_keep_alive_policy() {
+        let mut chunk_storage = ChunkStorage::default();
+        chunk_storage.cache_policy = ChunkCachePolicy::KeepAlive { ticks: 3 };
+        let mut partial_chunk_storage = PartialChunkStorage::default();
+        let pos = ChunkPos { x: 0, z: 0 };
+        partial_chunk_storage.set(&pos, Some(Chunk::default()), &mut chunk_storage);
+        partial_chunk_storage.set(&pos, None, &mut chunk_storage);
+
+        for _ in 0..3 {
+            chunk_storage.tick();
+            assert!(chunk_storage.get(&pos).is_some());
+        }
+        chunk_storage.tick();
+        assert!(chunk_storage.get(&pos).is_none());
+    }
+
+    #[test]
+    fn test_max_chunks_policy() {
+        let mut chunk_storage = ChunkStorage::default();
+        chunk_storage.cache_policy = ChunkCachePolicy::MaxChunks(2);
+        let mut partial_chunk_storage = PartialChunkStorage::default();
+        for x in 0..4 {
+            let pos = ChunkPos { x, z: 0 };
+            partial_chunk_storage.set(&pos, Some(Chunk::default()), &mut chunk_storage);
+            partial_chunk_storage.set(&pos, None, &mut chunk_storage);
+            chunk_storage.tick();
+        }
+        // the chunks that were unused for the longest are forgotten first
+        assert!(chunk_storage.get(&ChunkPos { x: 0, z: 0 }).is_none());
+        assert!(chunk_storage.get(&ChunkPos { x: 1, z: 0 }).is_none());
+        assert!(chunk_storage.get(&ChunkPos { x: 2, z: 0 }).is_some());
+        assert!(chunk_storage.get(&ChunkPos { x: 3, z: 0 }).is_some());
+        assert_eq!(chunk_storage.unused_cached_count(), 2);
+    }
 }
@@ -1,5 +1,5 @@
 use azalea_core::ResourceLocation;
-use bevy_ecs::system::Resource;
+use bevy_ecs::system::{ResMut, Resource};
 use log::error;
 use nohash_hasher::IntMap;
 use parking_lot::RwLock;
@@ -8,7 +8,7 @@ use std::{
     sync::{Arc, Weak},
 };
 
-use crate::{entity::WorldName, ChunkStorage, Instance};
+use crate::{entity::WorldName, ChunkCachePolicy, ChunkStorage, Instance};
 
 /// A container of [`Instance`]s (aka worlds). Instances are stored as a Weak
 /// pointer here, so if no clients are using an instance it will be forgotten.
@@ -27,12 +27,17 @@ pub struct InstanceContainer {
     // issue when there's multiple clients with the same WorldContainer in different worlds
     // anyways.
     pub worlds: HashMap<ResourceLocation, Weak<RwLock<Instance>>>,
+    /// The [`ChunkCachePolicy`] that new instances are created with. You can
+    /// change the policy of an existing instance with
+    /// [`ChunkStorage::cache_policy`].
+    pub chunk_cache_policy: ChunkCachePolicy,
 }
 
 impl InstanceContainer {
     pub fn new() -> Self {
         InstanceContainer {
             worlds: HashMap::new(),
+            chunk_cache_policy: ChunkCachePolicy::default(),
         }
     }
 
@@ -66,8 +71,10 @@ impl InstanceContainer {
             }
             existing_lock.clone()
         } else {
+            let mut chunks = ChunkStorage::new(height, min_y);
+            chunks.cache_policy = self.chunk_cache_policy;
             let world = Arc::new(RwLock::new(Instance {
-                chunks: ChunkStorage::new(height, min_y),
+                chunks,
                 entities_by_chunk: HashMap::new(),
                 entity_by_id: IntMap::default(),
             }));
@@ -76,3 +83,17 @@ impl InstanceContainer {
         }
     }
 }
+
+/// Apply the [`ChunkCachePolicy`] of every instance, and forget about instances
+/// that were dropped. This should run every tick.
+pub fn tick_instances(mut instance_container: ResMut<InstanceContainer>) {
+    instance_container
+        .worlds
+        .retain(|_, world| match world.upgrade() {
+            Some(world) => {
+                world.write().chunks.tick();
+                true
+            }
+            None => false,
+        });
+}
@@ -4,7 +4,8 @@
 use crate::{
     deduplicate_entities, deduplicate_local_entities,
     entity::{
-        self, add_dead, update_bounding_box, EntityUuid, MinecraftEntityId, Position, WorldName,
+        self, add_dead, update_bounding_box, EntityChunkPos, EntityUuid, MinecraftEntityId,
+        WorldName,
     },
     update_entity_by_id_index, update_uuid_index, InstanceContainer, PartialInstance,
 };
@@ -214,30 +215,34 @@ fn update_entity_chunk_positions(
         (
             Entity,
             &entity::Position,
-            &mut entity::LastSentPosition,
+            &mut entity::EntityChunkPos,
             &entity::WorldName,
         ),
         Changed<entity::Position>,
     >,
     instance_container: Res<InstanceContainer>,
 ) {
-    for (entity, pos, last_pos, world_name) in query.iter_mut() {
-        let world_lock = instance_container.get(world_name).unwrap();
-        let mut world = world_lock.write();
-
-        let old_chunk = ChunkPos::from(*last_pos);
+    for (entity, pos, mut chunk_pos, world_name) in query.iter_mut() {
+        let old_chunk = **chunk_pos;
         let new_chunk = ChunkPos::from(*pos);
 
-        if old_chunk != new_chunk {
-            // move the entity from the old chunk to the new one
-            if let Some(entities) = world.entities_by_chunk.get_mut(&old_chunk) {
-                entities.remove(&entity);
+        if old_chunk != Some(new_chunk) {
+            let world_lock = instance_container.get(world_name).unwrap();
+            let mut world = world_lock.write();
+
+            // move the entity from the old chunk to the new one, or just add
+            // it if it was just spawned
+            if let Some(old_chunk) = old_chunk {
+                if let Some(entities) = world.entities_by_chunk.get_mut(&old_chunk) {
+                    entities.remove(&entity);
+                }
             }
             world
                 .entities_by_chunk
                 .entry(new_chunk)
                 .or_default()
                 .insert(entity);
+            **chunk_pos = Some(new_chunk);
         }
     }
 }
@@ -287,9 +292,9 @@ fn remove_despawned_entities_from_indexes(
     mut commands: Commands,
     mut entity_infos: ResMut<EntityInfos>,
     instance_container: Res<InstanceContainer>,
-    query: Query<(Entity, &EntityUuid, &Position, &WorldName, &LoadedBy), Changed<LoadedBy>>,
+    query: Query<(Entity, &EntityUuid, &EntityChunkPos, &WorldName, &LoadedBy), Changed<LoadedBy>>,
 ) {
-    for (entity, uuid, position, world_name, loaded_by) in &query {
+    for (entity, uuid, chunk_pos, world_name, loaded_by) in &query {
         let world_lock = instance_container.get(world_name).unwrap();
         let mut world = world_lock.write();
 
@@ -298,19 +303,22 @@ fn remove_despawned_entities_from_indexes(
             continue;
         }
 
-        // remove the entity from the chunk index
-        let chunk = ChunkPos::from(*position);
-        if let Some(entities_in_chunk) = world.entities_by_chunk.get_mut(&chunk) {
-            if entities_in_chunk.remove(&entity) {
-                // remove the chunk if there's no entities in it anymore
-                if entities_in_chunk.is_empty() {
-                    world.entities_by_chunk.remove(&chunk);
+        // remove the entity from the chunk index, if it was added to it yet
+        if let Some(chunk) = **chunk_pos {
+            if let Some(entities_in_chunk) = world.entities_by_chunk.get_mut(&chunk) {
+                if entities_in_chunk.remove(&entity) {
+                    // remove the chunk if there's no entities in it anymore
+                    if entities_in_chunk.is_empty() {
+                        world.entities_by_chunk.remove(&chunk);
+                    }
+                } else {
+                    warn!(
+                        "Tried to remove entity from chunk {chunk:?} but the entity was not there."
+                    );
                 }
             } else {
-                warn!("Tried to remove entity from chunk {chunk:?} but the entity was not there.");
+                warn!("Tried to remove entity from chunk {chunk:?} but the chunk was not found.");
             }
-        } else {
-            warn!("Tried to remove entity from chunk {chunk:?} but the chunk was not found.");
         }
         // remove it from the uuid index
         if entity_infos.entity_by_uuid.remove(uuid).is_none() {
@@ -319,7 +327,6 @@ fn remove_despawned_entities_from_indexes(
         // and now remove the entity from the ecs
         commands.entity(entity).despawn();
         debug!("Despawned entity {entity:?} because it was not loaded by anything.");
-        return;
     }
 }
 
@@ -197,6 +197,14 @@ impl From<&LastSentPosition> for BlockPos {
     }
 }
 
+/// The chunk that the entity is in, according to
+/// [`Instance::entities_by_chunk`]. This is `None` until the entity is added
+/// to the index.
+///
+/// [`Instance::entities_by_chunk`]: crate::Instance::entities_by_chunk
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Deref, DerefMut)]
+pub struct EntityChunkPos(pub Option<ChunkPos>);
+
 /// The name of the world the entity is in. If two entities share the same world
 /// name, we assume they're in the same world.
 #[derive(Component, Clone, Debug, PartialEq, Deref, DerefMut)]
@@ -306,6 +314,7 @@ pub struct EntityBundle {
     pub world_name: WorldName,
     pub position: Position,
     pub last_sent_position: LastSentPosition,
+    pub chunk_pos: EntityChunkPos,
     pub physics: Physics,
     pub direction: LookDirection,
     pub eye_height: EyeHeight,
@@ -333,6 +342,7 @@ impl EntityBundle {
             world_name: WorldName(world_name),
             position: Position(pos),
             last_sent_position: LastSentPosition(pos),
+            chunk_pos: EntityChunkPos::default(),
             physics: Physics {
                 delta: Vec3::default(),
 
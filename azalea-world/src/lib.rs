@@ -14,7 +14,7 @@ mod world;
 use std::backtrace::Backtrace;
 
 pub use bit_storage::BitStorage;
-pub use chunk_storage::{Chunk, ChunkStorage, PartialChunkStorage, Section};
+pub use chunk_storage::{Chunk, ChunkCachePolicy, ChunkStorage, PartialChunkStorage, Section};
 pub use container::*;
 use thiserror::Error;
 pub use world::*;
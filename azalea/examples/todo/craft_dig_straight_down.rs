@@ -49,14 +49,12 @@ async fn handle(bot: Client, event: Event, state: State) -> anyhow::Result<()> {
                     .open_crafting_table(&bot.world.find_block(azalea::Block::CraftingTable))
                     .await
                     .unwrap();
-                bot.craft(&crafting_table, &bot.recipe_for("minecraft:sticks"))
-                    .await?;
-                let pickaxe = bot
-                    .craft(&crafting_table, &bot.recipe_for("minecraft:wooden_pickaxe"))
-                    .await?;
+                // crafting uses the grid of the crafting table while it's open
+                bot.craft(azalea::Item::Stick, 2).await?;
+                bot.craft(azalea::Item::WoodenPickaxe, 1).await?;
                 crafting_table.close().await;
 
-                bot.hold(&pickaxe);
+                bot.hold(azalea::Item::WoodenPickaxe);
 
                 loop {
                     if let Err(e) = bot
@@ -7,6 +7,7 @@ mod bot;
 mod container;
 pub mod pathfinder;
 pub mod prelude;
+pub mod reconnect;
 pub mod swarm;
 
 use app::{App, Plugin, PluginGroup};
@@ -14,6 +15,11 @@ pub use azalea_auth as auth;
 pub use azalea_block as blocks;
 pub use azalea_brigadier as brigadier;
 pub use azalea_client::*;
+use azalea_client::{
+    login_query::{LoginQueryHandler, LoginQueryHandlers},
+    plugin_channels::PluginChannels,
+    resource_pack::ResourcePackPolicy,
+};
 pub use azalea_core::{BlockPos, Vec3};
 pub use azalea_protocol as protocol;
 pub use azalea_registry::{Block, EntityKind, Item};
@@ -21,10 +27,9 @@ pub use azalea_world::{entity, Instance};
 use bot::DefaultBotPlugins;
 use ecs::component::Component;
 use futures::Future;
-use protocol::{
-    resolver::{self, ResolverError},
-    ServerAddress,
-};
+use log::warn;
+use protocol::{resolver::ResolverError, ServerAddress};
+use reconnect::ReconnectPolicy;
 use thiserror::Error;
 use tokio::sync::mpsc;
 
@@ -40,7 +45,18 @@ pub enum StartError {
     #[error(transparent)]
     ResolveAddress(#[from] ResolverError),
     #[error("Join error: {0}")]
-    Join(#[from] azalea_client::JoinError),
+    Join(azalea_client::JoinError),
+}
+
+impl From<JoinError> for StartError {
+    fn from(e: JoinError) -> Self {
+        // the address is resolved while joining now, but it's still reported
+        // as its own error
+        match e {
+            JoinError::Resolver(e) => StartError::ResolveAddress(e),
+            e => StartError::Join(e),
+        }
+    }
 }
 
 /// A builder for creating new [`Client`]s. This is the recommended way of
@@ -70,6 +86,8 @@ where
     /// The function that's called every time a bot receives an [`Event`].
     handler: Option<HandleFn<Fut, S>>,
     state: S,
+    /// Whether the bot joins again when it's disconnected.
+    reconnect_policy: Option<ReconnectPolicy>,
 }
 impl<S, Fut> ClientBuilder<S, Fut>
 where
@@ -86,6 +104,7 @@ where
 
             handler: None,
             state: S::default(),
+            reconnect_policy: None,
         }
         .add_plugins(DefaultBotPlugins)
     }
@@ -117,6 +136,19 @@ where
         self.state = state;
         self
     }
+    /// Join the server again when the bot gets disconnected, instead of
+    /// returning from [`Self::start`]. The handler gets
+    /// [`Event::Reconnecting`] before every attempt and
+    /// [`Event::Reconnected`] with the new [`Client`] once it joined, and the
+    /// state is kept.
+    ///
+    /// [`Event::Reconnecting`]: azalea_client::Event::Reconnecting
+    /// [`Event::Reconnected`]: azalea_client::Event::Reconnected
+    #[must_use]
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = Some(policy);
+        self
+    }
     /// Add a plugin to the client.
     #[must_use]
     pub fn add_plugin<T: Plugin>(mut self, plugin: T) -> Self {
@@ -130,6 +162,39 @@ where
         self
     }
 
+    /// Set how the client responds when the server asks it to use a resource
+    /// pack. Defaults to [`ResourcePackPolicy::AcceptAndFakeLoad`].
+    #[must_use]
+    pub fn resource_pack_policy(mut self, policy: ResourcePackPolicy) -> Self {
+        self.app.insert_resource(policy);
+        self
+    }
+
+    /// Set the client brand that's sent to the server when the client joins,
+    /// which is shown in the server's F3 menu. Defaults to "vanilla".
+    #[must_use]
+    pub fn brand(mut self, brand: &str) -> Self {
+        self.app
+            .world
+            .get_resource_or_insert_with(PluginChannels::default)
+            .brand = brand.to_string();
+        self
+    }
+
+    /// Add a handler that answers custom queries from the server while we're
+    /// logging in, like [`VelocityForwarding`]. Handlers are tried in the
+    /// order they're added.
+    ///
+    /// [`VelocityForwarding`]: azalea_client::login_query::VelocityForwarding
+    #[must_use]
+    pub fn login_query_handler(mut self, handler: impl LoginQueryHandler) -> Self {
+        self.app
+            .world
+            .get_resource_or_insert_with(LoginQueryHandlers::default)
+            .add(handler);
+        self
+    }
+
     /// Build this `ClientBuilder` into an actual [`Client`] and join the given
     /// server.
     ///
@@ -142,30 +207,91 @@ where
         account: Account,
         address: impl TryInto<ServerAddress>,
     ) -> Result<(), StartError> {
-        let address: ServerAddress = address.try_into().map_err(|_| JoinError::InvalidAddress)?;
-        let resolved_address = resolver::resolve_address(&address).await?;
+        self.start_with_opts(account, address, JoinOpts::default())
+            .await
+    }
 
+    /// Same as [`Self::start`], but with [`JoinOpts`] to use a proxy or set
+    /// timeouts.
+    pub async fn start_with_opts(
+        self,
+        account: Account,
+        address: impl TryInto<ServerAddress>,
+        opts: JoinOpts,
+    ) -> Result<(), StartError> {
+        let address: ServerAddress = address.try_into().map_err(|_| JoinError::InvalidAddress)?;
         // An event that causes the schedule to run. This is only used internally.
         let (run_schedule_sender, run_schedule_receiver) = mpsc::unbounded_channel();
 
         let ecs_lock = start_ecs(self.app, run_schedule_receiver, run_schedule_sender.clone());
+        let handle = |bot: &Client, event: Event| {
+            if let Some(handler) = self.handler {
+                tokio::spawn((handler)(bot.clone(), event, self.state.clone()));
+            }
+        };
 
-        let (bot, mut rx) = Client::start_client(
-            ecs_lock,
+        let (mut bot, mut rx) = Client::start_client(
+            ecs_lock.clone(),
             &account,
             &address,
-            &resolved_address,
-            run_schedule_sender,
+            None,
+            &opts,
+            run_schedule_sender.clone(),
         )
         .await?;
 
-        while let Some(event) = rx.recv().await {
-            if let Some(handler) = self.handler {
-                tokio::spawn((handler)(bot.clone(), event.clone(), self.state.clone()));
+        loop {
+            let mut disconnect_reason = None;
+            while let Some(event) = rx.recv().await {
+                if let Event::Disconnect(reason) = &event {
+                    disconnect_reason = Some(reason.clone());
+                }
+                handle(&bot, event);
             }
-        }
 
-        Ok(())
+            let Some(policy) = &self.reconnect_policy else {
+                return Ok(());
+            };
+            let reason = reconnect::disconnect_reason(disconnect_reason);
+            if !policy.should_reconnect(&reason) {
+                return Ok(());
+            }
+
+            let mut attempt = 1;
+            (bot, rx) = loop {
+                let delay = policy.delay(attempt);
+                handle(
+                    &bot,
+                    Event::Reconnecting {
+                        attempt,
+                        delay,
+                        reason: reason.clone(),
+                    },
+                );
+                tokio::time::sleep(delay).await;
+
+                match Client::start_client(
+                    ecs_lock.clone(),
+                    &account,
+                    &address,
+                    None,
+                    &opts,
+                    run_schedule_sender.clone(),
+                )
+                .await
+                {
+                    Ok(joined) => break joined,
+                    Err(e) => {
+                        warn!("Couldn't reconnect as {}: {e}", account.username);
+                        attempt += 1;
+                        if !policy.can_attempt(attempt) {
+                            return Err(e.into());
+                        }
+                    }
+                }
+            };
+            handle(&bot, Event::Reconnected);
+        }
     }
 }
 impl<S, Fut> Default for ClientBuilder<S, Fut>
@@ -0,0 +1,259 @@
+//! Join the server again when a bot gets disconnected.
+
+use azalea_client::disconnect::DisconnectReason;
+use azalea_protocol::read::ReadPacketError;
+use std::{fmt, sync::Arc, time::Duration};
+
+/// When and how often a bot tries to join the server again after it was
+/// disconnected. Set it with [`ClientBuilder::reconnect_policy`] or
+/// [`SwarmBuilder::reconnect_policy`].
+///
+/// The delay before each attempt starts at [`Self::initial_delay`] and is
+/// multiplied by [`Self::multiplier`] every time joining fails, up to
+/// [`Self::max_delay`]. It goes back to the initial delay once the bot joined.
+///
+/// ```
+/// # use azalea::reconnect::ReconnectPolicy;
+/// # use azalea::disconnect::DisconnectReason;
+/// # use std::time::Duration;
+/// let policy = ReconnectPolicy::default()
+///     .max_attempts(10)
+///     // don't reconnect if we got kicked
+///     .filter(|reason| !matches!(reason, DisconnectReason::Kicked(_)));
+/// assert_eq!(policy.delay(1), Duration::from_secs(5));
+/// assert_eq!(policy.delay(2), Duration::from_secs(10));
+/// ```
+///
+/// [`ClientBuilder::reconnect_policy`]: crate::ClientBuilder::reconnect_policy
+/// [`SwarmBuilder::reconnect_policy`]: crate::swarm::SwarmBuilder::reconnect_policy
+#[derive(Clone)]
+pub struct ReconnectPolicy {
+    /// How long we wait before the first attempt.
+    pub initial_delay: Duration,
+    /// The longest we ever wait between attempts.
+    pub max_delay: Duration,
+    /// How much longer we wait after every failed attempt.
+    pub multiplier: f64,
+    /// How many times in a row we try to join before giving up, or `None` to
+    /// never give up.
+    pub max_attempts: Option<u32>,
+    /// Whether we should reconnect after being disconnected for a reason.
+    filter: Arc<dyn Fn(&DisconnectReason) -> bool + Send + Sync>,
+}
+
+impl Default for ReconnectPolicy {
+    /// Wait 5 seconds, doubling up to 2 minutes, and never give up. We
+    /// reconnect for every reason except [`DisconnectReason::Requested`].
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(120),
+            multiplier: 2.,
+            max_attempts: None,
+            filter: Arc::new(|reason| !matches!(reason, DisconnectReason::Requested)),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    #[must_use]
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    #[must_use]
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    #[must_use]
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    #[must_use]
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Only reconnect if the function returns true for the reason we were
+    /// disconnected. This replaces the default filter, so you have to check
+    /// for [`DisconnectReason::Requested`] yourself if you don't want bots to
+    /// come back after
+    /// [`Client::disconnect`](azalea_client::Client::disconnect).
+    #[must_use]
+    pub fn filter(
+        mut self,
+        filter: impl Fn(&DisconnectReason) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.filter = Arc::new(filter);
+        self
+    }
+
+    /// Whether we should try to join again after being disconnected for this
+    /// reason.
+    pub fn should_reconnect(&self, reason: &DisconnectReason) -> bool {
+        (self.filter)(reason)
+    }
+
+    /// Whether we can try joining again after this many attempts in a row.
+    // newer clippy suggests `Option::is_none_or`, which our toolchain doesn't
+    // have yet
+    #[allow(unknown_lints, clippy::unnecessary_map_or)]
+    pub fn can_attempt(&self, attempt: u32) -> bool {
+        self.max_attempts.map_or(true, |max| attempt <= max)
+    }
+
+    /// How long we wait before an attempt, which starts at 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        // the delay is capped anyways, so this just stops the exponent from
+        // overflowing
+        let exponent = attempt.saturating_sub(1).min(64) as i32;
+        let multiplier = self.multiplier.powi(exponent);
+        self.initial_delay
+            .mul_f64(multiplier.min(u32::MAX as f64))
+            .min(self.max_delay)
+    }
+}
+
+impl fmt::Debug for ReconnectPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReconnectPolicy")
+            .field("initial_delay", &self.initial_delay)
+            .field("max_delay", &self.max_delay)
+            .field("multiplier", &self.multiplier)
+            .field("max_attempts", &self.max_attempts)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Why a bot was disconnected. The [`Event::Disconnect`] might be missing if
+/// the ECS stopped, in which case we treat it like the connection was lost.
+///
+/// [`Event::Disconnect`]: azalea_client::Event::Disconnect
+pub(crate) fn disconnect_reason(reason: Option<DisconnectReason>) -> DisconnectReason {
+    reason.unwrap_or_else(|| {
+        DisconnectReason::ConnectionLost(Arc::new(ReadPacketError::ConnectionClosed))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClientBuilder;
+    use azalea_client::{Account, Client, Event};
+    use azalea_protocol::server::{Listener, ServerConfig};
+    use bevy_ecs::component::Component;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_delay() {
+        let policy = ReconnectPolicy::default();
+        assert_eq!(policy.delay(1), Duration::from_secs(5));
+        assert_eq!(policy.delay(3), Duration::from_secs(20));
+        assert_eq!(policy.delay(10), Duration::from_secs(120));
+        // this would overflow if it wasn't capped
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_max_attempts() {
+        let policy = ReconnectPolicy::default();
+        assert!(policy.can_attempt(1000));
+        let policy = policy.max_attempts(3);
+        assert!(policy.can_attempt(3));
+        assert!(!policy.can_attempt(4));
+    }
+
+    #[test]
+    fn test_filter() {
+        let policy = ReconnectPolicy::default();
+        assert!(policy.should_reconnect(&DisconnectReason::TimedOut));
+        assert!(!policy.should_reconnect(&DisconnectReason::Requested));
+
+        let policy = policy.filter(|reason| matches!(reason, DisconnectReason::TimedOut));
+        assert!(policy.should_reconnect(&DisconnectReason::TimedOut));
+        assert!(!policy.should_reconnect(&DisconnectReason::BundleTooBig));
+    }
+
+    /// Passes the events the bot gets on to the test.
+    #[derive(Component, Clone, Default)]
+    struct State {
+        events: Option<mpsc::UnboundedSender<Event>>,
+    }
+
+    async fn handle(bot: Client, event: Event, state: State) -> anyhow::Result<()> {
+        if let Event::Reconnected = event {
+            // so `start` returns
+            bot.disconnect();
+        }
+        state.events.unwrap().send(event)?;
+        Ok(())
+    }
+
+    /// The next event that's about the bot disconnecting or reconnecting.
+    async fn next_event(events: &mut mpsc::UnboundedReceiver<Event>) -> Event {
+        loop {
+            match events.recv().await.unwrap() {
+                event
+                @ (Event::Disconnect(_) | Event::Reconnecting { .. } | Event::Reconnected) => {
+                    return event
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnect() {
+        let mut listener = Listener::bind("127.0.0.1:0", ServerConfig::default())
+            .await
+            .unwrap();
+        let address = listener.local_addr();
+        let (events_sender, mut events) = mpsc::unbounded_channel();
+        let bot = tokio::spawn(
+            ClientBuilder::new()
+                .set_handler(handle)
+                .set_state(State {
+                    events: Some(events_sender),
+                })
+                .reconnect_policy(
+                    ReconnectPolicy::default().initial_delay(Duration::from_millis(10)),
+                )
+                .start(Account::offline("bot"), address),
+        );
+
+        // the connection is closed right after the bot joins
+        drop(listener.next_player().await.unwrap());
+        assert!(matches!(
+            next_event(&mut events).await,
+            Event::Disconnect(DisconnectReason::ConnectionLost(_))
+        ));
+        let Event::Reconnecting {
+            attempt,
+            delay,
+            reason: DisconnectReason::ConnectionLost(error),
+        } = next_event(&mut events).await
+        else {
+            panic!("Expected to be reconnecting after losing the connection");
+        };
+        assert_eq!(attempt, 1);
+        assert_eq!(delay, Duration::from_millis(10));
+        assert!(matches!(*error, ReadPacketError::ConnectionClosed));
+
+        let player = listener.next_player().await.unwrap();
+        assert_eq!(player.game_profile.name, "bot");
+        assert!(matches!(next_event(&mut events).await, Event::Reconnected));
+
+        // disconnecting on purpose isn't reconnected by default
+        assert!(matches!(
+            next_event(&mut events).await,
+            Event::Disconnect(DisconnectReason::Requested)
+        ));
+        bot.await.unwrap().unwrap();
+    }
+}
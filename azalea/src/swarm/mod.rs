@@ -4,20 +4,28 @@ mod chat;
 mod events;
 pub mod prelude;
 
-use crate::{bot::DefaultBotPlugins, HandleFn};
-use azalea_client::{chat::ChatPacket, init_ecs_app, start_ecs, Account, Client, Event, JoinError};
-use azalea_protocol::{
-    connect::ConnectionError,
-    resolver::{self, ResolverError},
-    ServerAddress,
+use crate::{
+    bot::DefaultBotPlugins,
+    reconnect::{self, ReconnectPolicy},
+    HandleFn,
 };
+use azalea_client::{
+    chat::ChatPacket,
+    disconnect::DisconnectReason,
+    init_ecs_app,
+    login_query::{LoginQueryHandler, LoginQueryHandlers},
+    plugin_channels::PluginChannels,
+    resource_pack::ResourcePackPolicy,
+    start_ecs, Account, Client, Event, JoinError, JoinOpts,
+};
+use azalea_protocol::{connect::ConnectionError, resolver::ResolverError, ServerAddress};
 use azalea_world::InstanceContainer;
 use bevy_app::{App, Plugin, PluginGroup, PluginGroupBuilder};
 use bevy_ecs::{component::Component, entity::Entity, system::Resource, world::World};
-use futures::future::join_all;
-use log::error;
+use futures::future::{join_all, BoxFuture};
+use log::{error, warn};
 use parking_lot::{Mutex, RwLock};
-use std::{collections::HashMap, future::Future, net::SocketAddr, sync::Arc, time::Duration};
+use std::{collections::HashMap, future::Future, sync::Arc, time::Duration};
 use thiserror::Error;
 use tokio::sync::mpsc;
 
@@ -35,7 +43,6 @@ pub struct Swarm {
     bots: Arc<Mutex<HashMap<Entity, Client>>>,
 
     // bot_datas: Arc<Mutex<Vec<(Client, S)>>>,
-    resolved_address: SocketAddr,
     address: ServerAddress,
     pub instance_container: Arc<RwLock<InstanceContainer>>,
 
@@ -43,6 +50,9 @@ pub struct Swarm {
     swarm_tx: mpsc::UnboundedSender<SwarmEvent>,
 
     run_schedule_sender: mpsc::UnboundedSender<()>,
+
+    /// Whether bots join again when they're disconnected.
+    reconnect_policy: Option<ReconnectPolicy>,
 }
 
 /// Create a new [`Swarm`].
@@ -59,6 +69,9 @@ where
     /// The individual bot states. This must be the same length as `accounts`,
     /// since each bot gets one state.
     states: Vec<S>,
+    /// How each bot connects to the server. This must be the same length as
+    /// `accounts`.
+    join_opts: Vec<JoinOpts>,
     /// The state for the overall swarm.
     swarm_state: SS,
     /// The function that's called every time a bot receives an [`Event`].
@@ -72,6 +85,8 @@ where
     /// a duration of 0, since if a duration is present the bots will wait for
     /// the previous one to be ready.
     join_delay: Option<std::time::Duration>,
+    /// Whether bots join again when they're disconnected.
+    reconnect_policy: Option<ReconnectPolicy>,
 }
 impl<S, SS, Fut, SwarmFut> SwarmBuilder<S, SS, Fut, SwarmFut>
 where
@@ -90,10 +105,12 @@ where
 
             accounts: Vec::new(),
             states: Vec::new(),
+            join_opts: Vec::new(),
             swarm_state: SS::default(),
             handler: None,
             swarm_handler: None,
             join_delay: None,
+            reconnect_policy: None,
         }
         .add_plugins(DefaultSwarmPlugins)
         .add_plugins(DefaultBotPlugins)
@@ -123,9 +140,16 @@ where
     /// Add an account with a custom initial state. Use just
     /// [`Self::add_account`] to use the Default implementation for the state.
     #[must_use]
-    pub fn add_account_with_state(mut self, account: Account, state: S) -> Self {
+    pub fn add_account_with_state(self, account: Account, state: S) -> Self {
+        self.add_account_with_opts(account, state, JoinOpts::default())
+    }
+    /// Add an account with a custom initial state and [`JoinOpts`], which
+    /// let each bot use its own proxy.
+    #[must_use]
+    pub fn add_account_with_opts(mut self, account: Account, state: S, opts: JoinOpts) -> Self {
         self.accounts.push(account);
         self.states.push(state);
+        self.join_opts.push(opts);
         self
     }
 
@@ -225,6 +249,49 @@ where
         self
     }
 
+    /// Make bots join the server again when they get disconnected. Each bot
+    /// keeps its state, and the handler gets [`Event::Reconnecting`] before
+    /// every attempt and [`Event::Reconnected`] with the new [`Client`] once
+    /// it joined. [`SwarmEvent::Disconnect`] is still sent.
+    #[must_use]
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = Some(policy);
+        self
+    }
+
+    /// Set how the bots respond when the server asks them to use a resource
+    /// pack. Defaults to [`ResourcePackPolicy::AcceptAndFakeLoad`].
+    #[must_use]
+    pub fn resource_pack_policy(mut self, policy: ResourcePackPolicy) -> Self {
+        self.app.insert_resource(policy);
+        self
+    }
+
+    /// Set the client brand that's sent to the server when the bots join,
+    /// which is shown in the server's F3 menu. Defaults to "vanilla".
+    #[must_use]
+    pub fn brand(mut self, brand: &str) -> Self {
+        self.app
+            .world
+            .get_resource_or_insert_with(PluginChannels::default)
+            .brand = brand.to_string();
+        self
+    }
+
+    /// Add a handler that answers custom queries from the server while the
+    /// bots are logging in, like [`VelocityForwarding`]. Handlers are tried in
+    /// the order they're added.
+    ///
+    /// [`VelocityForwarding`]: azalea_client::login_query::VelocityForwarding
+    #[must_use]
+    pub fn login_query_handler(mut self, handler: impl LoginQueryHandler) -> Self {
+        self.app
+            .world
+            .get_resource_or_insert_with(LoginQueryHandlers::default)
+            .add(handler);
+        self
+    }
+
     /// Build this `SwarmBuilder` into an actual [`Swarm`] and join the given
     /// server.
     ///
@@ -245,9 +312,6 @@ where
             Err(_) => return Err(SwarmStartError::InvalidAddress),
         };
 
-        // resolve the address
-        let resolved_address = resolver::resolve_address(&address).await?;
-
         let instance_container = Arc::new(RwLock::new(InstanceContainer::default()));
 
         // we can't modify the swarm plugins after this
@@ -261,7 +325,6 @@ where
             ecs_lock: ecs_lock.clone(),
             bots: Arc::new(Mutex::new(HashMap::new())),
 
-            resolved_address,
             address,
             instance_container,
 
@@ -270,6 +333,8 @@ where
             swarm_tx: swarm_tx.clone(),
 
             run_schedule_sender,
+
+            reconnect_policy: self.reconnect_policy,
         };
         ecs_lock.lock().insert_resource(swarm.clone());
 
@@ -279,24 +344,25 @@ where
         let join_delay = self.join_delay;
         let accounts = self.accounts.clone();
         let states = self.states.clone();
+        let join_opts = self.join_opts.clone();
 
         let join_task = tokio::spawn(async move {
             if let Some(join_delay) = join_delay {
                 // if there's a join delay, then join one by one
-                for (account, state) in accounts.iter().zip(states) {
+                for ((account, state), opts) in accounts.iter().zip(states).zip(&join_opts) {
                     swarm_clone
-                        .add_with_exponential_backoff(account, state.clone())
+                        .add_with_exponential_backoff_with_opts(account, state.clone(), opts)
                         .await;
                     tokio::time::sleep(join_delay).await;
                 }
             } else {
                 // otherwise, join all at once
                 let swarm_borrow = &swarm_clone;
-                join_all(accounts.iter().zip(states).map(
-                    async move |(account, state)| -> Result<(), JoinError> {
+                join_all(accounts.iter().zip(states).zip(&join_opts).map(
+                    async move |((account, state), opts)| -> Result<(), JoinError> {
                         swarm_borrow
                             .clone()
-                            .add_with_exponential_backoff(account, state.clone())
+                            .add_with_exponential_backoff_with_opts(account, state.clone(), opts)
                             .await;
                         Ok(())
                     },
@@ -357,8 +423,9 @@ pub enum SwarmEvent {
     Init,
     /// A bot got disconnected from the server.
     ///
-    /// You can implement an auto-reconnect by calling [`Swarm::add`]
-    /// with the account from this event.
+    /// You can make bots reconnect automatically with
+    /// [`SwarmBuilder::reconnect_policy`], or by calling [`Swarm::add`] with
+    /// the account from this event.
     Disconnect(Account),
     /// At least one bot received a chat message.
     Chat(ChatPacket),
@@ -373,7 +440,7 @@ pub enum SwarmStartError {
     #[error(transparent)]
     ResolveAddress(#[from] ResolverError),
     #[error("Join error: {0}")]
-    Join(#[from] azalea_client::JoinError),
+    Join(azalea_client::JoinError),
 }
 
 /// Make a bot [`Swarm`].
@@ -450,6 +517,22 @@ impl Swarm {
         &mut self,
         account: &Account,
         state: S,
+    ) -> Result<Client, JoinError> {
+        self.add_with_opts(account, state, &JoinOpts::default())
+            .await
+    }
+
+    /// Add a new account to the swarm with the given [`JoinOpts`], which let
+    /// it use its own proxy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the bot could not do a handshake successfully.
+    pub async fn add_with_opts<S: Component + Clone>(
+        &mut self,
+        account: &Account,
+        state: S,
+        opts: &JoinOpts,
     ) -> Result<Client, JoinError> {
         // tx is moved to the bot so it can send us events
         // rx is used to receive events from the bot
@@ -460,14 +543,15 @@ impl Swarm {
             self.ecs_lock.clone(),
             account,
             &self.address,
-            &self.resolved_address,
+            None,
+            opts,
             self.run_schedule_sender.clone(),
         )
         .await?;
         // add the state to the client
         {
             let mut ecs = self.ecs_lock.lock();
-            ecs.entity_mut(bot.entity).insert(state);
+            ecs.entity_mut(bot.entity).insert(state.clone());
         }
 
         self.bots.lock().insert(bot.entity, bot.clone());
@@ -476,9 +560,15 @@ impl Swarm {
         let cloned_bots_tx = self.bots_tx.clone();
         let cloned_bot = bot.clone();
         let owned_account = account.clone();
+        let owned_opts = opts.clone();
         let swarm_tx = self.swarm_tx.clone();
+        let swarm = self.clone();
         tokio::spawn(async move {
+            let mut disconnect_reason = None;
             while let Some(event) = rx.recv().await {
+                if let Event::Disconnect(reason) = &event {
+                    disconnect_reason = Some(reason.clone());
+                }
                 // we can't handle events here (since we can't copy the handler),
                 // they're handled above in SwarmBuilder::start
                 if let Err(e) = cloned_bots_tx.send((Some(event), cloned_bot.clone())) {
@@ -487,13 +577,76 @@ impl Swarm {
             }
             cloned_bots.lock().remove(&bot.entity);
             swarm_tx
-                .send(SwarmEvent::Disconnect(owned_account))
+                .send(SwarmEvent::Disconnect(owned_account.clone()))
                 .unwrap();
+
+            // the state component is still on the old entity, and it might
+            // have been replaced since the bot joined
+            let state = cloned_bot.get_component::<S>().unwrap_or(state);
+            let reason = reconnect::disconnect_reason(disconnect_reason);
+            swarm
+                .reconnect(owned_account, owned_opts, cloned_bot, state, reason)
+                .await;
         });
 
         Ok(bot)
     }
 
+    /// Join the server again with a bot that was disconnected, based on the
+    /// [`ReconnectPolicy`]. This ends when the bot joined or we gave up.
+    ///
+    /// This returns a boxed future since it calls [`Self::add_with_opts`],
+    /// which calls this.
+    fn reconnect<S: Component + Clone>(
+        mut self,
+        account: Account,
+        opts: JoinOpts,
+        old_bot: Client,
+        state: S,
+        reason: DisconnectReason,
+    ) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let Some(policy) = self.reconnect_policy.clone() else {
+                return;
+            };
+            if !policy.should_reconnect(&reason) {
+                return;
+            }
+
+            let mut attempt = 1;
+            loop {
+                let delay = policy.delay(attempt);
+                let event = Event::Reconnecting {
+                    attempt,
+                    delay,
+                    reason: reason.clone(),
+                };
+                if let Err(e) = self.bots_tx.send((Some(event), old_bot.clone())) {
+                    error!("Error sending event to swarm: {e}");
+                }
+                tokio::time::sleep(delay).await;
+
+                match self.add_with_opts(&account, state.clone(), &opts).await {
+                    Ok(bot) => {
+                        if let Err(e) = self.bots_tx.send((Some(Event::Reconnected), bot)) {
+                            error!("Error sending event to swarm: {e}");
+                        }
+                        return;
+                    }
+                    Err(e) => {
+                        let username = &account.username;
+                        attempt += 1;
+                        if !policy.can_attempt(attempt) {
+                            error!("Couldn't reconnect as {username}: {e}. Giving up.");
+                            return;
+                        }
+                        warn!("Couldn't reconnect as {username}: {e}.");
+                    }
+                }
+            }
+        })
+    }
+
     /// Add a new account to the swarm, retrying if it couldn't join. This will
     /// run forever until the bot joins or the task is aborted.
     ///
@@ -503,10 +656,22 @@ impl Swarm {
         &mut self,
         account: &Account,
         state: S,
+    ) -> Client {
+        self.add_with_exponential_backoff_with_opts(account, state, &JoinOpts::default())
+            .await
+    }
+
+    /// Add a new account to the swarm with the given [`JoinOpts`], retrying if
+    /// it couldn't join. See [`Self::add_with_exponential_backoff`].
+    pub async fn add_with_exponential_backoff_with_opts<S: Component + Clone>(
+        &mut self,
+        account: &Account,
+        state: S,
+        opts: &JoinOpts,
     ) -> Client {
         let mut disconnects = 0;
         loop {
-            match self.add(account, state.clone()).await {
+            match self.add_with_opts(account, state.clone(), opts).await {
                 Ok(bot) => return bot,
                 Err(e) => {
                     disconnects += 1;
@@ -548,6 +713,15 @@ impl IntoIterator for Swarm {
     }
 }
 
+impl From<JoinError> for SwarmStartError {
+    fn from(e: JoinError) -> Self {
+        match e {
+            JoinError::Resolver(e) => SwarmStartError::ResolveAddress(e),
+            e => SwarmStartError::Join(e),
+        }
+    }
+}
+
 impl From<ConnectionError> for SwarmStartError {
     fn from(e: ConnectionError) -> Self {
         SwarmStartError::from(JoinError::from(e))
@@ -565,3 +739,96 @@ impl PluginGroup for DefaultSwarmPlugins {
             .add(events::SwarmPlugin)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azalea_protocol::server::{Listener, ServerConfig};
+
+    /// Passes the events the bot gets on to the test, along with the label
+    /// from its state.
+    #[derive(Component, Clone, Default)]
+    struct State {
+        events: Option<mpsc::UnboundedSender<(Event, Client, Option<&'static str>)>>,
+        label: Option<&'static str>,
+    }
+
+    #[derive(Resource, Clone, Default)]
+    struct SwarmState;
+
+    async fn handle(bot: Client, event: Event, state: State) -> anyhow::Result<()> {
+        // the test might not be listening anymore
+        let _ = state.events.unwrap().send((event, bot, state.label));
+        Ok(())
+    }
+
+    async fn swarm_handle(
+        _swarm: Swarm,
+        _event: SwarmEvent,
+        _state: SwarmState,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reconnect() {
+        let mut listener = Listener::bind("127.0.0.1:0", ServerConfig::default())
+            .await
+            .unwrap();
+        let address = listener.local_addr();
+        let (events_sender, mut events) = mpsc::unbounded_channel();
+        let swarm = tokio::spawn(
+            SwarmBuilder::new()
+                .add_account_with_state(
+                    Account::offline("bot"),
+                    State {
+                        events: Some(events_sender),
+                        label: None,
+                    },
+                )
+                .set_handler(handle)
+                .set_swarm_handler(swarm_handle)
+                .reconnect_policy(
+                    ReconnectPolicy::default().initial_delay(Duration::from_millis(10)),
+                )
+                .start(address),
+        );
+
+        let player = listener.next_player().await.unwrap();
+        let bot = loop {
+            if let (Event::Init, bot, _) = events.recv().await.unwrap() {
+                break bot;
+            }
+        };
+        // the state is changed after the bot joined, so the new bot should get
+        // this one instead of the state it started with
+        let state = bot.component::<State>();
+        bot.ecs.lock().entity_mut(bot.entity).insert(State {
+            label: Some("changed"),
+            ..state
+        });
+
+        // the connection is closed without a reason
+        drop(player);
+        let (reason, label) = loop {
+            if let (Event::Reconnecting { reason, .. }, _, label) = events.recv().await.unwrap() {
+                break (reason, label);
+            }
+        };
+        assert!(matches!(reason, DisconnectReason::ConnectionLost(_)));
+        assert_eq!(label, Some("changed"));
+
+        let player = listener.next_player().await.unwrap();
+        assert_eq!(player.game_profile.name, "bot");
+        let (new_bot, label) = loop {
+            if let (Event::Reconnected, new_bot, label) = events.recv().await.unwrap() {
+                break (new_bot, label);
+            }
+        };
+        assert_ne!(new_bot.entity, bot.entity);
+        assert_eq!(label, Some("changed"));
+        assert_eq!(new_bot.component::<State>().label, Some("changed"));
+
+        swarm.abort();
+    }
+}
@@ -0,0 +1,16 @@
+import lib.code.version_mappings
+import lib.code.version
+import lib.code.utils
+import sys
+
+version_id = lib.code.version.get_version_id()
+
+# the versions to generate tables for, or the ones that are already supported
+# if none are given
+old_version_ids = sys.argv[1:] or lib.code.version_mappings.get_supported_version_ids()
+
+lib.code.version_mappings.generate_version_mappings(version_id, old_version_ids)
+
+lib.code.utils.fmt()
+
+print('Done!')
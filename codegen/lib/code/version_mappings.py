@@ -0,0 +1,130 @@
+from lib.code.packet import fix_state, are_packet_instructions_identical
+from lib.utils import get_dir_location, padded_hex
+import lib.download
+import lib.extract
+import re
+
+MAPPINGS_RS_DIR = get_dir_location(
+    '../azalea-protocol/src/packets/version/mappings.rs')
+
+# the handshake isn't included since it can't change between versions
+STATES = ['game', 'login', 'status']
+DIRECTIONS = ['clientbound', 'serverbound']
+
+
+def get_supported_version_ids() -> list[str]:
+    '''
+    Get the ids of the versions in mappings.rs, without the current version.
+    '''
+    with open(MAPPINGS_RS_DIR, 'r') as f:
+        mappings_rs = f.read()
+    return re.findall(r'name: "(.*)",', mappings_rs)[1:]
+
+
+def get_packets(version_id: str):
+    '''
+    Returns the protocol version, and a dict of (direction, state) to a dict of
+    packet names to their id and instructions.
+    '''
+    mappings = lib.download.get_mappings_for_version(version_id)
+    burger_data = lib.extract.get_burger_data_for_version(version_id)
+
+    packets: dict[tuple[str, str], dict[str, tuple[int, list]]] = {}
+    for packet in burger_data[0]['packets']['packet'].values():
+        assert packet['class'].endswith('.class')
+        packet_name = mappings.get_class(packet['class'][:-6])
+        key = (packet['direction'].lower(), fix_state(packet['state']))
+        packets.setdefault(key, {})[packet_name] = (
+            packet['id'], packet.get('instructions'))
+
+    return burger_data[0]['version']['protocol'], packets
+
+
+def generate_id_table(current_packets: dict[str, tuple[int, list]], version_packets: dict[str, tuple[int, list]]):
+    '''
+    Returns the Rust code for a PacketIdTable, or None if the ids are the
+    same. Also returns the names of the packets whose layout changed.
+    '''
+    to_current: list[int] = [0] * len(version_packets)
+    from_current: list[int | None] = [None] * len(current_packets)
+    changed_layouts: list[str] = []
+
+    for packet_name, (version_id, version_instructions) in version_packets.items():
+        if packet_name not in current_packets:
+            # we don't have this packet so it can't be decoded anyways, but
+            # map it to an id that doesn't exist so it's not mistaken for
+            # another packet
+            to_current[version_id] = len(current_packets)
+            continue
+        current_id, current_instructions = current_packets[packet_name]
+        to_current[version_id] = current_id
+        from_current[current_id] = version_id
+        if not are_packet_instructions_identical(current_instructions, version_instructions):
+            changed_layouts.append(packet_name.split('.')[-1])
+
+    if to_current == list(range(len(current_packets))) and len(to_current) == len(from_current):
+        return None, changed_layouts
+
+    code = []
+    code.append('Some(PacketIdTable {')
+    code.append('to_current: &[')
+    code.append(', '.join(padded_hex(i) for i in to_current) + ',')
+    code.append('],')
+    code.append('from_current: &[')
+    code.append(', '.join('None' if i is None else f'Some({padded_hex(i)})'
+                          for i in from_current) + ',')
+    code.append('],')
+    code.append('})')
+    return '\n'.join(code), changed_layouts
+
+
+def generate_version_mappings(current_version_id: str, version_ids: list[str]):
+    '''
+    Generate the packet id tables for the versions, relative to the current
+    version, and write them to mappings.rs.
+    '''
+    current_protocol_version, current_packets = get_packets(
+        current_version_id)
+
+    code = []
+    code.append(
+        '// This file was generated by codegen/genversions.py, don\'t edit it manually!')
+    code.append('')
+    code.append('use super::{PacketIdTable, VersionPacketIds};')
+    code.append('')
+    code.append('pub(super) static VERSIONS: &[VersionPacketIds] = &[')
+
+    versions = [(current_version_id, current_protocol_version, current_packets)]
+    for version_id in version_ids:
+        protocol_version, packets = get_packets(version_id)
+        versions.append((version_id, protocol_version, packets))
+
+    for version_id, protocol_version, packets in versions:
+        tables = {}
+        changed_layouts = []
+        for state in STATES:
+            for direction in DIRECTIONS:
+                table, changed = generate_id_table(
+                    current_packets.get((direction, state), {}), packets.get((direction, state), {}))
+                tables[(direction, state)] = table
+                changed_layouts.extend(changed)
+
+        if changed_layouts:
+            code.append(
+                f'// TODO: these packets have a different layout than in {current_version_id}:')
+            for packet_name in changed_layouts:
+                code.append(f'// - {packet_name}')
+        code.append('VersionPacketIds {')
+        code.append(f'name: "{version_id}",')
+        code.append(f'protocol_version: {protocol_version},')
+        for state in STATES:
+            for direction in DIRECTIONS:
+                table = tables[(direction, state)]
+                code.append(f'{direction}_{state}: {table or "None"},')
+        code.append('},')
+
+    code.append('];')
+    code.append('')
+
+    with open(MAPPINGS_RS_DIR, 'w') as f:
+        f.write('\n'.join(code))
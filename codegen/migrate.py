@@ -3,6 +3,7 @@ from lib.utils import PacketIdentifier, group_packets
 import lib.code.inventory
 import lib.code.language
 import lib.code.registry
+import lib.code.version_mappings
 import lib.code.version
 import lib.code.blocks
 import lib.code.packet
@@ -144,6 +145,9 @@ lib.code.entity.generate_entity_metadata(burger_entities_data, new_mappings)
 print('Finishing touches, setting version in README and formatting code...')
 lib.code.version.set_version_id(new_version_id)
 
+print('Generating packet id tables for older versions...')
+lib.code.version_mappings.generate_version_mappings(
+    new_version_id, [old_version_id] + lib.code.version_mappings.get_supported_version_ids())
 
 lib.code.utils.fmt()
 